@@ -1,16 +1,54 @@
-use sqlx::{Row, SqlitePool};
+use super::currencies::{BASE_CURRENCY, convert, currency_code};
+use super::custom_fields::{CustomFieldEntity, CustomFieldValues, parse_values, values_json};
+use super::journal::journaled;
+use super::money::{Currency, Money};
+use super::transactions::insert_transfer;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
 
-/// Retrieves all financial accounts from the database.
+/// Which accounts a listing includes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    /// Accounts in use, not archived
+    Active,
+    /// Archived (including closed) accounts only
+    Archived,
+    /// Every account
+    #[default]
+    All,
+}
+
+/// Currency, notes and user-defined values recorded with an account.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountDetails {
+    /// ISO currency code of the account's amounts; new accounts default to the
+    /// ledger's base currency and updates without one keep the current currency
+    pub currency: Option<String>,
+    /// Free-text note, e.g. the branch or the account number's last digits
+    pub memo: Option<String>,
+    /// Values by custom field id, see [`custom_fields`](super::custom_fields)
+    pub custom_fields: CustomFieldValues,
+}
+
+const ACCOUNT_COLUMNS: &str =
+    "id, name, account_type, currency, archived, closed_date, display_order, opening_balance_cents, memo, custom_fields, cost_basis_method";
+
+/// Retrieves financial accounts from the database in sidebar order.
 ///
-/// Queries the accounts table and returns all records as JSON-serializable objects
-/// for frontend consumption. Results include account ID, name, and type but exclude
-/// internal timestamps to keep the API clean.
+/// Queries the accounts table and returns the records matching `status` as
+/// JSON-serializable objects for frontend consumption, sorted by their display
+/// order. Results include account ID, name, type, currency, archived state, closing
+/// date, display order, memo, custom field values and cost basis method but
+/// exclude internal timestamps to keep the API clean.
 ///
 /// # Arguments
 /// * `pool` - SQLite connection pool reference for executing the query
+/// * `status` - Whether to list active, archived or all accounts
 ///
 /// # Returns
-/// * `Ok(Vec<serde_json::Value>)` - Array of account objects with id, name, and account_type
+/// * `Ok(Vec<serde_json::Value>)` - Array of account objects
 /// * `Err(sqlx::Error)` - Database query or serialization failure
 ///
 /// # Errors
@@ -22,8 +60,8 @@ use sqlx::{Row, SqlitePool};
 ///
 /// # Examples
 /// ```no_run
-/// let accounts = get_all_accounts(&pool).await?;
-/// println!("Found {} accounts", accounts.len());
+/// let accounts = get_all_accounts(&pool, AccountStatus::Active).await?;
+/// println!("Found {} active accounts", accounts.len());
 ///
 /// for account in accounts {
 ///     println!("Account: {} ({})",
@@ -32,41 +70,51 @@ use sqlx::{Row, SqlitePool};
 ///     );
 /// }
 /// ```
-pub async fn get_all_accounts(pool: &SqlitePool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
-    let accounts = sqlx::query("SELECT id, name, account_type, created_at FROM accounts")
-        .fetch_all(pool)
-        .await?;
+pub async fn get_all_accounts(
+    pool: &SqlitePool,
+    status: AccountStatus,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let condition = match status {
+        AccountStatus::Active => "WHERE NOT archived",
+        AccountStatus::Archived => "WHERE archived",
+        AccountStatus::All => "",
+    };
+    let accounts = sqlx::query(&format!(
+        "SELECT {} FROM accounts {} ORDER BY display_order, id",
+        ACCOUNT_COLUMNS, condition
+    ))
+    .fetch_all(pool)
+    .await?;
 
-    let result: Vec<serde_json::Value> = accounts
-        .into_iter()
-        .map(|row| {
-            serde_json::json!({
-                "id": row.get::<i64, _>("id"),
-                "name": row.get::<String, _>("name"),
-                "account_type": row.get::<String, _>("account_type")
-            })
-        })
-        .collect();
-
-    Ok(result)
+    accounts.iter().map(account_json).collect()
 }
 
 pub async fn get_account(
     pool: &SqlitePool,
     account_id: i64,
 ) -> Result<serde_json::Value, sqlx::Error> {
-    let row = sqlx::query("SELECT id, name, account_type FROM accounts WHERE id = ?")
+    let row = sqlx::query(&format!("SELECT {} FROM accounts WHERE id = ?", ACCOUNT_COLUMNS))
         .bind(account_id)
         .fetch_one(pool)
         .await?;
 
-    let account = serde_json::json!({
+    account_json(&row)
+}
+
+fn account_json(row: &sqlx::sqlite::SqliteRow) -> Result<serde_json::Value, sqlx::Error> {
+    Ok(serde_json::json!({
         "id": row.get::<i64, _>("id"),
         "name": row.get::<String, _>("name"),
-        "account_type": row.get::<String, _>("account_type")
-    });
-
-    Ok(account)
+        "account_type": row.get::<String, _>("account_type"),
+        "currency": row.get::<String, _>("currency"),
+        "archived": row.get::<bool, _>("archived"),
+        "closed_date": row.get::<Option<String>, _>("closed_date"),
+        "display_order": row.get::<i64, _>("display_order"),
+        "opening_balance_cents": row.get::<i64, _>("opening_balance_cents"),
+        "memo": row.get::<Option<String>, _>("memo"),
+        "custom_fields": parse_values(row.get("custom_fields"))?,
+        "cost_basis_method": row.get::<String, _>("cost_basis_method")
+    }))
 }
 
 /// Creates a new financial account in the database.
@@ -105,13 +153,47 @@ pub async fn add_account(
     name: String,
     account_type: String,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query("INSERT INTO accounts (name, account_type) VALUES (?, ?)")
+    add_account_with_details(pool, name, account_type, &AccountDetails::default()).await?;
+
+    Ok(())
+}
+
+/// Creates an account with a currency, memo or custom field values.
+///
+/// Works like [`add_account()`], which is a shorthand for this function
+/// without details, creating an account in the ledger's base currency.
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new account
+/// * `Err(sqlx::Error)` - See [`add_account()`]; also fails for an invalid
+///   currency code, an unknown custom field or a value of the wrong type
+pub async fn add_account_with_details(
+    pool: &SqlitePool,
+    name: String,
+    account_type: String,
+    details: &AccountDetails,
+) -> Result<i64, sqlx::Error> {
+    let currency = details.currency.as_deref().map(currency_code).transpose()?;
+    journaled(pool, "Add account", async |conn| {
+        let custom_fields = values_json(&mut *conn, CustomFieldEntity::Account, &details.custom_fields).await?;
+
+        let id = sqlx::query(&format!(
+            "INSERT INTO accounts (name, account_type, currency, display_order, memo, custom_fields)
+             VALUES (?, ?, COALESCE(?, {}), (SELECT COALESCE(MAX(display_order), 0) + 1 FROM accounts), ?, ?)",
+            BASE_CURRENCY
+        ))
         .bind(name)
         .bind(account_type)
-        .execute(pool)
-        .await?;
+        .bind(currency)
+        .bind(&details.memo)
+        .bind(custom_fields)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
 
-    Ok(())
+        Ok(id)
+    })
+    .await
 }
 
 /// Updates an existing financial account with new values.
@@ -120,6 +202,7 @@ pub async fn add_account(
 /// including the archived status for soft deletion functionality. This
 /// replaces the entire account record to ensure consistency across all
 /// account attributes. The account ID remains immutable as the record identifier.
+/// Un-archiving a closed account reopens it and clears its closing date.
 ///
 /// # Arguments
 /// * `pool` - SQLite connection pool reference for executing the update
@@ -127,6 +210,9 @@ pub async fn add_account(
 /// * `name` - New human-readable account name
 /// * `account_type` - New account classification ("checking" or "savings")
 /// * `archived` - New archived status (true hides account, false shows it)
+/// * `details` - New memo and custom field values, replacing the current ones,
+///   and optionally a new currency; None leaves them unchanged. The currency
+///   can only change while the account holds no amounts
 ///
 /// # Returns
 /// * `Ok(())` - Account updated successfully
@@ -136,6 +222,8 @@ pub async fn add_account(
 /// Fails if:
 /// - Database connection cannot be established (pool exhaustion, file locks)
 /// - Account ID does not exist (no matching record to update)
+/// - A new currency is given for an account with transactions (also in the
+///   trash), an opening balance or trades, whose amounts it would relabel
 /// - Account name violates constraints (empty string, potential duplicates)
 /// - Account type is invalid or unsupported by application logic
 /// - Database update fails (permissions, corruption, constraint violations)
@@ -149,7 +237,8 @@ pub async fn add_account(
 ///     1,
 ///     "Chase Premium Checking".to_string(),
 ///     "checking".to_string(),
-///     false
+///     false,
+///     None
 /// ).await?;
 ///
 /// // Archive an old account
@@ -158,7 +247,8 @@ pub async fn add_account(
 ///     5,
 ///     "Old Savings Account".to_string(),
 ///     "savings".to_string(),
-///     true
+///     true,
+///     None
 /// ).await?;
 /// ```
 pub async fn update_account(
@@ -167,34 +257,324 @@ pub async fn update_account(
     name: String,
     account_type: String,
     archived: bool,
+    details: Option<&AccountDetails>,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE accounts SET name = ?, account_type = ?, archived = ? WHERE id = ?")
+    let currency = details
+        .and_then(|details| details.currency.as_deref())
+        .map(currency_code)
+        .transpose()?;
+    journaled(pool, "Update account", async |conn| {
+        let custom_fields = match details {
+            Some(details) => Some(values_json(&mut *conn, CustomFieldEntity::Account, &details.custom_fields).await?),
+            None => None,
+        };
+        if let Some(currency) = &currency {
+            let holds_amounts: Option<bool> = sqlx::query_scalar(
+                "SELECT currency != ?1 AND (opening_balance_cents != 0
+                        OR EXISTS (SELECT 1 FROM transactions WHERE account_id = ?2)
+                        OR EXISTS (SELECT 1 FROM investment_transactions WHERE account_id = ?2))
+                 FROM accounts WHERE id = ?2",
+            )
+            .bind(currency)
+            .bind(account_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+            if holds_amounts == Some(true) {
+                return Err(sqlx::Error::Protocol(
+                    "the currency of an account with transactions, an opening balance or trades cannot be changed".to_string(),
+                ));
+            }
+        }
+
+        sqlx::query(
+            "UPDATE accounts SET name = ?, account_type = ?, archived = ?,
+                closed_date = CASE WHEN ? THEN closed_date END,
+                currency = COALESCE(?, currency),
+                memo = CASE WHEN ? THEN ? ELSE memo END,
+                custom_fields = COALESCE(?, custom_fields)
+             WHERE id = ?",
+        )
         .bind(name)
         .bind(account_type)
         .bind(archived)
+        .bind(archived)
+        .bind(&currency)
+        .bind(details.is_some())
+        .bind(details.and_then(|details| details.memo.as_deref()))
+        .bind(custom_fields)
         .bind(account_id)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
-    Ok(())
+        Ok(())
+    })
+    .await
+}
+
+/// Sets the balance an account had before its first recorded transaction.
+///
+/// Running balances and [`get_balance()`] start from this amount, so an
+/// account can be tracked from today without importing its whole history.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference for executing the update
+/// * `account_id` - Database ID of the account
+/// * `opening_balance` - Signed opening balance in the account's currency (negative for debt)
+///
+/// # Returns
+/// * `Ok(())` - Opening balance updated
+/// * `Err(sqlx::Error)` - Account not found, an amount in another currency, or
+///   database failure
+pub async fn set_opening_balance(
+    pool: &SqlitePool,
+    account_id: i64,
+    opening_balance: Money,
+) -> Result<(), sqlx::Error> {
+    journaled(pool, "Set opening balance", async |conn| {
+        let opening_balance_cents = account_amount(&mut *conn, account_id, opening_balance).await?;
+        sqlx::query("UPDATE accounts SET opening_balance_cents = ? WHERE id = ?")
+            .bind(opening_balance_cents)
+            .bind(account_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Current balance of an account: its opening balance plus every transaction
+/// not in the trash.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `account_id` - Database ID of the account
+///
+/// # Returns
+/// * `Ok(Money)` - Balance in the account's currency
+/// * `Err(sqlx::Error)` - `RowNotFound` for an unknown account, a balance too
+///   large to represent, or database failure
+pub async fn get_balance(pool: &SqlitePool, account_id: i64) -> Result<Money, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    balance_of(&mut conn, account_id).await
+}
+
+/// Closes an account: archives it and records the closing date.
+///
+/// An account can only be closed once its balance is zero. With `transfer_to`
+/// set, a remaining balance is first moved to that account as a transfer dated
+/// on the closing date, converted at that day's rate when the two accounts'
+/// currencies differ. The transfer and the closing form one journaled operation.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `account_id` - Database ID of the account to close
+/// * `transfer_to` - Optional active account receiving any remaining balance
+/// * `closed_date` - Closing date (YYYY-MM-DD); defaults to today
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The closed account, plus the `transferred_cents` moved
+/// * `Err(sqlx::Error)` - Account not found, already closed, non-zero balance
+///   without a transfer target, or an invalid target; nothing is changed
+///
+/// # Examples
+/// ```no_run
+/// // Move what is left in the old savings account to checking and close it
+/// let closed = close_account(&pool, 5, Some(1), None).await?;
+/// println!("Transferred {} cents", closed["transferred_cents"]);
+/// ```
+pub async fn close_account(
+    pool: &SqlitePool,
+    account_id: i64,
+    transfer_to: Option<i64>,
+    closed_date: Option<String>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    journaled(pool, "Close account", async |conn| {
+        let account = sqlx::query("SELECT name, closed_date FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        if account.get::<Option<String>, _>("closed_date").is_some() {
+            return Err(sqlx::Error::Protocol("account is already closed".to_string()));
+        }
+        let name: String = account.get("name");
+        let closed_date: String = match closed_date {
+            Some(date) => date,
+            None => sqlx::query_scalar("SELECT date('now')").fetch_one(&mut *conn).await?,
+        };
+
+        let balance = balance_of(&mut *conn, account_id).await?;
+        if !balance.is_zero() {
+            let Some(target_id) = transfer_to else {
+                return Err(sqlx::Error::Protocol(format!(
+                    "account balance is {}; transfer the remainder before closing",
+                    balance
+                )));
+            };
+            transfer_remainder(&mut *conn, account_id, &name, target_id, balance, &closed_date).await?;
+        }
+
+        sqlx::query("UPDATE accounts SET archived = TRUE, closed_date = ? WHERE id = ?")
+            .bind(&closed_date)
+            .bind(account_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let row = sqlx::query(&format!("SELECT {} FROM accounts WHERE id = ?", ACCOUNT_COLUMNS))
+            .bind(account_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        let mut closed = account_json(&row)?;
+        closed["transferred_cents"] = serde_json::json!(balance.amount_cents());
+
+        Ok(closed)
+    })
+    .await
 }
 
-pub async fn get_balance(pool: &SqlitePool, account_id: i64) -> Result<i64, sqlx::Error> {
-    let transactions = sqlx::query("SELECT amount_cents FROM transactions WHERE account_id = ?")
+/// Current balance of an account inside an open unit of work.
+async fn balance_of(conn: &mut SqliteConnection, account_id: i64) -> Result<Money, sqlx::Error> {
+    let account = sqlx::query("SELECT opening_balance_cents, currency FROM accounts WHERE id = ?")
         .bind(account_id)
-        .fetch_all(pool)
+        .fetch_one(&mut *conn)
         .await?;
+    let currency = Currency::new(account.get::<&str, _>("currency"))?;
 
-    let mut balance: i64 = 0;
+    let transactions = sqlx::query("SELECT amount_cents FROM transactions WHERE account_id = ? AND deleted_at IS NULL")
+        .bind(account_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut balance = Money::new(account.get("opening_balance_cents"), currency);
 
     for row in transactions {
-        let amount: i64 = row.get("amount_cents");
-        balance += amount;
+        balance = balance.checked_add(Money::new(row.get("amount_cents"), currency))?;
     }
 
     Ok(balance)
 }
 
+/// Currency of an account inside an open unit of work.
+pub(crate) async fn account_currency(conn: &mut SqliteConnection, account_id: i64) -> Result<Currency, sqlx::Error> {
+    let code: String = sqlx::query_scalar("SELECT currency FROM accounts WHERE id = ?")
+        .bind(account_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(Currency::new(&code)?)
+}
+
+/// Minor units of `amount` for storing in an account, which only holds
+/// amounts in its own currency.
+pub(crate) async fn account_amount(
+    conn: &mut SqliteConnection,
+    account_id: i64,
+    amount: Money,
+) -> Result<i64, sqlx::Error> {
+    let currency = account_currency(&mut *conn, account_id).await?;
+    if amount.currency() != currency {
+        return Err(sqlx::Error::Protocol(format!(
+            "amount is in {} but account {} is in {}",
+            amount.currency(),
+            account_id,
+            currency
+        )));
+    }
+
+    Ok(amount.amount_cents())
+}
+
+/// Moves `balance` from a closing account into `target_id`, converted into the
+/// target's currency at the closing date's rate.
+async fn transfer_remainder(
+    conn: &mut SqliteConnection,
+    account_id: i64,
+    account_name: &str,
+    target_id: i64,
+    balance: Money,
+    date: &str,
+) -> Result<(), sqlx::Error> {
+    if target_id == account_id {
+        return Err(sqlx::Error::Protocol(
+            "cannot transfer the remainder to the account being closed".to_string(),
+        ));
+    }
+    let target = sqlx::query("SELECT name, archived FROM accounts WHERE id = ?")
+        .bind(target_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if target.get::<bool, _>("archived") {
+        return Err(sqlx::Error::Protocol(
+            "cannot transfer the remainder to an archived account".to_string(),
+        ));
+    }
+    let target_name: String = target.get("name");
+    let closing_description = format!("Transfer to {} (account closed)", target_name);
+    let target_description = format!("Transfer from {} (account closed)", account_name);
+
+    if balance.is_positive() {
+        insert_transfer(&mut *conn, account_id, target_id, balance, None, date, [closing_description, target_description])
+            .await?;
+    } else {
+        // A debt is paid off from the target, which sends whatever settles it exactly
+        let debt = balance.checked_neg()?;
+        let target_currency = account_currency(&mut *conn, target_id).await?;
+        let sent = convert(&mut *conn, debt, target_currency, date).await?;
+        insert_transfer(&mut *conn, target_id, account_id, sent, Some(debt), date, [target_description, closing_description])
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Persists the sidebar order of accounts.
+///
+/// Listed accounts take positions in the given order; accounts left out, such
+/// as archived ones hidden from the sidebar, keep their relative order after them.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `account_ids` - Account IDs in their new display order
+///
+/// # Returns
+/// * `Ok(())` - Order saved
+/// * `Err(sqlx::Error)` - `RowNotFound` for an unknown account, duplicate ids, or
+///   database failure; the previous order is kept
+pub async fn reorder_accounts(pool: &SqlitePool, account_ids: &[i64]) -> Result<(), sqlx::Error> {
+    for (index, account_id) in account_ids.iter().enumerate() {
+        if account_ids[..index].contains(account_id) {
+            return Err(sqlx::Error::Protocol(format!(
+                "account {} is listed more than once",
+                account_id
+            )));
+        }
+    }
+
+    journaled(pool, "Reorder accounts", async |conn| {
+        let rest: Vec<i64> = sqlx::query_scalar("SELECT id FROM accounts ORDER BY display_order, id")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .filter(|id| !account_ids.contains(id))
+            .collect();
+
+        for (position, account_id) in account_ids.iter().chain(&rest).enumerate() {
+            let result = sqlx::query("UPDATE accounts SET display_order = ? WHERE id = ?")
+                .bind(position as i64 + 1)
+                .bind(account_id)
+                .execute(&mut *conn)
+                .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(sqlx::Error::RowNotFound);
+            }
+        }
+
+        Ok(())
+    })
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -202,7 +582,6 @@ mod tests {
 
     async fn setup_test_db() -> SqlitePool {
         let pool = SqlitePool::connect(":memory:").await.unwrap();
-        crate::database::create_tables(&pool).await.unwrap();
         crate::database::migrations::run_migrations(&pool)
             .await
             .unwrap();
@@ -218,7 +597,7 @@ mod tests {
             .await
             .unwrap();
 
-        let accounts = get_all_accounts(&pool).await.unwrap();
+        let accounts = get_all_accounts(&pool, AccountStatus::All).await.unwrap();
         assert_eq!(accounts.len(), 1);
         assert_eq!(accounts[0]["name"], "Test Checking");
         assert_eq!(accounts[0]["account_type"], "checking");
@@ -238,14 +617,34 @@ mod tests {
             "Updated Name".to_string(),
             "savings".to_string(),
             true,
+            Some(&AccountDetails {
+                memo: Some("Joint with Alex".to_string()),
+                ..Default::default()
+            }),
         )
         .await
         .unwrap();
 
-        let accounts = get_all_accounts(&pool).await.unwrap();
+        let accounts = get_all_accounts(&pool, AccountStatus::All).await.unwrap();
         assert_eq!(accounts.len(), 1);
         assert_eq!(accounts[0]["name"], "Updated Name");
         assert_eq!(accounts[0]["account_type"], "savings");
+        assert_eq!(accounts[0]["memo"], "Joint with Alex");
+
+        // The currency can change until the account holds amounts
+        let euro = AccountDetails { currency: Some("EUR".to_string()), ..Default::default() };
+        update_account(&pool, 1, "Updated Name".to_string(), "savings".to_string(), true, Some(&euro))
+            .await
+            .unwrap();
+        set_opening_balance(&pool, 1, Money::new(1000, Currency::EUR)).await.unwrap();
+        let yen = AccountDetails { currency: Some("JPY".to_string()), ..Default::default() };
+        let relabel = update_account(&pool, 1, "Updated Name".to_string(), "savings".to_string(), true, Some(&yen));
+        assert!(relabel.await.is_err());
+        // Naming the current currency again is no change
+        update_account(&pool, 1, "Updated Name".to_string(), "savings".to_string(), true, Some(&euro))
+            .await
+            .unwrap();
+        assert_eq!(get_balance(&pool, 1).await.unwrap(), Money::new(1000, Currency::EUR));
     }
 
     #[tokio::test]
@@ -258,7 +657,7 @@ mod tests {
         crate::database::add_transaction(
             &pool,
             1,
-            50000,
+            Money::new(50000, Currency::USD),
             "credit".to_string(),
             "Salary deposit".to_string(),
             "2025-01-15".to_string(),
@@ -269,7 +668,7 @@ mod tests {
         crate::database::add_transaction(
             &pool,
             1,
-            -15000,
+            Money::new(-15000, Currency::USD),
             "debit".to_string(),
             "Grocery store".to_string(),
             "2025-01-16".to_string(),
@@ -279,6 +678,72 @@ mod tests {
         .unwrap();
 
         let balance = get_balance(&pool, 1).await.unwrap();
-        assert_eq!(balance, 35000)
+        assert_eq!(balance, Money::new(35000, Currency::USD));
+        assert!(get_balance(&pool, 2).await.is_err());
+
+        // Overflow is an error rather than a wrapped balance
+        set_opening_balance(&pool, 1, Money::new(i64::MAX - 1000, Currency::USD)).await.unwrap();
+        assert!(get_balance(&pool, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_account_status_filters_and_order() {
+        let pool = setup_test_db().await;
+        for name in ["Checking", "Savings", "Old Card"] {
+            add_account(&pool, name.to_string(), "checking".to_string()).await.unwrap();
+        }
+        update_account(&pool, 3, "Old Card".to_string(), "credit".to_string(), true, None)
+            .await
+            .unwrap();
+
+        let active = get_all_accounts(&pool, AccountStatus::Active).await.unwrap();
+        assert_eq!(active.len(), 2);
+        let archived = get_all_accounts(&pool, AccountStatus::Archived).await.unwrap();
+        assert_eq!(archived[0]["name"], "Old Card");
+        assert_eq!(archived[0]["archived"], true);
+
+        reorder_accounts(&pool, &[2, 1]).await.unwrap();
+        let names: Vec<String> = get_all_accounts(&pool, AccountStatus::All)
+            .await
+            .unwrap()
+            .iter()
+            .map(|a| a["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, vec!["Savings", "Checking", "Old Card"]);
+
+        assert!(reorder_accounts(&pool, &[1, 1]).await.is_err());
+        assert!(reorder_accounts(&pool, &[9]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_close_account_requires_zero_balance_or_transfer() {
+        let pool = setup_test_db().await;
+        add_account(&pool, "Checking".to_string(), "checking".to_string()).await.unwrap();
+        add_account(&pool, "Savings".to_string(), "savings".to_string()).await.unwrap();
+        set_opening_balance(&pool, 2, Money::new(12550, Currency::USD)).await.unwrap();
+
+        assert!(close_account(&pool, 2, None, None).await.is_err());
+        assert!(close_account(&pool, 2, Some(2), None).await.is_err());
+
+        let closed = close_account(&pool, 2, Some(1), Some("2025-06-30".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(closed["transferred_cents"], 12550);
+        assert_eq!(closed["archived"], true);
+        assert_eq!(closed["closed_date"], "2025-06-30");
+        assert_eq!(get_balance(&pool, 2).await.unwrap().amount_cents(), 0);
+        assert_eq!(get_balance(&pool, 1).await.unwrap().amount_cents(), 12550);
+        assert!(close_account(&pool, 2, None, None).await.is_err());
+
+        // Reopening clears the closing date
+        update_account(&pool, 2, "Savings".to_string(), "savings".to_string(), false, None)
+            .await
+            .unwrap();
+        let reopened = get_account(&pool, 2).await.unwrap();
+        assert_eq!(reopened["closed_date"], serde_json::Value::Null);
+
+        // Zero balance closes without a transfer
+        let closed = close_account(&pool, 2, None, None).await.unwrap();
+        assert_eq!(closed["transferred_cents"], 0);
     }
 }
@@ -0,0 +1,452 @@
+//! Receipts and other documents attached to transactions.
+//!
+//! Attachment files live outside the database in a content-addressed store, a
+//! per-ledger folder (`attachments/<ledger id>` in the app data directory)
+//! where each file is named after the SHA-256 hash of its contents and sharded
+//! by the hash's first two characters. The same receipt attached twice is
+//! stored once. The `attachments` table links a stored file to a transaction
+//! with its original file name and media type.
+//!
+//! Detaching only removes the link, so undo can bring it back. Files no longer
+//! linked to any transaction, nor to one in the undo history, are removed by
+//! [`collect_garbage()`]. Backups copy the files their snapshot links to into
+//! the backup folder's own store (see [`LedgerManager`](super::LedgerManager)).
+//!
+//! Attachment files are not encrypted along with an encrypted ledger, and
+//! portable JSON exports do not include them.
+
+use super::journal::journaled;
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Largest file that can be attached.
+pub const MAX_ATTACHMENT_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Media types by lowercase file extension; anything else is `application/octet-stream`.
+const MEDIA_TYPES: &[(&str, &str)] = &[
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("heic", "image/heic"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("txt", "text/plain"),
+    ("csv", "text/csv"),
+    ("html", "text/html"),
+    ("eml", "message/rfc822"),
+];
+
+/// Path of the file with the given hash in a store.
+fn stored_path(store: &Path, sha256: &str) -> PathBuf {
+    store.join(&sha256[..2]).join(sha256)
+}
+
+fn media_type(file_name: &str) -> &'static str {
+    let extension = Path::new(file_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    MEDIA_TYPES
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, media_type)| *media_type)
+        .unwrap_or("application/octet-stream")
+}
+
+/// Attaches a file to a transaction, copying it into the ledger's store.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `store` - The ledger's attachment store
+/// * `transaction_id` - Transaction to attach the file to; must not be in the trash
+/// * `source` - File to attach, e.g. a scanned receipt
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The new attachment, see [`get_attachments()`]
+/// * `Err(sqlx::Error)` - Unreadable or too large file, unknown transaction, or
+///   database failure
+///
+/// # Examples
+/// ```no_run
+/// let attachment = attach_file(&pool, &store, 42, Path::new("/home/sam/Scans/receipt.pdf")).await?;
+/// assert_eq!(attachment["media_type"], "application/pdf");
+/// ```
+pub async fn attach_file(
+    pool: &SqlitePool,
+    store: &Path,
+    transaction_id: i64,
+    source: &Path,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| sqlx::Error::Protocol(format!("{} is not a file", source.display())))?;
+    let size_bytes = std::fs::metadata(source)?.len();
+    if size_bytes > MAX_ATTACHMENT_BYTES {
+        return Err(sqlx::Error::Protocol(format!(
+            "{} is larger than {} MB",
+            file_name,
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        )));
+    }
+
+    let contents = std::fs::read(source)?;
+    let sha256: String = Sha256::digest(&contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    // Written before the row is inserted; a failed insert leaves an orphan for
+    // collect_garbage() rather than a row without a file
+    let path = stored_path(store, &sha256);
+    if !path.exists() {
+        std::fs::create_dir_all(path.parent().expect("stored path has a shard folder"))?;
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, &contents)?;
+        std::fs::rename(&partial, &path)?;
+    }
+
+    let attachment_id = journaled(pool, "Attach file", async |conn| {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM transactions WHERE id = ? AND deleted_at IS NULL)",
+        )
+        .bind(transaction_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        if !exists {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(sqlx::query(
+            "INSERT INTO attachments (transaction_id, sha256, file_name, media_type, size_bytes)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(transaction_id)
+        .bind(&sha256)
+        .bind(&file_name)
+        .bind(media_type(&file_name))
+        .bind(size_bytes as i64)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid())
+    })
+    .await?;
+
+    let attachment = sqlx::query(&format!("{} WHERE id = ?", ATTACHMENT_COLUMNS))
+        .bind(attachment_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(attachment_json(&attachment))
+}
+
+const ATTACHMENT_COLUMNS: &str =
+    "SELECT id, transaction_id, sha256, file_name, media_type, size_bytes, created_at FROM attachments";
+
+fn attachment_json(row: &sqlx::sqlite::SqliteRow) -> serde_json::Value {
+    serde_json::json!({
+        "id": row.get::<i64, _>("id"),
+        "transaction_id": row.get::<i64, _>("transaction_id"),
+        "sha256": row.get::<String, _>("sha256"),
+        "file_name": row.get::<String, _>("file_name"),
+        "media_type": row.get::<String, _>("media_type"),
+        "size_bytes": row.get::<i64, _>("size_bytes"),
+        "created_at": row.get::<Option<String>, _>("created_at")
+    })
+}
+
+/// Lists the files attached to a transaction, oldest first.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `transaction_id` - Transaction whose attachments to list
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with id, transaction_id, sha256,
+///   file_name, media_type, size_bytes and created_at
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_attachments(
+    pool: &SqlitePool,
+    transaction_id: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(&format!("{} WHERE transaction_id = ? ORDER BY id", ATTACHMENT_COLUMNS))
+        .bind(transaction_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.iter().map(attachment_json).collect())
+}
+
+/// Copies an attachment out of the store under its original file name.
+///
+/// The copy is what gets opened in the default app, so the file keeps a
+/// meaningful name and extension and the store's content-addressed file is
+/// never modified by an editor.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `store` - The ledger's attachment store
+/// * `attachment_id` - Attachment to check out
+/// * `target_dir` - Folder receiving the copy (created if missing)
+///
+/// # Returns
+/// * `Ok(PathBuf)` - Path of the copy
+/// * `Err(sqlx::Error)` - Unknown attachment, missing file, or copy failure
+pub async fn checkout_attachment(
+    pool: &SqlitePool,
+    store: &Path,
+    attachment_id: i64,
+    target_dir: &Path,
+) -> Result<PathBuf, sqlx::Error> {
+    let row = sqlx::query("SELECT sha256, file_name FROM attachments WHERE id = ?")
+        .bind(attachment_id)
+        .fetch_one(pool)
+        .await?;
+    let sha256: String = row.get("sha256");
+    let file_name: String = row.get("file_name");
+
+    let source = stored_path(store, &sha256);
+    if !source.is_file() {
+        return Err(sqlx::Error::Protocol(format!(
+            "the file of attachment {} is missing from the store",
+            file_name
+        )));
+    }
+
+    // One folder per attachment, so same-named receipts do not overwrite each other
+    let target = target_dir.join(attachment_id.to_string());
+    std::fs::create_dir_all(&target)?;
+    let target = target.join(&file_name);
+    std::fs::copy(&source, &target)?;
+
+    Ok(target)
+}
+
+/// Removes an attachment from its transaction. The file stays in the store
+/// until [`collect_garbage()`] finds it unused.
+pub async fn detach_file(pool: &SqlitePool, attachment_id: i64) -> Result<(), sqlx::Error> {
+    journaled(pool, "Detach file", async |conn| {
+        let result = sqlx::query("DELETE FROM attachments WHERE id = ?")
+            .bind(attachment_id)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Hashes of the files a ledger (or a backup of one) refers to.
+///
+/// Includes files linked by attachments in the undo history, so undoing a
+/// detach or an emptied trash finds its file again. Databases from before
+/// attachments existed refer to none.
+pub(crate) async fn referenced_hashes(pool: &SqlitePool) -> Result<HashSet<String>, sqlx::Error> {
+    let has_attachments: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'attachments'",
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_attachments {
+        return Ok(HashSet::new());
+    }
+
+    let hashes: Vec<String> = sqlx::query_scalar(
+        "SELECT sha256 FROM attachments
+         UNION
+         SELECT json_extract(image, '$.sha256') FROM (
+             SELECT old_values AS image FROM journal_changes WHERE table_name = 'attachments'
+             UNION ALL
+             SELECT new_values FROM journal_changes WHERE table_name = 'attachments'
+         ) WHERE image IS NOT NULL",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(hashes.into_iter().collect())
+}
+
+/// Copies the files a ledger refers to from one store to another.
+///
+/// Files already in the target store or missing from the source are skipped.
+///
+/// # Arguments
+/// * `pool` - Ledger (or backup) whose referenced files to copy
+/// * `from` - Store to copy from
+/// * `to` - Store to copy into (created if missing)
+///
+/// # Returns
+/// * `Ok(u64)` - Number of files copied
+/// * `Err(sqlx::Error)` - Database or file system failure
+pub(crate) async fn copy_files(pool: &SqlitePool, from: &Path, to: &Path) -> Result<u64, sqlx::Error> {
+    let mut copied = 0;
+    for sha256 in referenced_hashes(pool).await? {
+        let source = stored_path(from, &sha256);
+        let target = stored_path(to, &sha256);
+        if target.exists() || !source.is_file() {
+            continue;
+        }
+
+        std::fs::create_dir_all(target.parent().expect("stored path has a shard folder"))?;
+        let partial = target.with_extension("partial");
+        std::fs::copy(&source, &partial)?;
+        std::fs::rename(&partial, &target)?;
+        copied += 1;
+    }
+
+    Ok(copied)
+}
+
+/// Deletes every file in a store whose hash is not in `keep`.
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Number of deleted `files` and freed `bytes`
+/// * `Err(sqlx::Error)` - File system failure
+pub(crate) fn prune_store(store: &Path, keep: &HashSet<String>) -> Result<serde_json::Value, sqlx::Error> {
+    let (mut files, mut bytes) = (0u64, 0u64);
+    if store.exists() {
+        for shard in std::fs::read_dir(store)? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&shard)? {
+                let path = entry?.path();
+                let name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+                if name.is_some_and(|name| keep.contains(&name)) {
+                    continue;
+                }
+                bytes += std::fs::metadata(&path)?.len();
+                std::fs::remove_file(&path)?;
+                files += 1;
+            }
+            if std::fs::read_dir(&shard)?.next().is_none() {
+                std::fs::remove_dir(&shard)?;
+            }
+        }
+    }
+
+    Ok(serde_json::json!({ "files": files, "bytes": bytes }))
+}
+
+/// Deletes files in the ledger's store that nothing refers to any more.
+///
+/// Files still linked from a transaction, including one in the trash, or from
+/// an attachment in the undo history are kept. Also removes leftovers of
+/// interrupted copies.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `store` - The ledger's attachment store
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Number of deleted `files` and freed `bytes`
+/// * `Err(sqlx::Error)` - Database or file system failure
+pub async fn collect_garbage(pool: &SqlitePool, store: &Path) -> Result<serde_json::Value, sqlx::Error> {
+    let keep = referenced_hashes(pool).await?;
+
+    prune_store(store, &keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money};
+    use sqlx::SqlitePool;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("finsight-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        for description in ["Hardware Store", "Electronics"] {
+            crate::database::add_transaction(&pool, 1, Money::new(-25000, Currency::USD), "debit".to_string(), description.to_string(), "2025-04-02".to_string(), 1)
+                .await
+                .unwrap();
+        }
+
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_attach_checkout_and_detach() {
+        let dir = temp_dir("attachments");
+        let pool = setup_test_db().await;
+        let store = dir.join("store");
+        let receipt = dir.join("Receipt.PDF");
+        std::fs::write(&receipt, b"%PDF-1.7 receipt").unwrap();
+
+        let first = attach_file(&pool, &store, 1, &receipt).await.unwrap();
+        assert_eq!(first["media_type"], "application/pdf");
+        assert_eq!(first["size_bytes"], 16);
+        // The same file on another transaction is stored once
+        let second = attach_file(&pool, &store, 2, &receipt).await.unwrap();
+        assert_eq!(first["sha256"], second["sha256"]);
+        assert_eq!(std::fs::read_dir(&store).unwrap().count(), 1);
+        assert!(attach_file(&pool, &store, 99, &receipt).await.is_err());
+
+        let listed = get_attachments(&pool, 1).await.unwrap();
+        assert_eq!(listed, vec![first.clone()]);
+
+        let copy = checkout_attachment(&pool, &store, 1, &dir.join("open")).await.unwrap();
+        assert!(copy.ends_with("Receipt.PDF"));
+        assert_eq!(std::fs::read(&copy).unwrap(), b"%PDF-1.7 receipt");
+
+        detach_file(&pool, 1).await.unwrap();
+        assert!(get_attachments(&pool, 1).await.unwrap().is_empty());
+        crate::database::undo(&pool).await.unwrap();
+        assert_eq!(get_attachments(&pool, 1).await.unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_collect_garbage_keeps_referenced_files() {
+        let dir = temp_dir("attachments-gc");
+        let pool = setup_test_db().await;
+        let store = dir.join("store");
+        for (transaction_id, contents) in [(1, "warranty"), (2, "invoice")] {
+            let file = dir.join(format!("{}.txt", contents));
+            std::fs::write(&file, contents).unwrap();
+            attach_file(&pool, &store, transaction_id, &file).await.unwrap();
+        }
+        // A stray file, e.g. from an attach whose database insert failed
+        std::fs::create_dir_all(store.join("00")).unwrap();
+        std::fs::write(store.join("00").join("00ff"), b"orphan").unwrap();
+
+        detach_file(&pool, 2).await.unwrap();
+
+        // The detached file is still in the undo history
+        let removed = collect_garbage(&pool, &store).await.unwrap();
+        assert_eq!(removed["files"], 1);
+        assert_eq!(removed["bytes"], 6);
+        assert!(!store.join("00").exists());
+
+        // Once the history is gone, so is the file
+        sqlx::query("DELETE FROM journal_operations").execute(&pool).await.unwrap();
+        let removed = collect_garbage(&pool, &store).await.unwrap();
+        assert_eq!(removed["files"], 1);
+        assert_eq!(get_attachments(&pool, 1).await.unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+//! Audit trail of every change to accounts, categories, payees, tags, transactions
+//! and their attachments.
+//!
+//! Unlike the change journal, which only keeps recent user operations for
+//! undo, the audit log is permanent and records every insert, update and
+//! delete on the audited tables, however it was made: regular commands, undo
+//! and redo, imports and bulk edits alike. Each entry holds the row's JSON
+//! image before and after the change, the time, and the actor label set with
+//! [`set_audit_actor()`].
+//!
+//! The actor label belongs to the app instance, not the ledger: every
+//! connection the instance opens keeps it in a TEMP table, and a TEMP trigger
+//! copies it into each audit entry the connection writes. Instances working on
+//! shared books each record their own label, however they interleave.
+
+use super::journal::{JOURNALED_TABLES, json_image, table_columns};
+use super::unit_of_work;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::sync::RwLock;
+
+/// Tables whose changes are audited; the same tables the journal tracks.
+const AUDITED_TABLES: &[&str] = JOURNALED_TABLES;
+
+/// Actor recorded while no label has been set.
+const UNKNOWN_ACTOR: &str = "unknown";
+
+/// Actor label of this app instance, applied to its connections by [`attach_actor()`].
+static ACTOR: RwLock<Option<String>> = RwLock::new(None);
+
+/// Sets the label recorded as the actor of all following changes made by this
+/// app instance, in every ledger it opens.
+///
+/// # Arguments
+/// * `actor` - Who is making changes, for example a household member's name
+///
+/// # Returns
+/// * `Ok(())` - Actor label set
+/// * `Err(sqlx::Error)` - Empty label
+pub fn set_audit_actor(actor: String) -> Result<(), sqlx::Error> {
+    let actor = actor.trim();
+    if actor.is_empty() {
+        return Err(sqlx::Error::Protocol("actor label cannot be empty".to_string()));
+    }
+
+    *ACTOR.write().unwrap_or_else(|e| e.into_inner()) = Some(actor.to_string());
+
+    Ok(())
+}
+
+/// Brings a connection's TEMP actor table up to date with this instance's
+/// label and adds the TEMP trigger stamping it on new audit entries.
+///
+/// The pool runs this on every connection it opens or hands out, so a label
+/// set later reaches connections opened earlier. The trigger waits until the
+/// migrations have created the audit log.
+pub(crate) async fn attach_actor(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let actor = ACTOR.read().unwrap_or_else(|e| e.into_inner()).clone();
+
+    sqlx::query("CREATE TEMP TABLE IF NOT EXISTS audit_actor (id INTEGER PRIMARY KEY CHECK (id = 1), name TEXT NOT NULL)")
+        .execute(&mut *conn)
+        .await?;
+    if let Some(actor) = actor {
+        sqlx::query("INSERT INTO temp.audit_actor (id, name) VALUES (1, ?) ON CONFLICT (id) DO UPDATE SET name = excluded.name")
+            .bind(actor)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let audited: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM main.sqlite_master WHERE type = 'table' AND name = 'audit_log')",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    if audited {
+        sqlx::query(
+            "CREATE TEMP TRIGGER IF NOT EXISTS audit_log_actor AFTER INSERT ON main.audit_log
+             BEGIN
+                 UPDATE audit_log SET actor = COALESCE((SELECT name FROM temp.audit_actor WHERE id = 1), actor)
+                 WHERE id = NEW.id;
+             END",
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Retrieves the audit history of one record, oldest change first.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `table` - Record type: `"accounts"`, `"attachments"`, `"categories"`, `"custom_fields"`,
+///   `"payees"`, `"tags"`, `"transactions"` or `"transaction_tags"`
+/// * `row_id` - Id of the record
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Audit entries with id, table_name, row_id, action,
+///   old_values, new_values, actor and changed_at; deleted records keep their history
+/// * `Err(sqlx::Error)` - Unknown record type or database failure
+///
+/// # Examples
+/// ```no_run
+/// let history = get_record_history(&pool, "transactions", 42).await?;
+/// for entry in history {
+///     println!("{} {} by {}", entry["changed_at"], entry["action"], entry["actor"]);
+/// }
+/// ```
+pub async fn get_record_history(
+    pool: &SqlitePool,
+    table: &str,
+    row_id: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    if !AUDITED_TABLES.contains(&table) {
+        return Err(sqlx::Error::Protocol(format!("unknown record type: {}", table)));
+    }
+
+    let rows = sqlx::query(
+        "SELECT id, table_name, row_id, action, old_values, new_values, actor, changed_at
+         FROM audit_log
+         WHERE table_name = ? AND row_id = ?
+         ORDER BY id",
+    )
+    .bind(table)
+    .bind(row_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter().map(entry_json).collect()
+}
+
+/// Retrieves the most recent changes across all audited records, newest first.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `limit` - Maximum number of entries to return
+/// * `before_id` - Only return entries older than this entry id, to load further pages
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Audit entries in the same shape as [`get_record_history()`]
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_activity_feed(
+    pool: &SqlitePool,
+    limit: i64,
+    before_id: Option<i64>,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT id, table_name, row_id, action, old_values, new_values, actor, changed_at
+         FROM audit_log
+         WHERE ? IS NULL OR id < ?
+         ORDER BY id DESC
+         LIMIT ?",
+    )
+    .bind(before_id)
+    .bind(before_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter().map(entry_json).collect()
+}
+
+fn entry_json(row: &sqlx::sqlite::SqliteRow) -> Result<serde_json::Value, sqlx::Error> {
+    let image = |column: &str| -> Result<serde_json::Value, sqlx::Error> {
+        match row.get::<Option<String>, _>(column) {
+            Some(json) => serde_json::from_str(&json).map_err(|e| sqlx::Error::Decode(Box::new(e))),
+            None => Ok(serde_json::Value::Null),
+        }
+    };
+
+    Ok(serde_json::json!({
+        "id": row.get::<i64, _>("id"),
+        "table_name": row.get::<String, _>("table_name"),
+        "row_id": row.get::<i64, _>("row_id"),
+        "action": row.get::<String, _>("action"),
+        "old_values": image("old_values")?,
+        "new_values": image("new_values")?,
+        "actor": row.get::<String, _>("actor"),
+        "changed_at": row.get::<String, _>("changed_at")
+    }))
+}
+
+/// (Re)creates the audit triggers from the current table definitions.
+///
+/// Updates that leave every column unchanged are not recorded. Entries are
+/// written with the unknown actor, which the connection's TEMP trigger from
+/// [`attach_actor()`] replaces with the instance's label.
+pub(crate) async fn install_triggers(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        for table in AUDITED_TABLES {
+            let columns = table_columns(&mut *conn, table).await?;
+            let old_image = json_image(&columns, "OLD");
+            let new_image = json_image(&columns, "NEW");
+
+            for (event, row_id, old_values, new_values, condition) in [
+                ("INSERT", "NEW.id", "NULL", new_image.as_str(), String::new()),
+                ("UPDATE", "NEW.id", old_image.as_str(), new_image.as_str(), format!("WHEN {} IS NOT {}", old_image, new_image)),
+                ("DELETE", "OLD.id", old_image.as_str(), "NULL", String::new()),
+            ] {
+                let action = event.to_lowercase();
+                let name = format!("audit_{}_{}", table, action);
+                sqlx::query(&format!("DROP TRIGGER IF EXISTS {}", name))
+                    .execute(&mut *conn)
+                    .await?;
+                sqlx::query(&format!(
+                    "CREATE TRIGGER {name} AFTER {event} ON {table}
+                     {condition}
+                     BEGIN
+                         INSERT INTO audit_log (table_name, row_id, action, old_values, new_values, actor)
+                         VALUES ('{table}', {row_id}, '{action}', {old_values}, {new_values}, '{UNKNOWN_ACTOR}');
+                     END"
+                ))
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_record_history_tracks_actor_and_images() {
+        let dir = std::env::temp_dir().join(format!("finsight-audit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("shared.db");
+        let pool = crate::database::init_db(&path, None).await.unwrap();
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+
+        set_audit_actor("Alex".to_string()).unwrap();
+        crate::database::add_transaction(&pool, 1, Money::new(-4200, Currency::USD), "debit".to_string(), "Hardware store".to_string(), "2025-04-02".to_string(), 1)
+            .await
+            .unwrap();
+        set_audit_actor("Sam".to_string()).unwrap();
+        crate::database::update_transaction(&pool, 1, 1, Money::new(-4500, Currency::USD), "debit".to_string(), "Hardware store".to_string(), "2025-04-02".to_string(), 1, None)
+            .await
+            .unwrap();
+        crate::database::delete_transaction(&pool, 1).await.unwrap();
+        crate::database::purge_trash(&pool, 0).await.unwrap();
+
+        let history = get_record_history(&pool, "transactions", 1).await.unwrap();
+        let actions: Vec<&str> = history.iter().map(|e| e["action"].as_str().unwrap()).collect();
+        assert_eq!(actions, vec!["insert", "update", "update", "delete"]);
+        assert_eq!(history[0]["actor"], "Alex");
+        assert_eq!(history[1]["actor"], "Sam");
+        assert_eq!(history[1]["old_values"]["amount_cents"], -4200);
+        assert_eq!(history[1]["new_values"]["amount_cents"], -4500);
+        assert!(history[2]["new_values"]["deleted_at"].is_string()); // Moved to the trash
+        assert!(history[3]["new_values"].is_null());
+
+        assert!(get_record_history(&pool, "journal_changes", 1).await.is_err());
+
+        // Another instance sharing the file records its own actor, not Sam
+        let other = SqlitePool::connect_with(sqlx::sqlite::SqliteConnectOptions::new().filename(&path))
+            .await
+            .unwrap();
+        crate::database::add_category(&other, "Garden".to_string(), None).await.unwrap();
+        crate::database::add_category(&pool, "Tools".to_string(), None).await.unwrap();
+        let feed = get_activity_feed(&pool, 2, None).await.unwrap();
+        assert_eq!(feed[0]["actor"], "Sam");
+        assert_eq!(feed[1]["actor"], UNKNOWN_ACTOR);
+
+        other.close().await;
+        pool.close().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_activity_feed_pages_newest_first() {
+        let pool = setup_test_db().await;
+        crate::database::add_category(&pool, "Rent".to_string(), None).await.unwrap();
+        // Changes nothing, so nothing is recorded
+        crate::database::update_category(&pool, 2, "Rent".to_string(), None).await.unwrap();
+
+        let feed = get_activity_feed(&pool, 2, None).await.unwrap();
+        assert_eq!(feed.len(), 2);
+        assert_eq!(feed[0]["table_name"], "categories");
+        assert_eq!(feed[0]["action"], "insert");
+        assert_eq!(feed[0]["actor"], UNKNOWN_ACTOR);
+        assert_eq!(feed[1]["table_name"], "accounts");
+
+        let older = get_activity_feed(&pool, 10, feed[1]["id"].as_i64()).await.unwrap();
+        assert!(older.iter().all(|e| e["id"].as_i64() < feed[1]["id"].as_i64()));
+    }
+}
@@ -0,0 +1,479 @@
+//! Rotating snapshot backups of ledger databases.
+//!
+//! Backups are consistent copies taken with `VACUUM INTO`, stored as plain SQLite
+//! files in a per-ledger folder under the app data directory and named after the
+//! ledger and the UTC time they were taken (`household-20250815-071500.db`).
+//!
+//! Old backups are pruned with a grandfather-father-son scheme configured by
+//! [`BackupPolicy`]: the newest backup of each of the last N days, weeks and
+//! months is kept, and everything else is deleted. The newest backup is never
+//! pruned.
+//!
+//! Files attached to transactions are copied into the backup folder's own
+//! attachment store (`attachments/` next to the backup files) when a backup is
+//! taken, and pruned once no remaining backup refers to them.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use sqlx::sqlite::SqliteConnectOptions;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many daily, weekly and monthly backups to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupPolicy {
+    pub daily: usize,
+    pub weekly: usize,
+    pub monthly: usize,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            daily: 7,
+            weekly: 4,
+            monthly: 12,
+        }
+    }
+}
+
+/// Maps a day number to the retention period (day, week or month) it falls in.
+type PeriodOf = fn(i64) -> i64;
+
+/// A backup file in a ledger's backup folder.
+struct BackupFile {
+    path: PathBuf,
+    /// Days since 1970-01-01 (UTC) parsed from the file name
+    day: i64,
+    /// `YYYYMMDD-HHMMSS` stamp parsed from the file name; sorts chronologically
+    stamp: String,
+    /// Suffix distinguishing backups taken within the same second
+    counter: u32,
+}
+
+impl BackupFile {
+    fn sort_key(&self) -> (&str, u32) {
+        (&self.stamp, self.counter)
+    }
+}
+
+/// Takes a consistent snapshot of a ledger into its backup folder.
+///
+/// # Arguments
+/// * `pool` - Connection pool of the ledger to back up
+/// * `backup_dir` - The ledger's backup folder (created if missing)
+/// * `ledger_name` - Ledger name used as the file name prefix
+/// * `passphrase` - Passphrase of an encrypted ledger; the backup is encrypted with it
+///
+/// # Returns
+/// * `Ok(PathBuf)` - Path of the new backup file
+/// * `Err(sqlx::Error)` - Folder creation or snapshot failure
+pub async fn create_backup(
+    pool: &SqlitePool,
+    backup_dir: &Path,
+    ledger_name: &str,
+    passphrase: Option<&str>,
+) -> Result<PathBuf, sqlx::Error> {
+    std::fs::create_dir_all(backup_dir)?;
+
+    let stamp = utc_stamp(SystemTime::now());
+    let mut path = backup_dir.join(format!("{}-{}.db", ledger_name, stamp));
+    let mut counter = 1;
+    while path.exists() {
+        path = backup_dir.join(format!("{}-{}-{}.db", ledger_name, stamp, counter));
+        counter += 1;
+    }
+
+    super::location::snapshot_to(pool, &path, passphrase).await?;
+
+    Ok(path)
+}
+
+/// Deletes backups that fall outside the retention policy.
+///
+/// Keeps the newest backup of each of the `daily` most recent days, `weekly`
+/// most recent weeks (Monday-based) and `monthly` most recent months that have
+/// backups, plus the newest backup overall.
+///
+/// # Arguments
+/// * `backup_dir` - The ledger's backup folder
+/// * `policy` - Retention counts per tier
+///
+/// # Returns
+/// * `Ok(Vec<PathBuf>)` - Paths of the deleted backups
+/// * `Err(sqlx::Error)` - Folder listing or file deletion failure
+pub fn rotate_backups(backup_dir: &Path, policy: BackupPolicy) -> Result<Vec<PathBuf>, sqlx::Error> {
+    let mut backups = read_backup_dir(backup_dir)?;
+    backups.sort_by(|a, b| b.sort_key().cmp(&a.sort_key())); // Newest first
+
+    let mut keep: HashSet<PathBuf> = HashSet::new();
+    if let Some(newest) = backups.first() {
+        keep.insert(newest.path.clone());
+    }
+
+    let tiers: [(usize, PeriodOf); 3] = [
+        (policy.daily, |day| day),
+        (policy.weekly, |day| (day + 3).div_euclid(7)),
+        (policy.monthly, |day| {
+            let (year, month, _) = civil_from_days(day);
+            year * 12 + month
+        }),
+    ];
+    for (count, period_of) in tiers {
+        let mut seen = HashSet::new();
+        for backup in &backups {
+            let period = period_of(backup.day);
+            if seen.len() >= count && !seen.contains(&period) {
+                break;
+            }
+            if seen.insert(period) {
+                keep.insert(backup.path.clone());
+            }
+        }
+    }
+
+    let mut removed = Vec::new();
+    for backup in backups {
+        if !keep.contains(&backup.path) {
+            std::fs::remove_file(&backup.path)?;
+            removed.push(backup.path);
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Lists a ledger's backups, newest first, with size and transaction counts.
+///
+/// Each backup is opened read-only to count its transactions, so the listing
+/// also reveals unreadable (corrupted) backups: their count is `null`.
+///
+/// # Arguments
+/// * `backup_dir` - The ledger's backup folder
+/// * `passphrase` - Passphrase of an encrypted ledger, used to open its backups
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with file_name, path, size_bytes,
+///   created_at (UTC, `YYYY-MM-DD HH:MM:SS`) and transaction_count
+/// * `Err(sqlx::Error)` - Folder listing failure
+pub async fn list_backups(
+    backup_dir: &Path,
+    passphrase: Option<&str>,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let mut backups = read_backup_dir(backup_dir)?;
+    backups.sort_by(|a, b| b.sort_key().cmp(&a.sort_key()));
+
+    let mut result = Vec::new();
+    for backup in backups {
+        let size_bytes = std::fs::metadata(&backup.path)?.len();
+        let transaction_count = count_transactions(&backup.path, passphrase).await.ok();
+        let stamp = &backup.stamp;
+
+        result.push(serde_json::json!({
+            "file_name": backup.path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            "path": backup.path.to_string_lossy(),
+            "size_bytes": size_bytes,
+            "created_at": format!(
+                "{}-{}-{} {}:{}:{}",
+                &stamp[0..4], &stamp[4..6], &stamp[6..8], &stamp[9..11], &stamp[11..13], &stamp[13..15]
+            ),
+            "transaction_count": transaction_count
+        }));
+    }
+
+    Ok(result)
+}
+
+/// Deletes files from the backup folder's attachment store that no backup refers to.
+///
+/// Nothing is deleted when a backup cannot be opened, since its references are
+/// unknown.
+///
+/// # Arguments
+/// * `backup_dir` - The ledger's backup folder
+/// * `passphrase` - Passphrase of an encrypted ledger, used to open its backups
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Number of deleted `files` and freed `bytes`
+/// * `Err(sqlx::Error)` - Unreadable backup or file system failure
+pub async fn prune_backup_attachments(
+    backup_dir: &Path,
+    passphrase: Option<&str>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let mut keep = HashSet::new();
+    for backup in read_backup_dir(backup_dir)? {
+        let pool = open_read_only(&backup.path, passphrase).await?;
+        let hashes = super::attachments::referenced_hashes(&pool).await;
+        pool.close().await;
+        keep.extend(hashes?);
+    }
+
+    super::attachments::prune_store(&backup_dir.join("attachments"), &keep)
+}
+
+/// Re-keys every backup in a ledger's backup folder after a passphrase change.
+///
+/// Each backup is copied under the new key with
+/// [`super::encryption::export_copy()`] and the copy renamed over the original,
+/// so a failure leaves that backup readable under its old key.
+///
+/// # Arguments
+/// * `backup_dir` - The ledger's backup folder
+/// * `current` - Key the backups were written with, or `None` for plain files
+/// * `new` - Key for the backups, or `None` to store them unencrypted
+///
+/// # Returns
+/// * `Ok(usize)` - Number of re-keyed backups
+/// * `Err(sqlx::Error)` - A backup cannot be opened with `current` or re-keyed
+pub async fn rekey_backups(
+    backup_dir: &Path,
+    current: Option<&str>,
+    new: Option<&str>,
+) -> Result<usize, sqlx::Error> {
+    let mut rekeyed = 0;
+    for backup in read_backup_dir(backup_dir)? {
+        let mut converted = backup.path.as_os_str().to_owned();
+        converted.push(".rekey");
+        let converted = PathBuf::from(converted);
+        super::location::remove_database_files(&converted)?;
+
+        // Lets the export attach (and so create) the re-keyed copy
+        let mut options = SqliteConnectOptions::new()
+            .filename(&backup.path)
+            .create_if_missing(true);
+        if let Some(passphrase) = current {
+            options = options.pragma("key", super::encryption::quote_key(passphrase));
+        }
+        let pool = SqlitePool::connect_with(options).await?;
+        let exported = super::encryption::export_copy(&pool, &converted, new).await;
+        pool.close().await;
+        if let Err(e) = exported {
+            let _ = super::location::remove_database_files(&converted);
+            return Err(e);
+        }
+
+        std::fs::rename(&converted, &backup.path)?;
+        rekeyed += 1;
+    }
+
+    Ok(rekeyed)
+}
+
+/// Opens a backup file read-only.
+async fn open_read_only(path: &Path, passphrase: Option<&str>) -> Result<SqlitePool, sqlx::Error> {
+    let mut options = SqliteConnectOptions::new().filename(path).read_only(true);
+    if let Some(passphrase) = passphrase {
+        options = options.pragma("key", super::encryption::quote_key(passphrase));
+    }
+
+    SqlitePool::connect_with(options).await
+}
+
+/// Counts the transactions outside the trash in a database file without modifying it.
+async fn count_transactions(path: &Path, passphrase: Option<&str>) -> Result<i64, sqlx::Error> {
+    let pool = open_read_only(path, passphrase).await?;
+    let count = count_live_transactions(&pool).await;
+    pool.close().await;
+
+    count
+}
+
+/// Backups taken before the trash existed have no `deleted_at` column.
+async fn count_live_transactions(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let has_trash: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('transactions') WHERE name = 'deleted_at'",
+    )
+    .fetch_one(pool)
+    .await?;
+    let sql = if has_trash {
+        "SELECT COUNT(*) FROM transactions WHERE deleted_at IS NULL"
+    } else {
+        "SELECT COUNT(*) FROM transactions"
+    };
+
+    sqlx::query_scalar(sql).fetch_one(pool).await
+}
+
+/// Reads all backup files whose names end in a `YYYYMMDD-HHMMSS` stamp.
+fn read_backup_dir(backup_dir: &Path) -> Result<Vec<BackupFile>, sqlx::Error> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(backup_dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "db") {
+            continue;
+        }
+        let Some(stem) = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        if let Some((stamp, counter, day)) = parse_stamp(&stem) {
+            backups.push(BackupFile {
+                path,
+                day,
+                stamp,
+                counter,
+            });
+        }
+    }
+
+    Ok(backups)
+}
+
+/// Extracts the `YYYYMMDD-HHMMSS` stamp and optional `-N` counter from a file stem.
+fn parse_stamp(stem: &str) -> Option<(String, u32, i64)> {
+    let is_digits = |part: &str, len: usize| {
+        part.len() == len && part.chars().all(|c| c.is_ascii_digit())
+    };
+
+    let parts: Vec<&str> = stem.split('-').collect();
+    let (date, time, counter) = match parts.as_slice() {
+        [.., date, time] if is_digits(date, 8) && is_digits(time, 6) => (*date, *time, 0),
+        [.., date, time, counter] if is_digits(date, 8) && is_digits(time, 6) => {
+            (*date, *time, counter.parse().ok()?)
+        }
+        _ => return None,
+    };
+
+    let year: i64 = date[0..4].parse().ok()?;
+    let month: i64 = date[4..6].parse().ok()?;
+    let day: i64 = date[6..8].parse().ok()?;
+
+    Some((
+        format!("{}-{}", date, time),
+        counter,
+        days_from_civil(year, month, day),
+    ))
+}
+
+/// Formats a point in time as a UTC `YYYYMMDD-HHMMSS` stamp.
+fn utc_stamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rem = secs.rem_euclid(86_400);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Converts a proleptic Gregorian date to days since 1970-01-01.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian (year, month, day).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("finsight-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_civil_date_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+        assert_eq!(
+            utc_stamp(UNIX_EPOCH + std::time::Duration::from_secs(1_755_242_100)),
+            "20250815-071500"
+        );
+    }
+
+    #[test]
+    fn test_rotate_backups_keeps_one_per_period() {
+        let dir = temp_dir("rotate");
+
+        // Two backups per day for 40 days, ending 2025-08-15
+        for offset in 0..40 {
+            let (y, m, d) = civil_from_days(days_from_civil(2025, 8, 15) - offset);
+            for time in ["080000", "200000"] {
+                std::fs::write(dir.join(format!("books-{:04}{:02}{:02}-{}.db", y, m, d, time)), b"").unwrap();
+            }
+        }
+
+        let policy = BackupPolicy {
+            daily: 3,
+            weekly: 2,
+            monthly: 2,
+        };
+        rotate_backups(&dir, policy).unwrap();
+
+        let mut kept: Vec<String> = read_backup_dir(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.stamp)
+            .collect();
+        kept.sort();
+        assert_eq!(
+            kept,
+            vec![
+                "20250731-200000", // Newest of July
+                "20250810-200000", // Newest of the previous week (Aug 4-10)
+                "20250813-200000",
+                "20250814-200000",
+                "20250815-200000", // Newest overall, of the week and of August
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_and_list_backups() {
+        let dir = temp_dir("backups");
+        let pool = crate::database::init_db(&dir.join("books.db"), None).await.unwrap();
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_transaction(&pool, 1, Money::new(-1250, Currency::USD), "debit".to_string(), "Coffee".to_string(), "2025-08-15".to_string(), 1)
+            .await
+            .unwrap();
+
+        let first = create_backup(&pool, &dir.join("backups"), "books", None).await.unwrap();
+        let second = create_backup(&pool, &dir.join("backups"), "books", None).await.unwrap();
+        assert_ne!(first, second); // Same-second backups get a counter suffix
+
+        let backups = list_backups(&dir.join("backups"), None).await.unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0]["transaction_count"], 1);
+        assert!(backups[0]["size_bytes"].as_u64().unwrap() > 0);
+
+        pool.close().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
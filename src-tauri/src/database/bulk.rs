@@ -0,0 +1,441 @@
+//! Batch edits, tagging and deletions over many transactions at once.
+//!
+//! Every bulk command selects its transactions with a [`TransactionSelection`]
+//! and runs as a single journaled operation: either every selected row is
+//! changed or, if any row fails, none is. The result lists the outcome per
+//! requested row, so the UI can report ids that no longer exist.
+
+use super::accounts::account_currency;
+use super::categories::check_category_assignable;
+use super::journal::journaled;
+use super::payees::resolve_payee;
+use super::search::{TransactionFilter, load_saved_filter};
+use super::tags::{clean_tag_name, ensure_tag};
+use super::transactions::{check_trade_cash_leg, check_transfer_edit, trash_transaction};
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+
+/// Which transactions a bulk command applies to.
+///
+/// Serialized as exactly one of `{ "ids": [1, 2] }`, `{ "filter": { ... } }` or
+/// `{ "saved_filter": 3 }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionSelection {
+    /// Explicit transaction ids, e.g. the rows ticked in the register
+    Ids(Vec<i64>),
+    /// Every transaction matching a filter
+    Filter(Box<TransactionFilter>),
+    /// Every transaction matching a saved filter
+    SavedFilter(i64),
+}
+
+/// Changes applied to every selected transaction. Empty fields stay unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BulkEdit {
+    pub category_id: Option<i64>,
+    pub account_id: Option<i64>,
+    /// Days to move each transaction's date by; negative moves it earlier
+    pub date_shift_days: Option<i64>,
+    /// New description; also relinks the transactions to the matching payee
+    pub description: Option<String>,
+}
+
+impl BulkEdit {
+    fn is_empty(&self) -> bool {
+        self.category_id.is_none()
+            && self.account_id.is_none()
+            && self.date_shift_days.is_none()
+            && self.description.is_none()
+    }
+}
+
+/// Applies the same edit to every selected transaction.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `selection` - Transactions to edit
+/// * `edit` - Fields to change; at least one must be set
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - One `{id, status}` object per selected id, with
+///   status `"updated"` or `"not_found"`
+/// * `Err(sqlx::Error)` - Empty edit, unknown saved filter, or a row failed (for
+///   example an unknown or trashed category, an account in another currency, a
+///   new account or date for a transfer leg, or the cash side of a trade); the
+///   message names the row and nothing is changed
+///
+/// # Examples
+/// ```no_run
+/// let edit = BulkEdit { date_shift_days: Some(-1), ..Default::default() };
+/// let results = bulk_update_transactions(&pool, &TransactionSelection::Ids(vec![4, 5]), &edit).await?;
+/// ```
+pub async fn bulk_update_transactions(
+    pool: &SqlitePool,
+    selection: &TransactionSelection,
+    edit: &BulkEdit,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    apply_edit(pool, "Edit transactions", selection, edit).await
+}
+
+/// Moves every selected transaction to one category.
+///
+/// A shorthand for [`bulk_update_transactions()`] with only `category_id` set,
+/// recorded as its own kind of operation in the undo history.
+pub async fn bulk_recategorize_transactions(
+    pool: &SqlitePool,
+    selection: &TransactionSelection,
+    category_id: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let edit = BulkEdit {
+        category_id: Some(category_id),
+        ..Default::default()
+    };
+
+    apply_edit(pool, "Recategorize transactions", selection, &edit).await
+}
+
+/// Moves every selected transaction to the trash, with the other legs of
+/// selected transfers.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `selection` - Transactions to delete
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - One `{id, status}` object per selected id, with
+///   status `"deleted"` or `"not_found"`
+/// * `Err(sqlx::Error)` - Unknown saved filter, the cash side of a trade among
+///   the selection, or database failure; nothing is deleted
+pub async fn bulk_delete_transactions(
+    pool: &SqlitePool,
+    selection: &TransactionSelection,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    journaled(pool, "Delete transactions", async |conn| {
+        let ids = resolve_selection(&mut *conn, selection).await?;
+
+        let mut trashed = Vec::new();
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            // Both legs of a transfer may be selected; the first one trashed the other
+            if !trashed.contains(&id) {
+                trashed.extend(trash_transaction(&mut *conn, id).await.map_err(|e| row_error(id, e))?);
+            }
+
+            results.push(row_result(id, trashed.contains(&id) as u64, "deleted"));
+        }
+
+        Ok(results)
+    })
+    .await
+}
+
+/// Adds tags to every selected transaction, creating tags that do not exist yet.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `selection` - Transactions to tag
+/// * `tags` - Tag names, e.g. `["italy-2025"]`; a leading `#` is dropped
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - One `{id, status}` object per selected id, with
+///   status `"tagged"` or `"not_found"`; already tagged transactions count as tagged
+/// * `Err(sqlx::Error)` - No or empty tag names, unknown saved filter, or database
+///   failure; nothing is changed
+pub async fn bulk_tag_transactions(
+    pool: &SqlitePool,
+    selection: &TransactionSelection,
+    tags: &[String],
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    if tags.is_empty() {
+        return Err(sqlx::Error::Protocol("no tags to apply".to_string()));
+    }
+
+    journaled(pool, "Tag transactions", async |conn| {
+        let ids = resolve_selection(&mut *conn, selection).await?;
+        let mut tag_ids = Vec::with_capacity(tags.len());
+        for tag in tags {
+            tag_ids.push(ensure_tag(&mut *conn, tag).await?);
+        }
+
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            let exists: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM transactions WHERE id = ? AND deleted_at IS NULL)",
+            )
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            if exists {
+                for tag_id in &tag_ids {
+                    sqlx::query("INSERT OR IGNORE INTO transaction_tags (transaction_id, tag_id) VALUES (?, ?)")
+                        .bind(id)
+                        .bind(tag_id)
+                        .execute(&mut *conn)
+                        .await
+                        .map_err(|e| row_error(id, e))?;
+                }
+            }
+            results.push(row_result(id, exists as u64, "tagged"));
+        }
+
+        Ok(results)
+    })
+    .await
+}
+
+/// Removes tags from every selected transaction. The tags themselves are kept.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `selection` - Transactions to untag
+/// * `tags` - Tag names to remove; unknown names are ignored
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - One `{id, status}` object per selected id, with
+///   status `"untagged"` or `"not_found"`
+/// * `Err(sqlx::Error)` - Unknown saved filter or database failure; nothing is changed
+pub async fn bulk_untag_transactions(
+    pool: &SqlitePool,
+    selection: &TransactionSelection,
+    tags: &[String],
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let names = tags
+        .iter()
+        .map(|tag| clean_tag_name(tag))
+        .collect::<Result<Vec<String>, sqlx::Error>>()?;
+    let names = serde_json::to_string(&names).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+    journaled(pool, "Untag transactions", async |conn| {
+        let ids = resolve_selection(&mut *conn, selection).await?;
+
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            let exists: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM transactions WHERE id = ? AND deleted_at IS NULL)",
+            )
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            if exists {
+                sqlx::query(
+                    "DELETE FROM transaction_tags WHERE transaction_id = ?
+                       AND tag_id IN (SELECT id FROM tags WHERE name IN (SELECT value FROM json_each(?)))",
+                )
+                .bind(id)
+                .bind(&names)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| row_error(id, e))?;
+            }
+            results.push(row_result(id, exists as u64, "untagged"));
+        }
+
+        Ok(results)
+    })
+    .await
+}
+
+async fn apply_edit(
+    pool: &SqlitePool,
+    label: &str,
+    selection: &TransactionSelection,
+    edit: &BulkEdit,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    if edit.is_empty() {
+        return Err(sqlx::Error::Protocol("no changes to apply".to_string()));
+    }
+    let date_shift = edit.date_shift_days.map(|days| format!("{:+} days", days));
+
+    journaled(pool, label, async |conn| {
+        let ids = resolve_selection(&mut *conn, selection).await?;
+        if let Some(category_id) = edit.category_id {
+            check_category_assignable(&mut *conn, category_id).await?;
+        }
+        let payee_id = match &edit.description {
+            Some(description) => Some(resolve_payee(&mut *conn, description).await?.payee_id),
+            None => None,
+        };
+        let target_currency = match edit.account_id {
+            Some(account_id) => Some(account_currency(&mut *conn, account_id).await?.to_string()),
+            None => None,
+        };
+
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            check_trade_cash_leg(&mut *conn, id).await.map_err(|e| row_error(id, e))?;
+            check_transfer_edit(&mut *conn, id, edit.account_id.is_some() || edit.date_shift_days.is_some())
+                .await
+                .map_err(|e| row_error(id, e))?;
+            // Amounts are minor units of the account's currency and are not converted
+            if let Some(target_currency) = &target_currency {
+                let currency: Option<String> = sqlx::query_scalar(
+                    "SELECT a.currency FROM transactions t JOIN accounts a ON a.id = t.account_id
+                     WHERE t.id = ? AND t.deleted_at IS NULL",
+                )
+                .bind(id)
+                .fetch_optional(&mut *conn)
+                .await?;
+                if let Some(currency) = currency.filter(|currency| currency != target_currency) {
+                    return Err(row_error(
+                        id,
+                        sqlx::Error::Protocol(format!("it is in {}, the new account in {}", currency, target_currency)),
+                    ));
+                }
+            }
+            let updated = sqlx::query(
+                "UPDATE transactions SET
+                    category_id = COALESCE(?, category_id),
+                    account_id = COALESCE(?, account_id),
+                    transaction_date = COALESCE(date(transaction_date, ?), transaction_date),
+                    description = COALESCE(?, description),
+                    payee_id = COALESCE(?, payee_id)
+                 WHERE id = ? AND deleted_at IS NULL",
+            )
+            .bind(edit.category_id)
+            .bind(edit.account_id)
+            .bind(&date_shift)
+            .bind(&edit.description)
+            .bind(payee_id)
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| row_error(id, e))?
+            .rows_affected();
+
+            results.push(row_result(id, updated, "updated"));
+        }
+
+        Ok(results)
+    })
+    .await
+}
+
+/// Resolves a selection to transaction ids, inside the operation's transaction.
+///
+/// Explicit ids keep their order with duplicates removed; filters yield ids in
+/// ascending order.
+async fn resolve_selection(
+    conn: &mut SqliteConnection,
+    selection: &TransactionSelection,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let filter = match selection {
+        TransactionSelection::Ids(ids) => {
+            let mut unique = Vec::with_capacity(ids.len());
+            for id in ids {
+                if !unique.contains(id) {
+                    unique.push(*id);
+                }
+            }
+            return Ok(unique);
+        }
+        TransactionSelection::Filter(filter) => filter.as_ref().clone(),
+        TransactionSelection::SavedFilter(filter_id) => {
+            load_saved_filter(&mut *conn, *filter_id).await?
+        }
+    };
+
+    let mut builder = QueryBuilder::<Sqlite>::new("SELECT t.id");
+    filter.push_from_where(&mut builder, "transactions")?;
+    builder.push(" ORDER BY t.id");
+
+    builder.build_query_scalar().fetch_all(&mut *conn).await
+}
+
+fn row_result(id: i64, rows_affected: u64, status: &str) -> serde_json::Value {
+    serde_json::json!({
+        "id": id,
+        "status": if rows_affected > 0 { status } else { "not_found" }
+    })
+}
+
+fn row_error(id: i64, error: sqlx::Error) -> sqlx::Error {
+    sqlx::Error::Protocol(format!("transaction {}: {}", id, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Groceries".to_string(), None).await.unwrap();
+        for (description, date) in [("Market", "2025-03-01"), ("AMAZON", "2025-03-02"), ("Amazon", "2025-03-03")] {
+            crate::database::add_transaction(&pool, 1, Money::new(-1000, Currency::USD), "debit".to_string(), description.to_string(), date.to_string(), 1)
+                .await
+                .unwrap();
+        }
+
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_bulk_update_by_ids_reports_per_row() {
+        let pool = setup_test_db().await;
+
+        let edit = BulkEdit {
+            category_id: Some(2),
+            date_shift_days: Some(-2),
+            ..Default::default()
+        };
+        let results = bulk_update_transactions(&pool, &TransactionSelection::Ids(vec![1, 99, 1]), &edit)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["status"], "updated");
+        assert_eq!(results[1]["status"], "not_found");
+
+        let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
+        let market = transactions.iter().find(|tx| tx["id"] == 1).unwrap();
+        assert_eq!(market["category_id"], 2);
+        assert_eq!(market["transaction_date"], "2025-02-27");
+
+        // The operation was journaled as one undo entry
+        let history = crate::database::get_undo_history(&pool, 1).await.unwrap();
+        assert_eq!(history[0]["label"], "Edit transactions");
+        assert_eq!(history[0]["changes"], 1);
+
+        // Moving to an account in another currency would relabel the amounts
+        let details = crate::database::AccountDetails { currency: Some("EUR".to_string()), ..Default::default() };
+        crate::database::add_account_with_details(&pool, "Girokonto".to_string(), "checking".to_string(), &details)
+            .await
+            .unwrap();
+        let edit = BulkEdit { account_id: Some(2), ..Default::default() };
+        assert!(bulk_update_transactions(&pool, &TransactionSelection::Ids(vec![1]), &edit).await.is_err());
+        assert_eq!(crate::database::get_transactions(&pool, 1).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_recategorize_saved_filter_is_atomic() {
+        let pool = setup_test_db().await;
+        let filter = TransactionFilter {
+            query: Some("amazon".to_string()),
+            ..Default::default()
+        };
+        let filter_id = crate::database::save_filter(&pool, "Amazon".to_string(), &filter).await.unwrap();
+
+        // Unknown category: the whole batch fails and nothing changes
+        let selection = TransactionSelection::SavedFilter(filter_id);
+        assert!(bulk_recategorize_transactions(&pool, &selection, 42).await.is_err());
+        let unchanged = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert!(unchanged.iter().all(|tx| tx["category_id"] == 1));
+
+        let results = bulk_recategorize_transactions(&pool, &selection, 2).await.unwrap();
+        assert_eq!(results.len(), 2);
+
+        let results = bulk_delete_transactions(&pool, &TransactionSelection::Filter(Box::new(filter))).await.unwrap();
+        assert!(results.iter().all(|r| r["status"] == "deleted"));
+        assert_eq!(crate::database::get_transactions(&pool, 1).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,487 @@
+//! Realized capital gains for tax reporting.
+//!
+//! Every sale disposes of shares from one or more lots, picked and costed by
+//! the [`CostBasisMethod`](super::CostBasisMethod) its account had when the sale
+//! was recorded, so a report for a past tax year never changes. Each part is a
+//! realized gain or loss: long term when the shares were held for more than
+//! one year, short term otherwise. Stock splits keep the original purchase
+//! date of the split shares.
+//!
+//! A loss is flagged as a wash sale when shares of the same security were
+//! bought within 30 days before or after the sale, in any account of the
+//! ledger, other than the shares sold. The flag is informational: the
+//! disallowed loss and the basis adjustment of the replacement shares are
+//! left to the tax return.
+//!
+//! Amounts are reported in the account's currency and in the base currency,
+//! with proceeds converted at the sale date's rate and cost at the purchase
+//! date's rate.
+
+use super::currencies::{BASE_CURRENCY, convert};
+use super::investments::{Disposal, format_quantity, load_trades, quantity_of, replay, security_names};
+use super::money::{Currency, Money};
+use super::unit_of_work;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::BTreeMap;
+
+/// Header of the CSV written by [`export_realized_gains_csv()`].
+const CSV_HEADER: &str = "Description,Date Acquired,Date Sold,Proceeds,Cost Basis,Gain or Loss,Term,Wash Sale,Account,Currency";
+
+/// Part of a sale realized from one lot.
+struct RealizedGain {
+    account_id: i64,
+    security_id: i64,
+    disposal: Disposal,
+    base_proceeds: Money,
+    base_cost: Money,
+    long_term: bool,
+    wash_sale: bool,
+}
+
+impl RealizedGain {
+    fn gain(&self) -> Result<Money, sqlx::Error> {
+        Ok(self.disposal.proceeds.checked_sub(self.disposal.cost)?)
+    }
+
+    fn base_gain(&self) -> Result<Money, sqlx::Error> {
+        Ok(self.base_proceeds.checked_sub(self.base_cost)?)
+    }
+}
+
+/// Whether shares bought on `acquired` and sold on `sold` were held for more
+/// than one year. Both are YYYY-MM-DD dates.
+fn is_long_term(acquired: &str, sold: &str) -> bool {
+    match acquired.get(..4).and_then(|year| year.parse::<i32>().ok()) {
+        Some(year) => sold > format!("{:04}{}", year + 1, &acquired[4..]).as_str(),
+        None => false,
+    }
+}
+
+/// Realized gains of sales in `tax_year`, in sale order, inside an open unit
+/// of work.
+async fn realized_gains(
+    conn: &mut SqliteConnection,
+    tax_year: i32,
+    account_id: Option<i64>,
+) -> Result<Vec<RealizedGain>, sqlx::Error> {
+    if !(1..=9999).contains(&tax_year) {
+        return Err(sqlx::Error::Protocol(format!("invalid tax year {}", tax_year)));
+    }
+    let year = format!("{:04}", tax_year);
+    let base_currency: String = sqlx::query_scalar(&format!("SELECT {}", BASE_CURRENCY))
+        .fetch_one(&mut *conn)
+        .await?;
+    let base = Currency::new(&base_currency)?;
+
+    let trades = load_trades(&mut *conn, account_id, Some(&format!("{}-12-31", year))).await?;
+    let mut gains = Vec::new();
+    for ((account_id, security_id), position) in replay(&trades)? {
+        // The lots a sale took from are the shares sold, not replacement shares
+        let mut sold_lots: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+        for disposal in &position.disposals {
+            sold_lots.entry(disposal.sale_id).or_default().push(disposal.lot_trade_id);
+        }
+
+        for disposal in position.disposals.into_iter().filter(|disposal| disposal.sold.starts_with(&year)) {
+            let wash_sale = disposal.proceeds.checked_sub(disposal.cost)?.is_negative() && {
+                let sold_lots = serde_json::to_string(&sold_lots[&disposal.sale_id])
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                sqlx::query_scalar(
+                    "SELECT EXISTS (
+                        SELECT 1 FROM investment_transactions
+                        WHERE security_id = ?1 AND action = 'buy'
+                          AND trade_date BETWEEN date(?2, '-30 days') AND date(?2, '+30 days')
+                          AND id NOT IN (SELECT value FROM json_each(?3))
+                    )",
+                )
+                .bind(security_id)
+                .bind(&disposal.sold)
+                .bind(sold_lots)
+                .fetch_one(&mut *conn)
+                .await?
+            };
+
+            gains.push(RealizedGain {
+                account_id,
+                security_id,
+                base_proceeds: convert(&mut *conn, disposal.proceeds, base, &disposal.sold).await?,
+                base_cost: convert(&mut *conn, disposal.cost, base, &disposal.acquired).await?,
+                long_term: is_long_term(&disposal.acquired, &disposal.sold),
+                wash_sale,
+                disposal,
+            });
+        }
+    }
+    gains.sort_by(|a, b| {
+        (&a.disposal.sold, a.disposal.sale_id, &a.disposal.acquired, a.disposal.lot_trade_id).cmp(&(
+            &b.disposal.sold,
+            b.disposal.sale_id,
+            &b.disposal.acquired,
+            b.disposal.lot_trade_id,
+        ))
+    });
+
+    Ok(gains)
+}
+
+/// Names of all accounts by id.
+async fn account_names(conn: &mut SqliteConnection) -> Result<BTreeMap<i64, String>, sqlx::Error> {
+    let rows = sqlx::query("SELECT id, name FROM accounts").fetch_all(&mut *conn).await?;
+
+    Ok(rows.into_iter().map(|row| (row.get("id"), row.get("name"))).collect())
+}
+
+/// Reports the gains and losses realized by sales in a tax year, split into
+/// short and long term.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `tax_year` - Calendar year of the sales, e.g. 2025
+/// * `account_id` - One account, or None for all accounts
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `tax_year`, `base_currency`,
+///   `sales` (one per lot sold: account_id, account_name, security_id, ticker,
+///   sale_id, lot_id, acquired, sold, quantity, currency, proceeds_cents,
+///   cost_basis_cents and gain_cents in the account's currency, the same as
+///   base_proceeds_cents, base_cost_basis_cents and base_gain_cents, `term`
+///   ("short" or "long") and `wash_sale`), `short_term` and `long_term` totals
+///   (proceeds_cents, cost_basis_cents, gain_cents in the base currency),
+///   `total_gain_cents` and the number of `wash_sales`
+/// * `Err(sqlx::Error)` - Invalid year, no exchange rate for a sale in another
+///   currency, or database failure
+///
+/// # Examples
+/// ```no_run
+/// let gains = get_realized_gains(&pool, 2025, None).await?;
+/// println!("Long-term gain: {}", gains["long_term"]["gain_cents"]);
+/// ```
+pub async fn get_realized_gains(
+    pool: &SqlitePool,
+    tax_year: i32,
+    account_id: Option<i64>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        let gains = realized_gains(&mut *conn, tax_year, account_id).await?;
+        let base_currency: String = sqlx::query_scalar(&format!("SELECT {}", BASE_CURRENCY))
+            .fetch_one(&mut *conn)
+            .await?;
+        let base = Currency::new(&base_currency)?;
+        let names = security_names(&mut *conn).await?;
+        let accounts = account_names(&mut *conn).await?;
+
+        let mut sales = Vec::new();
+        for gain in &gains {
+            let disposal = &gain.disposal;
+            sales.push(serde_json::json!({
+                "account_id": gain.account_id,
+                "account_name": accounts.get(&gain.account_id),
+                "security_id": gain.security_id,
+                "ticker": names.get(&gain.security_id).map(|(ticker, _, _)| ticker),
+                "sale_id": disposal.sale_id,
+                "lot_id": disposal.lot_trade_id,
+                "acquired": disposal.acquired,
+                "sold": disposal.sold,
+                "quantity": quantity_of(disposal.quantity_units),
+                "currency": disposal.proceeds.currency(),
+                "proceeds_cents": disposal.proceeds.amount_cents(),
+                "cost_basis_cents": disposal.cost.amount_cents(),
+                "gain_cents": gain.gain()?.amount_cents(),
+                "base_proceeds_cents": gain.base_proceeds.amount_cents(),
+                "base_cost_basis_cents": gain.base_cost.amount_cents(),
+                "base_gain_cents": gain.base_gain()?.amount_cents(),
+                "term": if gain.long_term { "long" } else { "short" },
+                "wash_sale": gain.wash_sale
+            }));
+        }
+
+        let mut terms = Vec::new();
+        for long_term in [false, true] {
+            let term: Vec<&RealizedGain> = gains.iter().filter(|gain| gain.long_term == long_term).collect();
+            let proceeds = Money::sum(base, term.iter().map(|gain| gain.base_proceeds))?;
+            let cost = Money::sum(base, term.iter().map(|gain| gain.base_cost))?;
+            terms.push(serde_json::json!({
+                "proceeds_cents": proceeds.amount_cents(),
+                "cost_basis_cents": cost.amount_cents(),
+                "gain_cents": proceeds.checked_sub(cost)?.amount_cents()
+            }));
+        }
+        let total_gain = Money::sum(base, gains.iter().map(|gain| gain.base_gain()).collect::<Result<Vec<_>, _>>()?)?;
+
+        Ok(serde_json::json!({
+            "tax_year": tax_year,
+            "base_currency": base_currency,
+            "sales": sales,
+            "short_term": terms[0],
+            "long_term": terms[1],
+            "total_gain_cents": total_gain.amount_cents(),
+            "wash_sales": gains.iter().filter(|gain| gain.wash_sale).count()
+        }))
+    })
+    .await
+}
+
+/// Writes the realized gains of a tax year as CSV text, one row per lot sold,
+/// with the columns of a capital gains schedule such as IRS Form 8949.
+///
+/// Amounts are plain decimals in the base currency, e.g. `-1234.50`; the
+/// description is the quantity and ticker, e.g. `12.5 VTI`.
+///
+/// ```text
+/// Description,Date Acquired,Date Sold,Proceeds,Cost Basis,Gain or Loss,Term,Wash Sale,Account,Currency
+/// 10 VTI,2024-01-10,2025-03-10,2495.83,2005.00,490.83,long,no,Brokerage,USD
+/// ```
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `tax_year` - Calendar year of the sales
+/// * `account_id` - One account, or None for all accounts
+///
+/// # Returns
+/// * `Ok(String)` - CSV text with a header row
+/// * `Err(sqlx::Error)` - See [`get_realized_gains()`]
+pub async fn export_realized_gains_csv(
+    pool: &SqlitePool,
+    tax_year: i32,
+    account_id: Option<i64>,
+) -> Result<String, sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        let gains = realized_gains(&mut *conn, tax_year, account_id).await?;
+        let names = security_names(&mut *conn).await?;
+        let accounts = account_names(&mut *conn).await?;
+
+        let mut csv = format!("{}\n", CSV_HEADER);
+        for gain in &gains {
+            let disposal = &gain.disposal;
+            let ticker = names.get(&gain.security_id).map(|(ticker, _, _)| ticker.as_str()).unwrap_or_default();
+            let cells = [
+                format!("{} {}", format_quantity(disposal.quantity_units), ticker),
+                disposal.acquired.clone(),
+                disposal.sold.clone(),
+                gain.base_proceeds.to_decimal(),
+                gain.base_cost.to_decimal(),
+                gain.base_gain()?.to_decimal(),
+                (if gain.long_term { "long" } else { "short" }).to_string(),
+                (if gain.wash_sale { "yes" } else { "no" }).to_string(),
+                accounts.get(&gain.account_id).cloned().unwrap_or_default(),
+                gain.base_proceeds.currency().to_string(),
+            ];
+            let row: Vec<String> = cells.iter().map(|cell| csv_field(cell)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+
+        Ok(csv)
+    })
+    .await
+}
+
+/// Quotes a CSV cell when it contains a separator, quote or line break.
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{CostBasisMethod, InvestmentTrade, LotSelection, SecurityType};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Brokerage".to_string(), "investment".to_string())
+            .await
+            .unwrap();
+        crate::database::add_security(&pool, "VTI".to_string(), "Vanguard Total Stock Market".to_string(), SecurityType::Etf, None)
+            .await
+            .unwrap();
+        // Lot 1: 10 shares at $100, lot 2: 10 shares at $200
+        trade(&pool, "2024-01-10", buy(10.0, 100000)).await.unwrap();
+        trade(&pool, "2025-01-15", buy(10.0, 200000)).await.unwrap();
+        pool
+    }
+
+    fn usd(cents: i64) -> Money {
+        Money::new(cents, Currency::USD)
+    }
+
+    fn buy(quantity: f64, cents: i64) -> InvestmentTrade {
+        InvestmentTrade::Buy { quantity, amount: usd(cents), fee: None }
+    }
+
+    fn sell(quantity: f64, cents: i64, lots: Vec<LotSelection>) -> InvestmentTrade {
+        InvestmentTrade::Sell { quantity, amount: usd(cents), fee: None, lots }
+    }
+
+    async fn trade(pool: &SqlitePool, date: &str, trade: InvestmentTrade) -> Result<i64, sqlx::Error> {
+        crate::database::add_investment_transaction(pool, 1, 1, date.to_string(), &trade, None).await
+    }
+
+    fn rows(report: &serde_json::Value) -> Vec<(i64, String)> {
+        report["sales"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|sale| (sale["gain_cents"].as_i64().unwrap(), sale["term"].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cost_basis_methods() {
+        let lots = vec![
+            LotSelection { lot_id: 1, quantity: 2.0 },
+            LotSelection { lot_id: 2, quantity: 3.0 },
+        ];
+        let cases = [
+            (CostBasisMethod::Fifo, vec![], vec![(25000, "long")]),
+            (CostBasisMethod::Lifo, vec![], vec![(-25000, "short")]),
+            // 5 of 20 shares costing $3,000 in total, held since the oldest lot
+            (CostBasisMethod::Average, vec![], vec![(0, "long")]),
+            (CostBasisMethod::Specific, lots, vec![(10000, "long"), (-15000, "short")]),
+        ];
+        for (method, lots, expected) in cases {
+            let pool = setup_test_db().await;
+            crate::database::set_cost_basis_method(&pool, 1, method).await.unwrap();
+            trade(&pool, "2025-06-02", sell(5.0, 75000, lots)).await.unwrap();
+
+            let report = get_realized_gains(&pool, 2025, None).await.unwrap();
+            let expected: Vec<(i64, String)> = expected.into_iter().map(|(gain, term)| (gain, term.to_string())).collect();
+            assert_eq!(rows(&report), expected, "{:?}", method);
+            assert_eq!(report["total_gain_cents"], expected.iter().map(|(gain, _)| gain).sum::<i64>());
+        }
+
+        // Average cost leaves the remaining shares at the average
+        let pool = setup_test_db().await;
+        crate::database::set_cost_basis_method(&pool, 1, CostBasisMethod::Average).await.unwrap();
+        trade(&pool, "2025-06-02", sell(5.0, 75000, vec![])).await.unwrap();
+        let lots = crate::database::get_lots(&pool, 1).await.unwrap();
+        assert_eq!(lots[0]["cost_basis_cents"], 75000);
+        assert_eq!(lots[1]["cost_basis_cents"], 150000);
+
+        // Lots are only identified under specific identification, and must cover the sale
+        let pool = setup_test_db().await;
+        let one = vec![LotSelection { lot_id: 1, quantity: 1.0 }];
+        assert!(trade(&pool, "2025-06-02", sell(1.0, 15000, one.clone())).await.is_err());
+        crate::database::set_cost_basis_method(&pool, 1, CostBasisMethod::Specific).await.unwrap();
+        assert!(trade(&pool, "2025-06-02", sell(2.0, 30000, one.clone())).await.is_err());
+        let too_many = vec![LotSelection { lot_id: 1, quantity: 11.0 }];
+        assert!(trade(&pool, "2025-06-02", sell(11.0, 165000, too_many)).await.is_err());
+        trade(&pool, "2025-06-02", sell(1.0, 15000, one)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_method_changes_keep_earlier_sales() {
+        let pool = setup_test_db().await;
+        trade(&pool, "2025-06-02", sell(5.0, 75000, vec![])).await.unwrap();
+        crate::database::set_cost_basis_method(&pool, 1, CostBasisMethod::Lifo).await.unwrap();
+        // The earlier sale stays first in, first out
+        let report = get_realized_gains(&pool, 2025, None).await.unwrap();
+        assert_eq!(rows(&report), vec![(25000, "long".to_string())]);
+
+        trade(&pool, "2025-07-01", sell(5.0, 75000, vec![])).await.unwrap();
+        let report = get_realized_gains(&pool, 2025, None).await.unwrap();
+        assert_eq!(rows(&report), vec![(25000, "long".to_string()), (-25000, "short".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_partial_sales_across_a_split() {
+        // 2-for-1: lot 1 becomes 20 shares at $50, lot 2 20 shares at $100
+        let pool = setup_test_db().await;
+        crate::database::set_cost_basis_method(&pool, 1, CostBasisMethod::Lifo).await.unwrap();
+        trade(&pool, "2025-03-03", InvestmentTrade::Split { from: 1, to: 2 }).await.unwrap();
+        trade(&pool, "2025-06-02", sell(25.0, 375000, vec![])).await.unwrap();
+        let report = get_realized_gains(&pool, 2025, None).await.unwrap();
+        assert_eq!(rows(&report), vec![(50000, "long".to_string()), (100000, "short".to_string())]);
+        let lots = crate::database::get_lots(&pool, 1).await.unwrap();
+        assert_eq!(lots.len(), 1);
+        assert_eq!(lots[0]["quantity"], 15.0);
+        assert_eq!(lots[0]["cost_basis_cents"], 75000);
+
+        // 40 shares costing $3,000 in total average $75 each, before and after a sale
+        let pool = setup_test_db().await;
+        crate::database::set_cost_basis_method(&pool, 1, CostBasisMethod::Average).await.unwrap();
+        trade(&pool, "2025-03-03", InvestmentTrade::Split { from: 1, to: 2 }).await.unwrap();
+        trade(&pool, "2025-06-02", sell(5.0, 75000, vec![])).await.unwrap();
+        trade(&pool, "2025-07-01", sell(7.0, 105000, vec![])).await.unwrap();
+        let report = get_realized_gains(&pool, 2025, None).await.unwrap();
+        assert_eq!(rows(&report), vec![(37500, "long".to_string()), (52500, "long".to_string())]);
+        let lots = crate::database::get_lots(&pool, 1).await.unwrap();
+        assert_eq!(lots[0]["quantity"], 8.0);
+        assert_eq!(lots[0]["cost_basis_cents"], 60000);
+        assert_eq!(lots[1]["quantity"], 20.0);
+        assert_eq!(lots[1]["cost_basis_cents"], 150000);
+    }
+
+    #[tokio::test]
+    async fn test_wash_sale_replacements() {
+        // Lot 2 sold at a loss, replaced in another account
+        let pool = setup_test_db().await;
+        crate::database::set_cost_basis_method(&pool, 1, CostBasisMethod::Lifo).await.unwrap();
+        crate::database::add_account(&pool, "IRA".to_string(), "investment".to_string())
+            .await
+            .unwrap();
+        trade(&pool, "2025-09-02", sell(4.0, 60000, vec![])).await.unwrap();
+        crate::database::add_investment_transaction(&pool, 2, 1, "2025-09-20".to_string(), &buy(4.0, 60000), None)
+            .await
+            .unwrap();
+        let report = get_realized_gains(&pool, 2025, Some(1)).await.unwrap();
+        assert_eq!(rows(&report), vec![(-20000, "short".to_string())]);
+        assert_eq!(report["sales"][0]["wash_sale"], true);
+
+        // Replaced in the next year, after the reported one ends
+        let pool = setup_test_db().await;
+        crate::database::set_cost_basis_method(&pool, 1, CostBasisMethod::Lifo).await.unwrap();
+        trade(&pool, "2025-12-20", sell(4.0, 60000, vec![])).await.unwrap();
+        trade(&pool, "2026-01-05", buy(4.0, 60000)).await.unwrap();
+        let report = get_realized_gains(&pool, 2025, None).await.unwrap();
+        assert_eq!(report["sales"][0]["wash_sale"], true);
+        assert_eq!(report["wash_sales"], 1);
+
+        // Bought more than 30 days later, it is no replacement
+        let pool = setup_test_db().await;
+        crate::database::set_cost_basis_method(&pool, 1, CostBasisMethod::Lifo).await.unwrap();
+        trade(&pool, "2025-12-01", sell(4.0, 60000, vec![])).await.unwrap();
+        trade(&pool, "2026-01-05", buy(4.0, 60000)).await.unwrap();
+        let report = get_realized_gains(&pool, 2025, None).await.unwrap();
+        assert_eq!(report["sales"][0]["wash_sale"], false);
+    }
+
+    #[tokio::test]
+    async fn test_splits_wash_sales_and_csv_export() {
+        let pool = setup_test_db().await;
+        // 2-for-1: lot 1 becomes 20 shares at $50, still bought in 2024
+        trade(&pool, "2025-03-03", InvestmentTrade::Split { from: 1, to: 2 }).await.unwrap();
+        trade(&pool, "2025-03-10", sell(20.0, 150000, vec![])).await.unwrap();
+        // Lot 2 (now 20 shares at $100) sold at a loss, rebought within 30 days
+        trade(&pool, "2025-09-02", sell(4.0, 30000, vec![])).await.unwrap();
+        trade(&pool, "2025-09-20", buy(1.0, 8000)).await.unwrap();
+
+        let report = get_realized_gains(&pool, 2025, Some(1)).await.unwrap();
+        assert_eq!(rows(&report), vec![(50000, "long".to_string()), (-10000, "short".to_string())]);
+        assert_eq!(report["sales"][0]["quantity"], 20.0);
+        assert_eq!(report["sales"][0]["acquired"], "2024-01-10");
+        assert_eq!(report["sales"][0]["wash_sale"], false);
+        assert_eq!(report["sales"][1]["wash_sale"], true);
+        assert_eq!(report["long_term"]["gain_cents"], 50000);
+        assert_eq!(report["short_term"]["proceeds_cents"], 30000);
+        assert_eq!(report["wash_sales"], 1);
+
+        // Nothing was sold in 2024
+        let earlier = get_realized_gains(&pool, 2024, None).await.unwrap();
+        assert_eq!(earlier["sales"], serde_json::json!([]));
+
+        let csv = export_realized_gains_csv(&pool, 2025, None).await.unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "20 VTI,2024-01-10,2025-03-10,1500.00,1000.00,500.00,long,no,Brokerage,USD");
+        assert_eq!(lines[2], "4 VTI,2025-01-15,2025-09-02,300.00,400.00,-100.00,short,yes,Brokerage,USD");
+        assert_eq!(csv_field("Smith, Jane \"IRA\""), "\"Smith, Jane \"\"IRA\"\"\"");
+    }
+}
@@ -4,12 +4,13 @@
 //! Categories are required for all transactions and support nested organization for
 //! detailed expense tracking and analysis.
 
-use sqlx::{Row, SqlitePool};
+use super::journal::journaled;
+use sqlx::{Row, SqliteConnection, SqlitePool};
 
 /// Retrieves all categories from the database.
 ///
-/// Queries the categories table and returns all records as JSON-serializable objects
-/// for frontend consumption. Results include category ID, name, and parent relationship
+/// Queries the categories table and returns all records outside the trash as
+/// JSON-serializable objects for frontend consumption. Results include category ID, name, and parent relationship
 /// but exclude internal timestamps to keep the API clean.
 ///
 /// # Arguments
@@ -19,7 +20,7 @@ use sqlx::{Row, SqlitePool};
 /// * `Ok(Vec<serde_json::Value>)` - Array of category objects with id, name, and parent_id
 /// * `Err(sqlx::Error)` - Database query or serialization failure
 pub async fn get_all_categories(pool: &SqlitePool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
-    let categories = sqlx::query("SELECT id, name, parent_id FROM categories")
+    let categories = sqlx::query("SELECT id, name, parent_id FROM categories WHERE deleted_at IS NULL")
         .fetch_all(pool)
         .await?;
 
@@ -50,19 +51,35 @@ pub async fn get_all_categories(pool: &SqlitePool) -> Result<Vec<serde_json::Val
 ///
 /// # Returns
 /// * `Ok(())` - Category created successfully with auto-generated ID
-/// * `Err(sqlx::Error)` - Database insertion or validation failure
+/// * `Err(sqlx::Error)` - Database insertion or validation failure, including a
+///   trashed category with the same name
 pub async fn add_category(
     pool: &SqlitePool,
     name: String,
     parent_id: Option<i64>,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query("INSERT INTO categories (name, parent_id) VALUES (?, ?)")
-        .bind(name)
-        .bind(parent_id)
-        .execute(pool)
-        .await?;
-
-    Ok(())
+    journaled(pool, "Add category", async |conn| {
+        let trashed: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM categories WHERE name = ? AND deleted_at IS NOT NULL")
+                .bind(&name)
+                .fetch_optional(&mut *conn)
+                .await?;
+        if trashed.is_some() {
+            return Err(sqlx::Error::Protocol(format!(
+                "a category named \"{}\" is in the trash; restore it instead",
+                name
+            )));
+        }
+
+        sqlx::query("INSERT INTO categories (name, parent_id) VALUES (?, ?)")
+            .bind(name)
+            .bind(parent_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    })
+    .await
 }
 
 /// Updates an existing category with new values.
@@ -79,33 +96,75 @@ pub async fn add_category(
 ///
 /// # Returns
 /// * `Ok(())` - Category updated successfully
-/// * `Err(sqlx::Error)` - Database update failure or category not found
+/// * `Err(sqlx::Error)` - `RowNotFound` for an unknown or trashed category, or
+///   database update failure
 pub async fn update_category(
     pool: &SqlitePool,
     category_id: i64,
     name: String,
     parent_id: Option<i64>,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE categories SET name = ?, parent_id = ? WHERE id = ?")
-        .bind(name)
-        .bind(parent_id)
-        .bind(category_id)
-        .execute(pool)
-        .await?;
-
-    Ok(())
+    journaled(pool, "Update category", async |conn| {
+        let result = sqlx::query("UPDATE categories SET name = ?, parent_id = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(name)
+            .bind(parent_id)
+            .bind(category_id)
+            .execute(&mut *conn)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
 }
 
+/// Moves a category to the trash after re-homing its children and transactions.
+///
+/// Child categories are promoted one level, transactions move to
+/// "Uncategorized" and payees using it as their default lose that default
+/// before the row is marked deleted. All steps run as one
+/// journaled operation, so a failure midway leaves no orphaned references
+/// behind, and an undo restores the category together with every reassignment.
+/// Restoring from the trash instead brings back only the category itself.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference for executing the deletion
+/// * `category_id` - Database ID of the category to delete
+///
+/// # Returns
+/// * `Ok(())` - Category trashed and dependent rows reassigned
+/// * `Err(sqlx::Error)` - Attempt to delete "Uncategorized" or database failure;
+///   no changes are kept
 pub async fn delete_category(pool: &SqlitePool, category_id: i64) -> Result<(), sqlx::Error> {
-    handle_orphaned_categories(pool, category_id).await?;
-    handle_orphaned_transactions(pool, category_id).await?;
-
-    sqlx::query("DELETE FROM categories WHERE id = ?")
-        .bind(category_id)
-        .execute(pool)
-        .await?;
-
-    Ok(())
+    journaled(pool, "Delete category", async |conn| {
+        let name: Option<String> = sqlx::query_scalar("SELECT name FROM categories WHERE id = ?")
+            .bind(category_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if name.as_deref() == Some("Uncategorized") {
+            return Err(sqlx::Error::Protocol(
+                "the Uncategorized category cannot be deleted".to_string(),
+            ));
+        }
+
+        handle_orphaned_categories(conn, category_id).await?;
+        handle_orphaned_transactions(conn, category_id).await?;
+        sqlx::query("UPDATE payees SET default_category_id = NULL WHERE default_category_id = ?")
+            .bind(category_id)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query("UPDATE categories SET deleted_at = datetime('now') WHERE id = ? AND deleted_at IS NULL")
+            .bind(category_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    })
+    .await
 }
 
 /// Reassigns child categories when their parent is deleted.
@@ -116,7 +175,7 @@ pub async fn delete_category(pool: &SqlitePool, category_id: i64) -> Result<(),
 /// This preserves the category hierarchy structure while preventing orphaned references.
 ///
 /// # Arguments
-/// * `pool` - SQLite connection pool reference for executing database operations
+/// * `conn` - Connection of the enclosing unit of work
 /// * `category_id` - Database ID of the category being deleted
 ///
 /// # Returns
@@ -127,16 +186,16 @@ pub async fn delete_category(pool: &SqlitePool, category_id: i64) -> Result<(),
 /// ```no_run
 /// // Before: Discretionary -> Electronics -> Computers
 /// // Delete Electronics (ID 2)
-/// handle_orphaned_categories(&pool, 2).await?;
+/// handle_orphaned_categories(conn, 2).await?;
 /// // After: Discretionary -> Computers (Electronics children inherit Discretionary as parent)
 /// ```
 async fn handle_orphaned_categories(
-    pool: &SqlitePool,
+    conn: &mut SqliteConnection,
     category_id: i64,
 ) -> Result<(), sqlx::Error> {
-    let children = sqlx::query("SELECT id FROM categories WHERE parent_id = ?")
+    let children = sqlx::query("SELECT id FROM categories WHERE parent_id = ? AND deleted_at IS NULL")
         .bind(category_id)
-        .fetch_all(pool)
+        .fetch_all(&mut *conn)
         .await?;
 
     if children.is_empty() {
@@ -145,7 +204,7 @@ async fn handle_orphaned_categories(
 
     let parent_row = sqlx::query("SELECT parent_id FROM categories WHERE id = ?")
         .bind(category_id)
-        .fetch_all(pool)
+        .fetch_all(&mut *conn)
         .await?;
 
     let parent_id: Option<i64> = if !parent_row.is_empty() {
@@ -154,10 +213,10 @@ async fn handle_orphaned_categories(
         None
     };
 
-    sqlx::query("UPDATE categories SET parent_id = ? WHERE parent_id = ?")
+    sqlx::query("UPDATE categories SET parent_id = ? WHERE parent_id = ? AND deleted_at IS NULL")
         .bind(parent_id)
         .bind(category_id)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(())
@@ -165,13 +224,13 @@ async fn handle_orphaned_categories(
 
 /// Reassigns orphaned transactions when their category is deleted.
 ///
-/// Moves all transactions from the deleted category to the "Uncategorized"
-/// system category to prevent foreign key violations and ensure transaction
+/// Moves all transactions from the deleted category, trashed ones included, to
+/// the "Uncategorized" system category to prevent foreign key violations and ensure transaction
 /// data remains accessible. The "Uncategorized" category must exist in the
 /// database for this operation to succeed.
 ///
 /// # Arguments
-/// * `pool` - SQLite connection pool reference for executing database operations
+/// * `conn` - Connection of the enclosing unit of work
 /// * `category_id` - Database ID of the category being deleted
 ///
 /// # Returns
@@ -181,22 +240,238 @@ async fn handle_orphaned_categories(
 /// # Examples
 /// ```no_run
 /// // Move all transactions from deleted "Groceries" category to "Uncategorized"
-/// handle_orphaned_transactions(&pool, 5).await?;
+/// handle_orphaned_transactions(conn, 5).await?;
 /// ```
 async fn handle_orphaned_transactions(
-    pool: &SqlitePool,
+    conn: &mut SqliteConnection,
     category_id: i64,
 ) -> Result<(), sqlx::Error> {
     let uncategorized_row = sqlx::query("SELECT id FROM categories WHERE name = 'Uncategorized'")
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await?;
     let uncategorized_id: i64 = uncategorized_row.get("id");
 
     sqlx::query("UPDATE transactions SET category_id = ? WHERE category_id = ?")
         .bind(uncategorized_id)
         .bind(category_id)
-        .execute(pool)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Merges one category into another and deletes the source category.
+///
+/// Repoints every transaction and direct child category of the source, trashed
+/// ones included, and every payee defaulting to it at the target, then removes
+/// the source. All statements run as one journaled
+/// operation, so a failure part-way leaves the category tree untouched and the
+/// whole merge can be undone.
+/// Used for cleanups such as folding "Dining" into "Restaurants".
+///
+/// The ledger has no budgets or categorization rules yet; once those tables
+/// exist they must be repointed here and counted in the summary too.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference for executing the merge
+/// * `source_id` - Database ID of the category to merge away and delete
+/// * `target_id` - Database ID of the category receiving the source's data
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Summary object with the number of repointed
+///   `transactions`, `categories` and `payees`
+/// * `Err(sqlx::Error)` - Validation, lookup or database failure
+///
+/// # Errors
+/// Fails if:
+/// - Source and target are the same category
+/// - Either category does not exist
+/// - Source is the "Uncategorized" system category
+/// - Target lies inside the source's subtree (would create a cycle)
+///
+/// # Examples
+/// ```no_run
+/// let summary = merge_categories(&pool, dining_id, restaurants_id).await?;
+/// println!("Moved {} transactions", summary["transactions"]);
+/// ```
+pub async fn merge_categories(
+    pool: &SqlitePool,
+    source_id: i64,
+    target_id: i64,
+) -> Result<serde_json::Value, sqlx::Error> {
+    if source_id == target_id {
+        return Err(sqlx::Error::Protocol(
+            "cannot merge a category into itself".to_string(),
+        ));
+    }
+
+    journaled(pool, "Merge categories", async |conn| {
+        let source = sqlx::query("SELECT name FROM categories WHERE id = ? AND deleted_at IS NULL")
+            .bind(source_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        if source.get::<String, _>("name") == "Uncategorized" {
+            return Err(sqlx::Error::Protocol(
+                "the Uncategorized category cannot be merged away".to_string(),
+            ));
+        }
+
+        sqlx::query("SELECT id FROM categories WHERE id = ? AND deleted_at IS NULL")
+            .bind(target_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        if get_subtree_ids(conn, source_id).await?.contains(&target_id) {
+            return Err(sqlx::Error::Protocol(
+                "cannot merge a category into one of its descendants".to_string(),
+            ));
+        }
+
+        let transactions = sqlx::query("UPDATE transactions SET category_id = ? WHERE category_id = ?")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+        let categories = sqlx::query("UPDATE categories SET parent_id = ? WHERE parent_id = ?")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+        let payees = sqlx::query("UPDATE payees SET default_category_id = ? WHERE default_category_id = ?")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+        sqlx::query("DELETE FROM categories WHERE id = ?")
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(serde_json::json!({
+            "transactions": transactions,
+            "categories": categories,
+            "payees": payees
+        }))
+    })
+    .await
+}
+
+/// Moves a category and all of its descendants under a new parent.
+///
+/// Only the subtree root's `parent_id` changes; descendants keep their
+/// relative structure. The cycle check and update share one SQL transaction.
+/// Transactions and payees keep pointing at the same categories, so nothing
+/// else is repointed (the ledger has no budgets or categorization rules yet).
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference for executing the move
+/// * `category_id` - Database ID of the subtree root to move
+/// * `new_parent_id` - Destination parent category ID, or None for root level
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Summary object with the moved root and subtree size
+/// * `Err(sqlx::Error)` - Validation, lookup or database failure
+///
+/// # Errors
+/// Fails if:
+/// - Either category does not exist
+/// - The new parent is the category itself or one of its descendants
+pub async fn move_category_subtree(
+    pool: &SqlitePool,
+    category_id: i64,
+    new_parent_id: Option<i64>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    journaled(pool, "Move category", async |conn| {
+        let subtree = get_subtree_ids(conn, category_id).await?;
+        if subtree.is_empty() {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        if let Some(parent_id) = new_parent_id {
+            if subtree.contains(&parent_id) {
+                return Err(sqlx::Error::Protocol(
+                    "cannot move a category beneath itself or its descendants".to_string(),
+                ));
+            }
+
+            sqlx::query("SELECT id FROM categories WHERE id = ? AND deleted_at IS NULL")
+                .bind(parent_id)
+                .fetch_one(&mut *conn)
+                .await?;
+        }
+
+        let categories = sqlx::query("UPDATE categories SET parent_id = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(new_parent_id)
+            .bind(category_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        if categories == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(serde_json::json!({
+            "categories": categories,
+            "subtree_size": subtree.len()
+        }))
+    })
+    .await
+}
+
+/// Collects the IDs of a category and every category beneath it.
+///
+/// Walks the parent-child hierarchy with a recursive CTE. Returns an empty
+/// list when the root category does not exist.
+///
+/// # Arguments
+/// * `conn` - Connection or open transaction to run the query on
+/// * `category_id` - Database ID of the subtree root
+///
+/// # Returns
+/// * `Ok(Vec<i64>)` - Root ID followed by all descendant IDs
+/// * `Err(sqlx::Error)` - Database query failure
+async fn get_subtree_ids(
+    conn: &mut SqliteConnection,
+    category_id: i64,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        WITH RECURSIVE subtree(id) AS (
+            SELECT id FROM categories WHERE id = ?
+            UNION
+            SELECT c.id FROM categories c JOIN subtree s ON c.parent_id = s.id
+        )
+        SELECT id FROM subtree
+        "#,
+    )
+    .bind(category_id)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| row.get::<i64, _>("id")).collect())
+}
+
+/// Refuses to assign a category in the trash, inside an open unit of work.
+/// Unknown categories are left to the foreign key.
+pub(crate) async fn check_category_assignable(
+    conn: &mut SqliteConnection,
+    category_id: i64,
+) -> Result<(), sqlx::Error> {
+    let trashed: Option<bool> = sqlx::query_scalar("SELECT deleted_at IS NOT NULL FROM categories WHERE id = ?")
+        .bind(category_id)
+        .fetch_optional(&mut *conn)
         .await?;
+    if trashed == Some(true) {
+        return Err(sqlx::Error::Protocol(
+            "the category is in the trash; restore it first".to_string(),
+        ));
+    }
 
     Ok(())
 }
@@ -204,11 +479,11 @@ async fn handle_orphaned_transactions(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::{Currency, Money};
     use sqlx::SqlitePool;
 
     async fn setup_test_db() -> SqlitePool {
         let pool = SqlitePool::connect(":memory:").await.unwrap();
-        crate::database::create_tables(&pool).await.unwrap();
         crate::database::migrations::run_migrations(&pool)
             .await
             .unwrap();
@@ -251,6 +526,11 @@ mod tests {
 
         assert_eq!(updated_category["name"], "Updated Name");
         assert_eq!(updated_category["parent_id"], 1);
+
+        assert!(matches!(
+            update_category(&pool, 99, "Missing".to_string(), None).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
     }
 
     #[tokio::test]
@@ -310,7 +590,7 @@ mod tests {
         add_category(&pool, "Food".to_string(), None).await.unwrap();
 
         // Create transaction in Food category
-        crate::database::add_transaction(&pool, 1, -1000, "debit".to_string(), "groceries".to_string(), "2024-01-01".to_string(), 2).await.unwrap();
+        crate::database::add_transaction(&pool, 1, Money::new(-1000, Currency::USD), "debit".to_string(), "groceries".to_string(), "2024-01-01".to_string(), 2).await.unwrap();
 
         // Delete Food category - transaction should move to Uncategorized (ID 1)
         delete_category(&pool, 2).await.unwrap();
@@ -319,4 +599,67 @@ mod tests {
         let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
         assert_eq!(transactions[0]["category_id"], 1);
     }
-}
+
+    #[tokio::test]
+    async fn test_merge_categories() {
+        let pool = setup_test_db().await;
+
+        crate::database::add_account(&pool, "Test Account".to_string(), "checking".to_string()).await.unwrap();
+        add_category(&pool, "Dining".to_string(), None).await.unwrap(); // ID 2
+        add_category(&pool, "Restaurants".to_string(), None).await.unwrap(); // ID 3
+        add_category(&pool, "Coffee".to_string(), Some(2)).await.unwrap(); // ID 4, child of Dining
+        crate::database::add_transaction(&pool, 1, Money::new(-1500, Currency::USD), "debit".to_string(), "Bistro".to_string(), "2024-01-01".to_string(), 2).await.unwrap();
+        crate::database::add_transaction(&pool, 1, Money::new(-2500, Currency::USD), "debit".to_string(), "Diner".to_string(), "2024-01-02".to_string(), 2).await.unwrap();
+
+        let summary = merge_categories(&pool, 2, 3).await.unwrap();
+        assert_eq!(summary["transactions"], 2);
+        assert_eq!(summary["categories"], 1);
+        assert_eq!(summary["payees"], 0);
+
+        let categories = get_all_categories(&pool).await.unwrap();
+        assert!(categories.iter().all(|c| c["name"] != "Dining"));
+        let coffee = categories.iter().find(|c| c["name"] == "Coffee").unwrap();
+        assert_eq!(coffee["parent_id"], 3);
+
+        let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert!(transactions.iter().all(|t| t["category_id"] == 3));
+    }
+
+    #[tokio::test]
+    async fn test_merge_category_into_descendant_fails() {
+        let pool = setup_test_db().await;
+
+        add_category(&pool, "Food".to_string(), None).await.unwrap(); // ID 2
+        add_category(&pool, "Groceries".to_string(), Some(2)).await.unwrap(); // ID 3
+
+        assert!(merge_categories(&pool, 2, 3).await.is_err());
+        assert!(merge_categories(&pool, 1, 2).await.is_err()); // Uncategorized is protected
+
+        let categories = get_all_categories(&pool).await.unwrap();
+        assert_eq!(categories.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_move_category_subtree() {
+        let pool = setup_test_db().await;
+
+        // Create: Food (2) -> Groceries (3) -> Organic (4), Household (5)
+        add_category(&pool, "Food".to_string(), None).await.unwrap();
+        add_category(&pool, "Groceries".to_string(), Some(2)).await.unwrap();
+        add_category(&pool, "Organic".to_string(), Some(3)).await.unwrap();
+        add_category(&pool, "Household".to_string(), None).await.unwrap();
+
+        let summary = move_category_subtree(&pool, 3, Some(5)).await.unwrap();
+        assert_eq!(summary["subtree_size"], 2);
+
+        let categories = get_all_categories(&pool).await.unwrap();
+        let groceries = categories.iter().find(|c| c["name"] == "Groceries").unwrap();
+        let organic = categories.iter().find(|c| c["name"] == "Organic").unwrap();
+        assert_eq!(groceries["parent_id"], 5);
+        assert_eq!(organic["parent_id"], 3); // Structure below the moved root is kept
+
+        // Moving Food beneath its own grandchild must be rejected
+        add_category(&pool, "Snacks".to_string(), Some(2)).await.unwrap(); // ID 6
+        assert!(move_category_subtree(&pool, 2, Some(6)).await.is_err());
+    }
+}
\ No newline at end of file
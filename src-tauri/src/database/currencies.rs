@@ -0,0 +1,466 @@
+//! Account currencies, exchange rates and the ledger's base currency.
+//!
+//! Every account holds amounts in its own currency (an ISO 4217 code such as
+//! `EUR`); `amount_cents` is always in the minor unit of the account's
+//! currency. Reports convert into the ledger's base currency, set with
+//! [`set_base_currency()`], at the rate in effect on the transaction date or on
+//! the report's as-of date.
+//!
+//! Rates are stored per day as "1 `from_currency` = `rate` `to_currency`" and
+//! entered one by one or imported from a CSV history such as the ECB's. A
+//! conversion uses the latest rate on or before the date, taken directly, as
+//! the inverse of the opposite pair, or crossed through a currency both sides
+//! are quoted against on the same day (EUR for ECB data). A conversion without
+//! any such rate fails rather than silently counting the amount as zero.
+
+use super::money::{Currency, Money, THOUSANDTHS_CURRENCIES, WHOLE_UNIT_CURRENCIES};
+use super::unit_of_work;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+/// SQL expression for the ledger's base currency.
+pub(crate) const BASE_CURRENCY: &str = "(SELECT base_currency FROM ledger_settings WHERE id = 1)";
+
+/// Checks and normalizes a three-letter currency code, e.g. "eur" to "EUR".
+pub(crate) fn currency_code(code: &str) -> Result<String, sqlx::Error> {
+    Ok(Currency::new(code)?.to_string())
+}
+
+/// SQL expression for the rate converting `from` into `to` on `date`, or NULL
+/// when no rate is known. The arguments are SQL expressions themselves.
+pub(crate) fn rate_sql(from: &str, to: &str, date: &str) -> String {
+    format!(
+        "(CASE WHEN {from} = {to} THEN 1.0 ELSE COALESCE(
+            (SELECT r.rate FROM exchange_rates r
+             WHERE r.from_currency = {from} AND r.to_currency = {to} AND r.rate_date <= {date}
+             ORDER BY r.rate_date DESC LIMIT 1),
+            (SELECT 1.0 / r.rate FROM exchange_rates r
+             WHERE r.from_currency = {to} AND r.to_currency = {from} AND r.rate_date <= {date}
+             ORDER BY r.rate_date DESC LIMIT 1),
+            (SELECT q.rate / r.rate FROM exchange_rates r
+             JOIN exchange_rates q ON q.from_currency = r.from_currency AND q.rate_date = r.rate_date
+             WHERE r.to_currency = {from} AND q.to_currency = {to} AND r.rate_date <= {date}
+             ORDER BY r.rate_date DESC LIMIT 1)
+        ) END)"
+    )
+}
+
+/// SQL expression converting `amount` (minor units of `currency`) into the
+/// base currency at the rate of `date`, rounded to whole minor units.
+pub(crate) fn to_base_sql(amount: &str, currency: &str, date: &str) -> String {
+    format!(
+        "CAST(ROUND({} * {} * {} / {}) AS INTEGER)",
+        amount,
+        rate_sql(currency, BASE_CURRENCY, date),
+        minor_units_sql(BASE_CURRENCY),
+        minor_units_sql(currency)
+    )
+}
+
+/// SQL expression for the number of minor units in one unit of `currency`,
+/// e.g. 100 for EUR and 1 for JPY, see [`Currency::minor_digits()`].
+fn minor_units_sql(currency: &str) -> String {
+    let list = |codes: &[&str]| codes.iter().map(|code| format!("'{}'", code)).collect::<Vec<_>>().join(", ");
+    format!(
+        "(CASE WHEN {currency} IN ({}) THEN 1.0 WHEN {currency} IN ({}) THEN 1000.0 ELSE 100.0 END)",
+        list(WHOLE_UNIT_CURRENCIES),
+        list(THOUSANDTHS_CURRENCIES)
+    )
+}
+
+/// Fails unless every transaction in the date range can be converted into the
+/// base currency. Reports call this before summing converted amounts, which
+/// would otherwise skip unconvertible rows.
+pub(crate) async fn ensure_rates(
+    conn: &mut SqliteConnection,
+    date_from: &Option<String>,
+    date_to: &Option<String>,
+) -> Result<(), sqlx::Error> {
+    let missing = sqlx::query(&format!(
+        "SELECT a.currency, t.transaction_date, {} AS base_currency
+         FROM transactions t
+         JOIN accounts a ON a.id = t.account_id
+         WHERE t.deleted_at IS NULL
+           AND (? IS NULL OR t.transaction_date >= ?)
+           AND (? IS NULL OR t.transaction_date <= ?)
+           AND a.currency != {}
+           AND {} IS NULL
+         LIMIT 1",
+        BASE_CURRENCY,
+        BASE_CURRENCY,
+        rate_sql("a.currency", BASE_CURRENCY, "t.transaction_date")
+    ))
+    .bind(date_from)
+    .bind(date_from)
+    .bind(date_to)
+    .bind(date_to)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match missing {
+        Some(row) => Err(missing_rate(
+            &row.get::<String, _>("currency"),
+            &row.get::<String, _>("base_currency"),
+            &row.get::<String, _>("transaction_date"),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn missing_rate(from: &str, to: &str, date: &str) -> sqlx::Error {
+    sqlx::Error::Protocol(format!(
+        "no exchange rate from {} to {} on or before {}; add or import rates first",
+        from, to, date
+    ))
+}
+
+/// Converts `amount` into another currency at the rate of `date` inside an
+/// open unit of work, rounding half to even.
+pub(crate) async fn convert(
+    conn: &mut SqliteConnection,
+    amount: Money,
+    to: Currency,
+    date: &str,
+) -> Result<Money, sqlx::Error> {
+    if amount.currency() == to {
+        return Ok(amount);
+    }
+    let rate: Option<f64> = sqlx::query_scalar(&format!("SELECT {}", rate_sql("?1", "?2", "?3")))
+        .bind(amount.currency().code())
+        .bind(to.code())
+        .bind(date)
+        .fetch_one(&mut *conn)
+        .await?;
+    let rate = rate.ok_or_else(|| missing_rate(amount.currency().code(), to.code(), date))?;
+
+    Ok(amount.convert(rate, to)?)
+}
+
+/// Returns the currency reports are converted into.
+pub async fn get_base_currency(pool: &SqlitePool) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar(&format!("SELECT {}", BASE_CURRENCY))
+        .fetch_one(pool)
+        .await
+}
+
+/// Sets the currency reports are converted into.
+///
+/// Accounts keep their own currencies; new accounts default to the base currency.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `currency` - Three-letter ISO currency code, e.g. "EUR"
+///
+/// # Returns
+/// * `Ok(())` - Base currency stored
+/// * `Err(sqlx::Error)` - Invalid code or database failure
+pub async fn set_base_currency(pool: &SqlitePool, currency: String) -> Result<(), sqlx::Error> {
+    let currency = currency_code(&currency)?;
+
+    sqlx::query("UPDATE ledger_settings SET base_currency = ? WHERE id = 1")
+        .bind(currency)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Records the rate of one currency pair on one day, replacing an earlier
+/// rate for the same pair and day.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `rate_date` - Day the rate applies from (YYYY-MM-DD)
+/// * `from_currency` - Currency being converted
+/// * `to_currency` - Currency converted into
+/// * `rate` - Units of `to_currency` per unit of `from_currency`
+///
+/// # Returns
+/// * `Ok(())` - Rate stored
+/// * `Err(sqlx::Error)` - Invalid code, non-positive rate or database failure
+///
+/// # Examples
+/// ```no_run
+/// // 1 EUR = 1.0813 USD
+/// set_exchange_rate(&pool, "2025-01-03".to_string(), "EUR".to_string(), "USD".to_string(), 1.0813).await?;
+/// ```
+pub async fn set_exchange_rate(
+    pool: &SqlitePool,
+    rate_date: String,
+    from_currency: String,
+    to_currency: String,
+    rate: f64,
+) -> Result<(), sqlx::Error> {
+    let from_currency = currency_code(&from_currency)?;
+    let to_currency = currency_code(&to_currency)?;
+    unit_of_work(pool, async |conn| {
+        store_rate(&mut *conn, &rate_date, &from_currency, &to_currency, rate).await
+    })
+    .await
+}
+
+async fn store_rate(
+    conn: &mut SqliteConnection,
+    rate_date: &str,
+    from_currency: &str,
+    to_currency: &str,
+    rate: f64,
+) -> Result<(), sqlx::Error> {
+    if from_currency == to_currency {
+        return Err(sqlx::Error::Protocol(format!(
+            "cannot set a rate from {} to itself",
+            from_currency
+        )));
+    }
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(sqlx::Error::Protocol(format!("exchange rate must be positive, got {}", rate)));
+    }
+
+    sqlx::query(
+        "INSERT INTO exchange_rates (rate_date, from_currency, to_currency, rate) VALUES (?, ?, ?, ?)
+         ON CONFLICT (from_currency, to_currency, rate_date) DO UPDATE SET rate = excluded.rate",
+    )
+    .bind(rate_date)
+    .bind(from_currency)
+    .bind(to_currency)
+    .bind(rate)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Retrieves stored exchange rates, newest first.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `currency` - Only rates quoting this currency on either side, or None for all
+/// * `limit` - Maximum number of rates
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Rates with id, rate_date, from_currency,
+///   to_currency and rate
+/// * `Err(sqlx::Error)` - Invalid code or database failure
+pub async fn get_exchange_rates(
+    pool: &SqlitePool,
+    currency: Option<String>,
+    limit: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let currency = currency.as_deref().map(currency_code).transpose()?;
+    let rows = sqlx::query(
+        "SELECT id, rate_date, from_currency, to_currency, rate FROM exchange_rates
+         WHERE ?1 IS NULL OR from_currency = ?1 OR to_currency = ?1
+         ORDER BY rate_date DESC, from_currency, to_currency
+         LIMIT ?2",
+    )
+    .bind(currency)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let result: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "rate_date": row.get::<String, _>("rate_date"),
+                "from_currency": row.get::<String, _>("from_currency"),
+                "to_currency": row.get::<String, _>("to_currency"),
+                "rate": row.get::<f64, _>("rate")
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+/// Deletes one stored exchange rate.
+pub async fn delete_exchange_rate(pool: &SqlitePool, rate_id: i64) -> Result<(), sqlx::Error> {
+    let result = sqlx::query("DELETE FROM exchange_rates WHERE id = ?")
+        .bind(rate_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    Ok(())
+}
+
+/// Returns the rate converting one currency into another on a date.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `from_currency` - Currency being converted
+/// * `to_currency` - Currency converted into
+/// * `date` - Day of the conversion (YYYY-MM-DD); the latest rate on or before it applies
+///
+/// # Returns
+/// * `Ok(f64)` - Units of `to_currency` per unit of `from_currency`
+/// * `Err(sqlx::Error)` - Invalid code, no known rate, or database failure
+pub async fn get_exchange_rate(
+    pool: &SqlitePool,
+    from_currency: String,
+    to_currency: String,
+    date: String,
+) -> Result<f64, sqlx::Error> {
+    let from_currency = currency_code(&from_currency)?;
+    let to_currency = currency_code(&to_currency)?;
+    let rate: Option<f64> = sqlx::query_scalar(&format!("SELECT {}", rate_sql("?1", "?2", "?3")))
+        .bind(&from_currency)
+        .bind(&to_currency)
+        .bind(&date)
+        .fetch_one(pool)
+        .await?;
+
+    rate.ok_or_else(|| missing_rate(&from_currency, &to_currency, &date))
+}
+
+/// Imports a table of daily exchange rates from CSV text.
+///
+/// The first column holds the date (YYYY-MM-DD), every other column the rates
+/// of the currency named in its header against `quoted_against`. This is the
+/// layout of the ECB's euro foreign exchange reference rates history
+/// (`eurofxref-hist.csv`), quoted against EUR:
+///
+/// ```text
+/// Date,USD,JPY,CAD,
+/// 2025-01-03,1.0298,162.88,1.4853,
+/// ```
+///
+/// Empty and `N/A` cells are skipped. Rates already stored for the same pair
+/// and day are replaced, so a newer history file can be imported over an
+/// older one. The import is all or nothing.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `csv` - File contents
+/// * `quoted_against` - Currency the rates are expressed in units of, e.g. "EUR"
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with the number of `imported` rates,
+///   the `currencies` found and the first and last date (`date_from`, `date_to`)
+/// * `Err(sqlx::Error)` - Malformed CSV (with its line number), invalid code
+///   or database failure
+pub async fn import_exchange_rates(
+    pool: &SqlitePool,
+    csv: &str,
+    quoted_against: String,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let base = currency_code(&quoted_against)?;
+    let mut lines = csv
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let (_, header) = lines
+        .next()
+        .ok_or_else(|| sqlx::Error::Protocol("the rate file is empty".to_string()))?;
+    let currencies = csv_cells(header)
+        .skip(1)
+        .map(|cell| (!cell.is_empty()).then(|| currency_code(cell)).transpose())
+        .collect::<Result<Vec<Option<String>>, sqlx::Error>>()?;
+
+    let mut rates = Vec::new();
+    for (index, line) in lines {
+        let mut cells = csv_cells(line);
+        let date = cells.next().unwrap_or_default().to_string();
+        if !is_iso_date(&date) {
+            return Err(sqlx::Error::Protocol(format!(
+                "line {}: expected a YYYY-MM-DD date, got {:?}",
+                index + 1,
+                date
+            )));
+        }
+
+        for (currency, cell) in currencies.iter().zip(cells) {
+            let Some(currency) = currency else { continue };
+            if cell.is_empty() || cell.eq_ignore_ascii_case("N/A") || *currency == base {
+                continue;
+            }
+            let rate: f64 = cell.parse().map_err(|_| {
+                sqlx::Error::Protocol(format!("line {}: invalid {} rate {:?}", index + 1, currency, cell))
+            })?;
+            rates.push((date.clone(), currency.clone(), rate));
+        }
+    }
+
+    let imported = rates.len();
+    let date_from = rates.iter().map(|(date, _, _)| date).min().cloned();
+    let date_to = rates.iter().map(|(date, _, _)| date).max().cloned();
+    unit_of_work(pool, async |conn| {
+        for (date, currency, rate) in &rates {
+            store_rate(&mut *conn, date, &base, currency, *rate).await?;
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    Ok(serde_json::json!({
+        "imported": imported,
+        "currencies": currencies.into_iter().flatten().filter(|currency| *currency != base).collect::<Vec<String>>(),
+        "date_from": date_from,
+        "date_to": date_to
+    }))
+}
+
+/// Splits a CSV line into trimmed, unquoted cells.
+pub(crate) fn csv_cells(line: &str) -> impl Iterator<Item = &str> {
+    line.split(',').map(|cell| cell.trim().trim_matches('"').trim())
+}
+
+pub(crate) fn is_iso_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(index, byte)| index == 4 || index == 7 || byte.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_import_ecb_rates_and_cross_convert() {
+        let pool = setup_test_db().await;
+        let csv = "\u{feff}Date,USD,JPY,CAD,\n\
+                   2025-01-03,1.0298,162.88,1.4853,\n\
+                   2025-01-02,1.0321,N/A,1.4888,\n";
+        let summary = import_exchange_rates(&pool, csv, "eur".to_string()).await.unwrap();
+        assert_eq!(summary["imported"], 5);
+        assert_eq!(summary["currencies"], serde_json::json!(["USD", "JPY", "CAD"]));
+        assert_eq!(summary["date_from"], "2025-01-02");
+
+        // Direct, inverse and crossed through EUR; weekends use the last known rate
+        let direct = get_exchange_rate(&pool, "EUR".to_string(), "USD".to_string(), "2025-01-05".to_string()).await.unwrap();
+        assert_eq!(direct, 1.0298);
+        let inverse = get_exchange_rate(&pool, "USD".to_string(), "EUR".to_string(), "2025-01-02".to_string()).await.unwrap();
+        assert!((inverse - 1.0 / 1.0321).abs() < 1e-12);
+        let cross = get_exchange_rate(&pool, "CAD".to_string(), "USD".to_string(), "2025-01-03".to_string()).await.unwrap();
+        assert!((cross - 1.0298 / 1.4853).abs() < 1e-12);
+
+        // Nothing before the first rate
+        assert!(get_exchange_rate(&pool, "EUR".to_string(), "USD".to_string(), "2024-12-31".to_string()).await.is_err());
+
+        // Importing again replaces rather than duplicates
+        import_exchange_rates(&pool, "Date,USD\n2025-01-03,1.03\n", "EUR".to_string()).await.unwrap();
+        assert_eq!(get_exchange_rates(&pool, Some("usd".to_string()), 10).await.unwrap().len(), 2);
+
+        // A malformed file is rejected as a whole
+        assert!(import_exchange_rates(&pool, "Date,USD\n2025-01-06,1.04\n03/01/2025,1.0\n", "EUR".to_string()).await.is_err());
+        assert_eq!(get_exchange_rates(&pool, None, 100).await.unwrap().len(), 5);
+    }
+}
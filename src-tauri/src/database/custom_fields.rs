@@ -0,0 +1,348 @@
+//! User-defined fields on transactions and accounts.
+//!
+//! Each ledger defines its own fields, such as "Reimbursable" (boolean) on
+//! transactions or "Statement day" (number) on accounts. Field definitions live
+//! in the `custom_fields` table; the values are stored per record as a JSON
+//! object keyed by field id in the record's `custom_fields` column, so they are
+//! journaled, audited and exported with the record itself. Keying by id keeps
+//! values attached when a field is renamed.
+//!
+//! Values are checked against the field's type when written: a JSON string for
+//! text, a number for number, a `YYYY-MM-DD` string for date and `true`/`false`
+//! for boolean. Writing `null` clears a value.
+
+use super::journal::journaled;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::BTreeMap;
+
+/// Kind of record a custom field belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFieldEntity {
+    Account,
+    Transaction,
+}
+
+impl CustomFieldEntity {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            CustomFieldEntity::Account => "account",
+            CustomFieldEntity::Transaction => "transaction",
+        }
+    }
+}
+
+/// Type of the values a custom field holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFieldType {
+    Text,
+    Number,
+    /// ISO 8601 date (YYYY-MM-DD)
+    Date,
+    Boolean,
+}
+
+impl CustomFieldType {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            CustomFieldType::Text => "text",
+            CustomFieldType::Number => "number",
+            CustomFieldType::Date => "date",
+            CustomFieldType::Boolean => "boolean",
+        }
+    }
+
+    fn accepts(self, value: &serde_json::Value) -> bool {
+        match self {
+            CustomFieldType::Text => value.is_string(),
+            CustomFieldType::Number => value.is_number(),
+            CustomFieldType::Date => value.as_str().is_some_and(is_iso_date),
+            CustomFieldType::Boolean => value.is_boolean(),
+        }
+    }
+}
+
+/// Custom field values of one record, keyed by field id.
+pub type CustomFieldValues = BTreeMap<i64, serde_json::Value>;
+
+fn is_iso_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let digits = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+
+    bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && digits(0..4)
+        && digits(5..7)
+        && digits(8..10)
+        && matches!(text[5..7].parse::<u32>(), Ok(1..=12))
+        && matches!(text[8..10].parse::<u32>(), Ok(1..=31))
+}
+
+/// Validates values against their fields and returns the JSON to store.
+///
+/// Null values are dropped, so writing `null` clears a field.
+pub(crate) async fn values_json(
+    conn: &mut SqliteConnection,
+    entity: CustomFieldEntity,
+    values: &CustomFieldValues,
+) -> Result<String, sqlx::Error> {
+    let mut stored = serde_json::Map::new();
+    for (field_id, value) in values {
+        if value.is_null() {
+            continue;
+        }
+
+        let field = sqlx::query("SELECT name, field_type FROM custom_fields WHERE id = ? AND entity = ?")
+            .bind(field_id)
+            .bind(entity.as_str())
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or_else(|| {
+                sqlx::Error::Protocol(format!("unknown {} field {}", entity.as_str(), field_id))
+            })?;
+        let field_type: CustomFieldType = parse_type(field.get("field_type"))?;
+        if !field_type.accepts(value) {
+            return Err(sqlx::Error::Protocol(format!(
+                "{} is not a valid {} value for field {}",
+                value,
+                field_type.as_str(),
+                field.get::<String, _>("name")
+            )));
+        }
+
+        stored.insert(field_id.to_string(), value.clone());
+    }
+
+    Ok(serde_json::Value::Object(stored).to_string())
+}
+
+/// Parses a stored `custom_fields` column for output.
+pub(crate) fn parse_values(json: &str) -> Result<serde_json::Value, sqlx::Error> {
+    serde_json::from_str(json).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+fn parse_type(field_type: String) -> Result<CustomFieldType, sqlx::Error> {
+    serde_json::from_value(serde_json::Value::String(field_type))
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Retrieves the ledger's custom field definitions.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `entity` - Only fields of accounts or of transactions, or None for all
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with id, entity, name and field_type,
+///   in creation order
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_custom_fields(
+    pool: &SqlitePool,
+    entity: Option<CustomFieldEntity>,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT id, entity, name, field_type FROM custom_fields WHERE ? IS NULL OR entity = ? ORDER BY id",
+    )
+    .bind(entity.map(CustomFieldEntity::as_str))
+    .bind(entity.map(CustomFieldEntity::as_str))
+    .fetch_all(pool)
+    .await?;
+
+    let result: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "entity": row.get::<String, _>("entity"),
+                "name": row.get::<String, _>("name"),
+                "field_type": row.get::<String, _>("field_type")
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+/// Defines a new custom field for accounts or transactions.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `entity` - Whether the field belongs to accounts or transactions
+/// * `name` - Display name, unique per entity ignoring case
+/// * `field_type` - Type of the field's values; cannot be changed later
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new field, the key of its values
+/// * `Err(sqlx::Error)` - Empty or duplicate name, or database failure
+///
+/// # Examples
+/// ```no_run
+/// let reimbursable = add_custom_field(&pool, CustomFieldEntity::Transaction, "Reimbursable".to_string(), CustomFieldType::Boolean).await?;
+/// ```
+pub async fn add_custom_field(
+    pool: &SqlitePool,
+    entity: CustomFieldEntity,
+    name: String,
+    field_type: CustomFieldType,
+) -> Result<i64, sqlx::Error> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(sqlx::Error::Protocol("field name cannot be empty".to_string()));
+    }
+
+    journaled(pool, "Add custom field", async |conn| {
+        Ok(sqlx::query("INSERT INTO custom_fields (entity, name, field_type) VALUES (?, ?, ?)")
+            .bind(entity.as_str())
+            .bind(&name)
+            .bind(field_type.as_str())
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid())
+    })
+    .await
+}
+
+/// Renames a custom field. Stored values are keyed by id and stay attached.
+pub async fn rename_custom_field(pool: &SqlitePool, field_id: i64, name: String) -> Result<(), sqlx::Error> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(sqlx::Error::Protocol("field name cannot be empty".to_string()));
+    }
+
+    journaled(pool, "Rename custom field", async |conn| {
+        let result = sqlx::query("UPDATE custom_fields SET name = ? WHERE id = ?")
+            .bind(&name)
+            .bind(field_id)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Deletes a custom field together with its value on every record.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `field_id` - Field to delete
+///
+/// # Returns
+/// * `Ok(u64)` - Number of records that had a value for the field
+/// * `Err(sqlx::Error)` - Unknown field or database failure; nothing is deleted
+pub async fn delete_custom_field(pool: &SqlitePool, field_id: i64) -> Result<u64, sqlx::Error> {
+    journaled(pool, "Delete custom field", async |conn| {
+        let entity: String = sqlx::query_scalar("SELECT entity FROM custom_fields WHERE id = ?")
+            .bind(field_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        let table = if entity == "account" { "accounts" } else { "transactions" };
+
+        let path = format!("$.\"{}\"", field_id);
+        let cleared = sqlx::query(&format!(
+            "UPDATE {} SET custom_fields = json_remove(custom_fields, ?) WHERE json_extract(custom_fields, ?) IS NOT NULL",
+            table
+        ))
+        .bind(&path)
+        .bind(&path)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        sqlx::query("DELETE FROM custom_fields WHERE id = ?")
+            .bind(field_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(cleared)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money, TransactionDetails, TransactionFilter};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_custom_field_values_are_typed_and_searchable() {
+        let pool = setup_test_db().await;
+        let reimbursable = add_custom_field(&pool, CustomFieldEntity::Transaction, "Reimbursable".to_string(), CustomFieldType::Boolean)
+            .await
+            .unwrap();
+        let due = add_custom_field(&pool, CustomFieldEntity::Transaction, "Due".to_string(), CustomFieldType::Date)
+            .await
+            .unwrap();
+        let branch = add_custom_field(&pool, CustomFieldEntity::Account, "Branch".to_string(), CustomFieldType::Text)
+            .await
+            .unwrap();
+        assert!(add_custom_field(&pool, CustomFieldEntity::Transaction, "due".to_string(), CustomFieldType::Text).await.is_err());
+
+        let details = TransactionDetails {
+            memo: Some("Reimbursable by work".to_string()),
+            check_number: Some("1042".to_string()),
+            custom_fields: CustomFieldValues::from([(reimbursable, serde_json::json!(true))]),
+        };
+        let id = crate::database::add_transaction_with_details(&pool, 1, Money::new(-8900, Currency::USD), "debit".to_string(), "Conference Hotel".to_string(), "2025-05-20".to_string(), 1, &details)
+            .await
+            .unwrap();
+
+        // Values must match the field's type and entity
+        for (field_id, value) in [(due, serde_json::json!("20/05/2025")), (reimbursable, serde_json::json!("yes")), (branch, serde_json::json!("Main St"))] {
+            let bad = TransactionDetails {
+                custom_fields: CustomFieldValues::from([(field_id, value)]),
+                ..Default::default()
+            };
+            assert!(crate::database::add_transaction_with_details(&pool, 1, Money::new(-100, Currency::USD), "debit".to_string(), "Parking".to_string(), "2025-05-20".to_string(), 1, &bad)
+                .await
+                .is_err());
+        }
+
+        let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0]["id"], id);
+        assert_eq!(transactions[0]["memo"], "Reimbursable by work");
+        assert_eq!(transactions[0]["custom_fields"][reimbursable.to_string()], true);
+
+        // The memo is full-text searchable, the fields are filters
+        let filter = TransactionFilter {
+            query: Some("reimbursable".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(crate::database::search_transactions(&pool, &filter, 10).await.unwrap().len(), 1);
+        let filter = TransactionFilter {
+            check_number: Some("1042".to_string()),
+            custom_fields: CustomFieldValues::from([(reimbursable, serde_json::json!(true))]),
+            ..Default::default()
+        };
+        assert_eq!(crate::database::search_transactions(&pool, &filter, 10).await.unwrap().len(), 1);
+        let filter = TransactionFilter {
+            custom_fields: CustomFieldValues::from([(reimbursable, serde_json::json!(false))]),
+            ..Default::default()
+        };
+        assert!(crate::database::search_transactions(&pool, &filter, 10).await.unwrap().is_empty());
+
+        // Deleting the field removes its values
+        assert_eq!(delete_custom_field(&pool, reimbursable).await.unwrap(), 1);
+        let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(transactions[0]["custom_fields"], serde_json::json!({}));
+    }
+}
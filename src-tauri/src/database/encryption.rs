@@ -0,0 +1,156 @@
+//! Optional at-rest encryption of ledger files with SQLCipher.
+//!
+//! Builds with the `encryption` feature link SQLCipher in place of plain SQLite.
+//! An encrypted ledger is opened by sending `PRAGMA key` as the first statement
+//! on every connection (see [`super::init_db()`]); without the right passphrase
+//! the file is indistinguishable from random data.
+//!
+//! Conversions between plain and encrypted files, and passphrase changes, all
+//! use `sqlcipher_export()` to write a re-keyed copy that then replaces the
+//! original, so a failure midway never leaves a half-converted ledger.
+
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use std::io::Read;
+use std::path::Path;
+
+/// First 16 bytes of every unencrypted SQLite database file.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Reports whether a database file is encrypted.
+///
+/// A plain SQLite file always starts with a fixed header; an SQLCipher file
+/// starts with its random salt instead. Missing or empty files are not
+/// encrypted (a new ledger has not been written yet).
+///
+/// # Arguments
+/// * `path` - Path of the database file
+///
+/// # Returns
+/// * `Ok(bool)` - Whether the file is encrypted
+/// * `Err(sqlx::Error)` - The file exists but cannot be read
+pub fn is_encrypted(path: &Path) -> Result<bool, sqlx::Error> {
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    let mut header = Vec::with_capacity(SQLITE_HEADER.len());
+    std::fs::File::open(path)?
+        .take(SQLITE_HEADER.len() as u64)
+        .read_to_end(&mut header)?;
+
+    Ok(!header.is_empty() && header != SQLITE_HEADER)
+}
+
+/// Quotes a passphrase as an SQL string literal for `PRAGMA key`.
+///
+/// `PRAGMA` statements cannot take bound parameters, so the passphrase is
+/// embedded with single quotes doubled.
+pub fn quote_key(passphrase: &str) -> String {
+    format!("'{}'", passphrase.replace('\'', "''"))
+}
+
+/// Fails unless the linked SQLite library is SQLCipher.
+///
+/// Plain SQLite silently ignores `PRAGMA key`, which would leave a ledger the
+/// user believes is encrypted in plaintext. Every encryption path checks this
+/// first.
+///
+/// # Arguments
+/// * `pool` - Any open connection pool
+///
+/// # Returns
+/// * `Ok(())` - SQLCipher is available
+/// * `Err(sqlx::Error)` - This build has no encryption support
+pub async fn ensure_supported(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let version: Option<String> = sqlx::query_scalar("PRAGMA cipher_version")
+        .fetch_optional(pool)
+        .await?;
+
+    match version {
+        Some(_) => Ok(()),
+        None => Err(sqlx::Error::Protocol(
+            "this build of finsight does not include database encryption support".to_string(),
+        )),
+    }
+}
+
+/// Writes a copy of the database with a different key (or no key).
+///
+/// Attaches the destination with the requested key and copies schema and data
+/// into it with `sqlcipher_export()`. Passing `None` writes a plain SQLite file.
+///
+/// # Arguments
+/// * `pool` - Connection pool of the source database
+/// * `destination` - Path of the new file; must not exist yet
+/// * `passphrase` - Key for the copy, or `None` for an unencrypted copy
+///
+/// # Returns
+/// * `Ok(())` - Copy written successfully
+/// * `Err(sqlx::Error)` - Encryption unsupported, destination exists, or export failure
+pub async fn export_copy(
+    pool: &SqlitePool,
+    destination: &Path,
+    passphrase: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    ensure_supported(pool).await?;
+
+    if destination.exists() {
+        return Err(sqlx::Error::Protocol(format!(
+            "{} already exists",
+            destination.display()
+        )));
+    }
+
+    // ATTACH, export and DETACH must all run on the same connection, and on a
+    // fresh one: sqlcipher_export() fails with "database schema has changed" on
+    // a connection that has itself run DDL, as pooled ledger connections have
+    let mut conn = SqliteConnection::connect_with(&pool.connect_options()).await?;
+
+    sqlx::query("ATTACH DATABASE ? AS export KEY ?")
+        .bind(destination.to_string_lossy().into_owned())
+        .bind(passphrase.unwrap_or(""))
+        .execute(&mut conn)
+        .await?;
+
+    let exported = sqlx::query("SELECT sqlcipher_export('export')")
+        .execute(&mut conn)
+        .await;
+
+    sqlx::query("DETACH DATABASE export")
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+    exported?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_key_escapes_quotes() {
+        assert_eq!(quote_key("correct horse"), "'correct horse'");
+        assert_eq!(quote_key("it's"), "'it''s'");
+    }
+
+    #[tokio::test]
+    async fn test_is_encrypted_detects_plain_files() {
+        let dir = std::env::temp_dir().join(format!("finsight-encryption-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let plain = dir.join("plain.db");
+        let pool = crate::database::init_db(&plain, None).await.unwrap();
+        pool.close().await;
+        assert!(!is_encrypted(&plain).unwrap());
+        assert!(!is_encrypted(&dir.join("missing.db")).unwrap());
+
+        let scrambled = dir.join("scrambled.db");
+        std::fs::write(&scrambled, [0x5a_u8; 4096]).unwrap();
+        assert!(is_encrypted(&scrambled).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
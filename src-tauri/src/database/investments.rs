@@ -0,0 +1,1579 @@
+//! Investment accounts: securities, trades, lots, prices and holdings.
+//!
+//! Securities (stocks, ETFs, funds, ...) are defined once per ledger and
+//! traded in any account with the same currency. Each buy, sell, dividend or
+//! split is an investment transaction; buys, sells and dividends also record
+//! their cash side as an ordinary, linked transaction in the account, so the
+//! account balance stays the cash balance.
+//!
+//! Quantities are stored as integers in hundred-millionths of a share (see
+//! [`QUANTITY_SCALE`]), which covers fractional shares and crypto. Lots are not
+//! stored: they are rebuilt by replaying an account's trades in date order. A
+//! buy opens a lot costing its amount plus fee, a sale takes shares from lots
+//! as the [`CostBasisMethod`] its account had when the sale was recorded says,
+//! and a split scales every open lot's quantity while keeping its cost and
+//! purchase date. A split is recorded once per security and date, in any
+//! account, and applies to the security's lots in every account.
+//!
+//! Market values use the latest price on or before the valuation date, from
+//! prices entered by hand or imported from a CSV download, falling back to the
+//! price of the latest trade.
+
+use super::accounts::{account_amount, account_currency};
+use super::currencies::{BASE_CURRENCY, csv_cells, currency_code, is_iso_date};
+use super::journal::journaled;
+use super::money::{Currency, Money, div_round_half_even};
+use super::unit_of_work;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::BTreeMap;
+
+/// Stored units per share: quantities are kept in hundred-millionths of a share.
+pub const QUANTITY_SCALE: i64 = 100_000_000;
+
+/// Kind of security.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityType {
+    Stock,
+    Etf,
+    MutualFund,
+    Bond,
+    Crypto,
+    Other,
+}
+
+impl SecurityType {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            SecurityType::Stock => "stock",
+            SecurityType::Etf => "etf",
+            SecurityType::MutualFund => "mutual_fund",
+            SecurityType::Bond => "bond",
+            SecurityType::Crypto => "crypto",
+            SecurityType::Other => "other",
+        }
+    }
+}
+
+/// How a sale picks the lots it takes shares from and their cost, set per
+/// account with [`set_cost_basis_method()`] and stamped on each sale.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CostBasisMethod {
+    /// Oldest lots first
+    #[default]
+    Fifo,
+    /// Newest lots first
+    Lifo,
+    /// Lots chosen per sale; shares not identified are taken oldest first
+    Specific,
+    /// Oldest lots first for the holding period, at the average cost of all
+    /// shares held
+    Average,
+}
+
+impl CostBasisMethod {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            CostBasisMethod::Fifo => "fifo",
+            CostBasisMethod::Lifo => "lifo",
+            CostBasisMethod::Specific => "specific",
+            CostBasisMethod::Average => "average",
+        }
+    }
+}
+
+/// Shares of one lot sold by a sale with specific identification.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LotSelection {
+    /// ID of the buy that opened the lot
+    pub lot_id: i64,
+    pub quantity: f64,
+}
+
+/// One trade in an investment account.
+///
+/// Amounts are in the account's currency, which must be the security's
+/// currency. Quantities are numbers of shares and may be fractional.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum InvestmentTrade {
+    /// Shares bought for `amount` (positive), plus an optional commission
+    Buy {
+        quantity: f64,
+        amount: Money,
+        #[serde(default)]
+        fee: Option<Money>,
+    },
+    /// Shares sold for `amount` (positive, before the commission); `lots`
+    /// identifies the lots sold in accounts using [`CostBasisMethod::Specific`]
+    Sell {
+        quantity: f64,
+        amount: Money,
+        #[serde(default)]
+        fee: Option<Money>,
+        #[serde(default)]
+        lots: Vec<LotSelection>,
+    },
+    /// Cash dividend paid on the shares held
+    Dividend { amount: Money },
+    /// Stock split turning every `from` shares into `to` shares, e.g. 1 to 4;
+    /// a reverse split has `to` below `from`. Applies to the security in every
+    /// account
+    Split { from: u32, to: u32 },
+}
+
+/// Converts a number of shares into stored units.
+fn quantity_units(quantity: f64) -> Result<i64, sqlx::Error> {
+    let units = (quantity * QUANTITY_SCALE as f64).round();
+    if !units.is_finite() || units < 1.0 || units >= i64::MAX as f64 {
+        return Err(sqlx::Error::Protocol(format!("invalid quantity {}", quantity)));
+    }
+
+    Ok(units as i64)
+}
+
+/// Number of shares in stored units, for JSON output.
+pub(crate) fn quantity_of(units: i64) -> f64 {
+    units as f64 / QUANTITY_SCALE as f64
+}
+
+/// Shows stored units as a plain decimal number of shares, e.g. "12.5".
+pub(crate) fn format_quantity(units: i64) -> String {
+    let whole = units / QUANTITY_SCALE;
+    let fraction = units % QUANTITY_SCALE;
+    if fraction == 0 {
+        whole.to_string()
+    } else {
+        format!("{}.{:08}", whole, fraction).trim_end_matches('0').to_string()
+    }
+}
+
+/// Cleans up a ticker as typed by the user: trimmed and uppercase.
+fn clean_ticker(ticker: &str) -> Result<String, sqlx::Error> {
+    let ticker = ticker.trim().to_uppercase();
+    if ticker.is_empty() {
+        return Err(sqlx::Error::Protocol("ticker cannot be empty".to_string()));
+    }
+
+    Ok(ticker)
+}
+
+/// Retrieves all securities, by ticker, with their latest price.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Securities with id, ticker, name,
+///   security_type, currency, `latest_price` and `latest_price_date` (null
+///   without prices)
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_securities(pool: &SqlitePool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT s.id, s.ticker, s.name, s.security_type, s.currency, p.price, p.price_date
+         FROM securities s
+         LEFT JOIN security_prices p ON p.id = (
+             SELECT id FROM security_prices WHERE security_id = s.id ORDER BY price_date DESC LIMIT 1
+         )
+         ORDER BY s.ticker",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let result: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "ticker": row.get::<String, _>("ticker"),
+                "name": row.get::<String, _>("name"),
+                "security_type": row.get::<String, _>("security_type"),
+                "currency": row.get::<String, _>("currency"),
+                "latest_price": row.get::<Option<f64>, _>("price"),
+                "latest_price_date": row.get::<Option<String>, _>("price_date")
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+/// Defines a new security.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `ticker` - Trading symbol such as "VTI"; stored in uppercase and unique
+/// * `name` - Display name
+/// * `security_type` - Kind of security
+/// * `currency` - Currency the security trades in; defaults to the base currency
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new security
+/// * `Err(sqlx::Error)` - Empty ticker or name, a ticker already in use, an
+///   invalid currency code or database failure
+///
+/// # Examples
+/// ```no_run
+/// let id = add_security(&pool, "vti".to_string(), "Vanguard Total Stock Market ETF".to_string(), SecurityType::Etf, None).await?;
+/// ```
+pub async fn add_security(
+    pool: &SqlitePool,
+    ticker: String,
+    name: String,
+    security_type: SecurityType,
+    currency: Option<String>,
+) -> Result<i64, sqlx::Error> {
+    let ticker = clean_ticker(&ticker)?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(sqlx::Error::Protocol("security name cannot be empty".to_string()));
+    }
+    let currency = currency.as_deref().map(currency_code).transpose()?;
+
+    journaled(pool, "Add security", async |conn| {
+        let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM securities WHERE ticker = ?")
+            .bind(&ticker)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if existing.is_some() {
+            return Err(sqlx::Error::Protocol(format!("a security with ticker {} already exists", ticker)));
+        }
+
+        let id = sqlx::query(&format!(
+            "INSERT INTO securities (ticker, name, security_type, currency) VALUES (?, ?, ?, COALESCE(?, {}))",
+            BASE_CURRENCY
+        ))
+        .bind(&ticker)
+        .bind(&name)
+        .bind(security_type.as_str())
+        .bind(currency)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+        Ok(id)
+    })
+    .await
+}
+
+/// Renames a security or changes its type. The ticker and currency are fixed
+/// once the security exists, since trades and prices refer to them.
+///
+/// # Returns
+/// * `Ok(())` - Security updated
+/// * `Err(sqlx::Error)` - `RowNotFound` for an unknown security, an empty name
+///   or database failure
+pub async fn update_security(
+    pool: &SqlitePool,
+    security_id: i64,
+    name: String,
+    security_type: SecurityType,
+) -> Result<(), sqlx::Error> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(sqlx::Error::Protocol("security name cannot be empty".to_string()));
+    }
+
+    journaled(pool, "Update security", async |conn| {
+        let result = sqlx::query("UPDATE securities SET name = ?, security_type = ? WHERE id = ?")
+            .bind(&name)
+            .bind(security_type.as_str())
+            .bind(security_id)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Deletes a security that has never been traded, together with its prices.
+///
+/// Prices, like exchange rates, are not part of the change journal: undoing
+/// the deletion brings the security back without its price history.
+///
+/// # Returns
+/// * `Ok(())` - Security and prices deleted
+/// * `Err(sqlx::Error)` - `RowNotFound` for an unknown security, a security
+///   with investment transactions, or database failure
+pub async fn delete_security(pool: &SqlitePool, security_id: i64) -> Result<(), sqlx::Error> {
+    journaled(pool, "Delete security", async |conn| {
+        let trades: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM investment_transactions WHERE security_id = ?")
+            .bind(security_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        if trades > 0 {
+            return Err(sqlx::Error::Protocol(format!(
+                "the security has {} investment transactions; delete them first",
+                trades
+            )));
+        }
+
+        sqlx::query("DELETE FROM security_prices WHERE security_id = ?")
+            .bind(security_id)
+            .execute(&mut *conn)
+            .await?;
+        let result = sqlx::query("DELETE FROM securities WHERE id = ?")
+            .bind(security_id)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Records a buy, sell, dividend or split in an investment account.
+///
+/// Buys, sells and dividends also add their cash side to the account as an
+/// uncategorized transaction linked through `transaction_id`: a buy debits the
+/// amount plus fee, a sale credits the amount less fee and a dividend credits
+/// the amount. A split only changes quantities, of the security's lots in
+/// every account, and is recorded once per security and date. A sale may not
+/// sell more shares than the account holds on its date, and no trade may leave
+/// a later sale short of shares.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `account_id` - Database ID of the account
+/// * `security_id` - Database ID of the security traded
+/// * `trade_date` - Trade date (YYYY-MM-DD)
+/// * `trade` - What happened, with quantities and amounts
+/// * `description` - Description of the cash transaction; defaults to e.g. "Buy 10 VTI"
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new investment transaction
+/// * `Err(sqlx::Error)` - Unknown account or security, a security in another
+///   currency than the account, invalid quantities, amounts or split ratio, a
+///   second split of the security on the same date, a sale of more shares than
+///   held, or database failure; nothing is recorded
+///
+/// # Examples
+/// ```no_run
+/// let trade = InvestmentTrade::Buy {
+///     quantity: 10.0,
+///     amount: Money::new(250000, Currency::USD),
+///     fee: Some(Money::new(495, Currency::USD)),
+/// };
+/// add_investment_transaction(&pool, 3, 1, "2025-02-03".to_string(), &trade, None).await?;
+/// ```
+pub async fn add_investment_transaction(
+    pool: &SqlitePool,
+    account_id: i64,
+    security_id: i64,
+    trade_date: String,
+    trade: &InvestmentTrade,
+    description: Option<String>,
+) -> Result<i64, sqlx::Error> {
+    if !is_iso_date(&trade_date) {
+        return Err(sqlx::Error::Protocol(format!("expected a YYYY-MM-DD date, got {:?}", trade_date)));
+    }
+
+    journaled(pool, "Add investment transaction", async |conn| {
+        let security = sqlx::query("SELECT ticker, currency FROM securities WHERE id = ?")
+            .bind(security_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        let ticker: String = security.get("ticker");
+        let currency = account_currency(&mut *conn, account_id).await?;
+        if security.get::<&str, _>("currency") != currency.code() {
+            return Err(sqlx::Error::Protocol(format!(
+                "{} trades in {} but account {} is in {}",
+                ticker,
+                security.get::<&str, _>("currency"),
+                account_id,
+                currency
+            )));
+        }
+
+        let mut row = StoredTrade::default();
+        if let InvestmentTrade::Sell { .. } = trade {
+            row.method = Some(cost_basis_method(&mut *conn, account_id).await?);
+        }
+        let cash_cents = match trade {
+            InvestmentTrade::Buy { quantity, amount, fee } | InvestmentTrade::Sell { quantity, amount, fee, .. } => {
+                let buy = matches!(trade, InvestmentTrade::Buy { .. });
+                row.action = if buy { "buy" } else { "sell" };
+                row.quantity_units = quantity_units(*quantity)?;
+                row.amount_cents = trade_amount(&mut *conn, account_id, *amount).await?;
+                row.fee_cents = match fee {
+                    Some(fee) if fee.is_negative() => {
+                        return Err(sqlx::Error::Protocol("a fee cannot be negative".to_string()));
+                    }
+                    Some(fee) => account_amount(&mut *conn, account_id, *fee).await?,
+                    None => 0,
+                };
+                let action = if buy { "Buy" } else { "Sell" };
+                row.description = format!("{} {} {}", action, format_quantity(row.quantity_units), ticker);
+                let cash = if buy {
+                    row.amount_cents.checked_add(row.fee_cents).map(|cost| -cost)
+                } else {
+                    row.amount_cents.checked_sub(row.fee_cents)
+                };
+                Some(cash.ok_or_else(|| sqlx::Error::Protocol("trade amount is too large".to_string()))?)
+            }
+            InvestmentTrade::Dividend { amount } => {
+                row.action = "dividend";
+                row.amount_cents = trade_amount(&mut *conn, account_id, *amount).await?;
+                row.description = format!("Dividend {}", ticker);
+                Some(row.amount_cents)
+            }
+            InvestmentTrade::Split { from, to } => {
+                if *from == 0 || *to == 0 || from == to {
+                    return Err(sqlx::Error::Protocol(format!("invalid split ratio {}:{}", to, from)));
+                }
+                let recorded: bool = sqlx::query_scalar(
+                    "SELECT EXISTS (SELECT 1 FROM investment_transactions WHERE security_id = ? AND trade_date = ? AND action = 'split')",
+                )
+                .bind(security_id)
+                .bind(&trade_date)
+                .fetch_one(&mut *conn)
+                .await?;
+                if recorded {
+                    return Err(sqlx::Error::Protocol(format!(
+                        "a split of {} on {} is already recorded; it applies to every account",
+                        ticker, trade_date
+                    )));
+                }
+                row.action = "split";
+                row.split = Some((*from as i64, *to as i64));
+                row.description = format!("Split {}:{} {}", to, from, ticker);
+                None
+            }
+        };
+
+        let transaction_id = match cash_cents {
+            Some(cash_cents) => {
+                let description = description.clone().unwrap_or_else(|| row.description.clone());
+                Some(insert_cash_leg(&mut *conn, account_id, cash_cents, &description, &trade_date).await?)
+            }
+            None => None,
+        };
+
+        let id = sqlx::query(
+            "INSERT INTO investment_transactions (account_id, security_id, action, trade_date, quantity_units, amount_cents, fee_cents, split_from, split_to, transaction_id, cost_basis_method)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(account_id)
+        .bind(security_id)
+        .bind(row.action)
+        .bind(&trade_date)
+        .bind(row.quantity_units)
+        .bind(row.amount_cents)
+        .bind(row.fee_cents)
+        .bind(row.split.map(|(from, _)| from))
+        .bind(row.split.map(|(_, to)| to))
+        .bind(transaction_id)
+        .bind(row.method.map(CostBasisMethod::as_str))
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+        if let InvestmentTrade::Sell { lots, .. } = trade {
+            select_lots(&mut *conn, account_id, id, row.quantity_units, lots, row.method).await?;
+        }
+        check_positions(&mut *conn, (row.action != "split").then_some(account_id)).await?;
+
+        Ok(id)
+    })
+    .await
+}
+
+/// Columns of an investment transaction being added.
+#[derive(Default)]
+struct StoredTrade {
+    action: &'static str,
+    quantity_units: i64,
+    amount_cents: i64,
+    fee_cents: i64,
+    split: Option<(i64, i64)>,
+    /// Cost basis method a sale is recorded with
+    method: Option<CostBasisMethod>,
+    description: String,
+}
+
+/// Records the lots a sale identifies, which must add up to the shares sold.
+/// Whether each lot holds enough shares is checked by the replay.
+async fn select_lots(
+    conn: &mut SqliteConnection,
+    account_id: i64,
+    sale_id: i64,
+    sold_units: i64,
+    lots: &[LotSelection],
+    method: Option<CostBasisMethod>,
+) -> Result<(), sqlx::Error> {
+    if lots.is_empty() {
+        return Ok(());
+    }
+    if method != Some(CostBasisMethod::Specific) {
+        return Err(sqlx::Error::Protocol(
+            "lots can only be identified in accounts using specific identification".to_string(),
+        ));
+    }
+
+    let mut selected = 0;
+    for lot in lots {
+        let units = quantity_units(lot.quantity)?;
+        selected += units;
+        let buy: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM investment_transactions
+             WHERE id = ? AND account_id = ? AND action = 'buy'
+               AND security_id = (SELECT security_id FROM investment_transactions WHERE id = ?)",
+        )
+        .bind(lot.lot_id)
+        .bind(account_id)
+        .bind(sale_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if buy.is_none() {
+            return Err(sqlx::Error::Protocol(format!(
+                "{} is not a purchase of this security in the account",
+                lot.lot_id
+            )));
+        }
+        sqlx::query("INSERT INTO lot_selections (sale_id, lot_id, quantity_units) VALUES (?, ?, ?)")
+            .bind(sale_id)
+            .bind(lot.lot_id)
+            .bind(units)
+            .execute(&mut *conn)
+            .await
+            .map_err(|_| sqlx::Error::Protocol(format!("lot {} is selected twice", lot.lot_id)))?;
+    }
+    if selected != sold_units {
+        return Err(sqlx::Error::Protocol(format!(
+            "the selected lots hold {} shares but {} are sold",
+            format_quantity(selected),
+            format_quantity(sold_units)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Minor units of a trade's main amount, which must be positive.
+async fn trade_amount(conn: &mut SqliteConnection, account_id: i64, amount: Money) -> Result<i64, sqlx::Error> {
+    if !amount.is_positive() {
+        return Err(sqlx::Error::Protocol(format!("trade amount must be positive, got {}", amount)));
+    }
+
+    account_amount(&mut *conn, account_id, amount).await
+}
+
+/// Records the cash side of a trade as an uncategorized transaction.
+async fn insert_cash_leg(
+    conn: &mut SqliteConnection,
+    account_id: i64,
+    amount_cents: i64,
+    description: &str,
+    date: &str,
+) -> Result<i64, sqlx::Error> {
+    let uncategorized_id: i64 = sqlx::query_scalar("SELECT id FROM categories WHERE name = 'Uncategorized'")
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(sqlx::query(
+        "INSERT INTO transactions (account_id, amount_cents, transaction_type, description, transaction_date, category_id)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(account_id)
+    .bind(amount_cents)
+    .bind(if amount_cents < 0 { "debit" } else { "credit" })
+    .bind(description)
+    .bind(date)
+    .bind(uncategorized_id)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid())
+}
+
+/// Deletes an investment transaction and moves its cash transaction to the
+/// trash. Deleting a split takes it out of every account.
+///
+/// # Returns
+/// * `Ok(())` - Trade deleted
+/// * `Err(sqlx::Error)` - `RowNotFound` for an unknown trade, a deletion that
+///   would leave a later sale short of shares, or database failure
+pub async fn delete_investment_transaction(pool: &SqlitePool, investment_transaction_id: i64) -> Result<(), sqlx::Error> {
+    journaled(pool, "Delete investment transaction", async |conn| {
+        let row = sqlx::query("SELECT account_id, action, transaction_id FROM investment_transactions WHERE id = ?")
+            .bind(investment_transaction_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        sqlx::query("UPDATE transactions SET deleted_at = datetime('now') WHERE id = ? AND deleted_at IS NULL")
+            .bind(row.get::<Option<i64>, _>("transaction_id"))
+            .execute(&mut *conn)
+            .await?;
+        // Sales that identified a deleted lot take those shares oldest first instead
+        sqlx::query("DELETE FROM lot_selections WHERE sale_id = ?1 OR lot_id = ?1")
+            .bind(investment_transaction_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM investment_transactions WHERE id = ?")
+            .bind(investment_transaction_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let account_id = (row.get::<&str, _>("action") != "split").then(|| row.get("account_id"));
+        check_positions(&mut *conn, account_id).await
+    })
+    .await
+}
+
+/// Retrieves the trades of an account, most recent first, with the splits of
+/// the securities it traded wherever they were recorded.
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Trades with id, security_id, ticker,
+///   action, trade_date, quantity (shares), amount_cents, fee_cents,
+///   split_from, split_to and the transaction_id of the cash transaction
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_investment_transactions(
+    pool: &SqlitePool,
+    account_id: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT i.id, i.security_id, s.ticker, i.action, i.trade_date, i.quantity_units, i.amount_cents,
+                i.fee_cents, i.split_from, i.split_to, i.transaction_id
+         FROM investment_transactions i
+         JOIN securities s ON s.id = i.security_id
+         WHERE i.account_id = ?1
+            OR (i.action = 'split' AND i.security_id IN (SELECT security_id FROM investment_transactions WHERE account_id = ?1))
+         ORDER BY i.trade_date DESC, i.id DESC",
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await?;
+
+    let result: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "account_id": account_id,
+                "security_id": row.get::<i64, _>("security_id"),
+                "ticker": row.get::<String, _>("ticker"),
+                "action": row.get::<String, _>("action"),
+                "trade_date": row.get::<String, _>("trade_date"),
+                "quantity": quantity_of(row.get("quantity_units")),
+                "amount_cents": row.get::<i64, _>("amount_cents"),
+                "fee_cents": row.get::<i64, _>("fee_cents"),
+                "split_from": row.get::<Option<i64>, _>("split_from"),
+                "split_to": row.get::<Option<i64>, _>("split_to"),
+                "transaction_id": row.get::<Option<i64>, _>("transaction_id")
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+/// Sets the closing price of a security on a day, replacing any price
+/// already stored for that day.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `security_id` - Database ID of the security
+/// * `price_date` - Day of the price (YYYY-MM-DD)
+/// * `price` - Price of one share in the security's currency
+///
+/// # Returns
+/// * `Ok(())` - Price stored
+/// * `Err(sqlx::Error)` - Unknown security, invalid date or price, or database failure
+pub async fn set_security_price(
+    pool: &SqlitePool,
+    security_id: i64,
+    price_date: String,
+    price: f64,
+) -> Result<(), sqlx::Error> {
+    if !is_iso_date(&price_date) {
+        return Err(sqlx::Error::Protocol(format!("expected a YYYY-MM-DD date, got {:?}", price_date)));
+    }
+    unit_of_work(pool, async |conn| {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM securities WHERE id = ?")
+            .bind(security_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if exists.is_none() {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        store_price(&mut *conn, security_id, &price_date, price).await
+    })
+    .await
+}
+
+async fn store_price(conn: &mut SqliteConnection, security_id: i64, price_date: &str, price: f64) -> Result<(), sqlx::Error> {
+    if !price.is_finite() || price <= 0.0 {
+        return Err(sqlx::Error::Protocol(format!("price must be positive, got {}", price)));
+    }
+
+    sqlx::query(
+        "INSERT INTO security_prices (security_id, price_date, price) VALUES (?, ?, ?)
+         ON CONFLICT (security_id, price_date) DO UPDATE SET price = excluded.price",
+    )
+    .bind(security_id)
+    .bind(price_date)
+    .bind(price)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Retrieves stored prices of a security, newest first.
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Prices with id, price_date and price
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_security_prices(
+    pool: &SqlitePool,
+    security_id: i64,
+    limit: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT id, price_date, price FROM security_prices WHERE security_id = ? ORDER BY price_date DESC LIMIT ?",
+    )
+    .bind(security_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let result: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "price_date": row.get::<String, _>("price_date"),
+                "price": row.get::<f64, _>("price")
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+/// Imports daily security prices from CSV text.
+///
+/// The header names the columns, in any order and case: `Date` (YYYY-MM-DD),
+/// `Ticker` or `Symbol`, and the price as `Close`, `Price` or `NAV`. Other
+/// columns are ignored. A file with one security's history, such as a Yahoo
+/// Finance download, has no ticker column; pass the ticker instead:
+///
+/// ```text
+/// Date,Open,High,Low,Close,Adj Close,Volume
+/// 2025-01-03,289.10,292.35,288.40,291.86,291.86,3120400
+/// ```
+///
+/// Empty, `null` and `N/A` prices are skipped. Prices already stored for the
+/// same security and day are replaced. The import is all or nothing.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `csv` - File contents
+/// * `ticker` - Security of every row, for files without a ticker column
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with the number of `imported` prices,
+///   the `securities` (tickers) found and the first and last date
+///   (`date_from`, `date_to`)
+/// * `Err(sqlx::Error)` - Missing columns, malformed rows (with their line
+///   number), unknown tickers or database failure
+pub async fn import_security_prices(
+    pool: &SqlitePool,
+    csv: &str,
+    ticker: Option<String>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let ticker = ticker.as_deref().map(clean_ticker).transpose()?;
+    let mut lines = csv
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let (_, header) = lines
+        .next()
+        .ok_or_else(|| sqlx::Error::Protocol("the price file is empty".to_string()))?;
+    let columns: Vec<String> = csv_cells(header).map(|cell| cell.to_lowercase()).collect();
+    let column = |names: &[&str]| names.iter().find_map(|name| columns.iter().position(|column| column == name));
+    let date_column =
+        column(&["date"]).ok_or_else(|| sqlx::Error::Protocol("the price file has no Date column".to_string()))?;
+    let price_column = column(&["close", "price", "nav"])
+        .ok_or_else(|| sqlx::Error::Protocol("the price file has no Close, Price or NAV column".to_string()))?;
+    let ticker_column = match ticker {
+        Some(_) => None,
+        None => Some(column(&["ticker", "symbol"]).ok_or_else(|| {
+            sqlx::Error::Protocol("the price file has no Ticker column; choose the security it is for".to_string())
+        })?),
+    };
+
+    let mut prices = Vec::new();
+    for (index, line) in lines {
+        let cells: Vec<&str> = csv_cells(line).collect();
+        let cell = |column: usize| cells.get(column).copied().unwrap_or_default();
+        let price = cell(price_column);
+        if price.is_empty() || price.eq_ignore_ascii_case("null") || price.eq_ignore_ascii_case("N/A") {
+            continue;
+        }
+
+        let date = cell(date_column).to_string();
+        if !is_iso_date(&date) {
+            return Err(sqlx::Error::Protocol(format!(
+                "line {}: expected a YYYY-MM-DD date, got {:?}",
+                index + 1,
+                date
+            )));
+        }
+        let row_ticker = match (&ticker, ticker_column) {
+            (Some(ticker), _) => ticker.clone(),
+            (None, Some(column)) => clean_ticker(cell(column))
+                .map_err(|_| sqlx::Error::Protocol(format!("line {}: missing ticker", index + 1)))?,
+            (None, None) => unreachable!("a ticker column is required without a ticker"),
+        };
+        let price: f64 = price
+            .parse()
+            .ok()
+            .filter(|price: &f64| price.is_finite() && *price > 0.0)
+            .ok_or_else(|| sqlx::Error::Protocol(format!("line {}: invalid price {:?}", index + 1, price)))?;
+        prices.push((index + 1, row_ticker, date, price));
+    }
+
+    let imported = prices.len();
+    let date_from = prices.iter().map(|(_, _, date, _)| date).min().cloned();
+    let date_to = prices.iter().map(|(_, _, date, _)| date).max().cloned();
+    let securities = unit_of_work(pool, async |conn| {
+        let mut securities: BTreeMap<String, i64> = BTreeMap::new();
+        for (line, ticker, date, price) in &prices {
+            let security_id = match securities.get(ticker) {
+                Some(id) => *id,
+                None => {
+                    let id: i64 = sqlx::query_scalar("SELECT id FROM securities WHERE ticker = ?")
+                        .bind(ticker)
+                        .fetch_optional(&mut *conn)
+                        .await?
+                        .ok_or_else(|| sqlx::Error::Protocol(format!("line {}: unknown ticker {}", line, ticker)))?;
+                    securities.insert(ticker.clone(), id);
+                    id
+                }
+            };
+            store_price(&mut *conn, security_id, date, *price).await?;
+        }
+
+        Ok(securities)
+    })
+    .await?;
+
+    Ok(serde_json::json!({
+        "imported": imported,
+        "securities": securities.into_keys().collect::<Vec<String>>(),
+        "date_from": date_from,
+        "date_to": date_to
+    }))
+}
+
+/// Chooses how sales in an account pick lots and their cost.
+///
+/// The method applies to sales recorded from now on. Earlier sales keep the
+/// method they were recorded with, so gains already realized, and reported for
+/// past tax years, never change.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `account_id` - Database ID of the account
+/// * `method` - New cost basis method
+///
+/// # Returns
+/// * `Ok(())` - Method saved
+/// * `Err(sqlx::Error)` - `RowNotFound` for an unknown account, or database failure
+pub async fn set_cost_basis_method(
+    pool: &SqlitePool,
+    account_id: i64,
+    method: CostBasisMethod,
+) -> Result<(), sqlx::Error> {
+    journaled(pool, "Set cost basis method", async |conn| {
+        let result = sqlx::query("UPDATE accounts SET cost_basis_method = ? WHERE id = ?")
+            .bind(method.as_str())
+            .bind(account_id)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Cost basis method of an account inside an open unit of work.
+async fn cost_basis_method(conn: &mut SqliteConnection, account_id: i64) -> Result<CostBasisMethod, sqlx::Error> {
+    let method: String = sqlx::query_scalar("SELECT cost_basis_method FROM accounts WHERE id = ?")
+        .bind(account_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    parse_method(method)
+}
+
+fn parse_method(method: String) -> Result<CostBasisMethod, sqlx::Error> {
+    serde_json::from_value(serde_json::Value::String(method)).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// An investment transaction as stored, with amounts in the account's currency.
+pub(crate) struct Trade {
+    pub(crate) id: i64,
+    pub(crate) account_id: i64,
+    pub(crate) security_id: i64,
+    pub(crate) action: String,
+    pub(crate) trade_date: String,
+    pub(crate) quantity_units: i64,
+    pub(crate) amount: Money,
+    pub(crate) fee: Money,
+    pub(crate) split: Option<(i64, i64)>,
+    /// Cost basis method the sale was recorded with; the account's current
+    /// method for other trades
+    pub(crate) method: CostBasisMethod,
+    /// Lots a sale identifies, as buy IDs and quantities
+    pub(crate) selections: Vec<(i64, i64)>,
+}
+
+/// Shares bought in one trade and not sold yet.
+pub(crate) struct Lot {
+    pub(crate) trade_id: i64,
+    pub(crate) acquired: String,
+    pub(crate) quantity_units: i64,
+    pub(crate) cost: Money,
+}
+
+/// Shares of one lot sold in one trade.
+pub(crate) struct Disposal {
+    pub(crate) sale_id: i64,
+    pub(crate) lot_trade_id: i64,
+    pub(crate) acquired: String,
+    pub(crate) sold: String,
+    pub(crate) quantity_units: i64,
+    pub(crate) cost: Money,
+    pub(crate) proceeds: Money,
+}
+
+/// What an account holds of one security after replaying its trades.
+#[derive(Default)]
+pub(crate) struct Position {
+    pub(crate) lots: Vec<Lot>,
+    pub(crate) disposals: Vec<Disposal>,
+    /// Price per share of the latest buy or sale, and its date
+    pub(crate) trade_price: Option<(f64, String)>,
+}
+
+impl Position {
+    pub(crate) fn quantity_units(&self) -> i64 {
+        self.lots.iter().map(|lot| lot.quantity_units).sum()
+    }
+
+    fn buy(&mut self, trade: &Trade) -> Result<(), sqlx::Error> {
+        self.lots.push(Lot {
+            trade_id: trade.id,
+            acquired: trade.trade_date.clone(),
+            quantity_units: trade.quantity_units,
+            cost: trade.amount.checked_add(trade.fee)?,
+        });
+
+        Ok(())
+    }
+
+    fn sell(&mut self, trade: &Trade) -> Result<(), sqlx::Error> {
+        let held = self.quantity_units();
+        if trade.quantity_units > held {
+            return Err(sqlx::Error::Protocol(format!(
+                "the sale on {} of {} shares exceeds the {} held",
+                trade.trade_date,
+                format_quantity(trade.quantity_units),
+                format_quantity(held)
+            )));
+        }
+
+        let takes = self.pick_lots(trade)?;
+        let weights: Vec<u64> = takes.iter().map(|&(_, take)| take as u64).collect();
+        let proceeds = trade.amount.checked_sub(trade.fee)?.allocate(&weights)?;
+        let costs = match trade.method {
+            CostBasisMethod::Average => {
+                // Every share sold costs the same; the rest of the pool stays with the remaining lots
+                let pool = Money::sum(trade.amount.currency(), self.lots.iter().map(|lot| lot.cost))?;
+                let parts = pool.allocate(&[trade.quantity_units as u64, (held - trade.quantity_units) as u64])?;
+                for &(index, take) in &takes {
+                    self.lots[index].quantity_units -= take;
+                }
+                let remaining: Vec<u64> = self.lots.iter().map(|lot| lot.quantity_units as u64).collect();
+                if remaining.iter().any(|&units| units > 0) {
+                    for (lot, cost) in self.lots.iter_mut().zip(parts[1].allocate(&remaining)?) {
+                        lot.cost = cost;
+                    }
+                }
+                parts[0].allocate(&weights)?
+            }
+            _ => {
+                let mut costs = Vec::with_capacity(takes.len());
+                for &(index, take) in &takes {
+                    let lot = &mut self.lots[index];
+                    let cost = lot.cost.allocate(&[take as u64, (lot.quantity_units - take) as u64])?;
+                    lot.quantity_units -= take;
+                    lot.cost = cost[1];
+                    costs.push(cost[0]);
+                }
+                costs
+            }
+        };
+
+        for (((index, take), cost), proceeds) in takes.into_iter().zip(costs).zip(proceeds) {
+            let lot = &self.lots[index];
+            self.disposals.push(Disposal {
+                sale_id: trade.id,
+                lot_trade_id: lot.trade_id,
+                acquired: lot.acquired.clone(),
+                sold: trade.trade_date.clone(),
+                quantity_units: take,
+                cost,
+                proceeds,
+            });
+        }
+        self.lots.retain(|lot| lot.quantity_units > 0);
+
+        Ok(())
+    }
+
+    /// Lots a sale takes shares from, as indexes into `lots` and quantities.
+    fn pick_lots(&self, trade: &Trade) -> Result<Vec<(usize, i64)>, sqlx::Error> {
+        let mut available: Vec<i64> = self.lots.iter().map(|lot| lot.quantity_units).collect();
+        let mut takes: Vec<(usize, i64)> = Vec::new();
+        let mut remaining = trade.quantity_units;
+
+        for &(lot_id, units) in &trade.selections {
+            let index = self.lots.iter().position(|lot| lot.trade_id == lot_id);
+            let Some(index) = index.filter(|&index| available[index] >= units) else {
+                return Err(sqlx::Error::Protocol(format!(
+                    "the sale on {} selects {} shares of lot {}, which holds fewer",
+                    trade.trade_date,
+                    format_quantity(units),
+                    lot_id
+                )));
+            };
+            available[index] -= units;
+            takes.push((index, units));
+            remaining -= units;
+        }
+
+        let order: Vec<usize> = match trade.method {
+            CostBasisMethod::Lifo => (0..self.lots.len()).rev().collect(),
+            _ => (0..self.lots.len()).collect(),
+        };
+        for index in order {
+            if remaining == 0 {
+                break;
+            }
+            let take = remaining.min(available[index]);
+            if take > 0 {
+                takes.push((index, take));
+                remaining -= take;
+            }
+        }
+
+        Ok(takes)
+    }
+
+    fn split(&mut self, from: i64, to: i64) {
+        for lot in &mut self.lots {
+            lot.quantity_units = div_round_half_even(lot.quantity_units as i128 * to as i128, from as i128) as i64;
+        }
+        if let Some((price, _)) = &mut self.trade_price {
+            *price = *price * from as f64 / to as f64;
+        }
+    }
+}
+
+/// Loads investment transactions in replay order, of one account or all, up
+/// to an optional last date. Splits are loaded whichever account recorded
+/// them, as they apply to every account.
+pub(crate) async fn load_trades(
+    conn: &mut SqliteConnection,
+    account_id: Option<i64>,
+    as_of: Option<&str>,
+) -> Result<Vec<Trade>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT i.id, i.account_id, i.security_id, i.action, i.trade_date, i.quantity_units, i.amount_cents,
+                i.fee_cents, i.split_from, i.split_to, a.currency,
+                COALESCE(i.cost_basis_method, a.cost_basis_method) AS cost_basis_method
+         FROM investment_transactions i
+         JOIN accounts a ON a.id = i.account_id
+         WHERE (?1 IS NULL OR i.account_id = ?1 OR i.action = 'split') AND (?2 IS NULL OR i.trade_date <= ?2)
+         ORDER BY i.trade_date, i.id",
+    )
+    .bind(account_id)
+    .bind(as_of)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut selections: BTreeMap<i64, Vec<(i64, i64)>> = BTreeMap::new();
+    let selected = sqlx::query(
+        "SELECT s.sale_id, s.lot_id, s.quantity_units FROM lot_selections s
+         JOIN investment_transactions i ON i.id = s.sale_id
+         WHERE ?1 IS NULL OR i.account_id = ?1
+         ORDER BY s.id",
+    )
+    .bind(account_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    for row in selected {
+        selections.entry(row.get("sale_id")).or_default().push((row.get("lot_id"), row.get("quantity_units")));
+    }
+
+    rows.into_iter()
+        .map(|row| {
+            let currency = Currency::new(row.get::<&str, _>("currency"))?;
+            let split = match (row.get::<Option<i64>, _>("split_from"), row.get::<Option<i64>, _>("split_to")) {
+                (Some(from), Some(to)) => Some((from, to)),
+                _ => None,
+            };
+            Ok(Trade {
+                id: row.get("id"),
+                account_id: row.get("account_id"),
+                security_id: row.get("security_id"),
+                action: row.get("action"),
+                trade_date: row.get("trade_date"),
+                quantity_units: row.get("quantity_units"),
+                amount: Money::new(row.get("amount_cents"), currency),
+                fee: Money::new(row.get("fee_cents"), currency),
+                split,
+                method: parse_method(row.get("cost_basis_method"))?,
+                selections: selections.remove(&row.get::<i64, _>("id")).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Replays trades in order into positions keyed by account and security. A
+/// split applies to the positions of its security in every account.
+pub(crate) fn replay(trades: &[Trade]) -> Result<BTreeMap<(i64, i64), Position>, sqlx::Error> {
+    let mut positions: BTreeMap<(i64, i64), Position> = BTreeMap::new();
+    for trade in trades {
+        if let ("split", Some((from, to))) = (trade.action.as_str(), trade.split) {
+            for (_, position) in positions.iter_mut().filter(|((_, security_id), _)| *security_id == trade.security_id) {
+                position.split(from, to);
+            }
+            continue;
+        }
+        let position = positions.entry((trade.account_id, trade.security_id)).or_default();
+        match trade.action.as_str() {
+            "buy" => position.buy(trade)?,
+            "sell" => position.sell(trade)?,
+            _ => continue,
+        }
+        let minor_per_share = trade.amount.amount_cents() as f64 / quantity_of(trade.quantity_units);
+        let price = minor_per_share / 10_f64.powi(trade.amount.currency().minor_digits() as i32);
+        position.trade_price = Some((price, trade.trade_date.clone()));
+    }
+
+    Ok(positions)
+}
+
+/// Fails when an account's trades, or those of every account when None, sell
+/// more shares than were held.
+async fn check_positions(conn: &mut SqliteConnection, account_id: Option<i64>) -> Result<(), sqlx::Error> {
+    let trades = load_trades(&mut *conn, account_id, None).await?;
+    replay(&trades)?;
+
+    Ok(())
+}
+
+/// Ticker, name and type of every security.
+pub(crate) async fn security_names(conn: &mut SqliteConnection) -> Result<BTreeMap<i64, (String, String, String)>, sqlx::Error> {
+    let rows = sqlx::query("SELECT id, ticker, name, security_type FROM securities")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.get("id"), (row.get("ticker"), row.get("name"), row.get("security_type"))))
+        .collect())
+}
+
+/// Retrieves the open lots of an account: the shares of each buy not sold yet,
+/// adjusted for splits.
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Lots by ticker and purchase date with
+///   security_id, ticker, the buying trade's `trade_id`, `acquired` date,
+///   quantity and cost_basis_cents
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_lots(pool: &SqlitePool, account_id: i64) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        let names = security_names(&mut *conn).await?;
+        let trades = load_trades(&mut *conn, Some(account_id), None).await?;
+
+        let mut lots = Vec::new();
+        for ((_, security_id), position) in replay(&trades)? {
+            let ticker = names.get(&security_id).map(|(ticker, _, _)| ticker.clone()).unwrap_or_default();
+            for lot in position.lots {
+                lots.push(serde_json::json!({
+                    "security_id": security_id,
+                    "ticker": ticker,
+                    "trade_id": lot.trade_id,
+                    "acquired": lot.acquired,
+                    "quantity": quantity_of(lot.quantity_units),
+                    "cost_basis_cents": lot.cost.amount_cents()
+                }));
+            }
+        }
+        lots.sort_by(|a, b| (a["ticker"].as_str(), a["acquired"].as_str()).cmp(&(b["ticker"].as_str(), b["acquired"].as_str())));
+
+        Ok(lots)
+    })
+    .await
+}
+
+/// One security held in one account, valued on a date.
+pub(crate) struct Holding {
+    pub(crate) account_id: i64,
+    pub(crate) security_id: i64,
+    pub(crate) quantity_units: i64,
+    pub(crate) cost: Money,
+    pub(crate) price: f64,
+    pub(crate) price_date: String,
+    /// "quote" for a stored price, "trade" for the latest trade's price
+    pub(crate) price_source: &'static str,
+    pub(crate) market_value: Money,
+}
+
+/// Values every position still held on `as_of` (or today, when None) inside
+/// an open unit of work.
+pub(crate) async fn holdings_of(
+    conn: &mut SqliteConnection,
+    account_id: Option<i64>,
+    as_of: Option<&str>,
+) -> Result<Vec<Holding>, sqlx::Error> {
+    let trades = load_trades(&mut *conn, account_id, as_of).await?;
+
+    let mut holdings = Vec::new();
+    for ((account_id, security_id), position) in replay(&trades)? {
+        let quantity_units = position.quantity_units();
+        if quantity_units == 0 {
+            continue;
+        }
+        let cost = Money::sum(position.lots[0].cost.currency(), position.lots.iter().map(|lot| lot.cost))?;
+
+        let quote = sqlx::query(
+            "SELECT price, price_date FROM security_prices
+             WHERE security_id = ?1 AND (?2 IS NULL OR price_date <= ?2)
+             ORDER BY price_date DESC LIMIT 1",
+        )
+        .bind(security_id)
+        .bind(as_of)
+        .fetch_optional(&mut *conn)
+        .await?;
+        let traded = position.trade_price.clone().unwrap_or_default();
+        let (price, price_date, price_source) = match quote {
+            Some(row) if row.get::<String, _>("price_date") >= traded.1 => {
+                (row.get("price"), row.get("price_date"), "quote")
+            }
+            _ => (traded.0, traded.1, "trade"),
+        };
+
+        holdings.push(Holding {
+            account_id,
+            security_id,
+            quantity_units,
+            cost,
+            price,
+            price_date,
+            price_source,
+            market_value: Money::from_major(price * quantity_of(quantity_units), cost.currency())?,
+        });
+    }
+
+    Ok(holdings)
+}
+
+/// Reports the securities held per account with cost basis, market value and
+/// unrealized gain.
+///
+/// Each position is valued at the latest stored price on or before `as_of`,
+/// or at the latest trade's price when that is more recent or no price is
+/// stored; `price_source` says which ("quote" or "trade"). Amounts are in the
+/// account's currency.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `account_id` - One account, or None for every account holding securities
+/// * `as_of` - Last date (YYYY-MM-DD) whose trades and prices count; defaults to today
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `as_of` and `accounts` (account_id,
+///   name, currency, `positions` and the account's `cost_basis_cents`,
+///   `market_value_cents` and `unrealized_gain_cents`); every position has
+///   security_id, ticker, name, security_type, quantity, cost_basis_cents,
+///   price, price_date, price_source, market_value_cents and unrealized_gain_cents
+/// * `Err(sqlx::Error)` - Amounts too large to represent or database failure
+///
+/// # Examples
+/// ```no_run
+/// let holdings = get_holdings(&pool, None, None).await?;
+/// for account in holdings["accounts"].as_array().unwrap() {
+///     println!("{}: {}", account["name"], account["market_value_cents"]);
+/// }
+/// ```
+pub async fn get_holdings(
+    pool: &SqlitePool,
+    account_id: Option<i64>,
+    as_of: Option<String>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        let as_of: String = match as_of {
+            Some(date) => date,
+            None => sqlx::query_scalar("SELECT date('now')").fetch_one(&mut *conn).await?,
+        };
+        let names = security_names(&mut *conn).await?;
+
+        let mut by_account: BTreeMap<i64, Vec<Holding>> = BTreeMap::new();
+        for holding in holdings_of(&mut *conn, account_id, Some(&as_of)).await? {
+            by_account.entry(holding.account_id).or_default().push(holding);
+        }
+
+        let mut accounts = Vec::new();
+        for (account_id, holdings) in by_account {
+            let account = sqlx::query("SELECT name, currency FROM accounts WHERE id = ?")
+                .bind(account_id)
+                .fetch_one(&mut *conn)
+                .await?;
+            let currency = Currency::new(account.get::<&str, _>("currency"))?;
+            let cost = Money::sum(currency, holdings.iter().map(|holding| holding.cost))?;
+            let market_value = Money::sum(currency, holdings.iter().map(|holding| holding.market_value))?;
+
+            let mut positions = Vec::new();
+            for holding in holdings {
+                let (ticker, name, security_type) = names.get(&holding.security_id).cloned().unwrap_or_default();
+                positions.push(serde_json::json!({
+                    "security_id": holding.security_id,
+                    "ticker": ticker,
+                    "name": name,
+                    "security_type": security_type,
+                    "quantity": quantity_of(holding.quantity_units),
+                    "cost_basis_cents": holding.cost.amount_cents(),
+                    "price": holding.price,
+                    "price_date": holding.price_date,
+                    "price_source": holding.price_source,
+                    "market_value_cents": holding.market_value.amount_cents(),
+                    "unrealized_gain_cents": holding.market_value.checked_sub(holding.cost)?.amount_cents()
+                }));
+            }
+            positions.sort_by(|a, b| a["ticker"].as_str().cmp(&b["ticker"].as_str()));
+
+            accounts.push(serde_json::json!({
+                "account_id": account_id,
+                "name": account.get::<String, _>("name"),
+                "currency": currency,
+                "positions": positions,
+                "cost_basis_cents": cost.amount_cents(),
+                "market_value_cents": market_value.amount_cents(),
+                "unrealized_gain_cents": market_value.checked_sub(cost)?.amount_cents()
+            }));
+        }
+
+        Ok(serde_json::json!({
+            "as_of": as_of,
+            "accounts": accounts
+        }))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Brokerage".to_string(), "investment".to_string())
+            .await
+            .unwrap();
+        add_security(&pool, "vti".to_string(), "Vanguard Total Stock Market".to_string(), SecurityType::Etf, None)
+            .await
+            .unwrap();
+        pool
+    }
+
+    fn usd(cents: i64) -> Money {
+        Money::new(cents, Currency::USD)
+    }
+
+    async fn trade(pool: &SqlitePool, date: &str, trade: InvestmentTrade) -> Result<i64, sqlx::Error> {
+        add_investment_transaction(pool, 1, 1, date.to_string(), &trade, None).await
+    }
+
+    #[tokio::test]
+    async fn test_trades_replay_into_fifo_lots_and_cash() {
+        let pool = setup_test_db().await;
+        trade(&pool, "2025-01-10", InvestmentTrade::Buy { quantity: 10.0, amount: usd(200000), fee: Some(usd(500)) })
+            .await
+            .unwrap();
+        trade(&pool, "2025-02-10", InvestmentTrade::Buy { quantity: 5.0, amount: usd(110000), fee: None })
+            .await
+            .unwrap();
+        // Takes all of the first lot and 2 shares of the second
+        trade(&pool, "2025-03-10", InvestmentTrade::Sell { quantity: 12.0, amount: usd(300000), fee: Some(usd(500)), lots: vec![] })
+            .await
+            .unwrap();
+        trade(&pool, "2025-03-31", InvestmentTrade::Dividend { amount: usd(1234) }).await.unwrap();
+
+        let lots = get_lots(&pool, 1).await.unwrap();
+        assert_eq!(lots.len(), 1);
+        assert_eq!(lots[0]["trade_id"], 2);
+        assert_eq!(lots[0]["quantity"], 3.0);
+        assert_eq!(lots[0]["cost_basis_cents"], 66000);
+
+        // Cash: -2005.00 - 1100.00 + 2995.00 + 12.34
+        let balance = crate::database::get_balance(&pool, 1).await.unwrap();
+        assert_eq!(balance.amount_cents(), -9766);
+        let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(transactions[3]["description"], "Buy 10 VTI");
+
+        // Selling more than is held is refused as a whole
+        let oversell = trade(&pool, "2025-04-01", InvestmentTrade::Sell { quantity: 3.5, amount: usd(80000), fee: None, lots: vec![] });
+        assert!(oversell.await.is_err());
+        assert_eq!(get_investment_transactions(&pool, 1).await.unwrap().len(), 4);
+        // So is deleting a buy a later sale depends on
+        assert!(delete_investment_transaction(&pool, 1).await.is_err());
+
+        // Trades must be in the account's currency
+        let euro = InvestmentTrade::Buy { quantity: 1.0, amount: Money::new(100, Currency::EUR), fee: None };
+        assert!(trade(&pool, "2025-04-01", euro).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_split_and_holdings_valuation() {
+        let pool = setup_test_db().await;
+        trade(&pool, "2025-01-10", InvestmentTrade::Buy { quantity: 1.5, amount: usd(30000), fee: None })
+            .await
+            .unwrap();
+        trade(&pool, "2025-06-02", InvestmentTrade::Split { from: 1, to: 4 }).await.unwrap();
+        assert!(trade(&pool, "2025-06-03", InvestmentTrade::Split { from: 2, to: 2 }).await.is_err());
+
+        // Without prices, the last trade's price adjusted for the split
+        let holdings = get_holdings(&pool, None, Some("2025-06-30".to_string())).await.unwrap();
+        let position = &holdings["accounts"][0]["positions"][0];
+        assert_eq!(position["quantity"], 6.0);
+        assert_eq!(position["price"], 50.0);
+        assert_eq!(position["price_source"], "trade");
+        assert_eq!(position["market_value_cents"], 30000);
+
+        let csv = "Date,Open,High,Low,Close,Adj Close,Volume\n\
+                   2025-06-27,54.00,56.10,53.90,55.125,55.125,1000\n\
+                   2025-06-30,55.00,56.00,54.00,null,null,0\n\
+                   2025-07-01,56.00,57.00,55.50,56.5,56.5,1200\n";
+        let summary = import_security_prices(&pool, csv, Some("vti".to_string())).await.unwrap();
+        assert_eq!(summary["imported"], 2);
+        assert_eq!(summary["securities"], serde_json::json!(["VTI"]));
+
+        let holdings = get_holdings(&pool, Some(1), Some("2025-06-30".to_string())).await.unwrap();
+        let account = &holdings["accounts"][0];
+        assert_eq!(account["positions"][0]["price_date"], "2025-06-27");
+        assert_eq!(account["market_value_cents"], 33075);
+        assert_eq!(account["unrealized_gain_cents"], 3075);
+
+        // Holdings count towards net worth on top of the cash balance
+        let worth = crate::database::get_net_worth(&pool, Some("2025-06-30".to_string())).await.unwrap();
+        assert_eq!(worth["accounts"][0]["holdings_value_cents"], 33075);
+        assert_eq!(worth["net_worth_cents"], 33075 - 30000);
+
+        // Unknown tickers reject the whole file
+        let bad = "Ticker,Date,Price\nVTI,2025-07-02,57\nXYZ,2025-07-02,1\n";
+        assert!(import_security_prices(&pool, bad, None).await.is_err());
+        assert_eq!(get_security_prices(&pool, 1, 10).await.unwrap().len(), 2);
+
+        // Traded securities cannot be deleted
+        assert!(delete_security(&pool, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cash_leg_changes_only_with_its_trade() {
+        let pool = setup_test_db().await;
+        let buy = trade(&pool, "2025-01-10", InvestmentTrade::Buy { quantity: 1.0, amount: usd(20000), fee: None })
+            .await
+            .unwrap();
+        let cash = get_investment_transactions(&pool, 1).await.unwrap()[0]["transaction_id"].as_i64().unwrap();
+
+        assert!(crate::database::delete_transaction(&pool, cash).await.is_err());
+        let edit = crate::database::update_transaction(&pool, cash, 1, usd(-10000), "debit".to_string(), "Buy 1 VTI".to_string(), "2025-01-10".to_string(), 1, None);
+        assert!(edit.await.is_err());
+        let selection = crate::database::TransactionSelection::Ids(vec![cash]);
+        assert!(crate::database::bulk_delete_transactions(&pool, &selection).await.is_err());
+        assert!(crate::database::bulk_recategorize_transactions(&pool, &selection, 1).await.is_err());
+        assert_eq!(crate::database::get_balance(&pool, 1).await.unwrap().amount_cents(), -20000);
+
+        delete_investment_transaction(&pool, buy).await.unwrap();
+        assert_eq!(crate::database::get_balance(&pool, 1).await.unwrap().amount_cents(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_split_applies_to_every_account() {
+        let pool = setup_test_db().await;
+        crate::database::add_account(&pool, "IRA".to_string(), "investment".to_string()).await.unwrap();
+        trade(&pool, "2025-01-10", InvestmentTrade::Buy { quantity: 1.0, amount: usd(20000), fee: None })
+            .await
+            .unwrap();
+        let ira_buy = InvestmentTrade::Buy { quantity: 2.0, amount: usd(40000), fee: None };
+        add_investment_transaction(&pool, 2, 1, "2025-02-10".to_string(), &ira_buy, None).await.unwrap();
+
+        // Recorded in one account, the split scales the lots in both
+        let split = trade(&pool, "2025-06-02", InvestmentTrade::Split { from: 1, to: 4 }).await.unwrap();
+        assert_eq!(get_lots(&pool, 1).await.unwrap()[0]["quantity"], 4.0);
+        let lots = get_lots(&pool, 2).await.unwrap();
+        assert_eq!(lots[0]["quantity"], 8.0);
+        assert_eq!(lots[0]["cost_basis_cents"], 40000);
+        assert_eq!(get_investment_transactions(&pool, 2).await.unwrap()[0]["id"], split);
+
+        // It is recorded once
+        let again = InvestmentTrade::Split { from: 1, to: 4 };
+        assert!(add_investment_transaction(&pool, 2, 1, "2025-06-02".to_string(), &again, None).await.is_err());
+
+        // A sale of the split shares in the other account depends on it
+        let sale = InvestmentTrade::Sell { quantity: 8.0, amount: usd(44000), fee: None, lots: vec![] };
+        add_investment_transaction(&pool, 2, 1, "2025-07-01".to_string(), &sale, None).await.unwrap();
+        assert!(delete_investment_transaction(&pool, split).await.is_err());
+    }
+    #[tokio::test]
+    async fn test_sales_keep_their_recorded_method() {
+        let pool = setup_test_db().await;
+        for date in ["2025-01-10", "2025-02-10", "2025-03-10"] {
+            trade(&pool, date, InvestmentTrade::Buy { quantity: 10.0, amount: usd(100000), fee: None })
+                .await
+                .unwrap();
+        }
+        set_cost_basis_method(&pool, 1, CostBasisMethod::Specific).await.unwrap();
+        let lots = vec![LotSelection { lot_id: 2, quantity: 5.0 }, LotSelection { lot_id: 3, quantity: 3.0 }];
+        trade(&pool, "2025-04-10", InvestmentTrade::Sell { quantity: 8.0, amount: usd(100000), fee: None, lots })
+            .await
+            .unwrap();
+
+        // Switching the method leaves the sale with specific identification
+        set_cost_basis_method(&pool, 1, CostBasisMethod::Lifo).await.unwrap();
+        let quantities = |lots: Vec<serde_json::Value>| -> Vec<(i64, f64)> {
+            lots.iter().map(|lot| (lot["trade_id"].as_i64().unwrap(), lot["quantity"].as_f64().unwrap())).collect()
+        };
+        assert_eq!(quantities(get_lots(&pool, 1).await.unwrap()), vec![(1, 10.0), (2, 5.0), (3, 7.0)]);
+
+        // With lot 3 gone, the 3 shares no longer identified come from the oldest lot
+        delete_investment_transaction(&pool, 3).await.unwrap();
+        assert_eq!(quantities(get_lots(&pool, 1).await.unwrap()), vec![(1, 7.0), (2, 5.0)]);
+    }
+}
@@ -0,0 +1,557 @@
+//! Change journal recording what each user-level operation did to the data.
+//!
+//! An operation (for example "Recategorize 200 transactions") is opened by
+//! [`journaled()`], which runs its work in one [`unit_of_work()`]. While an
+//! operation is open, row triggers on the journaled tables copy every inserted,
+//! updated and deleted row into `journal_changes` as JSON before/after images,
+//! tagged with the operation. Writes made outside [`journaled()`] are not
+//! recorded.
+//!
+//! Every mutating function of the database layer runs through [`journaled()`].
+//! [`undo()`] reverts the newest operation by writing the before images back in
+//! reverse order, and [`redo()`] re-applies the after images of the oldest
+//! undone operation. Both check that each row still looks the way the journal
+//! expects, and refuse to overwrite changes made outside the journal.
+//!
+//! The triggers are generated from the live table definitions by
+//! [`install_triggers()`] after migrations run, so a migration that adds a
+//! column never leaves the journal capturing stale row images.
+
+use super::unit_of_work;
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+
+/// Tables whose row changes are journaled.
+pub(crate) const JOURNALED_TABLES: &[&str] = &[
+    "accounts",
+    "attachments",
+    "categories",
+    "custom_fields",
+    "investment_transactions",
+    "lot_selections",
+    "payees",
+    "securities",
+    "tags",
+    "transactions",
+    "transaction_tags",
+];
+
+/// Number of most recent operations kept in the journal.
+const JOURNAL_LIMIT: i64 = 1000;
+
+/// Runs `work` atomically as one journaled operation.
+///
+/// Opening an operation discards operations that were undone, since a new
+/// change makes them impossible to redo. Operations that change nothing are
+/// dropped, and the journal is trimmed to the newest [`JOURNAL_LIMIT`] entries.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `label` - Human-readable description shown in the undo history
+/// * `work` - Database changes making up the operation
+///
+/// # Returns
+/// * `Ok(T)` - Result of `work`; its changes and their journal entry are committed
+/// * `Err(sqlx::Error)` - Error from `work` or the journal; nothing is committed
+pub(crate) async fn journaled<T, F>(pool: &SqlitePool, label: &str, work: F) -> Result<T, sqlx::Error>
+where
+    F: AsyncFnOnce(&mut SqliteConnection) -> Result<T, sqlx::Error>,
+{
+    unit_of_work(pool, async move |conn| {
+        sqlx::query("DELETE FROM journal_operations WHERE undone")
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("INSERT INTO journal_operations (label, open) VALUES (?, TRUE)")
+            .bind(label)
+            .execute(&mut *conn)
+            .await?;
+
+        let result = work(&mut *conn).await?;
+
+        sqlx::query(
+            "DELETE FROM journal_operations
+             WHERE open AND NOT EXISTS (
+                 SELECT 1 FROM journal_changes WHERE operation_id = journal_operations.id
+             )",
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query("UPDATE journal_operations SET open = FALSE WHERE open")
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM journal_operations WHERE id <= (SELECT MAX(id) FROM journal_operations) - ?")
+            .bind(JOURNAL_LIMIT)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(result)
+    })
+    .await
+}
+
+/// Reverts the most recent operation that has not been undone.
+///
+/// Replays the operation's before images in reverse order inside one
+/// transaction. Undo itself is not journaled; the operation is only marked as
+/// undone, so [`redo()`] can re-apply it until a new operation is recorded.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The undone operation's `id` and `label`
+/// * `Err(sqlx::Error)` - Nothing to undo, a row was changed outside the
+///   journal since, or the database rejected a change; nothing is reverted
+///
+/// # Examples
+/// ```no_run
+/// delete_transaction(&pool, 42).await?;
+/// let undone = undo(&pool).await?;
+/// assert_eq!(undone["label"], "Delete transaction");
+/// ```
+pub async fn undo(pool: &SqlitePool) -> Result<serde_json::Value, sqlx::Error> {
+    replay(pool, Direction::Undo).await
+}
+
+/// Re-applies the most recently undone operation.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The redone operation's `id` and `label`
+/// * `Err(sqlx::Error)` - Nothing to redo, a conflicting change, or database failure
+pub async fn redo(pool: &SqlitePool) -> Result<serde_json::Value, sqlx::Error> {
+    replay(pool, Direction::Redo).await
+}
+
+/// Lists journaled operations, newest first.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `limit` - Maximum number of operations to return
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with id, label, created_at, `undone`
+///   flag and the number of row `changes`
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_undo_history(
+    pool: &SqlitePool,
+    limit: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT o.id, o.label, o.created_at, o.undone,
+                (SELECT COUNT(*) FROM journal_changes c WHERE c.operation_id = o.id) AS changes
+         FROM journal_operations o
+         WHERE NOT o.open
+         ORDER BY o.id DESC
+         LIMIT ?",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let result: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "label": row.get::<String, _>("label"),
+                "created_at": row.get::<String, _>("created_at"),
+                "undone": row.get::<bool, _>("undone"),
+                "changes": row.get::<i64, _>("changes")
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Undo,
+    Redo,
+}
+
+async fn replay(pool: &SqlitePool, direction: Direction) -> Result<serde_json::Value, sqlx::Error> {
+    let (action, select_operation, changes_order, mark_undone) = match direction {
+        Direction::Undo => (
+            "undo",
+            "SELECT id, label FROM journal_operations WHERE NOT undone AND NOT open ORDER BY id DESC LIMIT 1",
+            "DESC",
+            true,
+        ),
+        Direction::Redo => (
+            "redo",
+            "SELECT id, label FROM journal_operations WHERE undone ORDER BY id ASC LIMIT 1",
+            "ASC",
+            false,
+        ),
+    };
+
+    unit_of_work(pool, async |conn| {
+        let Some(operation) = sqlx::query(select_operation)
+            .fetch_optional(&mut *conn)
+            .await?
+        else {
+            return Err(sqlx::Error::Protocol(format!("nothing to {}", action)));
+        };
+        let operation_id: i64 = operation.get("id");
+        let label: String = operation.get("label");
+
+        let changes = sqlx::query(&format!(
+            "SELECT table_name, row_id, old_values, new_values FROM journal_changes
+             WHERE operation_id = ? ORDER BY id {}",
+            changes_order
+        ))
+        .bind(operation_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for change in changes {
+            let table: String = change.get("table_name");
+            let row_id: i64 = change.get("row_id");
+            let old_values = parse_image(change.get("old_values"))?;
+            let new_values = parse_image(change.get("new_values"))?;
+
+            let (expected, target) = match direction {
+                Direction::Undo => (new_values, old_values),
+                Direction::Redo => (old_values, new_values),
+            };
+            apply_image(&mut *conn, &table, row_id, expected.as_ref(), target.as_ref())
+                .await
+                .map_err(|e| sqlx::Error::Protocol(format!("cannot {} \"{}\": {}", action, label, e)))?;
+        }
+
+        sqlx::query("UPDATE journal_operations SET undone = ? WHERE id = ?")
+            .bind(mark_undone)
+            .bind(operation_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(serde_json::json!({ "id": operation_id, "label": label }))
+    })
+    .await
+}
+
+fn parse_image(json: Option<String>) -> Result<Option<serde_json::Value>, sqlx::Error> {
+    json.map(|json| serde_json::from_str(&json).map_err(|e| sqlx::Error::Decode(Box::new(e))))
+        .transpose()
+}
+
+/// Moves one row from its `expected` image to its `target` image.
+///
+/// `None` means the row does not exist, so a `None` target deletes the row and
+/// a `None` expected image re-inserts it with its original id.
+async fn apply_image(
+    conn: &mut SqliteConnection,
+    table: &str,
+    row_id: i64,
+    expected: Option<&serde_json::Value>,
+    target: Option<&serde_json::Value>,
+) -> Result<(), sqlx::Error> {
+    if !JOURNALED_TABLES.contains(&table) {
+        return Err(sqlx::Error::Protocol(format!("{} is not journaled", table)));
+    }
+    let columns = table_columns(&mut *conn, table).await?;
+
+    let current = row_image(&mut *conn, table, &columns, row_id).await?;
+    let matches = match (expected, &current) {
+        (None, None) => true,
+        (Some(expected), Some(current)) => expected
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(column, _)| columns.contains(column))
+            .all(|(column, value)| current.get(column) == Some(value)),
+        _ => false,
+    };
+    if !matches {
+        return Err(sqlx::Error::Protocol(format!(
+            "{} row {} was changed outside the undo history",
+            table, row_id
+        )));
+    }
+
+    let Some(target) = target else {
+        sqlx::query(&format!("DELETE FROM {} WHERE id = ?", table))
+            .bind(row_id)
+            .execute(&mut *conn)
+            .await?;
+        return Ok(());
+    };
+    let values: Vec<(&String, &serde_json::Value)> = target
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(column, _)| columns.contains(column))
+        .collect();
+
+    let mut builder = QueryBuilder::<Sqlite>::new("");
+    if current.is_some() {
+        builder.push(format!("UPDATE {} SET ", table));
+        for (index, (column, value)) in values.iter().enumerate() {
+            if index > 0 {
+                builder.push(", ");
+            }
+            builder.push(format!("{} = ", column));
+            push_json_bind(&mut builder, value);
+        }
+        builder.push(" WHERE id = ").push_bind(row_id);
+    } else {
+        let names: Vec<&str> = values.iter().map(|(column, _)| column.as_str()).collect();
+        builder.push(format!("INSERT INTO {} ({}) VALUES (", table, names.join(", ")));
+        for (index, (_, value)) in values.iter().enumerate() {
+            if index > 0 {
+                builder.push(", ");
+            }
+            push_json_bind(&mut builder, value);
+        }
+        builder.push(")");
+    }
+    builder.build().execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Current image of a row in the same shape the journal triggers record.
+async fn row_image(
+    conn: &mut SqliteConnection,
+    table: &str,
+    columns: &[String],
+    row_id: i64,
+) -> Result<Option<serde_json::Value>, sqlx::Error> {
+    let json: Option<String> = sqlx::query_scalar(&format!(
+        "SELECT {} FROM {} WHERE id = ?",
+        json_image(columns, table),
+        table
+    ))
+    .bind(row_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    parse_image(json)
+}
+
+/// Binds a JSON scalar with the matching SQLite type.
+fn push_json_bind(builder: &mut QueryBuilder<'_, Sqlite>, value: &serde_json::Value) {
+    match value {
+        serde_json::Value::Bool(flag) => builder.push_bind(*flag),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(integer) => builder.push_bind(integer),
+            None => builder.push_bind(number.as_f64()),
+        },
+        serde_json::Value::String(text) => builder.push_bind(text.clone()),
+        _ => builder.push_bind(None::<i64>),
+    };
+}
+
+/// SQL expression building a JSON object of `columns` from `row`, which is a
+/// table name or a trigger's `OLD`/`NEW`.
+pub(crate) fn json_image(columns: &[String], row: &str) -> String {
+    let pairs: Vec<String> = columns
+        .iter()
+        .map(|column| format!("'{}', {}.{}", column, row, column))
+        .collect();
+    format!("json_object({})", pairs.join(", "))
+}
+
+/// (Re)creates the journal triggers for every table in [`JOURNALED_TABLES`].
+///
+/// Each trigger stores full row images built with `json_object()` over the
+/// table's current columns, and only fires while an operation is open.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(())` - Triggers match the current schema
+/// * `Err(sqlx::Error)` - Schema inspection or DDL failure
+pub(crate) async fn install_triggers(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        for table in JOURNALED_TABLES {
+            let columns = table_columns(&mut *conn, table).await?;
+            let image = |row: &str| json_image(&columns, row);
+
+            for (event, row_id, old_values, new_values) in [
+                ("INSERT", "NEW.id", "NULL".to_string(), image("NEW")),
+                ("UPDATE", "NEW.id", image("OLD"), image("NEW")),
+                ("DELETE", "OLD.id", image("OLD"), "NULL".to_string()),
+            ] {
+                let name = format!("journal_{}_{}", table, event.to_lowercase());
+                sqlx::query(&format!("DROP TRIGGER IF EXISTS {}", name))
+                    .execute(&mut *conn)
+                    .await?;
+                sqlx::query(&format!(
+                    "CREATE TRIGGER {name} AFTER {event} ON {table}
+                     WHEN EXISTS (SELECT 1 FROM journal_operations WHERE open)
+                     BEGIN
+                         INSERT INTO journal_changes (operation_id, table_name, row_id, old_values, new_values)
+                         SELECT id, '{table}', {row_id}, {old_values}, {new_values}
+                         FROM journal_operations WHERE open;
+                     END"
+                ))
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Column names of a table, in definition order.
+pub(crate) async fn table_columns(
+    conn: &mut SqliteConnection,
+    table: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query("SELECT name FROM pragma_table_info(?) ORDER BY cid")
+        .bind(table)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(rows.into_iter().map(|row| row.get::<String, _>("name")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_journaled_records_row_images() {
+        let pool = setup_test_db().await;
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+
+        journaled(&pool, "Rename account", async |conn| {
+            sqlx::query("UPDATE accounts SET name = 'Joint Checking' WHERE id = 1")
+                .execute(&mut *conn)
+                .await?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let changes = sqlx::query(
+            "SELECT c.table_name, c.row_id, c.old_values, c.new_values FROM journal_changes c
+             JOIN journal_operations o ON o.id = c.operation_id
+             WHERE o.label = 'Rename account'",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(changes.len(), 1);
+        let old: serde_json::Value = serde_json::from_str(changes[0].get("old_values")).unwrap();
+        let new: serde_json::Value = serde_json::from_str(changes[0].get("new_values")).unwrap();
+        assert_eq!(old["name"], "Checking");
+        assert_eq!(new["name"], "Joint Checking");
+        assert_eq!(new["opening_balance_cents"], 0); // Columns from later migrations included
+
+        // Failed operations leave no journal entry behind
+        let result: Result<(), sqlx::Error> = journaled(&pool, "Broken", async |conn| {
+            sqlx::query("DELETE FROM accounts WHERE id = 1").execute(&mut *conn).await?;
+            Err(sqlx::Error::RowNotFound)
+        })
+        .await;
+        assert!(result.is_err());
+        let operations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM journal_operations")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(operations, 2);
+    }
+
+    #[tokio::test]
+    async fn test_undo_redo_delete_transaction() {
+        let pool = setup_test_db().await;
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_transaction(&pool, 1, Money::new(-2500, Currency::USD), "debit".to_string(), "Groceries".to_string(), "2025-03-01".to_string(), 1)
+            .await
+            .unwrap();
+        crate::database::delete_transaction(&pool, 1).await.unwrap();
+        assert!(crate::database::get_transactions(&pool, 1).await.unwrap().is_empty());
+
+        let undone = undo(&pool).await.unwrap();
+        assert_eq!(undone["label"], "Delete transaction");
+        let restored = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0]["id"], 1);
+        assert_eq!(restored[0]["description"], "Groceries");
+        assert_eq!(crate::database::get_balance(&pool, 1).await.unwrap().amount_cents(), -2500);
+
+        redo(&pool).await.unwrap();
+        assert!(crate::database::get_transactions(&pool, 1).await.unwrap().is_empty());
+        assert!(redo(&pool).await.is_err());
+
+        // A new operation discards what could be redone
+        undo(&pool).await.unwrap();
+        crate::database::add_account(&pool, "Savings".to_string(), "savings".to_string())
+            .await
+            .unwrap();
+        assert!(redo(&pool).await.is_err());
+
+        let history = get_undo_history(&pool, 10).await.unwrap();
+        let labels: Vec<&str> = history.iter().map(|op| op["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, vec!["Add account", "Add transaction", "Add account"]);
+    }
+
+    #[tokio::test]
+    async fn test_undo_delete_category_restores_links() {
+        let pool = setup_test_db().await;
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Food".to_string(), None).await.unwrap();
+        let food_id = 2;
+        crate::database::add_category(&pool, "Restaurants".to_string(), Some(food_id))
+            .await
+            .unwrap();
+        crate::database::add_transaction(&pool, 1, Money::new(-1200, Currency::USD), "debit".to_string(), "Bistro".to_string(), "2025-03-01".to_string(), food_id)
+            .await
+            .unwrap();
+
+        crate::database::delete_category(&pool, food_id).await.unwrap();
+        undo(&pool).await.unwrap();
+
+        let categories = crate::database::get_all_categories(&pool).await.unwrap();
+        let restaurants = categories.iter().find(|c| c["name"] == "Restaurants").unwrap();
+        assert_eq!(restaurants["parent_id"], food_id);
+        let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(transactions[0]["category_id"], food_id);
+    }
+
+    #[tokio::test]
+    async fn test_undo_refuses_to_overwrite_outside_changes() {
+        let pool = setup_test_db().await;
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::update_account(&pool, 1, "Joint".to_string(), "checking".to_string(), false, None)
+            .await
+            .unwrap();
+
+        // A write that bypasses the journal
+        sqlx::query("UPDATE accounts SET name = 'Household' WHERE id = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let error = undo(&pool).await.unwrap_err();
+        assert!(error.to_string().contains("changed outside the undo history"));
+        let account = crate::database::get_account(&pool, 1).await.unwrap();
+        assert_eq!(account["name"], "Household");
+    }
+}
@@ -0,0 +1,1045 @@
+//! Ledger manager for working with several database files at runtime.
+//!
+//! A ledger is one SQLite database file holding a complete set of books, such as
+//! the household's own finances or those of a relative managed on their behalf.
+//! [`LedgerManager`] is managed as Tauri state: it keeps a connection pool for
+//! every open ledger, tracks which one is active, and remembers recently used
+//! files in the settings file. Commands borrow the active ledger's pool through
+//! [`LedgerManager::pool()`], so every existing command follows ledger switches.
+//!
+//! Encrypted ledgers need a passphrase before they can be opened. When the
+//! startup ledger is encrypted, the manager starts *locked*: `pool()` fails until
+//! the frontend asks the user for the passphrase and calls [`LedgerManager::unlock()`].
+//!
+//! The manager also owns each ledger's backup folder (`backups/<ledger id>` in
+//! the app data directory) and restores backups in place, and each ledger's
+//! attachment store (`attachments/<ledger id>`). The ledger id is a random
+//! identifier stored in the ledger itself, so it survives relocation and keeps
+//! same-named ledger files in different folders apart.
+
+use super::{attachments, backups, encryption, location};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::sync::RwLock;
+
+/// Number of recently used ledger files remembered in the settings file.
+const MAX_RECENT_LEDGERS: usize = 10;
+
+/// Owns the open ledgers and routes database access to the active one.
+pub struct LedgerManager {
+    inner: RwLock<Ledgers>,
+    data_dir: PathBuf,
+    /// Error of the most recent backup attempt, cleared by the next success
+    last_backup_failure: Mutex<Option<String>>,
+}
+
+struct Ledgers {
+    open: Vec<OpenLedger>,
+    /// Active ledger; `None` only while the startup ledger is locked
+    active: Option<PathBuf>,
+    /// Encrypted startup ledger waiting for its passphrase
+    locked: Option<PathBuf>,
+}
+
+struct OpenLedger {
+    pool: SqlitePool,
+    path: PathBuf,
+    /// Stable identifier naming the ledger's backup folder and attachment store
+    id: String,
+    /// Kept in memory so backups and relocation can re-key their copies
+    passphrase: Option<String>,
+}
+
+impl Ledgers {
+    fn active(&self) -> Result<&OpenLedger, sqlx::Error> {
+        let Some(active) = &self.active else {
+            return Err(match &self.locked {
+                Some(path) => sqlx::Error::Protocol(format!(
+                    "ledger {} is encrypted; unlock it with its passphrase",
+                    path.display()
+                )),
+                None => sqlx::Error::Protocol("no ledger is open".to_string()),
+            });
+        };
+
+        self.open
+            .iter()
+            .find(|ledger| ledger.path == *active)
+            .ok_or_else(|| not_open(active))
+    }
+
+    fn active_index(&self) -> Result<usize, sqlx::Error> {
+        let path = self.active()?.path.clone();
+
+        self.position(&path).ok_or_else(|| not_open(&path))
+    }
+
+    fn position(&self, path: &Path) -> Option<usize> {
+        self.open.iter().position(|ledger| ledger.path == path)
+    }
+}
+
+impl LedgerManager {
+    /// Resolves, migrates and opens the startup ledger.
+    ///
+    /// Creates the app data directory if needed, resolves the database path via
+    /// [`location::resolve_database_path`], performs the one-time legacy
+    /// `./finsight.db` migration when the default location is in use, and
+    /// initializes the schema. An encrypted startup ledger is not opened; the
+    /// manager starts locked instead.
+    ///
+    /// # Arguments
+    /// * `data_dir` - Application data directory from Tauri's path resolver
+    ///
+    /// # Returns
+    /// * `Ok(LedgerManager)` - Manager with the startup ledger active or locked
+    /// * `Err(sqlx::Error)` - Directory, settings, migration or connection failure
+    pub async fn open(data_dir: PathBuf) -> Result<Self, sqlx::Error> {
+        std::fs::create_dir_all(&data_dir)?;
+
+        let env_override = std::env::var_os(location::DATABASE_PATH_ENV).map(PathBuf::from);
+        let path = location::resolve_database_path(&data_dir, env_override)?;
+
+        if path == data_dir.join(location::DEFAULT_DATABASE_FILE) {
+            location::migrate_legacy_database(&path).await?;
+        }
+
+        Self::with_ledger(data_dir, &path).await
+    }
+
+    /// Opens the given ledger file as the only, active ledger (or locks on it).
+    async fn with_ledger(data_dir: PathBuf, path: &Path) -> Result<Self, sqlx::Error> {
+        let manager = Self {
+            inner: RwLock::new(Ledgers {
+                open: Vec::new(),
+                active: None,
+                locked: None,
+            }),
+            data_dir,
+            last_backup_failure: Mutex::new(None),
+        };
+
+        if encryption::is_encrypted(path)? {
+            manager.inner.write().await.locked = Some(path.to_path_buf());
+        } else {
+            manager.open_ledger(path, None).await?;
+        }
+
+        Ok(manager)
+    }
+
+    /// Returns a handle to the active ledger's connection pool.
+    ///
+    /// Pools are reference-counted, so the clone is cheap and stays valid for the
+    /// duration of a command even if the active ledger changes meanwhile.
+    ///
+    /// # Returns
+    /// * `Ok(SqlitePool)` - Pool of the active ledger
+    /// * `Err(sqlx::Error)` - The startup ledger is still locked
+    pub async fn pool(&self) -> Result<SqlitePool, sqlx::Error> {
+        Ok(self.inner.read().await.active()?.pool.clone())
+    }
+
+    /// Returns the file path of the active ledger.
+    pub async fn path(&self) -> Result<PathBuf, sqlx::Error> {
+        Ok(self.inner.read().await.active()?.path.clone())
+    }
+
+    /// Returns the active ledger's attachment store, see [`attachments`].
+    pub async fn attachment_dir(&self) -> Result<PathBuf, sqlx::Error> {
+        Ok(self.attachment_dir_of(&self.inner.read().await.active()?.id))
+    }
+
+    /// Lists open and recently used ledgers.
+    ///
+    /// # Returns
+    /// * `Ok(serde_json::Value)` - Object with `active` path (null while locked),
+    ///   `locked` path of an encrypted ledger awaiting its passphrase, `open`
+    ///   ledgers and `recent` paths (most recent first, including files that are
+    ///   not open)
+    /// * `Err(sqlx::Error)` - Settings file cannot be read
+    pub async fn list(&self) -> Result<serde_json::Value, sqlx::Error> {
+        let ledgers = self.inner.read().await;
+        let recent = location::load_settings(&self.data_dir)?.recent_ledgers;
+
+        let open: Vec<serde_json::Value> = ledgers
+            .open
+            .iter()
+            .map(|ledger| {
+                serde_json::json!({
+                    "name": ledger_name(&ledger.path),
+                    "path": ledger.path.to_string_lossy(),
+                    "active": ledgers.active.as_ref() == Some(&ledger.path),
+                    "encrypted": ledger.passphrase.is_some()
+                })
+            })
+            .collect();
+
+        Ok(serde_json::json!({
+            "active": ledgers.active.as_ref().map(|path| path.to_string_lossy()),
+            "locked": ledgers.locked.as_ref().map(|path| path.to_string_lossy()),
+            "open": open,
+            "recent": recent
+        }))
+    }
+
+    /// Unlocks the encrypted startup ledger and makes it active.
+    ///
+    /// # Arguments
+    /// * `passphrase` - The ledger's passphrase
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - Path of the now active ledger
+    /// * `Err(sqlx::Error)` - Nothing is locked, or the passphrase is wrong
+    pub async fn unlock(&self, passphrase: &str) -> Result<PathBuf, sqlx::Error> {
+        let Some(path) = self.inner.read().await.locked.clone() else {
+            return Err(sqlx::Error::Protocol("no ledger is locked".to_string()));
+        };
+
+        let path = self.open_ledger(&path, Some(passphrase)).await?;
+        self.inner.write().await.locked = None;
+
+        Ok(path)
+    }
+
+    /// Creates a new, empty ledger file and makes it active.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the new database file; must not exist yet
+    /// * `passphrase` - Passphrase to encrypt the new ledger with, if any
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - Path of the new active ledger
+    /// * `Err(sqlx::Error)` - File already exists or cannot be created
+    pub async fn create(
+        &self,
+        path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<PathBuf, sqlx::Error> {
+        if path.exists() {
+            return Err(sqlx::Error::Protocol(format!(
+                "{} already exists",
+                path.display()
+            )));
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        self.open_ledger(path, passphrase).await
+    }
+
+    /// Opens an existing ledger file (if not already open) and makes it active.
+    ///
+    /// # Arguments
+    /// * `path` - Path of an existing database file
+    /// * `passphrase` - Passphrase of an encrypted ledger, if any
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - Path of the new active ledger
+    /// * `Err(sqlx::Error)` - File does not exist, needs a passphrase, or cannot
+    ///   be opened or migrated
+    pub async fn open_existing(
+        &self,
+        path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<PathBuf, sqlx::Error> {
+        if !path.exists() {
+            return Err(sqlx::Error::Protocol(format!(
+                "{} does not exist",
+                path.display()
+            )));
+        }
+
+        if passphrase.is_none() && encryption::is_encrypted(path)? {
+            return Err(sqlx::Error::Protocol(format!(
+                "{} is encrypted; a passphrase is required",
+                path.display()
+            )));
+        }
+
+        self.open_ledger(path, passphrase).await
+    }
+
+    async fn open_ledger(
+        &self,
+        path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<PathBuf, sqlx::Error> {
+        let mut ledgers = self.inner.write().await;
+
+        let active = match ledgers.position(&normalize_path(path)?) {
+            Some(index) => ledgers.open[index].path.clone(),
+            None => {
+                let ledger = open_ledger_file(path, passphrase).await?;
+                self.adopt_legacy_folders(&ledger).await?;
+                let active = ledger.path.clone();
+                ledgers.open.push(ledger);
+                active
+            }
+        };
+        ledgers.active = Some(active.clone());
+        drop(ledgers);
+
+        self.remember(&active)?;
+
+        Ok(active)
+    }
+
+    /// Makes an already open ledger the active one.
+    ///
+    /// # Arguments
+    /// * `path` - Path of an open ledger
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - Path of the new active ledger
+    /// * `Err(sqlx::Error)` - No open ledger has this path
+    pub async fn switch(&self, path: &Path) -> Result<PathBuf, sqlx::Error> {
+        let mut ledgers = self.inner.write().await;
+
+        let Some(index) = ledgers.position(&normalize_path(path)?) else {
+            return Err(not_open(path));
+        };
+        let active = ledgers.open[index].path.clone();
+        ledgers.active = Some(active.clone());
+        drop(ledgers);
+
+        self.remember(&active)?;
+
+        Ok(active)
+    }
+
+    /// Closes an open ledger and releases its connections.
+    ///
+    /// Closing the active ledger activates the most recently opened remaining
+    /// one. The last open ledger cannot be closed.
+    ///
+    /// # Arguments
+    /// * `path` - Path of an open ledger
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - Path of the active ledger after closing
+    /// * `Err(sqlx::Error)` - Ledger is not open or is the only open ledger
+    pub async fn close(&self, path: &Path) -> Result<PathBuf, sqlx::Error> {
+        let mut ledgers = self.inner.write().await;
+
+        let Some(index) = ledgers.position(&normalize_path(path)?) else {
+            return Err(not_open(path));
+        };
+        if ledgers.open.len() == 1 {
+            return Err(sqlx::Error::Protocol(
+                "the last open ledger cannot be closed".to_string(),
+            ));
+        }
+
+        let closed = ledgers.open.remove(index);
+        if ledgers.active.as_ref() == Some(&closed.path) {
+            ledgers.active = ledgers.open.last().map(|ledger| ledger.path.clone());
+        }
+        closed.pool.close().await;
+
+        Ok(ledgers.active()?.path.clone())
+    }
+
+    /// Moves the active ledger's file to a new location without losing writes.
+    ///
+    /// Holds the write lock for the whole operation so no command can use the old
+    /// pool mid-move. Writes a consistent snapshot to the destination, opens and
+    /// migrates it, persists the new location in the settings file, and only then
+    /// closes the old pool and deletes the old files. Any failure before the
+    /// switch leaves the current database in place and in use.
+    ///
+    /// # Arguments
+    /// * `destination` - New database file path; must not exist yet
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - The new database path
+    /// * `Err(sqlx::Error)` - Location is pinned by `FINSIGHT_DB_PATH`, the
+    ///   destination exists, or the copy, open or settings write failed
+    pub async fn relocate(&self, destination: &Path) -> Result<PathBuf, sqlx::Error> {
+        if std::env::var_os(location::DATABASE_PATH_ENV).is_some() {
+            return Err(sqlx::Error::Protocol(format!(
+                "the database location is set by {} and cannot be changed from the app",
+                location::DATABASE_PATH_ENV
+            )));
+        }
+
+        let mut ledgers = self.inner.write().await;
+        let index = ledgers.active_index()?;
+        let passphrase = ledgers.open[index].passphrase.clone();
+
+        location::snapshot_to(
+            &ledgers.open[index].pool,
+            destination,
+            passphrase.as_deref(),
+        )
+        .await?;
+        let moved = match open_ledger_file(destination, passphrase.as_deref()).await {
+            Ok(ledger) => ledger,
+            Err(e) => {
+                let _ = location::remove_database_files(destination);
+                return Err(e);
+            }
+        };
+        let new_path = moved.path.clone();
+
+        let mut settings = location::load_settings(&self.data_dir)?;
+        for recent in settings.recent_ledgers.iter_mut() {
+            if *recent == ledgers.open[index].path {
+                *recent = new_path.clone();
+            }
+        }
+        settings.database_path = Some(new_path.clone());
+        location::save_settings(&self.data_dir, &settings)?;
+
+        let old = std::mem::replace(&mut ledgers.open[index], moved);
+        ledgers.active = Some(new_path.clone());
+        old.pool.close().await;
+        location::remove_database_files(&old.path)?;
+
+        Ok(new_path)
+    }
+
+    /// Encrypts, decrypts or re-keys the active ledger.
+    ///
+    /// Writes a copy under the new key with `sqlcipher_export()`, then renames it
+    /// over the original while holding the write lock and reopens the ledger
+    /// with the new key, see [`replace_ledger_file()`]. Existing backups are re-keyed the same way, so they can
+    /// still be listed and restored. The current passphrase must be supplied for encrypted
+    /// ledgers so an unattended session cannot silently strip encryption.
+    ///
+    /// # Arguments
+    /// * `current` - Current passphrase; `None` when the ledger is not encrypted
+    /// * `new` - New passphrase; `None` converts the ledger to a plain file
+    ///
+    /// # Returns
+    /// * `Ok(())` - Ledger re-keyed and reopened
+    /// * `Err(sqlx::Error)` - Wrong current passphrase, encryption unsupported, or
+    ///   export/swap failure (the original file stays in place and in use)
+    pub async fn change_encryption(
+        &self,
+        current: Option<&str>,
+        new: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let mut ledgers = self.inner.write().await;
+        let index = ledgers.active_index()?;
+
+        if ledgers.open[index].passphrase.as_deref() != current {
+            return Err(sqlx::Error::Protocol(
+                "the current passphrase is incorrect".to_string(),
+            ));
+        }
+        if let Some("") = new {
+            return Err(sqlx::Error::Protocol(
+                "the new passphrase must not be empty".to_string(),
+            ));
+        }
+
+        let path = ledgers.open[index].path.clone();
+        let converted = sibling_path(&path, ".rekey");
+        location::remove_database_files(&converted)?;
+
+        if let Err(e) = encryption::export_copy(&ledgers.open[index].pool, &converted, new).await {
+            let _ = location::remove_database_files(&converted);
+            return Err(e);
+        }
+        replace_ledger_file(&mut ledgers.open[index], &converted, new).await?;
+
+        // Backups follow the new key so they stay listable and restorable
+        backups::rekey_backups(&self.backup_dir(&ledgers.open[index].id), current, new)
+            .await
+            .map_err(|e| {
+                sqlx::Error::Protocol(format!(
+                    "the ledger was re-keyed, but re-keying its backups failed: {}",
+                    e
+                ))
+            })?;
+
+        Ok(())
+    }
+
+    /// Backs up the active ledger and its attachments and prunes old backups.
+    ///
+    /// Called on startup and then daily by the backup scheduler, and on demand.
+    /// Retention follows the `backup_policy` in the settings file; attachment
+    /// files only referenced by pruned backups are deleted with them. The
+    /// outcome is remembered for [`LedgerManager::last_backup_failure()`].
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - Path of the new backup file
+    /// * `Err(sqlx::Error)` - Snapshot, settings or rotation failure
+    pub async fn backup(&self) -> Result<PathBuf, sqlx::Error> {
+        let result = self.take_backup().await;
+        *self.last_backup_failure.lock().unwrap_or_else(|e| e.into_inner()) =
+            result.as_ref().err().map(|e| e.to_string());
+
+        result
+    }
+
+    /// Returns the error of the most recent backup, or `None` if it succeeded.
+    pub fn last_backup_failure(&self) -> Option<String> {
+        self.last_backup_failure
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    async fn take_backup(&self) -> Result<PathBuf, sqlx::Error> {
+        let ledgers = self.inner.read().await;
+        let active = ledgers.active()?;
+
+        let backup_dir = self.backup_dir(&active.id);
+        let path = backups::create_backup(
+            &active.pool,
+            &backup_dir,
+            &ledger_name(&active.path),
+            active.passphrase.as_deref(),
+        )
+        .await?;
+        attachments::copy_files(
+            &active.pool,
+            &self.attachment_dir_of(&active.id),
+            &backup_dir.join("attachments"),
+        )
+        .await?;
+        backups::rotate_backups(
+            &backup_dir,
+            location::load_settings(&self.data_dir)?.backup_policy,
+        )?;
+        backups::prune_backup_attachments(&backup_dir, active.passphrase.as_deref()).await?;
+
+        Ok(path)
+    }
+
+    /// Lists the active ledger's backups, newest first.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<serde_json::Value>)` - Backup descriptions, see [`backups::list_backups`]
+    /// * `Err(sqlx::Error)` - Folder listing failure
+    pub async fn list_backups(&self) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+        let (id, passphrase) = {
+            let ledgers = self.inner.read().await;
+            let active = ledgers.active()?;
+            (active.id.clone(), active.passphrase.clone())
+        };
+
+        backups::list_backups(&self.backup_dir(&id), passphrase.as_deref()).await
+    }
+
+    /// Replaces the active ledger's contents with one of its backups.
+    ///
+    /// A safety backup of the current state is taken first, so a restore can
+    /// itself be undone by restoring that safety backup. The backup is copied
+    /// next to the ledger and opened (and migrated, for backups made by older
+    /// versions) there before it replaces the live file, so an unreadable
+    /// backup leaves the ledger untouched and in use, see
+    /// [`replace_ledger_file()`]. Attachment files the backup refers to are
+    /// copied back into the ledger's store if they are missing there.
+    ///
+    /// # Arguments
+    /// * `file_name` - File name of a backup in the active ledger's backup folder
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - Path of the safety backup taken before restoring
+    /// * `Err(sqlx::Error)` - Unknown backup, safety backup failure, a backup that
+    ///   cannot be opened with the ledger's passphrase, or copy/swap failure
+    pub async fn restore_backup(&self, file_name: &str) -> Result<PathBuf, sqlx::Error> {
+        let mut ledgers = self.inner.write().await;
+        let index = ledgers.active_index()?;
+        let active_path = ledgers.open[index].path.clone();
+        let passphrase = ledgers.open[index].passphrase.clone();
+        let backup_dir = self.backup_dir(&ledgers.open[index].id);
+
+        let source = backup_dir.join(file_name);
+        if Path::new(file_name).file_name() != Some(file_name.as_ref()) || !source.is_file() {
+            return Err(sqlx::Error::Protocol(format!(
+                "backup {} not found",
+                file_name
+            )));
+        }
+
+        let safety = backups::create_backup(
+            &ledgers.open[index].pool,
+            &backup_dir,
+            &format!("{}-pre-restore", ledger_name(&active_path)),
+            passphrase.as_deref(),
+        )
+        .await?;
+        let store = self.attachment_dir_of(&ledgers.open[index].id);
+        attachments::copy_files(&ledgers.open[index].pool, &store, &backup_dir.join("attachments")).await?;
+
+        let staged = sibling_path(&active_path, ".restore");
+        location::remove_database_files(&staged)?;
+        std::fs::copy(&source, &staged)?;
+        replace_ledger_file(&mut ledgers.open[index], &staged, passphrase.as_deref()).await?;
+        attachments::copy_files(&ledgers.open[index].pool, &backup_dir.join("attachments"), &store).await?;
+
+        Ok(safety)
+    }
+
+    /// Sets how many daily, weekly and monthly backups are kept.
+    ///
+    /// # Arguments
+    /// * `policy` - New retention counts, applied from the next backup on
+    ///
+    /// # Returns
+    /// * `Ok(())` - Policy saved to the settings file
+    /// * `Err(sqlx::Error)` - Settings read or write failure
+    pub fn set_backup_policy(&self, policy: backups::BackupPolicy) -> Result<(), sqlx::Error> {
+        let mut settings = location::load_settings(&self.data_dir)?;
+        settings.backup_policy = policy;
+
+        location::save_settings(&self.data_dir, &settings)
+    }
+
+    /// Backup folder of a ledger: `backups/<ledger id>` in the app data directory.
+    fn backup_dir(&self, ledger_id: &str) -> PathBuf {
+        self.data_dir.join("backups").join(ledger_id)
+    }
+
+    /// Moves a ledger's backups and attachments from the folders named after its
+    /// file, used before ledgers had ids, to its id-keyed folders.
+    ///
+    /// Same-named ledgers shared those folders. The first of them opened claims
+    /// the backup folder; each copies the attachment files it refers to, and the
+    /// old store is left in place for the others.
+    async fn adopt_legacy_folders(&self, ledger: &OpenLedger) -> Result<(), sqlx::Error> {
+        let name = ledger_name(&ledger.path);
+
+        let legacy = self.data_dir.join("backups").join(&name);
+        let current = self.backup_dir(&ledger.id);
+        if legacy.is_dir() && !current.exists() {
+            std::fs::rename(legacy, current)?;
+        }
+
+        let legacy = self.data_dir.join("attachments").join(&name);
+        let current = self.attachment_dir_of(&ledger.id);
+        if legacy.is_dir() && !current.exists() {
+            attachments::copy_files(&ledger.pool, &legacy, &current).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Attachment store of a ledger: `attachments/<ledger id>` in the app data directory.
+    fn attachment_dir_of(&self, ledger_id: &str) -> PathBuf {
+        self.data_dir.join("attachments").join(ledger_id)
+    }
+
+    /// Records a ledger as most recently used and as the one to open on startup.
+    fn remember(&self, path: &Path) -> Result<(), sqlx::Error> {
+        let mut settings = location::load_settings(&self.data_dir)?;
+
+        settings.recent_ledgers.retain(|recent| recent != path);
+        settings.recent_ledgers.insert(0, path.to_path_buf());
+        settings.recent_ledgers.truncate(MAX_RECENT_LEDGERS);
+        settings.database_path = Some(path.to_path_buf());
+
+        location::save_settings(&self.data_dir, &settings)
+    }
+}
+
+/// Opens a ledger file, applying the schema, migrations and system data.
+async fn open_ledger_file(
+    path: &Path,
+    passphrase: Option<&str>,
+) -> Result<OpenLedger, sqlx::Error> {
+    let pool = super::init_db(path, passphrase).await?;
+    let path = normalize_path(path)?;
+    let id = sqlx::query_scalar("SELECT ledger_id FROM ledger_settings WHERE id = 1")
+        .fetch_one(&pool)
+        .await?;
+
+    Ok(OpenLedger {
+        pool,
+        path,
+        id,
+        passphrase: passphrase.map(str::to_string),
+    })
+}
+
+/// Swaps a prepared database file in for an open ledger's file.
+///
+/// The replacement is opened and migrated at its own path first, and given the
+/// ledger's id, so a corrupt file or a wrong passphrase fails before the ledger
+/// is touched. Only then is
+/// the ledger's pool closed and the replacement renamed over the live file,
+/// which replaces it atomically. If the rename fails, the original file is
+/// reopened; the ledger's slot is only overwritten with a pool that opened.
+///
+/// # Arguments
+/// * `ledger` - The open ledger whose file is replaced
+/// * `replacement` - Prepared database file next to the ledger; consumed
+/// * `passphrase` - Key of the replacement, which becomes the ledger's key
+///
+/// # Returns
+/// * `Ok(())` - Ledger now uses the replacement
+/// * `Err(sqlx::Error)` - Replacement unreadable, or rename/reopen failure
+async fn replace_ledger_file(
+    ledger: &mut OpenLedger,
+    replacement: &Path,
+    passphrase: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    // A backup taken before ledgers had ids gets a fresh one when migrated
+    let prepared = async {
+        let pool = super::init_db(replacement, passphrase).await?;
+        let kept = sqlx::query("UPDATE ledger_settings SET ledger_id = ? WHERE id = 1")
+            .bind(&ledger.id)
+            .execute(&pool)
+            .await;
+        pool.close().await;
+        kept
+    };
+    if let Err(e) = prepared.await {
+        let _ = location::remove_database_files(replacement);
+        return Err(e);
+    }
+
+    ledger.pool.close().await;
+    let path = ledger.path.clone();
+    if let Err(e) = swap_database_file(replacement, &path) {
+        let _ = location::remove_database_files(replacement);
+        ledger.pool = super::init_db(&path, ledger.passphrase.as_deref()).await?;
+        return Err(e);
+    }
+
+    *ledger = open_ledger_file(&path, passphrase).await?;
+
+    Ok(())
+}
+
+/// Renames a closed database file over another, discarding the target's stale
+/// WAL and shared-memory files so they cannot be replayed into the new file.
+fn swap_database_file(source: &Path, target: &Path) -> Result<(), sqlx::Error> {
+    for suffix in ["-wal", "-shm"] {
+        let side = sibling_path(target, suffix);
+        if side.exists() {
+            std::fs::remove_file(side)?;
+        }
+    }
+    std::fs::rename(source, target)?;
+
+    Ok(())
+}
+
+/// Path next to a database file with a suffix appended to its name.
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling = path.as_os_str().to_owned();
+    sibling.push(suffix);
+    PathBuf::from(sibling)
+}
+
+/// Resolves a ledger path to the absolute form open ledgers are keyed by.
+///
+/// Relative paths, `..` components and symlinks all resolve to the same key,
+/// whether or not the file exists yet: a missing file is resolved through its
+/// parent directory, so a ledger keeps its key once it has been created.
+fn normalize_path(path: &Path) -> Result<PathBuf, sqlx::Error> {
+    let absolute = std::path::absolute(path)?;
+    if let Ok(canonical) = std::fs::canonicalize(&absolute) {
+        return Ok(canonical);
+    }
+
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => match std::fs::canonicalize(parent) {
+            Ok(parent) => Ok(parent.join(name)),
+            Err(_) => Ok(absolute),
+        },
+        _ => Ok(absolute),
+    }
+}
+
+fn not_open(path: &Path) -> sqlx::Error {
+    sqlx::Error::Protocol(format!("ledger {} is not open", path.display()))
+}
+
+/// Display name of a ledger, derived from its file name.
+fn ledger_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{AccountStatus, Currency, Money};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("finsight-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::canonicalize(dir).unwrap()
+    }
+
+    async fn active_backup_dir(manager: &LedgerManager) -> PathBuf {
+        manager.backup_dir(&manager.inner.read().await.active().unwrap().id)
+    }
+
+    #[tokio::test]
+    async fn test_switch_between_ledgers() {
+        let dir = temp_dir("ledgers");
+        let household = dir.join("household.db");
+        let parent = dir.join("parent.db");
+
+        let manager = LedgerManager::with_ledger(dir.clone(), &household)
+            .await
+            .unwrap();
+        crate::database::add_account(
+            &manager.pool().await.unwrap(),
+            "Joint Checking".to_string(),
+            "checking".to_string(),
+        )
+        .await
+        .unwrap();
+
+        manager.create(&parent, None).await.unwrap();
+        assert_eq!(manager.path().await.unwrap(), parent);
+        let accounts = crate::database::get_all_accounts(&manager.pool().await.unwrap(), AccountStatus::All)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 0); // New ledger starts empty
+
+        manager.switch(&household).await.unwrap();
+        let accounts = crate::database::get_all_accounts(&manager.pool().await.unwrap(), AccountStatus::All)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+
+        let listing = manager.list().await.unwrap();
+        assert_eq!(listing["open"].as_array().unwrap().len(), 2);
+        assert_eq!(listing["recent"][0], household.to_string_lossy().as_ref());
+
+        // Closing the active ledger falls back to the remaining one
+        assert_eq!(manager.close(&household).await.unwrap(), parent);
+        assert!(manager.close(&parent).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ledger_paths_are_normalized() {
+        let dir = temp_dir("normalize");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        let books = dir.join("books.db");
+
+        // A new ledger reached through `..` is keyed by its resolved path
+        let manager = LedgerManager::with_ledger(dir.clone(), &dir.join("nested/../books.db"))
+            .await
+            .unwrap();
+        assert_eq!(manager.path().await.unwrap(), books);
+        manager.pool().await.unwrap();
+
+        // Opening it again by another spelling reuses the open ledger
+        manager.create(&dir.join("other.db"), None).await.unwrap();
+        manager.open_existing(&books, None).await.unwrap();
+        assert_eq!(manager.list().await.unwrap()["open"].as_array().unwrap().len(), 2);
+        manager.switch(&dir.join("nested/../other.db")).await.unwrap();
+        assert_eq!(manager.path().await.unwrap(), dir.join("other.db"));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&books, dir.join("link.db")).unwrap();
+            assert_eq!(manager.switch(&dir.join("link.db")).await.unwrap(), books);
+        }
+
+        let relative = Path::new("finsight-relative.db");
+        assert_eq!(
+            normalize_path(relative).unwrap(),
+            std::env::current_dir().unwrap().canonicalize().unwrap().join(relative)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_same_named_ledgers_keep_separate_backups() {
+        let dir = temp_dir("same-name");
+        let (first, second) = (dir.join("a/books.db"), dir.join("b/books.db"));
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+
+        let manager = LedgerManager::with_ledger(dir.clone(), &first).await.unwrap();
+        let first_backup = manager.backup().await.unwrap();
+        let first_backups = active_backup_dir(&manager).await;
+
+        manager.create(&second, None).await.unwrap();
+        manager.backup().await.unwrap();
+        let second_backups = active_backup_dir(&manager).await;
+        assert_ne!(first_backups, second_backups);
+
+        // Rotating one ledger's backups leaves the other's alone
+        manager.set_backup_policy(backups::BackupPolicy { daily: 0, weekly: 0, monthly: 0 }).unwrap();
+        manager.backup().await.unwrap();
+        assert!(first_backup.exists());
+        manager.switch(&first).await.unwrap();
+        assert_eq!(manager.list_backups().await.unwrap().len(), 1);
+
+        // Garbage collection in one ledger keeps the other's attachment files
+        let pool = manager.pool().await.unwrap();
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_transaction(&pool, 1, Money::new(-4500, Currency::USD), "debit".to_string(), "Plumber".to_string(), "2025-03-02".to_string(), 1)
+            .await
+            .unwrap();
+        let invoice = dir.join("invoice.pdf");
+        std::fs::write(&invoice, b"%PDF invoice").unwrap();
+        let first_store = manager.attachment_dir().await.unwrap();
+        attachments::attach_file(&pool, &first_store, 1, &invoice).await.unwrap();
+
+        manager.switch(&second).await.unwrap();
+        let second_store = manager.attachment_dir().await.unwrap();
+        assert_ne!(first_store, second_store);
+        attachments::collect_garbage(&manager.pool().await.unwrap(), &second_store)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_dir(&first_store).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_backup_takes_safety_backup() {
+        let dir = temp_dir("restore");
+        let manager = LedgerManager::with_ledger(dir.clone(), &dir.join("books.db"))
+            .await
+            .unwrap();
+
+        crate::database::add_account(
+            &manager.pool().await.unwrap(),
+            "Checking".to_string(),
+            "checking".to_string(),
+        )
+        .await
+        .unwrap();
+        let backup = manager.backup().await.unwrap();
+        crate::database::add_account(
+            &manager.pool().await.unwrap(),
+            "Savings".to_string(),
+            "savings".to_string(),
+        )
+        .await
+        .unwrap();
+
+        let file_name = backup.file_name().unwrap().to_string_lossy().into_owned();
+        let safety = manager.restore_backup(&file_name).await.unwrap();
+        assert!(safety.exists());
+
+        let accounts = crate::database::get_all_accounts(&manager.pool().await.unwrap(), AccountStatus::All)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 1); // Back to the state at backup time
+
+        assert!(manager.restore_backup("../books.db").await.is_err());
+
+        // A corrupt backup fails before the live ledger is touched
+        std::fs::write(active_backup_dir(&manager).await.join("books-20250101-000000.db"), [0x5a_u8; 4096]).unwrap();
+        assert!(manager.restore_backup("books-20250101-000000.db").await.is_err());
+        let accounts = crate::database::get_all_accounts(&manager.pool().await.unwrap(), AccountStatus::All)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert!(!dir.join("books.db.restore").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backups_keep_attachments() {
+        let dir = temp_dir("backup-attachments");
+        let manager = LedgerManager::with_ledger(dir.clone(), &dir.join("books.db"))
+            .await
+            .unwrap();
+        let pool = manager.pool().await.unwrap();
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_transaction(&pool, 1, Money::new(-89900, Currency::USD), "debit".to_string(), "Laptop".to_string(), "2025-02-10".to_string(), 1)
+            .await
+            .unwrap();
+        let receipt = dir.join("laptop.pdf");
+        std::fs::write(&receipt, b"%PDF warranty").unwrap();
+        let store = manager.attachment_dir().await.unwrap();
+        attachments::attach_file(&pool, &store, 1, &receipt).await.unwrap();
+
+        let backup = manager.backup().await.unwrap();
+        assert_eq!(std::fs::read_dir(active_backup_dir(&manager).await.join("attachments")).unwrap().count(), 1);
+
+        // Detached and garbage collected, the file only survives in the backup
+        attachments::detach_file(&pool, 1).await.unwrap();
+        sqlx::query("DELETE FROM journal_operations").execute(&pool).await.unwrap();
+        attachments::collect_garbage(&pool, &store).await.unwrap();
+        assert_eq!(std::fs::read_dir(&store).unwrap().count(), 0);
+
+        let file_name = backup.file_name().unwrap().to_string_lossy().into_owned();
+        manager.restore_backup(&file_name).await.unwrap();
+        let pool = manager.pool().await.unwrap();
+        let copy = attachments::checkout_attachment(&pool, &store, 1, &dir.join("open")).await.unwrap();
+        assert_eq!(std::fs::read(copy).unwrap(), b"%PDF warranty");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn test_encrypted_ledger_lifecycle() {
+        let dir = temp_dir("encrypted");
+        let path = dir.join("private.db");
+
+        let manager = LedgerManager::with_ledger(dir.clone(), &dir.join("plain.db"))
+            .await
+            .unwrap();
+        manager.create(&path, Some("s3cret")).await.unwrap();
+        crate::database::add_account(
+            &manager.pool().await.unwrap(),
+            "Checking".to_string(),
+            "checking".to_string(),
+        )
+        .await
+        .unwrap();
+        manager.backup().await.unwrap();
+        assert!(encryption::is_encrypted(&path).unwrap());
+
+        // Backups are encrypted with the same key and still readable in the listing
+        let backups = manager.list_backups().await.unwrap();
+        assert_eq!(backups[0]["transaction_count"], 0);
+        assert!(encryption::is_encrypted(Path::new(backups[0]["path"].as_str().unwrap())).unwrap());
+
+        // A fresh start on an encrypted ledger is locked until unlocked
+        let locked = LedgerManager::with_ledger(dir.clone(), &path)
+            .await
+            .unwrap();
+        assert!(locked.pool().await.is_err());
+        assert!(locked.unlock("wrong").await.is_err());
+        locked.unlock("s3cret").await.unwrap();
+        locked.pool().await.unwrap().close().await;
+
+        // Re-key, then decrypt back to a plain file
+        assert!(
+            manager
+                .change_encryption(Some("wrong"), Some("new"))
+                .await
+                .is_err()
+        );
+        manager
+            .change_encryption(Some("s3cret"), Some("n3w"))
+            .await
+            .unwrap();
+
+        // Backups follow the new key, so they stay readable and restorable
+        let backups = manager.list_backups().await.unwrap();
+        assert_eq!(backups[0]["transaction_count"], 0);
+        let file_name = backups[0]["file_name"].as_str().unwrap().to_string();
+        manager.restore_backup(&file_name).await.unwrap();
+        manager.change_encryption(Some("n3w"), None).await.unwrap();
+        assert!(!encryption::is_encrypted(&path).unwrap());
+        let accounts = crate::database::get_all_accounts(&manager.pool().await.unwrap(), AccountStatus::All)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
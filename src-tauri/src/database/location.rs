@@ -0,0 +1,274 @@
+//! Database file location resolution and relocation.
+//!
+//! Decides where the ledger file lives instead of relying on the process working
+//! directory. The location is resolved in priority order:
+//!
+//! 1. The `FINSIGHT_DB_PATH` environment variable
+//! 2. The `database_path` entry in `settings.json` inside the app data directory,
+//!    which tracks the most recently active ledger
+//! 3. `finsight.db` inside the app data directory
+//!
+//! Databases created by earlier versions in `./finsight.db` are copied into the
+//! default location once, the first time the app starts without one.
+
+use super::backups::BackupPolicy;
+use super::encryption;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that overrides every other database location setting.
+pub const DATABASE_PATH_ENV: &str = "FINSIGHT_DB_PATH";
+
+/// File name of the settings file stored in the app data directory.
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// File name of the database inside the app data directory.
+pub const DEFAULT_DATABASE_FILE: &str = "finsight.db";
+
+/// Location used by versions that stored the database in the working directory.
+const LEGACY_DATABASE_PATH: &str = "./finsight.db";
+
+/// User-editable application settings persisted as JSON in the app data directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Settings {
+    /// Ledger file opened on startup; `None` uses the app data directory default
+    #[serde(default)]
+    pub database_path: Option<PathBuf>,
+    /// Recently used ledger files, most recent first
+    #[serde(default)]
+    pub recent_ledgers: Vec<PathBuf>,
+    /// Retention of automatic backups
+    #[serde(default)]
+    pub backup_policy: BackupPolicy,
+}
+
+/// Reads the settings file from the app data directory.
+///
+/// A missing settings file is not an error and yields default settings, so a
+/// fresh installation needs no setup step.
+///
+/// # Arguments
+/// * `data_dir` - Application data directory containing `settings.json`
+///
+/// # Returns
+/// * `Ok(Settings)` - Parsed settings or defaults when the file does not exist
+/// * `Err(sqlx::Error)` - The file exists but cannot be read or parsed
+pub fn load_settings(data_dir: &Path) -> Result<Settings, sqlx::Error> {
+    let path = data_dir.join(SETTINGS_FILE);
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+
+    let contents = std::fs::read_to_string(&path)?;
+    serde_json::from_str(&contents).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Writes the settings file to the app data directory.
+///
+/// # Arguments
+/// * `data_dir` - Application data directory receiving `settings.json`
+/// * `settings` - Settings to persist
+///
+/// # Returns
+/// * `Ok(())` - Settings written successfully
+/// * `Err(sqlx::Error)` - Directory creation, serialization or write failure
+pub fn save_settings(data_dir: &Path, settings: &Settings) -> Result<(), sqlx::Error> {
+    std::fs::create_dir_all(data_dir)?;
+    let contents =
+        serde_json::to_string_pretty(settings).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+    std::fs::write(data_dir.join(SETTINGS_FILE), contents)?;
+
+    Ok(())
+}
+
+/// Resolves the database file path from the override, settings and defaults.
+///
+/// # Arguments
+/// * `data_dir` - Application data directory (from Tauri's path resolver)
+/// * `env_override` - Value of `FINSIGHT_DB_PATH`, if set
+///
+/// # Returns
+/// * `Ok(PathBuf)` - Path of the database file to open
+/// * `Err(sqlx::Error)` - The settings file exists but is unreadable
+///
+/// # Examples
+/// ```no_run
+/// let env_override = std::env::var_os(DATABASE_PATH_ENV).map(PathBuf::from);
+/// let path = resolve_database_path(&data_dir, env_override)?;
+/// ```
+pub fn resolve_database_path(
+    data_dir: &Path,
+    env_override: Option<PathBuf>,
+) -> Result<PathBuf, sqlx::Error> {
+    if let Some(path) = env_override {
+        return Ok(path);
+    }
+
+    match load_settings(data_dir)?.database_path {
+        Some(path) => Ok(path),
+        None => Ok(data_dir.join(DEFAULT_DATABASE_FILE)),
+    }
+}
+
+/// Copies a database from the legacy working-directory location, once.
+///
+/// Runs only when `target` does not exist yet and a legacy `./finsight.db` is
+/// present. The copy is made with `VACUUM INTO`, which produces a consistent
+/// snapshot that includes any pending WAL content. The legacy file is then
+/// renamed to `finsight.db.migrated` so the migration never runs twice and the
+/// original data stays on disk until the user removes it.
+///
+/// # Arguments
+/// * `target` - Resolved database path in the app data directory
+///
+/// # Returns
+/// * `Ok(true)` - A legacy database was migrated
+/// * `Ok(false)` - Nothing to migrate
+/// * `Err(sqlx::Error)` - Snapshot or rename failure; the legacy file is untouched
+pub async fn migrate_legacy_database(target: &Path) -> Result<bool, sqlx::Error> {
+    migrate_database_from(Path::new(LEGACY_DATABASE_PATH), target).await
+}
+
+async fn migrate_database_from(legacy: &Path, target: &Path) -> Result<bool, sqlx::Error> {
+    if target.exists() || !legacy.exists() {
+        return Ok(false);
+    }
+
+    let legacy_pool = SqlitePool::connect_with(
+        sqlx::sqlite::SqliteConnectOptions::new().filename(legacy),
+    )
+    .await?;
+    let result = snapshot_to(&legacy_pool, target, None).await;
+    legacy_pool.close().await;
+    result?;
+
+    std::fs::rename(legacy, legacy.with_extension("db.migrated"))?;
+
+    Ok(true)
+}
+
+/// Writes a consistent copy of the open database to a new file.
+///
+/// Uses `VACUUM INTO`, which reads a single transaction snapshot and therefore
+/// is safe while other connections are writing. Encrypted ledgers are copied
+/// with [`encryption::export_copy()`] under the same passphrase instead, so the
+/// copy is never written in plaintext. The destination must not exist.
+///
+/// # Arguments
+/// * `pool` - Connection pool of the database to copy
+/// * `destination` - Path of the new database file
+/// * `passphrase` - Passphrase of an encrypted ledger, or `None` for a plain file
+///
+/// # Returns
+/// * `Ok(())` - Snapshot written successfully
+/// * `Err(sqlx::Error)` - Destination exists, is not writable, or the copy failed
+pub async fn snapshot_to(
+    pool: &SqlitePool,
+    destination: &Path,
+    passphrase: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    if destination.exists() {
+        return Err(sqlx::Error::Protocol(format!(
+            "{} already exists",
+            destination.display()
+        )));
+    }
+
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    if passphrase.is_some() {
+        return encryption::export_copy(pool, destination, passphrase).await;
+    }
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(destination.to_string_lossy().into_owned())
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Removes a database file together with its WAL and shared-memory side files.
+///
+/// # Arguments
+/// * `path` - Path of the main database file
+///
+/// # Returns
+/// * `Ok(())` - All existing files removed
+/// * `Err(sqlx::Error)` - A file could not be removed
+pub fn remove_database_files(path: &Path) -> Result<(), sqlx::Error> {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        let file = PathBuf::from(file);
+        if file.exists() {
+            std::fs::remove_file(file)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::AccountStatus;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("finsight-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_resolve_database_path_priority() {
+        let dir = temp_dir("resolve");
+
+        let default = resolve_database_path(&dir, None).unwrap();
+        assert_eq!(default, dir.join(DEFAULT_DATABASE_FILE));
+
+        let custom = dir.join("custom").join("books.db");
+        save_settings(
+            &dir,
+            &Settings {
+                database_path: Some(custom.clone()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(resolve_database_path(&dir, None).unwrap(), custom);
+
+        let env = PathBuf::from("/tmp/override.db");
+        assert_eq!(resolve_database_path(&dir, Some(env.clone())).unwrap(), env);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_migrate_legacy_database_runs_once() {
+        let dir = temp_dir("legacy");
+        let legacy = dir.join("finsight.db");
+        let target = dir.join("data").join("finsight.db");
+
+        let pool = crate::database::init_db(&legacy, None).await.unwrap();
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        pool.close().await;
+
+        assert!(migrate_database_from(&legacy, &target).await.unwrap());
+        assert!(!legacy.exists());
+        assert!(!migrate_database_from(&legacy, &target).await.unwrap());
+
+        let pool = crate::database::init_db(&target, None).await.unwrap();
+        let accounts = crate::database::get_all_accounts(&pool, AccountStatus::All).await.unwrap();
+        assert_eq!(accounts.len(), 1);
+        pool.close().await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
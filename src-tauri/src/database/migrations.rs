@@ -1,40 +1,33 @@
-//! # Development Note
-//!
-//! Migration system is currently hibernated during active development.
-//! Schema changes are handled via create_tables() and database deletion.
-//! Will resume migrations when moving to production with real user data.
-//!
 //! Database migration system for the finsight personal finance application.
 //!
 //! Provides automatic schema evolution through versioned migrations that run
 //! on application startup. Each migration is tracked in the database to ensure
-//! they only run once and can be safely applied to existing data.
+//! it only runs once, and its checksum is stored so that edits to an already
+//! applied migration are detected instead of silently diverging schemas.
 //!
 //! # Migration System Design
 //!
-//! - **Name Registry Pattern**: All migrations are registered by name in `run_migrations()`
-//! - **Match-based Execution**: Migration functions called via match statement to avoid type issues
-//! - **Automatic Execution**: Migrations run during database initialization
-//! - **Tracking**: Applied migrations are recorded in the `migrations` table
-//! - **One-Time Execution**: Each migration runs only once per database
+//! - **Declarative Registry**: All migrations are listed in [`MIGRATIONS`] as plain SQL
+//! - **Baseline**: `000_baseline` creates the original schema, replacing the old `create_tables()`
+//! - **Atomicity**: Each migration, its tracking row and the schema version bump share one SQL transaction
+//! - **Checksums**: A SHA-256 of each migration's SQL is recorded and verified on every startup
+//! - **Schema Version**: `PRAGMA user_version` holds the version of the newest applied migration
 //! - **Sequential Naming**: Migrations use numbered prefixes (001_, 002_, etc.)
 //!
 //! # Adding New Migrations
 //!
-//! 1. Create a new migration function: `migration_XXX_description`
-//! 2. Add the name to the `migration_registry` vector in `run_migrations()`
-//! 3. Add a match arm for the new migration name
-//! 4. Migrations will automatically run on next app startup
+//! 1. Append a [`Migration`] entry to [`MIGRATIONS`] with the next version number
+//! 2. Never edit or reorder an entry once it has shipped; write a new migration instead
+//! 3. Migrations will automatically run on next app startup
 //!
 //! # Example Migration
 //!
 //! ```rust
-//! async fn migration_002_add_user_field(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-//!     sqlx::query("ALTER TABLE accounts ADD COLUMN user_id INTEGER")
-//!         .execute(pool)
-//!         .await?;
-//!     Ok(())
-//! }
+//! Migration {
+//!     version: 4,
+//!     name: "004_add_user_field",
+//!     sql: "ALTER TABLE accounts ADD COLUMN user_id INTEGER",
+//! },
 //! ```
 //!
 //! # Safety
@@ -43,212 +36,737 @@
 //! - Use `DEFAULT` values for new required columns
 //! - Test migrations against real data during development
 
-use sqlx::{Row, SqlitePool};
+use super::unit_of_work;
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+/// A single schema change, applied at most once per database.
+pub struct Migration {
+    /// Sequential version stored in `PRAGMA user_version` once applied
+    pub version: i64,
+    /// Unique name recorded in the `migrations` table
+    pub name: &'static str,
+    /// SQL script executed in the migration's transaction; may hold several statements
+    pub sql: &'static str,
+}
+
+impl Migration {
+    /// SHA-256 of the migration's SQL as lowercase hex.
+    fn checksum(&self) -> String {
+        Sha256::digest(self.sql.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// Every migration known to this build, in application order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        name: "000_baseline",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS accounts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                account_type TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS categories (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                parent_id INTEGER,
+                created_at TEXT DEFAULT (datetime('now')),
+                FOREIGN KEY (parent_id) REFERENCES categories(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS transactions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id INTEGER NOT NULL REFERENCES accounts(id),
+                amount_cents INTEGER NOT NULL,
+                transaction_type TEXT NOT NULL,
+                description TEXT NOT NULL,
+                transaction_date TEXT NOT NULL,
+                category_id INTEGER NOT NULL REFERENCES categories(id),
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+        "#,
+    },
+    // Account archiving as an alternative to hard deletion; existing accounts stay active
+    Migration {
+        version: 1,
+        name: "001_add_archived_column",
+        sql: "ALTER TABLE accounts ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE",
+    },
+    // Debits become negative so balances are a plain SUM(amount_cents)
+    Migration {
+        version: 2,
+        name: "002_convert_amounts_to_signed_values",
+        sql: "UPDATE transactions SET amount_cents = -amount_cents WHERE transaction_type = 'debit'",
+    },
+    // Indexes backing per-account, per-category and hierarchy queries
+    Migration {
+        version: 3,
+        name: "003_add_query_indexes",
+        sql: r#"
+            CREATE INDEX IF NOT EXISTS idx_transactions_account_date ON transactions(account_id, transaction_date);
+            CREATE INDEX IF NOT EXISTS idx_transactions_category ON transactions(category_id);
+            CREATE INDEX IF NOT EXISTS idx_categories_parent ON categories(parent_id);
+        "#,
+    },
+    // Full-text index over transaction text. Memo and payee columns are reserved
+    // for when transactions gain those fields; triggers keep the index in sync.
+    Migration {
+        version: 4,
+        name: "004_add_transaction_search",
+        sql: r#"
+            CREATE VIRTUAL TABLE transactions_fts USING fts5(
+                description, memo, payee,
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+            INSERT INTO transactions_fts (rowid, description, memo, payee)
+                SELECT id, description, '', '' FROM transactions;
+
+            CREATE TRIGGER transactions_fts_insert AFTER INSERT ON transactions BEGIN
+                INSERT INTO transactions_fts (rowid, description, memo, payee)
+                    VALUES (new.id, new.description, '', '');
+            END;
+            CREATE TRIGGER transactions_fts_update AFTER UPDATE OF description ON transactions BEGIN
+                UPDATE transactions_fts SET description = new.description WHERE rowid = new.id;
+            END;
+            CREATE TRIGGER transactions_fts_delete AFTER DELETE ON transactions BEGIN
+                DELETE FROM transactions_fts WHERE rowid = old.id;
+            END;
+        "#,
+    },
+    // Reconciliation flag set once a transaction shows up on the bank statement
+    Migration {
+        version: 5,
+        name: "005_add_cleared_column",
+        sql: "ALTER TABLE transactions ADD COLUMN cleared BOOLEAN NOT NULL DEFAULT FALSE",
+    },
+    // Balance carried over from before the first recorded transaction
+    Migration {
+        version: 6,
+        name: "006_add_opening_balance",
+        sql: "ALTER TABLE accounts ADD COLUMN opening_balance_cents INTEGER NOT NULL DEFAULT 0",
+    },
+    // Named transaction filters, stored as TransactionFilter JSON
+    Migration {
+        version: 7,
+        name: "007_add_saved_filters",
+        sql: r#"
+            CREATE TABLE saved_filters (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                filter TEXT NOT NULL,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+        "#,
+    },
+    // Change journal grouping row-level before/after images into user operations.
+    // The row triggers are generated from the live schema, see journal.rs.
+    Migration {
+        version: 8,
+        name: "008_add_change_journal",
+        sql: r#"
+            CREATE TABLE journal_operations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                label TEXT NOT NULL,
+                open BOOLEAN NOT NULL DEFAULT FALSE,
+                undone BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE TABLE journal_changes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                operation_id INTEGER NOT NULL REFERENCES journal_operations(id) ON DELETE CASCADE,
+                table_name TEXT NOT NULL,
+                row_id INTEGER NOT NULL,
+                old_values TEXT,
+                new_values TEXT
+            );
+            CREATE INDEX idx_journal_changes_operation ON journal_changes(operation_id);
+        "#,
+    },
+    Migration {
+        version: 9,
+        name: "009_add_audit_log",
+        sql: r#"
+            CREATE TABLE audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                table_name TEXT NOT NULL,
+                row_id INTEGER NOT NULL,
+                action TEXT NOT NULL CHECK (action IN ('insert', 'update', 'delete')),
+                old_values TEXT,
+                new_values TEXT,
+                actor TEXT NOT NULL,
+                changed_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE INDEX idx_audit_log_record ON audit_log(table_name, row_id);
+            CREATE TABLE audit_actor (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                name TEXT NOT NULL
+            );
+        "#,
+    },
+    // Deleting moves transactions and categories to the trash instead of removing them
+    Migration {
+        version: 10,
+        name: "010_add_soft_delete",
+        sql: r#"
+            ALTER TABLE transactions ADD COLUMN deleted_at TEXT;
+            ALTER TABLE categories ADD COLUMN deleted_at TEXT;
+            CREATE INDEX idx_transactions_deleted ON transactions(deleted_at) WHERE deleted_at IS NOT NULL;
+            CREATE INDEX idx_categories_deleted ON categories(deleted_at) WHERE deleted_at IS NOT NULL;
+        "#,
+    },
+    // Closing date for archived accounts and a user-defined sidebar order, initially creation order
+    Migration {
+        version: 11,
+        name: "011_add_account_lifecycle",
+        sql: r#"
+            ALTER TABLE accounts ADD COLUMN closed_date TEXT;
+            ALTER TABLE accounts ADD COLUMN display_order INTEGER NOT NULL DEFAULT 0;
+            UPDATE accounts SET display_order = id;
+        "#,
+    },
+    // Payees with aliases and match patterns (JSON arrays). The full-text index
+    // now fills its payee column from the linked payee's canonical name.
+    Migration {
+        version: 12,
+        name: "012_add_payees",
+        sql: r#"
+            CREATE TABLE payees (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                aliases TEXT NOT NULL DEFAULT '[]',
+                patterns TEXT NOT NULL DEFAULT '[]',
+                default_category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            ALTER TABLE transactions ADD COLUMN payee_id INTEGER REFERENCES payees(id) ON DELETE SET NULL;
+            ALTER TABLE transactions ADD COLUMN original_description TEXT;
+            CREATE INDEX idx_transactions_payee ON transactions(payee_id);
+
+            DROP TRIGGER transactions_fts_insert;
+            CREATE TRIGGER transactions_fts_insert AFTER INSERT ON transactions BEGIN
+                INSERT INTO transactions_fts (rowid, description, memo, payee)
+                    VALUES (new.id, new.description, '',
+                            COALESCE((SELECT name FROM payees WHERE id = new.payee_id), ''));
+            END;
+            DROP TRIGGER transactions_fts_update;
+            CREATE TRIGGER transactions_fts_update AFTER UPDATE OF description, payee_id ON transactions BEGIN
+                UPDATE transactions_fts
+                    SET description = new.description,
+                        payee = COALESCE((SELECT name FROM payees WHERE id = new.payee_id), '')
+                    WHERE rowid = new.id;
+            END;
+            CREATE TRIGGER payees_fts_rename AFTER UPDATE OF name ON payees BEGIN
+                UPDATE transactions_fts SET payee = new.name
+                    WHERE rowid IN (SELECT id FROM transactions WHERE payee_id = new.id);
+            END;
+        "#,
+    },
+    // Tags and their many-to-many link to transactions. The link table has its
+    // own id so links are journaled and audited like other records.
+    Migration {
+        version: 13,
+        name: "013_add_tags",
+        sql: r#"
+            CREATE TABLE tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE TABLE transaction_tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transaction_id INTEGER NOT NULL REFERENCES transactions(id) ON DELETE CASCADE,
+                tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                UNIQUE (transaction_id, tag_id)
+            );
+            CREATE INDEX idx_transaction_tags_tag ON transaction_tags(tag_id);
+        "#,
+    },
+    // Files attached to transactions. The files themselves live in the ledger's
+    // content-addressed attachment store, named by `sha256`.
+    Migration {
+        version: 14,
+        name: "014_add_attachments",
+        sql: r#"
+            CREATE TABLE attachments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transaction_id INTEGER NOT NULL REFERENCES transactions(id) ON DELETE CASCADE,
+                sha256 TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                media_type TEXT NOT NULL,
+                size_bytes INTEGER NOT NULL,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE INDEX idx_attachments_transaction ON attachments(transaction_id);
+        "#,
+    },
+    // Memos, check numbers and user-defined fields. Custom field values are a
+    // JSON object keyed by field id; the full-text index now fills its memo column.
+    Migration {
+        version: 15,
+        name: "015_add_memos_and_custom_fields",
+        sql: r#"
+            CREATE TABLE custom_fields (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                entity TEXT NOT NULL CHECK (entity IN ('account', 'transaction')),
+                name TEXT NOT NULL COLLATE NOCASE,
+                field_type TEXT NOT NULL CHECK (field_type IN ('text', 'number', 'date', 'boolean')),
+                created_at TEXT DEFAULT (datetime('now')),
+                UNIQUE (entity, name)
+            );
+            ALTER TABLE transactions ADD COLUMN memo TEXT;
+            ALTER TABLE transactions ADD COLUMN check_number TEXT;
+            ALTER TABLE transactions ADD COLUMN custom_fields TEXT NOT NULL DEFAULT '{}';
+            ALTER TABLE accounts ADD COLUMN memo TEXT;
+            ALTER TABLE accounts ADD COLUMN custom_fields TEXT NOT NULL DEFAULT '{}';
+
+            DROP TRIGGER transactions_fts_insert;
+            CREATE TRIGGER transactions_fts_insert AFTER INSERT ON transactions BEGIN
+                INSERT INTO transactions_fts (rowid, description, memo, payee)
+                    VALUES (new.id, new.description, COALESCE(new.memo, ''),
+                            COALESCE((SELECT name FROM payees WHERE id = new.payee_id), ''));
+            END;
+            DROP TRIGGER transactions_fts_update;
+            CREATE TRIGGER transactions_fts_update AFTER UPDATE OF description, memo, payee_id ON transactions BEGIN
+                UPDATE transactions_fts
+                    SET description = new.description,
+                        memo = COALESCE(new.memo, ''),
+                        payee = COALESCE((SELECT name FROM payees WHERE id = new.payee_id), '')
+                    WHERE rowid = new.id;
+            END;
+        "#,
+    },
+    // Account currencies, dated exchange rates (1 from_currency = rate
+    // to_currency) and the ledger's base currency for reports. Existing
+    // accounts are taken to be in the base currency. The two legs of a
+    // transfer point at each other through transfer_id.
+    Migration {
+        version: 16,
+        name: "016_add_currencies",
+        sql: r#"
+            CREATE TABLE ledger_settings (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                base_currency TEXT NOT NULL DEFAULT 'USD'
+            );
+            INSERT INTO ledger_settings (id) VALUES (1);
+            ALTER TABLE accounts ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+            CREATE TABLE exchange_rates (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                rate_date TEXT NOT NULL,
+                from_currency TEXT NOT NULL,
+                to_currency TEXT NOT NULL,
+                rate REAL NOT NULL CHECK (rate > 0),
+                UNIQUE (from_currency, to_currency, rate_date)
+            );
+            CREATE INDEX idx_exchange_rates_to ON exchange_rates(to_currency, rate_date);
+            ALTER TABLE transactions ADD COLUMN transfer_id INTEGER REFERENCES transactions(id) ON DELETE SET NULL;
+        "#,
+    },
+    // Securities, trades in investment accounts and daily prices. Quantities are
+    // integers in hundred-millionths of a share; split_from/split_to hold a split's ratio
+    Migration {
+        version: 17,
+        name: "017_add_investments",
+        sql: r#"
+            CREATE TABLE securities (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ticker TEXT NOT NULL UNIQUE,
+                name TEXT NOT NULL,
+                security_type TEXT NOT NULL,
+                currency TEXT NOT NULL DEFAULT 'USD',
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE TABLE security_prices (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                security_id INTEGER NOT NULL REFERENCES securities(id),
+                price_date TEXT NOT NULL,
+                price REAL NOT NULL CHECK (price > 0),
+                UNIQUE (security_id, price_date)
+            );
+            CREATE TABLE investment_transactions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id INTEGER NOT NULL REFERENCES accounts(id),
+                security_id INTEGER NOT NULL REFERENCES securities(id),
+                action TEXT NOT NULL,
+                trade_date TEXT NOT NULL,
+                quantity_units INTEGER NOT NULL DEFAULT 0,
+                amount_cents INTEGER NOT NULL DEFAULT 0,
+                fee_cents INTEGER NOT NULL DEFAULT 0,
+                split_from INTEGER,
+                split_to INTEGER,
+                transaction_id INTEGER REFERENCES transactions(id) ON DELETE SET NULL,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE INDEX idx_investment_transactions_account ON investment_transactions(account_id, security_id, trade_date);
+        "#,
+    },
+    // How an account picks the lots a sale takes shares from, and the lots chosen
+    // for sales with specific identification
+    Migration {
+        version: 18,
+        name: "018_add_cost_basis_methods",
+        sql: r#"
+            ALTER TABLE accounts ADD COLUMN cost_basis_method TEXT NOT NULL DEFAULT 'fifo';
+            CREATE TABLE lot_selections (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sale_id INTEGER NOT NULL REFERENCES investment_transactions(id),
+                lot_id INTEGER NOT NULL REFERENCES investment_transactions(id),
+                quantity_units INTEGER NOT NULL CHECK (quantity_units > 0),
+                UNIQUE (sale_id, lot_id)
+            );
+        "#,
+    },
+    // Random, stable identifier naming the ledger's backup folder and attachment
+    // store, so same-named ledger files in different folders never share them
+    Migration {
+        version: 19,
+        name: "019_add_ledger_id",
+        sql: r#"
+            ALTER TABLE ledger_settings ADD COLUMN ledger_id TEXT;
+            UPDATE ledger_settings SET ledger_id = lower(hex(randomblob(16)));
+        "#,
+    },
+    // Cost basis method each sale was recorded with, so changing an account's
+    // method never rewrites gains already realized; existing sales keep the
+    // account's current method
+    Migration {
+        version: 20,
+        name: "020_stamp_sale_cost_basis_methods",
+        sql: r#"
+            ALTER TABLE investment_transactions ADD COLUMN cost_basis_method TEXT;
+            UPDATE investment_transactions
+                SET cost_basis_method = (SELECT cost_basis_method FROM accounts WHERE accounts.id = investment_transactions.account_id)
+                WHERE action = 'sell';
+        "#,
+    },
+    // The audit actor is kept per connection by the app instance that opened it,
+    // not in the file, where instances sharing the ledger overwrote each other's
+    Migration {
+        version: 21,
+        name: "021_drop_shared_audit_actor",
+        sql: r#"
+            DROP TABLE audit_actor;
+        "#,
+    },
+    // A split applies to a security in every account, so it is recorded once per
+    // security and date; copies recorded in each account holding it are dropped
+    Migration {
+        version: 22,
+        name: "022_one_split_per_security",
+        sql: r#"
+            DELETE FROM investment_transactions
+                WHERE action = 'split'
+                  AND id NOT IN (SELECT MIN(id) FROM investment_transactions WHERE action = 'split' GROUP BY security_id, trade_date);
+            CREATE UNIQUE INDEX idx_investment_transactions_split ON investment_transactions(security_id, trade_date) WHERE action = 'split';
+        "#,
+    },
+];
 
 /// Executes all pending database migrations in sequential order.
 ///
-/// Checks the migrations table to determine which migrations have already been
-/// applied, then runs any missing migrations from the registry. Each migration
-/// is executed exactly once and recorded in the migrations table to prevent
-/// duplicate execution on future application starts.
-///
-/// # Migration Registry
-///
-/// All available migrations are defined by name in the `migration_registry` vector.
-/// Each migration is executed via match statement to avoid Rust function pointer
-/// type complications. New migrations should be added to both the registry and match arms.
+/// Verifies the checksums of already applied migrations, then runs every
+/// migration from [`MIGRATIONS`] that has not been applied yet. A migration, its
+/// tracking row and the `user_version` bump are written in the same unit of
+/// work, so a failing migration leaves neither a partial schema change nor a
+/// bogus "applied" record.
 ///
 /// # Arguments
 /// * `pool` - SQLite connection pool for executing migrations and tracking
 ///
 /// # Returns
 /// * `Ok(())` - All pending migrations completed successfully
-/// * `Err(sqlx::Error)` - Migration execution or tracking failure
+/// * `Err(sqlx::Error)` - Verification, migration execution or tracking failure
 ///
 /// # Errors
 /// Fails if:
-/// - Cannot query existing migrations from database
-/// - Migration function execution fails (SQL errors, schema conflicts)
-/// - Cannot record migration completion in migrations table
+/// - An applied migration's SQL has changed since it ran (checksum mismatch)
+/// - The database records a migration this build does not know (newer app version)
+/// - Migration SQL fails (syntax errors, schema conflicts)
 /// - Database connection issues during migration process
 ///
 /// # Examples
 /// ```no_run
 /// // Called automatically during database initialization
-/// let pool = init_db().await?;
+/// let pool = init_db(&path, None).await?;
 /// // Migrations have already been applied
 /// ```
-///
-/// # Adding Migrations
-/// ```rust
-/// let migration_registry = vec![
-///     "001_add_archived_column",
-///     "002_new_migration", // <- Add here
-/// ];
-///
-/// // And add to match statement:
-/// match name {
-///     "001_add_archived_column" => migration_001_add_archived_column(pool).await?,
-///     "002_new_migration" => migration_002_new_migration(pool).await?, // <- And here
-///     _ => panic!("Unknown migration: {}", name),
-/// }
-/// ```
 pub async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-    let applied = get_applied_migrations(pool).await?;
+    apply_migrations(pool, MIGRATIONS).await?;
 
-    let migration_registry = vec!["001_add_archived_column", "002_convert_amounts_to_signed_values"];
+    // Derived from the migrated schema, so regenerated on every start
+    super::journal::install_triggers(pool).await?;
+    super::audit::install_triggers(pool).await
+}
 
-    for name in migration_registry {
-        if !applied.contains(&name.to_string()) {
-            match name {
-                "001_add_archived_column" => migration_001_add_archived_column(pool).await?,
-                "002_convert_amounts_to_signed_values" => migration_002_convert_amounts_to_signed_values(pool).await?,
-                _ => panic!("Unknown migration: {}", name),
-            }
-            record_migration(pool, name).await?;
+/// Returns the schema version of the database (`PRAGMA user_version`).
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool for the database to inspect
+///
+/// # Returns
+/// * `Ok(i64)` - Version of the newest applied migration
+/// * `Err(sqlx::Error)` - Database query failure
+pub async fn schema_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query("PRAGMA user_version").fetch_one(pool).await?;
+
+    Ok(row.get::<i64, _>(0))
+}
+
+async fn apply_migrations(pool: &SqlitePool, registry: &[Migration]) -> Result<(), sqlx::Error> {
+    ensure_migrations_table(pool).await?;
+
+    let applied = get_applied_migrations(pool).await?;
+    verify_applied_migrations(pool, registry, &applied).await?;
+
+    for migration in registry {
+        if applied.iter().any(|(name, _)| name == migration.name) {
+            continue;
         }
+
+        unit_of_work(pool, async |conn| {
+            sqlx::query(migration.sql).execute(&mut *conn).await?;
+            record_migration(conn, migration).await?;
+
+            // PRAGMA does not accept bound parameters; the version is a trusted constant
+            sqlx::query(&format!("PRAGMA user_version = {}", migration.version))
+                .execute(&mut *conn)
+                .await?;
+
+            Ok(())
+        })
+        .await?;
     }
 
     Ok(())
 }
 
-/// Retrieves the names of all migrations that have been applied to the database.
+/// Creates the migrations tracking table and upgrades it to hold checksums.
 ///
-/// Queries the migrations table and returns a list of migration names that have
-/// already been executed. This list is used by the migration runner to determine
-/// which migrations still need to be applied to bring the database schema up to date.
+/// Databases created before checksums were introduced have a `migrations` table
+/// without a `checksum` column; it is added in place so their history is kept.
 ///
 /// # Arguments
-/// * `pool` - SQLite connection pool for querying the migrations table
+/// * `pool` - SQLite connection pool for the database being migrated
 ///
 /// # Returns
-/// * `Ok(Vec<String>)` - List of migration names that have been applied
-/// * `Err(sqlx::Error)` - Database query or data extraction failure
+/// * `Ok(())` - Tracking table exists with the current layout
+/// * `Err(sqlx::Error)` - Schema inspection or modification failure
+async fn ensure_migrations_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS migrations(
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            migration_name TEXT NOT NULL UNIQUE,
+            applied_at TEXT DEFAULT (datetime('now')),
+            checksum TEXT
+        )"#,
+    )
+    .execute(pool)
+    .await?;
+
+    let columns = sqlx::query("SELECT name FROM pragma_table_info('migrations')")
+        .fetch_all(pool)
+        .await?;
+    if !columns
+        .iter()
+        .any(|row| row.get::<String, _>("name") == "checksum")
+    {
+        sqlx::query("ALTER TABLE migrations ADD COLUMN checksum TEXT")
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Retrieves the names and checksums of all migrations applied to the database.
 ///
-/// # Errors
-/// Fails if:
-/// - Cannot connect to database or query migrations table
-/// - Migrations table doesn't exist (should be created by `create_tables()`)
-/// - Row data extraction fails due to schema changes
-/// - Database file corruption or permission issues
+/// # Arguments
+/// * `pool` - SQLite connection pool for querying the migrations table
 ///
-/// # Examples
-/// ```no_run
-/// let applied = get_applied_migrations(&pool).await?;
-/// println!("Applied migrations: {:?}", applied);
-/// // Output: ["001_add_archived_column", "002_add_user_field"]
-/// ```
-async fn get_applied_migrations(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
-    let rows = sqlx::query("SELECT migration_name FROM migrations")
+/// # Returns
+/// * `Ok(Vec<(String, Option<String>)>)` - Applied migration names with their
+///   recorded checksum (`None` for rows written before checksums existed)
+/// * `Err(sqlx::Error)` - Database query or data extraction failure
+async fn get_applied_migrations(
+    pool: &SqlitePool,
+) -> Result<Vec<(String, Option<String>)>, sqlx::Error> {
+    let rows = sqlx::query("SELECT migration_name, checksum FROM migrations")
         .fetch_all(pool)
         .await?;
 
-    let result: Vec<String> = rows
+    let result = rows
         .into_iter()
-        .map(|row| row.get::<String, _>("migration_name"))
+        .map(|row| {
+            (
+                row.get::<String, _>("migration_name"),
+                row.get::<Option<String>, _>("checksum"),
+            )
+        })
         .collect();
 
     Ok(result)
 }
 
-/// Records a successfully applied migration in the migrations table.
+/// Checks applied migrations against the registry before anything new runs.
 ///
-/// Inserts the migration name into the migrations table with an automatic timestamp
-/// to track when it was applied. This prevents the migration from being executed
-/// again on future application starts and provides an audit trail of schema changes.
+/// Rows recorded before checksums existed are adopted by storing the current
+/// checksum. Any other mismatch, or an applied migration missing from the
+/// registry, aborts startup rather than running against an unknown schema.
 ///
 /// # Arguments
-/// * `pool` - SQLite connection pool for inserting the migration record
-/// * `migration_name` - Name of the migration that was successfully applied
+/// * `pool` - SQLite connection pool for the database being migrated
+/// * `registry` - Migrations known to this build
+/// * `applied` - Names and checksums read from the migrations table
 ///
 /// # Returns
-/// * `Ok(())` - Migration recorded successfully in the database
-/// * `Err(sqlx::Error)` - Database insertion or connection failure
-///
-/// # Errors
-/// Fails if:
-/// - Cannot connect to database or access migrations table
-/// - Migration name violates database constraints (duplicate entries)
-/// - Database insertion fails due to permissions or disk space
-/// - Connection pool exhaustion or database file locks
-///
-/// # Examples
-/// ```no_run
-/// // Called automatically after successful migration execution
-/// migration_001_add_archived_column(&pool).await?;
-/// record_migration(&pool, "001_add_archived_column").await?;
-/// ```
-///
-/// # Database Record
-/// Creates a record with migration name and automatic timestamp:
-/// ```sql
-/// INSERT INTO migrations (migration_name) VALUES ('001_add_archived_column')
-/// -- Applied_at timestamp set automatically by database
-/// ```
-async fn record_migration(pool: &SqlitePool, migration_name: &str) -> Result<(), sqlx::Error> {
-    sqlx::query("INSERT INTO migrations (migration_name) VALUES (?)")
-        .bind(migration_name)
-        .execute(pool)
-        .await?;
+/// * `Ok(())` - Every applied migration matches the registry
+/// * `Err(sqlx::Error)` - Checksum mismatch, unknown migration or database failure
+async fn verify_applied_migrations(
+    pool: &SqlitePool,
+    registry: &[Migration],
+    applied: &[(String, Option<String>)],
+) -> Result<(), sqlx::Error> {
+    for (name, recorded) in applied {
+        let Some(migration) = registry.iter().find(|m| m.name == name) else {
+            return Err(sqlx::Error::Protocol(format!(
+                "database has migration {} applied, which this version of finsight does not know",
+                name
+            )));
+        };
+
+        let checksum = migration.checksum();
+        match recorded {
+            Some(recorded) if *recorded != checksum => {
+                return Err(sqlx::Error::Protocol(format!(
+                    "migration {} has changed since it was applied (checksum {} != {})",
+                    name, recorded, checksum
+                )));
+            }
+            Some(_) => {}
+            None => {
+                sqlx::query("UPDATE migrations SET checksum = ? WHERE migration_name = ?")
+                    .bind(&checksum)
+                    .bind(name)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+    }
 
     Ok(())
 }
 
-/// Adds an archived column to the accounts table for soft deletion functionality.
-///
-/// This migration introduces account archiving as an alternative to hard deletion,
-/// preserving historical financial data while allowing accounts to be hidden from
-/// active use. All existing accounts are automatically set to not archived (FALSE)
-/// when the column is added.
+/// Records a successfully applied migration in the migrations table.
 ///
-/// # Schema Changes
-/// - Adds `archived BOOLEAN NOT NULL DEFAULT FALSE` column to accounts table
-/// - Existing accounts receive archived = FALSE automatically
-/// - New accounts default to archived = FALSE unless explicitly set
+/// Inserts the migration name and checksum with an automatic timestamp to track
+/// when it was applied. This prevents the migration from being executed again on
+/// future application starts and provides an audit trail of schema changes.
 ///
 /// # Arguments
-/// * `pool` - SQLite connection pool for executing the schema change
+/// * `conn` - Connection of the unit of work that applied the migration
+/// * `migration` - The migration that was just applied
 ///
 /// # Returns
-/// * `Ok(())` - Column added successfully to accounts table
-/// * `Err(sqlx::Error)` - Schema modification or database access failure
-///
-/// # Errors
-/// Fails if:
-/// - Accounts table doesn't exist (should be created by `create_tables()`)
-/// - Column already exists (migration previously applied)
-/// - Database schema modification permissions denied
-/// - Insufficient disk space for table restructuring
-///
-/// # Impact on Existing Data
-/// - All current account records get archived = FALSE
-/// - No data loss or corruption - purely additive change
-/// - Maintains backward compatibility with existing account queries
-///
-/// # Usage After Migration
-/// ```sql
-/// -- Hide account instead of deleting
-/// UPDATE accounts SET archived = TRUE WHERE id = 123;
-///
-/// -- Query only active accounts
-/// SELECT * FROM accounts WHERE archived = FALSE;
-/// ```
-async fn migration_001_add_archived_column(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-    sqlx::query("ALTER TABLE accounts ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE")
-        .execute(pool)
+/// * `Ok(())` - Migration recorded successfully in the database
+/// * `Err(sqlx::Error)` - Database insertion or connection failure
+async fn record_migration(
+    conn: &mut SqliteConnection,
+    migration: &Migration,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO migrations (migration_name, checksum) VALUES (?, ?)")
+        .bind(migration.name)
+        .bind(migration.checksum())
+        .execute(conn)
         .await?;
 
     Ok(())
 }
 
-async fn migration_002_convert_amounts_to_signed_values(
-    pool: &SqlitePool,
-) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        "UPDATE transactions SET amount_cents = -amount_cents WHERE transaction_type = 'debit'",
-    )
-    .execute(pool)
-    .await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(())
+    #[tokio::test]
+    async fn test_run_migrations_records_versions_and_checksums() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+
+        run_migrations(&pool).await.unwrap();
+        run_migrations(&pool).await.unwrap(); // Second run is a no-op
+
+        let recorded = get_applied_migrations(&pool).await.unwrap();
+        assert_eq!(recorded.len(), MIGRATIONS.len());
+        assert!(recorded.iter().all(|(_, checksum)| checksum.is_some()));
+        assert_eq!(
+            schema_version(&pool).await.unwrap(),
+            MIGRATIONS.last().unwrap().version
+        );
+    }
+
+    #[tokio::test]
+    async fn test_changed_migration_fails_loudly() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        sqlx::query("UPDATE migrations SET checksum = 'tampered' WHERE migration_name = '001_add_archived_column'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(run_migrations(&pool).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_rolls_back() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+
+        const REGISTRY: &[Migration] = &[Migration {
+            version: 1,
+            name: "001_broken",
+            sql: "CREATE TABLE half_done (id INTEGER); INSERT INTO missing_table VALUES (1);",
+        }];
+        assert!(apply_migrations(&pool, REGISTRY).await.is_err());
+
+        let tables = sqlx::query("SELECT name FROM sqlite_master WHERE name = 'half_done'")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(tables.is_empty());
+        assert!(get_applied_migrations(&pool).await.unwrap().is_empty());
+        assert_eq!(schema_version(&pool).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_rows_without_checksum_are_adopted() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+
+        // Tracking table as created before checksums existed
+        sqlx::query(
+            r#"CREATE TABLE migrations(
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                migration_name TEXT NOT NULL UNIQUE,
+                applied_at TEXT DEFAULT (datetime('now'))
+            )"#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::raw_sql(MIGRATIONS[0].sql).execute(&pool).await.unwrap();
+        sqlx::raw_sql(MIGRATIONS[1].sql).execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO migrations (migration_name) VALUES ('001_add_archived_column')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        run_migrations(&pool).await.unwrap();
+
+        let recorded = get_applied_migrations(&pool).await.unwrap();
+        assert_eq!(recorded.len(), MIGRATIONS.len());
+        assert!(recorded.iter().all(|(_, checksum)| checksum.is_some()));
+    }
 }
@@ -6,36 +6,105 @@
 //!
 //! # Architecture
 //!
-//! - **Connection Management**: SQLite connection pool with automatic file creation
-//! - **Schema Management**: Automatic table creation with proper foreign key constraints
-//! - **Data Types**: Integer cents for precise financial calculations, ISO 8601 dates
+//! - **Connection Management**: SQLite connection pool with automatic file creation, enforced
+//!   foreign keys and WAL journaling
+//! - **Schema Management**: Versioned, checksummed migrations starting from a baseline schema
+//! - **Data Types**: [`Money`] (integer minor units tagged with a currency) for precise
+//!   financial calculations, ISO 8601 dates
 //! - **Error Handling**: All functions return `Result<T, sqlx::Error>` for proper error propagation
+//! - **Atomicity**: Multi-statement operations run through [`unit_of_work()`] so they
+//!   either fully apply or fully roll back
 //!
 //! # Entity Operations
 //!
 //! ## Accounts
-//! - [`get_all_accounts()`] - Retrieve all financial accounts
+//! - [`get_all_accounts()`] - Retrieve active, archived or all financial accounts in sidebar order
+//! - [`close_account()`] - Archive an account once its balance is settled or transferred
 //! - [`add_account()`] - Create new account records
 //!
 //! ## Transactions  
 //! - [`get_transactions()`] - Query transactions for specific accounts
 //! - [`add_transaction()`] - Create new transaction records with debit/credit types
+//! - [`search_transactions()`] - Ranked full-text search combined with structured filters
+//! - [`add_transfer()`] - Move money between accounts, also across currencies
+//!
+//! ## Currencies and Reports
+//! - [`Money::parse()`] / [`Money::format()`] - Read and display amounts such as "1.234,56 €" per locale
+//! - [`import_exchange_rates()`] - Load dated exchange rates from a CSV history such as the ECB's
+//! - [`set_base_currency()`] - Choose the currency reports are converted into
+//! - [`get_net_worth()`] / [`get_income_statement()`] - Balances, income and expenses in the base currency
+//!
+//! ## Investments
+//! - [`add_investment_transaction()`] - Record buys, sells, dividends and splits of a security
+//! - [`import_security_prices()`] - Load daily closing prices from a CSV download
+//! - [`get_holdings()`] - Quantity, cost basis, market value and unrealized gain per position
+//! - [`set_cost_basis_method()`] - Pick lots by FIFO, LIFO, specific identification or average cost
+//! - [`get_realized_gains()`] / [`export_realized_gains_csv()`] - Short- and long-term gains per tax year
+//!
+//! ## Payees
+//! - [`normalize_description()`] - Clean up bank descriptions before linking a payee
+//! - [`get_top_payees()`] - Spending per payee across all accounts
+//!
+//! ## Attachments
+//! - [`attachments::attach_file()`] - Keep a receipt or document with a transaction
+//! - [`attachments::collect_garbage()`] - Remove stored files nothing refers to any more
+//!
+//! ## Custom Fields
+//! - [`add_custom_field()`] - Define a typed field such as "Reimbursable" for transactions or accounts
+//! - [`delete_custom_field()`] - Remove a field together with its values
+//!
+//! ## Tags
+//! - [`bulk_tag_transactions()`] / [`bulk_untag_transactions()`] - Label transactions by trip or project
+//! - [`get_tag_spending()`] - Spending per tag across all accounts and categories
+//!
+//! ## History
+//! - [`undo()`] / [`redo()`] - Revert or re-apply the latest operation from the change journal
+//! - [`get_record_history()`] / [`get_activity_feed()`] - Permanent audit trail with actor labels
+//! - [`get_trash()`] / [`purge_trash()`] - Deleted transactions and categories awaiting restore or purge
 //!
 //! # Database Schema
 //!
 //! The database uses a simple relational model with accounts containing multiple
-//! transactions. All monetary values are stored as integer cents to avoid
-//! floating-point precision issues common in financial applications.
+//! transactions. All monetary values are stored as integer cents (minor units
+//! of the account's currency) to avoid floating-point precision issues common
+//! in financial applications.
 
-use sqlx::{Pool, Sqlite, SqlitePool, sqlite::SqliteConnectOptions};
-use std::str::FromStr;
+use sqlx::{
+    Pool, Sqlite, SqliteConnection, SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+};
+use std::path::Path;
+use std::time::Duration;
 
 mod accounts;
+pub mod attachments;
+mod audit;
+pub mod backups;
+mod bulk;
+mod capital_gains;
 mod categories;
+mod currencies;
+mod custom_fields;
+pub mod encryption;
+mod investments;
+mod journal;
+mod ledgers;
+pub mod location;
 mod migrations;
+mod money;
+mod payees;
+pub mod portable;
+mod reports;
+mod search;
+mod tags;
 mod transactions;
+mod trash;
 
-pub use {accounts::*, categories::*, transactions::*};
+pub use {
+    accounts::*, audit::*, bulk::*, capital_gains::*, categories::*, currencies::*, custom_fields::*, investments::*,
+    journal::{get_undo_history, redo, undo}, ledgers::LedgerManager, money::*, payees::*, reports::*, search::*, tags::*,
+    transactions::*, trash::*,
+};
 
 /// Initializes the SQLite database connection pool for the application.
 ///
@@ -46,8 +115,19 @@ pub use {accounts::*, categories::*, transactions::*};
 ///
 /// # Database Location
 ///
-/// Creates `finsight.db` in the current working directory. For desktop applications,
-/// this is typically the application's executable directory.
+/// Opens the file at `path`, creating it if missing. The application resolves this
+/// path through [`location::resolve_database_path()`], which defaults to the OS
+/// app data directory rather than the working directory.
+///
+/// # Encryption
+///
+/// When `passphrase` is given, it is sent as `PRAGMA key` before anything else
+/// on every connection, unlocking (or, for a new file, encrypting) the ledger.
+/// This requires a build with the `encryption` feature.
+///
+/// # Arguments
+/// * `path` - Filesystem path of the SQLite database file
+/// * `passphrase` - Passphrase of an encrypted ledger, or `None` for a plain file
 ///
 /// # Returns
 /// * `Ok(SqlitePool)` - Connection pool ready for database operations
@@ -59,6 +139,7 @@ pub use {accounts::*, categories::*, transactions::*};
 /// - SQLite connection fails (file corruption, unsupported version)
 /// - Table schema creation fails (SQL syntax errors, constraint violations)
 /// - Connection pool setup fails (system resource limits)
+/// - The passphrase is wrong ("file is not a database") or encryption is unsupported
 ///
 /// # Examples
 /// ```no_run
@@ -66,19 +147,24 @@ pub use {accounts::*, categories::*, transactions::*};
 ///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
-///     let db_pool = database::init_db().await?;
+///     let db_pool = database::init_db(Path::new("/home/me/.local/share/com.finsight.app/finsight.db"), None).await?;
 ///     
 ///     // Pool is now ready for all database operations
-///     let accounts = database::get_all_accounts(&db_pool).await?;
+///     let accounts = database::get_all_accounts(&db_pool, database::AccountStatus::All).await?;
 ///     Ok(())
 /// }
 /// ```
-pub async fn init_db() -> Result<Pool<Sqlite>, sqlx::Error> {
-    let options = SqliteConnectOptions::from_str("sqlite:./finsight.db")?.create_if_missing(true);
+pub async fn init_db(path: &Path, passphrase: Option<&str>) -> Result<Pool<Sqlite>, sqlx::Error> {
+    let mut options = SqliteConnectOptions::new().filename(path);
+    if let Some(passphrase) = passphrase {
+        options = options.pragma("key", encryption::quote_key(passphrase));
+    }
 
-    let pool = SqlitePool::connect_with(options).await?;
+    let pool = pool_options().connect_with(connect_options(options)).await?;
 
-    create_tables(&pool).await?;
+    if passphrase.is_some() {
+        encryption::ensure_supported(&pool).await?;
+    }
 
     migrations::run_migrations(&pool).await?;
 
@@ -87,94 +173,91 @@ pub async fn init_db() -> Result<Pool<Sqlite>, sqlx::Error> {
     Ok(pool)
 }
 
-/// Creates all required database tables with proper schema if they don't exist.
+/// Builds the connection options applied to every application database connection.
 ///
-/// Executes CREATE TABLE IF NOT EXISTS statements for the complete database schema.
-/// Currently creates accounts and transactions tables with proper foreign key
-/// relationships and constraints for financial data integrity.
+/// SQLite leaves most integrity and concurrency features off by default, and
+/// they are per-connection settings, so they must be set on every connection
+/// the pool opens:
 ///
-/// # Schema Created
-/// - **accounts**: Financial account records with name, type, and timestamps
-/// - **transactions**: Transaction records linked to accounts with amount, type, and dates
+/// - **Foreign keys**: Enforces the `REFERENCES` clauses on transactions and categories
+/// - **WAL journaling**: Lets readers proceed while a write is in progress
+/// - **Synchronous NORMAL**: Durable at checkpoints, which is safe under WAL and much faster than FULL
+/// - **Busy timeout**: Waits up to five seconds for a lock instead of failing immediately
 ///
 /// # Arguments
-/// * `pool` - SQLite connection pool reference for executing table creation queries
+/// * `options` - Base options identifying the database (file name or in-memory URL)
 ///
 /// # Returns
-/// * `Ok(())` - All tables created or verified to exist
-/// * `Err(sqlx::Error)` - Table creation or schema validation failure
+/// * `SqliteConnectOptions` - Options ready for `SqlitePool::connect_with`
+fn connect_options(options: SqliteConnectOptions) -> SqliteConnectOptions {
+    options
+        .create_if_missing(true)
+        .foreign_keys(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(Duration::from_secs(5))
+}
+
+/// Builds the pool options of application database pools.
 ///
-/// # Errors
-/// Fails if:
-/// - SQL schema syntax is invalid (programming error)
-/// - Database file is read-only or locked by another process
-/// - Insufficient disk space for table metadata
-/// - Database corruption prevents schema operations
-/// - Connection pool is exhausted or disconnected
+/// Every connection the pool opens or hands out records this app instance's
+/// audit actor, see [`audit::attach_actor()`].
+pub(crate) fn pool_options() -> SqlitePoolOptions {
+    SqlitePoolOptions::new()
+        .after_connect(|conn, _| Box::pin(audit::attach_actor(conn)))
+        .before_acquire(|conn, _| Box::pin(async move { audit::attach_actor(conn).await.map(|()| true) }))
+}
+
+/// Runs a multi-statement database operation as a single SQL transaction.
+///
+/// Begins a transaction on the pool, hands its connection to `work`, and commits
+/// only when `work` succeeds. Any error returned by `work` rolls back every
+/// statement it executed, so callers never observe a half-applied change such as
+/// a category deleted while its transactions still point at it.
+///
+/// All operations that issue more than one write (category deletes and merges,
+/// migrations together with their tracking row, imports) should go through this
+/// helper rather than executing against the pool directly.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool to begin the transaction on
+/// * `work` - Async closure receiving the transaction's connection
+///
+/// # Returns
+/// * `Ok(T)` - Value produced by `work` after a successful commit
+/// * `Err(sqlx::Error)` - Error from `work` (after rollback) or from begin/commit
 ///
 /// # Examples
 /// ```no_run
-/// use sqlx::SqlitePool;
-///
-/// let pool = SqlitePool::connect("sqlite:test.db").await?;
-/// create_tables(&pool).await?;
-/// // Database now has accounts and transactions tables ready
+/// let moved = unit_of_work(&pool, async |conn| {
+///     sqlx::query("UPDATE transactions SET category_id = 1 WHERE category_id = ?")
+///         .bind(5)
+///         .execute(&mut *conn)
+///         .await?;
+///     sqlx::query("DELETE FROM categories WHERE id = ?")
+///         .bind(5)
+///         .execute(&mut *conn)
+///         .await?;
+///     Ok(())
+/// })
+/// .await?;
 /// ```
-async fn create_tables(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        r#"CREATE TABLE IF NOT EXISTS migrations(
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            migration_name TEXT NOT NULL UNIQUE,
-            applied_at TEXT DEFAULT (datetime('now'))
-        )"#,
-    )
-    .execute(pool)
-    .await?;
+pub async fn unit_of_work<T, F>(pool: &SqlitePool, work: F) -> Result<T, sqlx::Error>
+where
+    F: AsyncFnOnce(&mut SqliteConnection) -> Result<T, sqlx::Error>,
+{
+    let mut tx = pool.begin().await?;
 
-    sqlx::query(
-        r#"
-            CREATE TABLE IF NOT EXISTS accounts (
-                id INTEGER PRIMARY KEY AUTOINCREMENT, 
-                name TEXT NOT NULL, 
-                account_type TEXT NOT NULL, 
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )"#,
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-            CREATE TABLE IF NOT EXISTS categories (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL UNIQUE,
-                parent_id INTEGER,
-                created_at TEXT DEFAULT (datetime('now')),
-                FOREIGN KEY (parent_id) REFERENCES categories(id)
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-            CREATE TABLE IF NOT EXISTS transactions (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                account_id INTEGER NOT NULL REFERENCES accounts(id),
-                amount_cents INTEGER NOT NULL,
-                transaction_type TEXT NOT NULL,
-                description TEXT NOT NULL,
-                transaction_date TEXT NOT NULL,
-                category_id INTEGER NOT NULL REFERENCES categories(id),
-                created_at TEXT DEFAULT (datetime('now'))
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    Ok(())
+    match work(&mut *tx).await {
+        Ok(value) => {
+            tx.commit().await?;
+            Ok(value)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
 }
 
 /// Seeds essential system data required for application functionality.
@@ -208,7 +291,6 @@ mod tests {
     async fn test_init_db_creates_tables() {
         let pool = SqlitePool::connect(":memory:").await.unwrap();
 
-        create_tables(&pool).await.unwrap();
         migrations::run_migrations(&pool).await.unwrap();
         seed_system_data(&pool).await.unwrap();
 
@@ -224,4 +306,107 @@ mod tests {
             .unwrap();
         assert_eq!(categories.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_unit_of_work_rolls_back_on_error() {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        migrations::run_migrations(&pool).await.unwrap();
+        seed_system_data(&pool).await.unwrap();
+
+        let result: Result<(), sqlx::Error> = unit_of_work(&pool, async |conn| {
+            sqlx::query("INSERT INTO categories (name) VALUES ('Groceries')")
+                .execute(&mut *conn)
+                .await?;
+            Err(sqlx::Error::RowNotFound)
+        })
+        .await;
+        assert!(result.is_err());
+
+        let categories = get_all_categories(&pool).await.unwrap();
+        assert_eq!(categories.len(), 1); // Only 'Uncategorized' remains
+    }
+
+    #[tokio::test]
+    async fn test_connect_options_enforce_foreign_keys() {
+        let options = connect_options("sqlite::memory:".parse().unwrap());
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        migrations::run_migrations(&pool).await.unwrap();
+        seed_system_data(&pool).await.unwrap();
+
+        // Account 99 does not exist, so the REFERENCES clause must reject the row
+        let result = sqlx::query(
+            "INSERT INTO transactions (account_id, amount_cents, transaction_type, description, transaction_date, category_id)
+             VALUES (99, -1000, 'debit', 'Orphan', '2025-01-01', 1)",
+        )
+        .execute(&pool)
+        .await;
+        assert!(result.is_err());
+    }
+
+    /// Seeds 500k transactions and checks that account queries stay responsive.
+    ///
+    /// Slow to set up, so it only runs on request, in an optimized build:
+    /// `cargo test --release -- --ignored`.
+    #[tokio::test]
+    #[ignore]
+    async fn bench_transaction_queries_on_large_ledger() {
+        let options = connect_options("sqlite::memory:".parse().unwrap());
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        migrations::run_migrations(&pool).await.unwrap();
+        seed_system_data(&pool).await.unwrap();
+
+        for i in 1..=50 {
+            add_account(&pool, format!("Account {}", i), "checking".to_string())
+                .await
+                .unwrap();
+        }
+
+        sqlx::query(
+            r#"
+            WITH RECURSIVE seq(n) AS (
+                SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 500000
+            )
+            INSERT INTO transactions (
+                account_id, amount_cents, transaction_type, description, transaction_date, category_id
+            )
+            SELECT
+                (n % 50) + 1,
+                CASE WHEN n % 4 = 0 THEN 250000 ELSE -(n % 10000) END,
+                CASE WHEN n % 4 = 0 THEN 'credit' ELSE 'debit' END,
+                'Seeded transaction ' || n,
+                date('2015-01-01', '+' || (n % 3650) || ' days'),
+                1
+            FROM seq
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let start = std::time::Instant::now();
+        let transactions = get_transactions(&pool, 7).await.unwrap();
+        let transactions_elapsed = start.elapsed();
+        assert_eq!(transactions.len(), 10000);
+
+        let start = std::time::Instant::now();
+        get_balance(&pool, 7).await.unwrap();
+        let balance_elapsed = start.elapsed();
+
+        let mut query = TransactionQuery {
+            filter: TransactionFilter {
+                account_id: Some(7),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let page = query_transactions(&pool, &query).await.unwrap();
+        query.after = page["next_cursor"].as_str().map(str::to_string);
+        query_transactions(&pool, &query).await.unwrap();
+        let page_elapsed = start.elapsed() / 2;
+
+        assert!(transactions_elapsed < Duration::from_millis(500), "get_transactions took {:?}", transactions_elapsed);
+        assert!(balance_elapsed < Duration::from_millis(250), "get_balance took {:?}", balance_elapsed);
+        assert!(page_elapsed < Duration::from_millis(100), "a query_transactions page took {:?}", page_elapsed);
+    }
 }
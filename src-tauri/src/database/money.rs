@@ -0,0 +1,653 @@
+//! Money amounts tagged with their currency.
+//!
+//! Amounts are stored as signed integers in the minor unit of their currency
+//! (the `amount_cents` columns): credits are positive and debits negative, so
+//! a balance is the opening balance plus the plain sum of an account's
+//! amounts. [`Money`] keeps that integer together with its [`Currency`] so the
+//! two cannot drift apart, and its arithmetic is checked: mixing currencies or
+//! leaving the `i64` range is an error rather than a wrapped or mixed total.
+//!
+//! Amounts are parsed from and formatted for a [`Locale`], e.g. "1.234,56 €"
+//! in German or "($42.75)" in US accounting notation.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Errors from constructing, combining, parsing or converting amounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    /// Not a three-letter ISO 4217 code
+    InvalidCurrency(String),
+    /// Arithmetic between amounts in different currencies
+    CurrencyMismatch(Currency, Currency),
+    /// Result outside the range of `i64` minor units
+    Overflow,
+    /// Text that cannot be read as an amount, with the reason
+    InvalidAmount(String),
+    /// Locale tag without formatting rules
+    UnsupportedLocale(String),
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::InvalidCurrency(code) => write!(
+                f,
+                "invalid currency code {:?}; use a three-letter ISO code such as EUR",
+                code
+            ),
+            MoneyError::CurrencyMismatch(left, right) => {
+                write!(f, "cannot combine amounts in {} and {}", left, right)
+            }
+            MoneyError::Overflow => write!(f, "amount is too large"),
+            MoneyError::InvalidAmount(reason) => write!(f, "{}", reason),
+            MoneyError::UnsupportedLocale(tag) => write!(
+                f,
+                "unsupported locale {:?}; use one of en-US, en-GB, de-DE, de-CH or fr-FR",
+                tag
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+impl From<MoneyError> for sqlx::Error {
+    fn from(error: MoneyError) -> Self {
+        sqlx::Error::Protocol(error.to_string())
+    }
+}
+
+/// Currencies without a minor unit; amounts are whole units.
+pub(crate) const WHOLE_UNIT_CURRENCIES: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "ISK", "JPY", "KMF", "KRW", "PYG", "RWF", "UGX", "UYI", "VND", "VUV", "XAF", "XOF",
+    "XPF",
+];
+
+/// Currencies whose minor unit is a thousandth.
+pub(crate) const THOUSANDTHS_CURRENCIES: &[&str] = &["BHD", "IQD", "JOD", "KWD", "LYD", "OMR", "TND"];
+
+/// An ISO 4217 currency code such as `EUR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const USD: Currency = Currency(*b"USD");
+    pub const EUR: Currency = Currency(*b"EUR");
+    pub const GBP: Currency = Currency(*b"GBP");
+    pub const CHF: Currency = Currency(*b"CHF");
+    pub const JPY: Currency = Currency(*b"JPY");
+
+    /// Checks and normalizes a three-letter code, e.g. "eur" to `EUR`.
+    pub fn new(code: &str) -> Result<Self, MoneyError> {
+        let normalized = code.trim().to_ascii_uppercase();
+        match *normalized.as_bytes() {
+            [a, b, c] if [a, b, c].iter().all(u8::is_ascii_uppercase) => Ok(Currency([a, b, c])),
+            _ => Err(MoneyError::InvalidCurrency(normalized)),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        std::str::from_utf8(&self.0).expect("currency codes are ASCII letters")
+    }
+
+    /// Number of decimal places of the minor unit: 2 for most currencies,
+    /// 0 for e.g. JPY and 3 for e.g. KWD.
+    pub fn minor_digits(self) -> u32 {
+        if WHOLE_UNIT_CURRENCIES.contains(&self.code()) {
+            0
+        } else if THOUSANDTHS_CURRENCIES.contains(&self.code()) {
+            3
+        } else {
+            2
+        }
+    }
+
+    fn symbol(self) -> Option<&'static str> {
+        match &self.0 {
+            b"USD" => Some("$"),
+            b"EUR" => Some("€"),
+            b"GBP" => Some("£"),
+            b"JPY" => Some("¥"),
+            b"INR" => Some("₹"),
+            _ => None,
+        }
+    }
+
+    fn minor_per_major(self) -> i64 {
+        10_i64.pow(self.minor_digits())
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = MoneyError;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        Currency::new(&code)
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.code().to_string()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Number formatting conventions for displaying and entering amounts.
+///
+/// Tags are matched by language with a region fallback, so "de-AT" formats
+/// like "de-DE" and "en-AU" like "en-US".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Locale {
+    /// `$1,234.56`
+    #[default]
+    EnUs,
+    /// `£1,234.56`
+    EnGb,
+    /// `1.234,56 €`
+    DeDe,
+    /// `CHF 1’234.56`
+    DeCh,
+    /// `1 234,56 €`
+    FrFr,
+}
+
+impl Locale {
+    /// Picks the conventions for a BCP 47 tag such as "de-DE" or "fr_CA".
+    pub fn new(tag: &str) -> Result<Self, MoneyError> {
+        let normalized = tag.trim().replace('_', "-").to_ascii_lowercase();
+        let mut parts = normalized.split('-');
+        match (parts.next(), parts.next()) {
+            (Some("en"), Some("gb" | "ie")) => Ok(Locale::EnGb),
+            (Some("en"), _) => Ok(Locale::EnUs),
+            (Some("de"), Some("ch" | "li")) => Ok(Locale::DeCh),
+            (Some("de"), _) => Ok(Locale::DeDe),
+            (Some("fr"), _) => Ok(Locale::FrFr),
+            _ => Err(MoneyError::UnsupportedLocale(tag.to_string())),
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::EnUs => "en-US",
+            Locale::EnGb => "en-GB",
+            Locale::DeDe => "de-DE",
+            Locale::DeCh => "de-CH",
+            Locale::FrFr => "fr-FR",
+        }
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            Locale::EnUs | Locale::EnGb | Locale::DeCh => '.',
+            Locale::DeDe | Locale::FrFr => ',',
+        }
+    }
+
+    fn group_separator(self) -> char {
+        match self {
+            Locale::EnUs | Locale::EnGb => ',',
+            Locale::DeDe => '.',
+            Locale::DeCh => '’',
+            Locale::FrFr => '\u{202f}',
+        }
+    }
+
+    fn is_group_separator(self, ch: char) -> bool {
+        match self {
+            Locale::DeCh => ch == '’' || ch == '\'',
+            // Typed amounts use any kind of space
+            Locale::FrFr => ch.is_whitespace(),
+            _ => ch == self.group_separator(),
+        }
+    }
+
+    fn symbol_first(self) -> bool {
+        matches!(self, Locale::EnUs | Locale::EnGb | Locale::DeCh)
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = MoneyError;
+
+    fn try_from(tag: String) -> Result<Self, Self::Error> {
+        Locale::new(&tag)
+    }
+}
+
+impl From<Locale> for String {
+    fn from(locale: Locale) -> Self {
+        locale.tag().to_string()
+    }
+}
+
+/// A signed amount in the minor unit of its currency.
+///
+/// Serializes as `{"amount_cents": -4275, "currency": "USD"}`, matching the
+/// `amount_cents` fields of transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Money {
+    amount_cents: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount_cents: i64, currency: Currency) -> Self {
+        Money { amount_cents, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money::new(0, currency)
+    }
+
+    /// Amount in minor units, e.g. cents; negative for debits.
+    pub fn amount_cents(self) -> i64 {
+        self.amount_cents
+    }
+
+    pub fn currency(self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.amount_cents == 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.amount_cents < 0
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.amount_cents > 0
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let amount_cents = self.amount_cents.checked_add(other.amount_cents).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount_cents, self.currency))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let amount_cents = self.amount_cents.checked_sub(other.amount_cents).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount_cents, self.currency))
+    }
+
+    pub fn checked_neg(self) -> Result<Money, MoneyError> {
+        let amount_cents = self.amount_cents.checked_neg().ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount_cents, self.currency))
+    }
+
+    pub fn checked_abs(self) -> Result<Money, MoneyError> {
+        let amount_cents = self.amount_cents.checked_abs().ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount_cents, self.currency))
+    }
+
+    /// Adds up amounts in `currency`; an empty iterator sums to zero.
+    pub fn sum(currency: Currency, amounts: impl IntoIterator<Item = Money>) -> Result<Money, MoneyError> {
+        amounts
+            .into_iter()
+            .try_fold(Money::zero(currency), |total, amount| total.checked_add(amount))
+    }
+
+    fn same_currency(self, other: Money) -> Result<(), MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, other.currency));
+        }
+        Ok(())
+    }
+
+    /// Splits the amount in proportion to `weights` without losing a cent.
+    ///
+    /// Each share is rounded half to even (banker's rounding); the few minor
+    /// units that rounding leaves over or takes too much then go, one each, to
+    /// the shares whose exact value was closest to the next unit, earlier
+    /// shares first on ties. The parts always add up to the amount.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// let rent = Money::new(100000, Currency::USD);
+    /// let shares = rent.allocate(&[1, 1, 1])?; // 333.34, 333.33, 333.33
+    /// ```
+    pub fn allocate(self, weights: &[u64]) -> Result<Vec<Money>, MoneyError> {
+        let total: u128 = weights.iter().map(|&weight| weight as u128).sum();
+        if total == 0 {
+            return Err(MoneyError::InvalidAmount(
+                "an allocation needs at least one positive weight".to_string(),
+            ));
+        }
+        let total = i128::try_from(total).map_err(|_| MoneyError::Overflow)?;
+        let amount = self.amount_cents as i128;
+
+        let mut shares = Vec::with_capacity(weights.len());
+        let mut remainders = Vec::with_capacity(weights.len());
+        for &weight in weights {
+            let exact = amount.checked_mul(weight as i128).ok_or(MoneyError::Overflow)?;
+            let share = div_round_half_even(exact, total);
+            shares.push(share);
+            // Positive when the share was rounded down, in units of 1/total
+            remainders.push(exact - share * total);
+        }
+
+        let mut left_over = amount - shares.iter().sum::<i128>();
+        let mut order: Vec<usize> = (0..shares.len()).collect();
+        if left_over > 0 {
+            order.sort_by_key(|&index| std::cmp::Reverse(remainders[index]));
+        } else {
+            order.sort_by_key(|&index| remainders[index]);
+        }
+        for index in order.into_iter().cycle() {
+            if left_over == 0 {
+                break;
+            }
+            if weights[index] == 0 {
+                continue;
+            }
+            shares[index] += left_over.signum();
+            left_over -= left_over.signum();
+        }
+
+        shares
+            .into_iter()
+            .map(|share| {
+                let amount_cents = i64::try_from(share).map_err(|_| MoneyError::Overflow)?;
+                Ok(Money::new(amount_cents, self.currency))
+            })
+            .collect()
+    }
+
+    /// Converts into another currency at `rate` ("1 unit of this currency =
+    /// `rate` units of `to`"), rounding half to even to whole minor units of `to`.
+    pub fn convert(self, rate: f64, to: Currency) -> Result<Money, MoneyError> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(MoneyError::InvalidAmount(format!("invalid exchange rate {}", rate)));
+        }
+        let scale = 10_f64.powi(to.minor_digits() as i32 - self.currency.minor_digits() as i32);
+        Ok(Money::new(round_minor_units(self.amount_cents as f64 * rate * scale)?, to))
+    }
+
+    /// Rounds an amount in major units, e.g. 12.345 dollars, half to even to
+    /// whole minor units of `currency`.
+    pub fn from_major(amount: f64, currency: Currency) -> Result<Money, MoneyError> {
+        if !amount.is_finite() {
+            return Err(MoneyError::InvalidAmount(format!("invalid amount {}", amount)));
+        }
+        let minor_units = amount * 10_f64.powi(currency.minor_digits() as i32);
+        Ok(Money::new(round_minor_units(minor_units)?, currency))
+    }
+
+    /// Reads an amount as typed in `locale`.
+    ///
+    /// Accepts the locale's grouping and decimal separators, a leading or
+    /// trailing currency symbol or code (which must be `currency`), a minus
+    /// sign and accounting parentheses for negative amounts. More decimal
+    /// places than the currency has are rejected rather than rounded.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// let rent = Money::parse("1.234,56 €", Currency::EUR, Locale::DeDe)?;
+    /// assert_eq!(rent.amount_cents(), 123456);
+    /// let refund = Money::parse("($42.75)", Currency::USD, Locale::EnUs)?;
+    /// assert_eq!(refund.amount_cents(), -4275);
+    /// ```
+    pub fn parse(text: &str, currency: Currency, locale: Locale) -> Result<Money, MoneyError> {
+        let invalid = |reason: &str| {
+            MoneyError::InvalidAmount(format!(
+                "cannot read {:?} as an amount in {} ({}): {}",
+                text,
+                currency,
+                locale.tag(),
+                reason
+            ))
+        };
+
+        let mut body = text.trim();
+        let mut negative = false;
+        if let Some(inner) = body.strip_prefix('(').and_then(|body| body.strip_suffix(')')) {
+            body = inner;
+            negative = true;
+        }
+
+        let mut number = String::new();
+        let mut marker = String::new();
+        let mut has_sign = false;
+        let mut number_ended = false;
+        for ch in body.chars() {
+            if ch.is_ascii_digit() {
+                if number_ended {
+                    return Err(invalid("unexpected characters inside the number"));
+                }
+                number.push(ch);
+            } else if ch == locale.decimal_separator() && !number_ended {
+                if number.contains('.') {
+                    return Err(invalid("more than one decimal separator"));
+                }
+                number.push('.');
+            } else if locale.is_group_separator(ch) && !number.is_empty() && !number.contains('.') {
+                continue;
+            } else if ch.is_whitespace() {
+                number_ended |= !number.is_empty();
+            } else if ch == '-' || ch == '\u{2212}' {
+                if has_sign || negative {
+                    return Err(invalid("more than one sign"));
+                }
+                has_sign = true;
+                negative = true;
+                number_ended |= !number.is_empty();
+            } else {
+                marker.push(ch);
+                number_ended |= !number.is_empty();
+            }
+        }
+
+        if !marker.is_empty()
+            && !marker.eq_ignore_ascii_case(currency.code())
+            && currency.symbol() != Some(marker.as_str())
+        {
+            return Err(invalid(&format!("{:?} is not the currency {}", marker, currency)));
+        }
+        let (major, minor) = number.split_once('.').unwrap_or((&number, ""));
+        if major.is_empty() && minor.is_empty() {
+            return Err(invalid("no digits"));
+        }
+        let digits = currency.minor_digits() as usize;
+        if minor.len() > digits {
+            return Err(invalid(&format!("{} has {} decimal places", currency, digits)));
+        }
+
+        let mut amount_cents: i64 = 0;
+        for digit in major.bytes().chain(minor.bytes()).chain(std::iter::repeat_n(b'0', digits - minor.len())) {
+            amount_cents = amount_cents
+                .checked_mul(10)
+                .and_then(|amount| amount.checked_add((digit - b'0') as i64))
+                .ok_or(MoneyError::Overflow)?;
+        }
+
+        Ok(Money::new(if negative { -amount_cents } else { amount_cents }, currency))
+    }
+
+    /// Plain decimal number of major units without grouping or currency, e.g.
+    /// "-1234.50", for CSV files and other machine-readable output.
+    pub fn to_decimal(self) -> String {
+        let digits = self.currency.minor_digits() as usize;
+        let per_major = self.currency.minor_per_major().unsigned_abs();
+        let magnitude = self.amount_cents.unsigned_abs();
+        let sign = if self.is_negative() { "-" } else { "" };
+        if digits == 0 {
+            return format!("{}{}", sign, magnitude);
+        }
+        format!("{}{}.{:0width$}", sign, magnitude / per_major, magnitude % per_major, width = digits)
+    }
+
+    /// Formats for display in `locale`, e.g. "-$1,234.56" or "-1.234,56 €".
+    pub fn format(self, locale: Locale) -> String {
+        let unsigned = self.format_unsigned(locale);
+        if self.is_negative() { format!("-{}", unsigned) } else { unsigned }
+    }
+
+    /// Formats in accounting notation, with negative amounts in parentheses,
+    /// e.g. "($42.75)".
+    pub fn format_accounting(self, locale: Locale) -> String {
+        let unsigned = self.format_unsigned(locale);
+        if self.is_negative() { format!("({})", unsigned) } else { unsigned }
+    }
+
+    fn format_unsigned(self, locale: Locale) -> String {
+        let digits = self.currency.minor_digits() as usize;
+        let per_major = self.currency.minor_per_major().unsigned_abs();
+        let magnitude = self.amount_cents.unsigned_abs();
+
+        let major = (magnitude / per_major).to_string();
+        let mut number = String::new();
+        for (index, digit) in major.chars().enumerate() {
+            if index > 0 && (major.len() - index).is_multiple_of(3) {
+                number.push(locale.group_separator());
+            }
+            number.push(digit);
+        }
+        if digits > 0 {
+            number.push(locale.decimal_separator());
+            number.push_str(&format!("{:0width$}", magnitude % per_major, width = digits));
+        }
+
+        match (self.currency.symbol(), locale.symbol_first()) {
+            (Some(symbol), true) if locale != Locale::DeCh => format!("{}{}", symbol, number),
+            (Some(symbol), true) => format!("{} {}", symbol, number),
+            (None, true) => format!("{} {}", self.currency, number),
+            (symbol, false) => format!("{}\u{a0}{}", number, symbol.unwrap_or(self.currency.code())),
+        }
+    }
+}
+
+impl fmt::Display for Money {
+    /// Plain notation for messages, e.g. "-42.75 USD".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.to_decimal(), self.currency)
+    }
+}
+
+/// Rounds a fractional number of minor units half to even.
+fn round_minor_units(minor_units: f64) -> Result<i64, MoneyError> {
+    let rounded = minor_units.round_ties_even();
+    // i64::MAX itself is not representable as f64; anything at 2^63 is out of range
+    if !(rounded >= i64::MIN as f64 && rounded < i64::MAX as f64) {
+        return Err(MoneyError::Overflow);
+    }
+
+    Ok(rounded as i64)
+}
+
+/// `numerator / denominator` rounded to the nearest integer, ties to even.
+pub(crate) fn div_round_half_even(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator.div_euclid(denominator);
+    let twice_remainder = 2 * numerator.rem_euclid(denominator);
+    if twice_remainder > denominator || (twice_remainder == denominator && quotient % 2 != 0) {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usd(amount_cents: i64) -> Money {
+        Money::new(amount_cents, Currency::USD)
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(usd(1050).checked_add(usd(-250)).unwrap(), usd(800));
+        assert_eq!(usd(i64::MAX).checked_add(usd(1)), Err(MoneyError::Overflow));
+        assert_eq!(usd(i64::MIN).checked_neg(), Err(MoneyError::Overflow));
+        assert_eq!(
+            usd(100).checked_sub(Money::new(100, Currency::EUR)),
+            Err(MoneyError::CurrencyMismatch(Currency::USD, Currency::EUR))
+        );
+        assert_eq!(Money::sum(Currency::USD, [usd(i64::MAX - 1), usd(1)]).unwrap(), usd(i64::MAX));
+        assert_eq!(Money::sum(Currency::USD, [usd(i64::MAX), usd(1)]), Err(MoneyError::Overflow));
+        assert!(Currency::new("euro").is_err());
+        assert_eq!(Currency::new(" chf ").unwrap(), Currency::CHF);
+    }
+
+    #[test]
+    fn test_allocate_rounds_half_to_even_and_keeps_the_total() {
+        let cents = |parts: Vec<Money>| parts.into_iter().map(Money::amount_cents).collect::<Vec<_>>();
+
+        assert_eq!(cents(usd(100000).allocate(&[1, 1, 1]).unwrap()), vec![33334, 33333, 33333]);
+        // 2.5 and 7.5 round to even (2 and 8), so nothing is left over
+        assert_eq!(cents(usd(10).allocate(&[1, 3]).unwrap()), vec![2, 8]);
+        assert_eq!(cents(usd(-10).allocate(&[1, 3]).unwrap()), vec![-2, -8]);
+        // 3.5 + 3.5 rounds to 4 + 4; the extra unit comes back off the first share
+        assert_eq!(cents(usd(7).allocate(&[1, 1]).unwrap()), vec![3, 4]);
+        assert_eq!(cents(usd(5).allocate(&[0, 1, 1]).unwrap()), vec![0, 3, 2]);
+        assert_eq!(cents(usd(i64::MAX).allocate(&[1, 1]).unwrap()).iter().map(|&c| c as i128).sum::<i128>(), i64::MAX as i128);
+        assert!(usd(100).allocate(&[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_convert_adjusts_minor_units() {
+        assert_eq!(usd(1000).convert(150.25, Currency::JPY).unwrap(), Money::new(1502, Currency::JPY));
+        assert_eq!(Money::new(1503, Currency::JPY).convert(1.0 / 150.3, Currency::USD).unwrap(), usd(1000));
+        // 12.5 rounds to the even 12
+        assert_eq!(usd(25).convert(0.5, Currency::EUR).unwrap(), Money::new(12, Currency::EUR));
+        assert_eq!(usd(i64::MAX).convert(2.0, Currency::EUR), Err(MoneyError::Overflow));
+        assert!(usd(100).convert(0.0, Currency::EUR).is_err());
+        assert_eq!(Money::from_major(12.345, Currency::USD).unwrap(), usd(1234));
+        assert_eq!(Money::from_major(-0.5, Currency::JPY).unwrap(), Money::new(0, Currency::JPY));
+    }
+
+    #[test]
+    fn test_parse_locales() {
+        let eur = |amount_cents| Money::new(amount_cents, Currency::EUR);
+
+        assert_eq!(Money::parse("1.234,56 €", Currency::EUR, Locale::DeDe).unwrap(), eur(123456));
+        assert_eq!(Money::parse("-1.234,5", Currency::EUR, Locale::DeDe).unwrap(), eur(-123450));
+        assert_eq!(Money::parse("($42.75)", Currency::USD, Locale::EnUs).unwrap(), usd(-4275));
+        assert_eq!(Money::parse("-$1,234.56", Currency::USD, Locale::EnUs).unwrap(), usd(-123456));
+        assert_eq!(Money::parse("usd 12", Currency::USD, Locale::EnUs).unwrap(), usd(1200));
+        assert_eq!(Money::parse("1 234,56 €", Currency::EUR, Locale::FrFr).unwrap(), eur(123456));
+        assert_eq!(Money::parse("CHF 1'234.50", Currency::CHF, Locale::DeCh).unwrap(), Money::new(123450, Currency::CHF));
+        assert_eq!(Money::parse("¥1,500", Currency::JPY, Locale::EnUs).unwrap(), Money::new(1500, Currency::JPY));
+
+        // Wrong separators, currency or precision are errors, never guesses
+        assert!(Money::parse("1.234,56", Currency::USD, Locale::EnUs).is_err());
+        assert!(Money::parse("12.345", Currency::USD, Locale::EnUs).is_err());
+        assert!(Money::parse("€12.00", Currency::USD, Locale::EnUs).is_err());
+        assert!(Money::parse("12 34", Currency::USD, Locale::EnUs).is_err());
+        assert!(Money::parse("--5", Currency::USD, Locale::EnUs).is_err());
+        assert!(Money::parse("$", Currency::USD, Locale::EnUs).is_err());
+        assert_eq!(Money::parse("99999999999999999999", Currency::USD, Locale::EnUs), Err(MoneyError::Overflow));
+    }
+
+    #[test]
+    fn test_format_round_trips() {
+        assert_eq!(usd(-123456).format(Locale::EnUs), "-$1,234.56");
+        assert_eq!(usd(-4275).format_accounting(Locale::EnUs), "($42.75)");
+        assert_eq!(Money::new(123456, Currency::EUR).format(Locale::DeDe), "1.234,56\u{a0}€");
+        assert_eq!(Money::new(123456, Currency::EUR).format(Locale::FrFr), "1\u{202f}234,56\u{a0}€");
+        assert_eq!(Money::new(-123450, Currency::CHF).format(Locale::DeCh), "-CHF 1’234.50");
+        assert_eq!(Money::new(1500, Currency::JPY).format(Locale::EnGb), "¥1,500");
+        assert_eq!(Money::new(5, Currency::new("KWD").unwrap()).to_string(), "0.005 KWD");
+        assert_eq!(usd(-4275).to_string(), "-42.75 USD");
+
+        for locale in [Locale::EnUs, Locale::EnGb, Locale::DeDe, Locale::DeCh, Locale::FrFr] {
+            for amount in [usd(0), usd(7), usd(-123456789), usd(i64::MIN + 1)] {
+                assert_eq!(Money::parse(&amount.format(locale), Currency::USD, locale).unwrap(), amount);
+                assert_eq!(Money::parse(&amount.format_accounting(locale), Currency::USD, locale).unwrap(), amount);
+            }
+        }
+        assert_eq!(Locale::new("de_AT").unwrap(), Locale::DeDe);
+        assert_eq!(Locale::new("en-GB").unwrap(), Locale::EnGb);
+        assert!(Locale::new("xx").is_err());
+    }
+}
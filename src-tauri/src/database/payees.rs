@@ -0,0 +1,660 @@
+//! Payees: the merchants and people money goes to or comes from.
+//!
+//! Bank descriptions such as "SQ *BLUE BOTTLE 0423 OAKLAND CA" are cleaned up
+//! by [`normalize_description()`] when a transaction is added or imported, and
+//! the transaction is linked to a payee. A payee has a canonical name, aliases
+//! (other cleaned-up descriptions that mean the same payee) and match patterns
+//! (SQL `LIKE` patterns tested against the raw bank description), plus an
+//! optional default category for new transactions. New transactions that match
+//! no payee create one named after the cleaned-up description.
+//!
+//! Aliases and patterns are stored as JSON arrays on the payee row, so payee
+//! changes are journaled and audited like any other record.
+
+use super::currencies::{ensure_rates, to_base_sql};
+use super::journal::journaled;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+/// Card processor and point-of-sale prefixes banks put before the merchant name.
+const PROCESSOR_PREFIXES: &[&str] = &[
+    "SQ *", "SQ*", "TST*", "TST *", "PAYPAL *", "PP*", "SP *", "SP*", "GOOGLE *", "APL*",
+    "POS DEBIT ", "POS PURCHASE ", "POS ", "DEBIT CARD PURCHASE ", "CHECKCARD ", "PURCHASE ",
+];
+
+/// US state and Canadian province codes that end a card transaction's location.
+const REGION_CODES: &[&str] = &[
+    "AK", "AL", "AR", "AZ", "CA", "CO", "CT", "DC", "DE", "FL", "GA", "HI", "IA", "ID", "IL",
+    "IN", "KS", "KY", "LA", "MA", "MD", "ME", "MI", "MN", "MO", "MS", "MT", "NC", "ND", "NE",
+    "NH", "NJ", "NM", "NV", "NY", "OH", "OK", "OR", "PA", "RI", "SC", "SD", "TN", "TX", "UT",
+    "VA", "VT", "WA", "WI", "WV", "WY", "AB", "BC", "MB", "NB", "NL", "NS", "ON", "PE", "QC",
+    "SK",
+];
+
+/// A payee as created or edited by the user.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PayeeInput {
+    /// Canonical name shown in the register and reports
+    pub name: String,
+    /// Cleaned-up descriptions that also mean this payee, e.g. "Blue Bottle Coffee"
+    pub aliases: Vec<String>,
+    /// SQL `LIKE` patterns matched against raw bank descriptions, e.g. "%BLUE BOTTLE%"
+    pub patterns: Vec<String>,
+    /// Category given to new uncategorized transactions of this payee
+    pub default_category_id: Option<i64>,
+}
+
+/// Cleans up a bank transaction description.
+///
+/// Only bank-formatted text is changed: descriptions that are entirely upper
+/// case or start with a card processor prefix ("SQ *", "TST*", "PAYPAL *",
+/// ...). Those lose the prefix, store numbers and everything after them, and a
+/// trailing "CITY ST" location, and upper case text is converted to title case.
+/// Descriptions typed by the user are kept as typed, numbers included.
+///
+/// # Examples
+/// ```no_run
+/// assert_eq!(normalize_description("SQ *BLUE BOTTLE 0423 OAKLAND CA"), "Blue Bottle");
+/// assert_eq!(normalize_description("Rent for March 2025"), "Rent for March 2025");
+/// ```
+pub fn normalize_description(raw: &str) -> String {
+    let mut text = raw.trim();
+    let mut prefixed = false;
+    while let Some(prefix) = PROCESSOR_PREFIXES
+        .iter()
+        .find(|prefix| text.len() > prefix.len() && text.starts_with(*prefix))
+    {
+        text = text[prefix.len()..].trim_start();
+        prefixed = true;
+    }
+    if !prefixed && text.chars().any(|c| c.is_lowercase()) {
+        return text.to_string();
+    }
+
+    let mut words: Vec<&str> = text.split_whitespace().collect();
+    let has_region = words.len() > 2 && words.last().is_some_and(|word| REGION_CODES.contains(word));
+    if has_region {
+        words.pop();
+    }
+    match words.iter().skip(1).position(|word| is_store_number(word)) {
+        Some(index) => {
+            words.truncate(index + 1);
+            if words.len() > 1 && words.last().is_some_and(|word| word.eq_ignore_ascii_case("STORE")) {
+                words.pop();
+            }
+        }
+        // Without a store number to cut at, the city is taken to be one word
+        None if has_region => {
+            words.pop();
+        }
+        None => {}
+    }
+
+    let cleaned = words.join(" ");
+    if cleaned.is_empty() {
+        return raw.trim().to_string();
+    }
+    if cleaned.chars().any(|c| c.is_lowercase()) {
+        cleaned
+    } else {
+        title_case(&cleaned)
+    }
+}
+
+/// Store numbers such as "0423", "#12" or "00123-4".
+fn is_store_number(word: &str) -> bool {
+    let digits = word.trim_start_matches('#');
+    digits.chars().any(|c| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '-')
+        && (word.starts_with('#') || digits.len() >= 2)
+}
+
+fn title_case(text: &str) -> String {
+    text.split(' ')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Description and payee for a new transaction.
+pub(crate) struct ResolvedPayee {
+    pub description: String,
+    pub payee_id: i64,
+    pub default_category_id: Option<i64>,
+}
+
+impl ResolvedPayee {
+    /// The payee's default category when `category_id` is "Uncategorized".
+    pub async fn category(
+        &self,
+        conn: &mut SqliteConnection,
+        category_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        let Some(default_category_id) = self.default_category_id else {
+            return Ok(category_id);
+        };
+        let name: Option<String> = sqlx::query_scalar("SELECT name FROM categories WHERE id = ?")
+            .bind(category_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(if name.as_deref() == Some("Uncategorized") { default_category_id } else { category_id })
+    }
+}
+
+/// Normalizes a raw description and finds or creates its payee.
+///
+/// Match patterns are tried first, then aliases, then payee names; the oldest
+/// matching payee wins.
+pub(crate) async fn resolve_payee(
+    conn: &mut SqliteConnection,
+    raw_description: &str,
+) -> Result<ResolvedPayee, sqlx::Error> {
+    let description = normalize_description(raw_description);
+
+    let (payee_id, default_category_id) = match find_payee(&mut *conn, raw_description, &description).await? {
+        Some(existing) => existing,
+        None => {
+            let id = sqlx::query("INSERT INTO payees (name) VALUES (?)")
+                .bind(&description)
+                .execute(&mut *conn)
+                .await?
+                .last_insert_rowid();
+            (id, None)
+        }
+    };
+
+    Ok(ResolvedPayee {
+        description,
+        payee_id,
+        default_category_id,
+    })
+}
+
+/// Id and default category of the payee a description matches, if any; see
+/// [`resolve_payee()`] for the order rules are tried in.
+async fn find_payee(
+    conn: &mut SqliteConnection,
+    raw_description: &str,
+    description: &str,
+) -> Result<Option<(i64, Option<i64>)>, sqlx::Error> {
+    let existing = sqlx::query(
+        "SELECT id, default_category_id FROM payees p
+         WHERE EXISTS (SELECT 1 FROM json_each(p.patterns) WHERE ? LIKE json_each.value)
+            OR EXISTS (SELECT 1 FROM json_each(p.aliases) WHERE json_each.value = ? COLLATE NOCASE)
+            OR p.name = ?
+         ORDER BY
+            EXISTS (SELECT 1 FROM json_each(p.patterns) WHERE ? LIKE json_each.value) DESC,
+            EXISTS (SELECT 1 FROM json_each(p.aliases) WHERE json_each.value = ? COLLATE NOCASE) DESC,
+            p.id
+         LIMIT 1",
+    )
+    .bind(raw_description)
+    .bind(description)
+    .bind(description)
+    .bind(raw_description)
+    .bind(description)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(existing.map(|row| (row.get("id"), row.get("default_category_id"))))
+}
+
+/// Retrieves all payees, sorted by name.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with id, name, aliases, patterns,
+///   default_category_id and the number of linked `transactions`
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_payees(pool: &SqlitePool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT p.id, p.name, p.aliases, p.patterns, p.default_category_id,
+                (SELECT COUNT(*) FROM transactions t WHERE t.payee_id = p.id AND t.deleted_at IS NULL) AS transactions
+         FROM payees p
+         ORDER BY p.name",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let aliases: serde_json::Value = serde_json::from_str(row.get("aliases"))
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            let patterns: serde_json::Value = serde_json::from_str(row.get("patterns"))
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            Ok(serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "name": row.get::<String, _>("name"),
+                "aliases": aliases,
+                "patterns": patterns,
+                "default_category_id": row.get::<Option<i64>, _>("default_category_id"),
+                "transactions": row.get::<i64, _>("transactions")
+            }))
+        })
+        .collect()
+}
+
+/// Creates a payee, or replaces an existing payee's settings.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `payee_id` - Payee to update, or None to create one
+/// * `payee` - Name, aliases, patterns and default category
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the saved payee
+/// * `Err(sqlx::Error)` - Empty or duplicate name, unknown payee or category
+///
+/// # Examples
+/// ```no_run
+/// let payee = PayeeInput {
+///     name: "Blue Bottle Coffee".to_string(),
+///     patterns: vec!["%BLUE BOTTLE%".to_string()],
+///     default_category_id: Some(coffee_id),
+///     ..Default::default()
+/// };
+/// let payee_id = save_payee(&pool, None, &payee).await?;
+/// ```
+pub async fn save_payee(
+    pool: &SqlitePool,
+    payee_id: Option<i64>,
+    payee: &PayeeInput,
+) -> Result<i64, sqlx::Error> {
+    let name = payee.name.trim();
+    if name.is_empty() {
+        return Err(sqlx::Error::Protocol("payee name cannot be empty".to_string()));
+    }
+    let aliases = serde_json::to_string(&payee.aliases).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+    let patterns = serde_json::to_string(&payee.patterns).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+    let label = if payee_id.is_some() { "Update payee" } else { "Add payee" };
+    journaled(pool, label, async |conn| {
+        match payee_id {
+            Some(id) => {
+                let result = sqlx::query(
+                    "UPDATE payees SET name = ?, aliases = ?, patterns = ?, default_category_id = ? WHERE id = ?",
+                )
+                .bind(name)
+                .bind(&aliases)
+                .bind(&patterns)
+                .bind(payee.default_category_id)
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+
+                if result.rows_affected() == 0 {
+                    return Err(sqlx::Error::RowNotFound);
+                }
+                Ok(id)
+            }
+            None => {
+                let id = sqlx::query(
+                    "INSERT INTO payees (name, aliases, patterns, default_category_id) VALUES (?, ?, ?, ?)",
+                )
+                .bind(name)
+                .bind(&aliases)
+                .bind(&patterns)
+                .bind(payee.default_category_id)
+                .execute(&mut *conn)
+                .await?
+                .last_insert_rowid();
+                Ok(id)
+            }
+        }
+    })
+    .await
+}
+
+/// Deletes a payee. Its transactions keep their descriptions but lose the link.
+pub async fn delete_payee(pool: &SqlitePool, payee_id: i64) -> Result<(), sqlx::Error> {
+    journaled(pool, "Delete payee", async |conn| {
+        sqlx::query("UPDATE transactions SET payee_id = NULL WHERE payee_id = ?")
+            .bind(payee_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM payees WHERE id = ?")
+            .bind(payee_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Merges one payee into another.
+///
+/// The source's transactions move to the target, and the source's name,
+/// aliases and patterns become the target's aliases and patterns, so future
+/// transactions land on the target too.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `source_id` - Payee to merge away
+/// * `target_id` - Payee to keep
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Number of relinked `transactions`
+/// * `Err(sqlx::Error)` - Same payee twice, unknown payee, or database failure
+pub async fn merge_payees(
+    pool: &SqlitePool,
+    source_id: i64,
+    target_id: i64,
+) -> Result<serde_json::Value, sqlx::Error> {
+    if source_id == target_id {
+        return Err(sqlx::Error::Protocol("cannot merge a payee into itself".to_string()));
+    }
+
+    journaled(pool, "Merge payees", async |conn| {
+        let source = sqlx::query("SELECT name, aliases, patterns FROM payees WHERE id = ?")
+            .bind(source_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let transactions = sqlx::query("UPDATE transactions SET payee_id = ? WHERE payee_id = ?")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        sqlx::query("DELETE FROM payees WHERE id = ?")
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let result = sqlx::query(
+            "UPDATE payees SET
+                aliases = (SELECT json_group_array(DISTINCT value) FROM (
+                    SELECT value FROM json_each(payees.aliases)
+                    UNION ALL SELECT ?
+                    UNION ALL SELECT value FROM json_each(?)
+                )),
+                patterns = (SELECT json_group_array(DISTINCT value) FROM (
+                    SELECT value FROM json_each(payees.patterns)
+                    UNION ALL SELECT value FROM json_each(?)
+                ))
+             WHERE id = ?",
+        )
+        .bind(source.get::<String, _>("name"))
+        .bind(source.get::<String, _>("aliases"))
+        .bind(source.get::<String, _>("patterns"))
+        .bind(target_id)
+        .execute(&mut *conn)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(serde_json::json!({ "transactions": transactions }))
+    })
+    .await
+}
+
+/// Links transactions without a payee to the existing payee they match, using
+/// the current rules.
+///
+/// Useful after adding aliases or patterns, and for transactions recorded
+/// before payees existed. Linked descriptions are normalized along the way;
+/// the raw text is kept as `original_description`. Transactions matching no
+/// payee stay unlinked rather than creating one each. Transfers, the cash
+/// side of trades and transactions in the trash have no payee and are skipped.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(u64)` - Number of transactions linked
+/// * `Err(sqlx::Error)` - Database failure; nothing is linked
+pub async fn link_payees(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+    journaled(pool, "Link payees", async |conn| {
+        let unlinked = sqlx::query(
+            "SELECT t.id, COALESCE(t.original_description, t.description) AS raw FROM transactions t
+             WHERE t.payee_id IS NULL AND t.deleted_at IS NULL AND t.transfer_id IS NULL
+               AND NOT EXISTS (SELECT 1 FROM investment_transactions i WHERE i.transaction_id = t.id)",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut linked = 0;
+        for row in &unlinked {
+            let raw: String = row.get("raw");
+            let description = normalize_description(&raw);
+            let Some((payee_id, _)) = find_payee(&mut *conn, &raw, &description).await? else {
+                continue;
+            };
+            sqlx::query(
+                "UPDATE transactions SET description = ?, original_description = ?, payee_id = ? WHERE id = ?",
+            )
+            .bind(&description)
+            .bind(&raw)
+            .bind(payee_id)
+            .bind(row.get::<i64, _>("id"))
+            .execute(&mut *conn)
+            .await?;
+            linked += 1;
+        }
+
+        Ok(linked)
+    })
+    .await
+}
+
+/// Reports the payees with the most spending across all accounts.
+///
+/// Amounts are converted into the base currency at each transaction date's rate.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `date_from` - Optional first date (YYYY-MM-DD) included
+/// * `date_to` - Optional last date (YYYY-MM-DD) included
+/// * `limit` - Maximum number of payees to return
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with payee_id, name, `spent_cents`
+///   (total of debits, positive), `net_cents` and transaction `count`, highest
+///   spending first
+/// * `Err(sqlx::Error)` - A transaction in the period cannot be converted, or
+///   database failure
+pub async fn get_top_payees(
+    pool: &SqlitePool,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    limit: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    ensure_rates(&mut conn, &date_from, &date_to).await?;
+
+    let rows = sqlx::query(&format!(
+        "SELECT p.id, p.name,
+                SUM(CASE WHEN t.base_cents < 0 THEN -t.base_cents ELSE 0 END) AS spent_cents,
+                SUM(t.base_cents) AS net_cents,
+                COUNT(*) AS count
+         FROM (
+             SELECT t.payee_id, {} AS base_cents
+             FROM transactions t
+             JOIN accounts a ON a.id = t.account_id
+             WHERE t.deleted_at IS NULL
+               AND (? IS NULL OR t.transaction_date >= ?)
+               AND (? IS NULL OR t.transaction_date <= ?)
+         ) t
+         JOIN payees p ON p.id = t.payee_id
+         GROUP BY p.id
+         ORDER BY spent_cents DESC, p.name
+         LIMIT ?",
+        to_base_sql("t.amount_cents", "a.currency", "t.transaction_date")
+    ))
+    .bind(&date_from)
+    .bind(&date_from)
+    .bind(&date_to)
+    .bind(&date_to)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let result: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "payee_id": row.get::<i64, _>("id"),
+                "name": row.get::<String, _>("name"),
+                "spent_cents": row.get::<i64, _>("spent_cents"),
+                "net_cents": row.get::<i64, _>("net_cents"),
+                "count": row.get::<i64, _>("count")
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Coffee".to_string(), None).await.unwrap();
+
+        pool
+    }
+
+    #[test]
+    fn test_normalize_description() {
+        for (raw, expected) in [
+            ("SQ *BLUE BOTTLE 0423 OAKLAND CA", "Blue Bottle"),
+            ("TST* JOE'S PIZZA #12 BROOKLYN NY", "Joe's Pizza"),
+            ("POS DEBIT STARBUCKS STORE 01234 SEATTLE WA", "Starbucks"),
+            ("PAYPAL *SPOTIFY", "Spotify"),
+            ("SAFEWAY OAKLAND CA", "Safeway"),
+            ("NETFLIX.COM", "Netflix.com"),
+            ("Dinner with Sam", "Dinner with Sam"),
+            ("SHELL OIL 57442 #8 AUSTIN TX", "Shell Oil"),
+            ("Rent for March 2025", "Rent for March 2025"),
+            ("Invoice 2025 Q1", "Invoice 2025 Q1"),
+            ("Purchase of shoes", "Purchase of shoes"),
+            ("SQ *Blue Bottle 0423", "Blue Bottle"),
+        ] {
+            assert_eq!(normalize_description(raw), expected, "normalizing {:?}", raw);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_transaction_links_payee_and_default_category() {
+        let pool = setup_test_db().await;
+        let payee = PayeeInput {
+            name: "Blue Bottle Coffee".to_string(),
+            patterns: vec!["%BLUE BOTTLE%".to_string()],
+            default_category_id: Some(2),
+            ..Default::default()
+        };
+        let payee_id = save_payee(&pool, None, &payee).await.unwrap();
+
+        for (raw, date) in [("SQ *BLUE BOTTLE 0423 OAKLAND CA", "2025-04-01"), ("BLUE BOTTLE #7 BERKELEY CA", "2025-04-03")] {
+            crate::database::add_transaction(&pool, 1, Money::new(-650, Currency::USD), "debit".to_string(), raw.to_string(), date.to_string(), 1)
+                .await
+                .unwrap();
+        }
+        crate::database::add_transaction(&pool, 1, Money::new(-1200, Currency::USD), "debit".to_string(), "TST* CHEZ PANISSE".to_string(), "2025-04-02".to_string(), 1)
+            .await
+            .unwrap();
+
+        let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
+        let coffee: Vec<&serde_json::Value> = transactions.iter().filter(|tx| tx["payee_id"] == payee_id).collect();
+        assert_eq!(coffee.len(), 2);
+        assert!(coffee.iter().all(|tx| tx["category_id"] == 2));
+        assert_eq!(coffee[0]["description"], "Blue Bottle");
+        assert_eq!(coffee[0]["original_description"], "BLUE BOTTLE #7 BERKELEY CA");
+
+        let top = get_top_payees(&pool, None, None, 10).await.unwrap();
+        assert_eq!(top[0]["name"], "Blue Bottle Coffee");
+        assert_eq!(top[0]["spent_cents"], 1300);
+        assert_eq!(top[0]["count"], 2);
+        assert_eq!(top[1]["name"], "Chez Panisse");
+        assert_eq!(top[1]["spent_cents"], 1200);
+
+        // Typed text is stored as typed
+        crate::database::add_transaction(&pool, 1, Money::new(-150000, Currency::USD), "debit".to_string(), "Rent for March 2025".to_string(), "2025-03-31".to_string(), 1)
+            .await
+            .unwrap();
+        let transactions = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(transactions[3]["description"], "Rent for March 2025");
+    }
+
+    #[tokio::test]
+    async fn test_merge_payees_turns_name_into_alias() {
+        let pool = setup_test_db().await;
+        crate::database::add_transaction(&pool, 1, Money::new(-900, Currency::USD), "debit".to_string(), "WHOLEFDS MKT 10234".to_string(), "2025-04-01".to_string(), 1)
+            .await
+            .unwrap();
+        let target = save_payee(&pool, None, &PayeeInput { name: "Whole Foods".to_string(), ..Default::default() })
+            .await
+            .unwrap();
+
+        let summary = merge_payees(&pool, 1, target).await.unwrap();
+        assert_eq!(summary["transactions"], 1);
+        let payees = get_payees(&pool).await.unwrap();
+        assert_eq!(payees.len(), 1);
+        assert_eq!(payees[0]["aliases"], serde_json::json!(["Wholefds Mkt"]));
+
+        // The alias now routes new transactions to the merged payee
+        crate::database::add_transaction(&pool, 1, Money::new(-300, Currency::USD), "debit".to_string(), "WHOLEFDS MKT 10301".to_string(), "2025-04-08".to_string(), 1)
+            .await
+            .unwrap();
+        let payees = get_payees(&pool).await.unwrap();
+        assert_eq!(payees[0]["transactions"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_link_payees_only_links_to_existing_payees() {
+        let pool = setup_test_db().await;
+        crate::database::add_account(&pool, "Savings".to_string(), "savings".to_string()).await.unwrap();
+        for description in ["WHOLEFDS MKT 10234", "CORNER DELI 0042"] {
+            crate::database::add_transaction(&pool, 1, Money::new(-900, Currency::USD), "debit".to_string(), description.to_string(), "2025-04-01".to_string(), 1)
+                .await
+                .unwrap();
+        }
+        crate::database::add_transaction(&pool, 1, Money::new(-500, Currency::USD), "debit".to_string(), "WHOLEFDS MKT 10301".to_string(), "2025-04-02".to_string(), 1)
+            .await
+            .unwrap();
+        crate::database::delete_transaction(&pool, 3).await.unwrap();
+        crate::database::add_transfer(&pool, 1, 2, Money::new(1000, Currency::USD), None, "2025-04-03".to_string(), Some("WHOLEFDS MKT".to_string()))
+            .await
+            .unwrap();
+        // As recorded before payees existed
+        sqlx::query("UPDATE transactions SET payee_id = NULL").execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM payees").execute(&pool).await.unwrap();
+        let whole_foods = save_payee(&pool, None, &PayeeInput { name: "Wholefds Mkt".to_string(), ..Default::default() })
+            .await
+            .unwrap();
+
+        // Only the live Whole Foods purchase: no deli payee, no trashed row, no transfer legs
+        assert_eq!(link_payees(&pool).await.unwrap(), 1);
+        let payees = get_payees(&pool).await.unwrap();
+        assert_eq!(payees.len(), 1);
+        assert_eq!(payees[0]["id"], whole_foods);
+        assert_eq!(payees[0]["transactions"], 1);
+        let linked: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE payee_id IS NOT NULL")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(linked, 1);
+    }
+}
@@ -0,0 +1,1370 @@
+//! Portable JSON export and import of a whole ledger.
+//!
+//! A [`LedgerDocument`] is a self-contained, human-diffable snapshot of a ledger
+//! that does not depend on SQLite: accounts (including archived ones), the
+//! category tree, payees, tags, custom field definitions, exchange rates,
+//! securities with their prices, all transactions outside the trash and all
+//! investment transactions, with their database ids so references
+//! between records stay readable. Records are written in id order, so exporting
+//! the same ledger twice yields identical documents apart from `exported_at`.
+//!
+//! Documents carry a `format` marker and a `version`. Readers accept every
+//! version up to [`FORMAT_VERSION`] and refuse newer ones rather than silently
+//! dropping data they do not understand. Record types added in later versions
+//! (budgets, rules and schedules once the ledger has them) are optional fields,
+//! so older documents keep importing.
+//!
+//! Imports run in a single [`unit_of_work()`](super::unit_of_work) and come in two
+//! modes, see [`ImportMode`].
+
+use super::currencies::{BASE_CURRENCY, currency_code};
+use super::custom_fields::{CustomFieldEntity, CustomFieldType, CustomFieldValues, values_json};
+use super::investments::{CostBasisMethod, SecurityType};
+use super::payees::resolve_payee;
+use super::tags::{TAG_NAMES, ensure_tag, tag_names};
+use super::trash::restore_category_row;
+use super::unit_of_work;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Value of the `format` field identifying finsight ledger documents.
+pub const FORMAT_NAME: &str = "finsight-ledger";
+
+/// Current document schema version written by [`export_ledger()`].
+pub const FORMAT_VERSION: u32 = 11;
+
+/// Versioned, SQLite-independent snapshot of a ledger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerDocument {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    /// Added in version 8. Restoring an older document keeps the ledger's base
+    /// currency; merging never changes it
+    #[serde(default)]
+    pub base_currency: Option<String>,
+    pub accounts: Vec<AccountRecord>,
+    pub categories: Vec<CategoryRecord>,
+    /// Added in version 5
+    #[serde(default)]
+    pub payees: Vec<PayeeRecord>,
+    /// Added in version 6
+    #[serde(default)]
+    pub tags: Vec<TagRecord>,
+    /// Added in version 7
+    #[serde(default)]
+    pub custom_fields: Vec<CustomFieldRecord>,
+    /// Added in version 8
+    #[serde(default)]
+    pub exchange_rates: Vec<ExchangeRateRecord>,
+    /// Added in version 9
+    #[serde(default)]
+    pub securities: Vec<SecurityRecord>,
+    /// Added in version 9
+    #[serde(default)]
+    pub security_prices: Vec<SecurityPriceRecord>,
+    pub transactions: Vec<TransactionRecord>,
+    /// Added in version 9
+    #[serde(default)]
+    pub investment_transactions: Vec<InvestmentTransactionRecord>,
+    /// Added in version 10
+    #[serde(default)]
+    pub lot_selections: Vec<LotSelectionRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountRecord {
+    pub id: i64,
+    pub name: String,
+    pub account_type: String,
+    /// Added in version 8; accounts without one are in the ledger's base currency
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
+    /// Added in version 3
+    #[serde(default)]
+    pub opening_balance_cents: i64,
+    /// Added in version 4
+    #[serde(default)]
+    pub closed_date: Option<String>,
+    /// Added in version 4; accounts without one are placed last
+    #[serde(default)]
+    pub display_order: Option<i64>,
+    /// Added in version 7
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// Added in version 7. Values keyed by custom field id
+    #[serde(default)]
+    pub custom_fields: CustomFieldValues,
+    /// Added in version 10
+    #[serde(default)]
+    pub cost_basis_method: CostBasisMethod,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoryRecord {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<i64>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayeeRecord {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub default_category_id: Option<i64>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagRecord {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomFieldRecord {
+    pub id: i64,
+    pub entity: CustomFieldEntity,
+    pub name: String,
+    pub field_type: CustomFieldType,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeRateRecord {
+    pub rate_date: String,
+    pub from_currency: String,
+    pub to_currency: String,
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecurityRecord {
+    pub id: i64,
+    pub ticker: String,
+    pub name: String,
+    pub security_type: SecurityType,
+    pub currency: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecurityPriceRecord {
+    pub security_id: i64,
+    pub price_date: String,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvestmentTransactionRecord {
+    pub id: i64,
+    pub account_id: i64,
+    pub security_id: i64,
+    /// "buy", "sell", "dividend" or "split"
+    pub action: String,
+    pub trade_date: String,
+    /// Hundred-millionths of a share, see [`QUANTITY_SCALE`](super::QUANTITY_SCALE)
+    #[serde(default)]
+    pub quantity_units: i64,
+    #[serde(default)]
+    pub amount_cents: i64,
+    #[serde(default)]
+    pub fee_cents: i64,
+    #[serde(default)]
+    pub split_from: Option<i64>,
+    #[serde(default)]
+    pub split_to: Option<i64>,
+    /// The transaction recording the trade's cash side
+    #[serde(default)]
+    pub transaction_id: Option<i64>,
+    /// Added in version 11. Cost basis method a sale was recorded with; sales
+    /// without one take their account's method on import
+    #[serde(default)]
+    pub cost_basis_method: Option<CostBasisMethod>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// Shares of a lot sold by a sale with specific identification.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LotSelectionRecord {
+    /// The selling investment transaction
+    pub sale_id: i64,
+    /// The buy that opened the lot
+    pub lot_id: i64,
+    pub quantity_units: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    pub id: i64,
+    pub account_id: i64,
+    /// Signed amount: negative for debits, positive for credits
+    pub amount_cents: i64,
+    pub transaction_type: String,
+    pub description: String,
+    pub transaction_date: String,
+    pub category_id: i64,
+    /// Added in version 2
+    #[serde(default)]
+    pub cleared: bool,
+    /// Added in version 5. Transactions from older documents are linked to
+    /// payees by their description on import.
+    #[serde(default)]
+    pub payee_id: Option<i64>,
+    /// Added in version 5
+    #[serde(default)]
+    pub original_description: Option<String>,
+    /// Added in version 6. Tag names, sorted
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Added in version 7
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// Added in version 7
+    #[serde(default)]
+    pub check_number: Option<String>,
+    /// Added in version 7. Values keyed by custom field id
+    #[serde(default)]
+    pub custom_fields: CustomFieldValues,
+    /// Added in version 8. The other leg of a transfer
+    #[serde(default)]
+    pub transfer_id: Option<i64>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// How an imported document is combined with the ledger's existing data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Recreate the document in an empty ledger, keeping all ids
+    Restore,
+    /// Add the document to an existing ledger. Accounts and transactions get
+    /// new ids; categories, payees, tags and custom fields are matched to
+    /// existing ones by name (names are unique) and securities by ticker, and
+    /// created otherwise. All references are remapped.
+    Merge,
+}
+
+/// Exports the whole ledger as a [`LedgerDocument`].
+///
+/// Reads inside one transaction so the document is a consistent snapshot even
+/// while other connections write.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(LedgerDocument)` - Snapshot of all accounts, categories and transactions
+/// * `Err(sqlx::Error)` - Query failure
+pub async fn export_ledger(pool: &SqlitePool) -> Result<LedgerDocument, sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        let exported_at: String = sqlx::query_scalar("SELECT strftime('%Y-%m-%dT%H:%M:%SZ', 'now')")
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let base_currency: String = sqlx::query_scalar(&format!("SELECT {}", BASE_CURRENCY))
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let accounts = sqlx::query(
+            "SELECT id, name, account_type, currency, archived, opening_balance_cents, closed_date, display_order, memo, custom_fields,
+                    cost_basis_method, created_at
+             FROM accounts ORDER BY id",
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(AccountRecord {
+                id: row.get("id"),
+                name: row.get("name"),
+                account_type: row.get("account_type"),
+                currency: row.get("currency"),
+                archived: row.get("archived"),
+                opening_balance_cents: row.get("opening_balance_cents"),
+                closed_date: row.get("closed_date"),
+                display_order: row.get("display_order"),
+                memo: row.get("memo"),
+                custom_fields: parse_values(row.get("custom_fields"))?,
+                cost_basis_method: parse_enum(row.get("cost_basis_method"))?,
+                created_at: row.get("created_at"),
+            })
+        })
+        .collect::<Result<Vec<AccountRecord>, sqlx::Error>>()?;
+
+        let categories =
+            sqlx::query("SELECT id, name, parent_id, created_at FROM categories WHERE deleted_at IS NULL ORDER BY id")
+                .fetch_all(&mut *conn)
+                .await?
+                .into_iter()
+                .map(|row| CategoryRecord {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    parent_id: row.get("parent_id"),
+                    created_at: row.get("created_at"),
+                })
+                .collect();
+
+        let payees = sqlx::query(
+            "SELECT id, name, aliases, patterns, default_category_id, created_at FROM payees ORDER BY id",
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(PayeeRecord {
+                id: row.get("id"),
+                name: row.get("name"),
+                aliases: serde_json::from_str(row.get("aliases"))
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+                patterns: serde_json::from_str(row.get("patterns"))
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+                default_category_id: row.get("default_category_id"),
+                created_at: row.get("created_at"),
+            })
+        })
+        .collect::<Result<Vec<PayeeRecord>, sqlx::Error>>()?;
+
+        let tags = sqlx::query("SELECT id, name, created_at FROM tags ORDER BY id")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| TagRecord {
+                id: row.get("id"),
+                name: row.get("name"),
+                created_at: row.get("created_at"),
+            })
+            .collect();
+
+        let custom_fields = sqlx::query("SELECT id, entity, name, field_type, created_at FROM custom_fields ORDER BY id")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok(CustomFieldRecord {
+                    id: row.get("id"),
+                    entity: parse_enum(row.get("entity"))?,
+                    name: row.get("name"),
+                    field_type: parse_enum(row.get("field_type"))?,
+                    created_at: row.get("created_at"),
+                })
+            })
+            .collect::<Result<Vec<CustomFieldRecord>, sqlx::Error>>()?;
+
+        let exchange_rates = sqlx::query(
+            "SELECT rate_date, from_currency, to_currency, rate FROM exchange_rates
+             ORDER BY from_currency, to_currency, rate_date",
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| ExchangeRateRecord {
+            rate_date: row.get("rate_date"),
+            from_currency: row.get("from_currency"),
+            to_currency: row.get("to_currency"),
+            rate: row.get("rate"),
+        })
+        .collect();
+
+        let securities = sqlx::query("SELECT id, ticker, name, security_type, currency, created_at FROM securities ORDER BY id")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok(SecurityRecord {
+                    id: row.get("id"),
+                    ticker: row.get("ticker"),
+                    name: row.get("name"),
+                    security_type: parse_enum(row.get("security_type"))?,
+                    currency: row.get("currency"),
+                    created_at: row.get("created_at"),
+                })
+            })
+            .collect::<Result<Vec<SecurityRecord>, sqlx::Error>>()?;
+
+        let security_prices =
+            sqlx::query("SELECT security_id, price_date, price FROM security_prices ORDER BY security_id, price_date")
+                .fetch_all(&mut *conn)
+                .await?
+                .into_iter()
+                .map(|row| SecurityPriceRecord {
+                    security_id: row.get("security_id"),
+                    price_date: row.get("price_date"),
+                    price: row.get("price"),
+                })
+                .collect();
+
+        let transactions = sqlx::query(&format!(
+            "SELECT id, account_id, amount_cents, transaction_type, description, transaction_date, category_id, cleared,
+                    payee_id, original_description, {} AS tags, memo, check_number, custom_fields,
+                    (SELECT p.id FROM transactions p WHERE p.id = t.transfer_id AND p.deleted_at IS NULL) AS transfer_id,
+                    created_at
+             FROM transactions t WHERE deleted_at IS NULL ORDER BY id",
+            TAG_NAMES
+        ))
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| Ok(TransactionRecord {
+            id: row.get("id"),
+            account_id: row.get("account_id"),
+            amount_cents: row.get("amount_cents"),
+            transaction_type: row.get("transaction_type"),
+            description: row.get("description"),
+            transaction_date: row.get("transaction_date"),
+            category_id: row.get("category_id"),
+            cleared: row.get("cleared"),
+            payee_id: row.get("payee_id"),
+            original_description: row.get("original_description"),
+            tags: tag_names(row.get("tags"))?,
+            memo: row.get("memo"),
+            check_number: row.get("check_number"),
+            custom_fields: parse_values(row.get("custom_fields"))?,
+            transfer_id: row.get("transfer_id"),
+            created_at: row.get("created_at"),
+        }))
+        .collect::<Result<Vec<TransactionRecord>, sqlx::Error>>()?;
+
+        // Cash transactions in the trash are not exported, so trades lose the link
+        let investment_transactions = sqlx::query(
+            "SELECT i.id, i.account_id, i.security_id, i.action, i.trade_date, i.quantity_units, i.amount_cents, i.fee_cents,
+                    i.split_from, i.split_to, t.id AS transaction_id, i.cost_basis_method, i.created_at
+             FROM investment_transactions i
+             LEFT JOIN transactions t ON t.id = i.transaction_id AND t.deleted_at IS NULL
+             ORDER BY i.id",
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(InvestmentTransactionRecord {
+                id: row.get("id"),
+                account_id: row.get("account_id"),
+                security_id: row.get("security_id"),
+                action: row.get("action"),
+                trade_date: row.get("trade_date"),
+                quantity_units: row.get("quantity_units"),
+                amount_cents: row.get("amount_cents"),
+                fee_cents: row.get("fee_cents"),
+                split_from: row.get("split_from"),
+                split_to: row.get("split_to"),
+                transaction_id: row.get("transaction_id"),
+                cost_basis_method: row.get::<Option<String>, _>("cost_basis_method").map(parse_enum).transpose()?,
+                created_at: row.get("created_at"),
+            })
+        })
+        .collect::<Result<Vec<InvestmentTransactionRecord>, sqlx::Error>>()?;
+
+        let lot_selections =
+            sqlx::query("SELECT sale_id, lot_id, quantity_units FROM lot_selections ORDER BY sale_id, lot_id")
+                .fetch_all(&mut *conn)
+                .await?
+                .into_iter()
+                .map(|row| LotSelectionRecord {
+                    sale_id: row.get("sale_id"),
+                    lot_id: row.get("lot_id"),
+                    quantity_units: row.get("quantity_units"),
+                })
+                .collect();
+
+        Ok(LedgerDocument {
+            format: FORMAT_NAME.to_string(),
+            version: FORMAT_VERSION,
+            exported_at,
+            base_currency: Some(base_currency),
+            accounts,
+            categories,
+            payees,
+            tags,
+            custom_fields,
+            exchange_rates,
+            securities,
+            security_prices,
+            transactions,
+            investment_transactions,
+            lot_selections,
+        })
+    })
+    .await
+}
+
+/// Imports a [`LedgerDocument`] into the ledger.
+///
+/// The document is validated first (dangling references and category cycles
+/// are rejected) and then written in a single transaction, so a failed import
+/// leaves the ledger untouched.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `document` - Document to import
+/// * `mode` - [`ImportMode::Restore`] into an empty ledger or [`ImportMode::Merge`]
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with the number of `accounts`, `categories`,
+///   `payees`, `tags`, `custom_fields`, `securities`, `transactions` and
+///   `investment_transactions` created, and `categories_matched` (existing
+///   categories reused by name when merging)
+/// * `Err(sqlx::Error)` - Invalid document, non-empty ledger for a restore, or
+///   database failure
+///
+/// # Examples
+/// ```no_run
+/// let document = read_document(Path::new("household.json"))?;
+/// let summary = import_ledger(&pool, &document, ImportMode::Merge).await?;
+/// println!("Imported {} transactions", summary["transactions"]);
+/// ```
+pub async fn import_ledger(
+    pool: &SqlitePool,
+    document: &LedgerDocument,
+    mode: ImportMode,
+) -> Result<serde_json::Value, sqlx::Error> {
+    check_version(document.version)?;
+    let categories = categories_parents_first(&document.categories)?;
+    check_references(document)?;
+
+    unit_of_work(pool, async |conn| {
+        if mode == ImportMode::Restore {
+            ensure_empty(&mut *conn).await?;
+            if let Some(currency) = &document.base_currency {
+                sqlx::query("UPDATE ledger_settings SET base_currency = ? WHERE id = 1")
+                    .bind(currency_code(currency)?)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            // Only system categories remain; the document brings its own
+            sqlx::query("DELETE FROM categories")
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        let mut category_ids = HashMap::new();
+        let mut categories_created = 0;
+        for category in categories {
+            let parent_id = category.parent_id.map(|id| category_ids[&id]);
+
+            let existing: Option<i64> = match mode {
+                ImportMode::Restore => None,
+                ImportMode::Merge => sqlx::query_scalar("SELECT id FROM categories WHERE name = ?")
+                    .bind(&category.name)
+                    .fetch_optional(&mut *conn)
+                    .await?,
+            };
+            // A trashed namesake comes back rather than blocking the unique name
+            if let Some(id) = existing {
+                restore_category_row(&mut *conn, id).await?;
+            }
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    categories_created += 1;
+                    insert_category(&mut *conn, category, parent_id, mode).await?
+                }
+            };
+            category_ids.insert(category.id, id);
+        }
+
+        let mut payee_ids = HashMap::new();
+        let mut payees_created = 0;
+        for payee in &document.payees {
+            let existing: Option<i64> = match mode {
+                ImportMode::Restore => None,
+                ImportMode::Merge => sqlx::query_scalar("SELECT id FROM payees WHERE name = ?")
+                    .bind(&payee.name)
+                    .fetch_optional(&mut *conn)
+                    .await?,
+            };
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    payees_created += 1;
+                    insert_payee(&mut *conn, payee, &category_ids, mode).await?
+                }
+            };
+            payee_ids.insert(payee.id, id);
+        }
+
+        let mut tags_created = 0;
+        for tag in &document.tags {
+            let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM tags WHERE name = ?)")
+                .bind(&tag.name)
+                .fetch_one(&mut *conn)
+                .await?;
+            if !exists {
+                tags_created += 1;
+                sqlx::query("INSERT INTO tags (id, name, created_at) VALUES (?, ?, COALESCE(?, datetime('now')))")
+                    .bind(restored_id(tag.id, mode))
+                    .bind(&tag.name)
+                    .bind(&tag.created_at)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+
+        let mut field_ids = HashMap::new();
+        let mut fields_created = 0;
+        for field in &document.custom_fields {
+            let existing = match mode {
+                ImportMode::Restore => None,
+                ImportMode::Merge => sqlx::query("SELECT id, field_type FROM custom_fields WHERE entity = ? AND name = ?")
+                    .bind(field.entity.as_str())
+                    .bind(&field.name)
+                    .fetch_optional(&mut *conn)
+                    .await?,
+            };
+            let id = match existing {
+                Some(row) if row.get::<String, _>("field_type") != field.field_type.as_str() => {
+                    return Err(sqlx::Error::Protocol(format!(
+                        "custom field {} is a {} field in this ledger",
+                        field.name,
+                        row.get::<String, _>("field_type")
+                    )));
+                }
+                Some(row) => row.get("id"),
+                None => {
+                    fields_created += 1;
+                    sqlx::query(
+                        "INSERT INTO custom_fields (id, entity, name, field_type, created_at)
+                         VALUES (?, ?, ?, ?, COALESCE(?, datetime('now')))",
+                    )
+                    .bind(restored_id(field.id, mode))
+                    .bind(field.entity.as_str())
+                    .bind(&field.name)
+                    .bind(field.field_type.as_str())
+                    .bind(&field.created_at)
+                    .execute(&mut *conn)
+                    .await?
+                    .last_insert_rowid()
+                }
+            };
+            field_ids.insert(field.id, id);
+        }
+
+        for rate in &document.exchange_rates {
+            sqlx::query(
+                "INSERT INTO exchange_rates (rate_date, from_currency, to_currency, rate) VALUES (?, ?, ?, ?)
+                 ON CONFLICT (from_currency, to_currency, rate_date) DO UPDATE SET rate = excluded.rate",
+            )
+            .bind(&rate.rate_date)
+            .bind(currency_code(&rate.from_currency)?)
+            .bind(currency_code(&rate.to_currency)?)
+            .bind(rate.rate)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        let mut security_ids = HashMap::new();
+        let mut securities_created = 0;
+        for security in &document.securities {
+            let currency = currency_code(&security.currency)?;
+            let existing = match mode {
+                ImportMode::Restore => None,
+                ImportMode::Merge => sqlx::query("SELECT id, currency FROM securities WHERE ticker = ?")
+                    .bind(&security.ticker)
+                    .fetch_optional(&mut *conn)
+                    .await?,
+            };
+            let id = match existing {
+                Some(row) if row.get::<String, _>("currency") != currency => {
+                    return Err(sqlx::Error::Protocol(format!(
+                        "security {} trades in {} in this ledger",
+                        security.ticker,
+                        row.get::<String, _>("currency")
+                    )));
+                }
+                Some(row) => row.get("id"),
+                None => {
+                    securities_created += 1;
+                    sqlx::query(
+                        "INSERT INTO securities (id, ticker, name, security_type, currency, created_at)
+                         VALUES (?, ?, ?, ?, ?, COALESCE(?, datetime('now')))",
+                    )
+                    .bind(restored_id(security.id, mode))
+                    .bind(&security.ticker)
+                    .bind(&security.name)
+                    .bind(security.security_type.as_str())
+                    .bind(currency)
+                    .bind(&security.created_at)
+                    .execute(&mut *conn)
+                    .await?
+                    .last_insert_rowid()
+                }
+            };
+            security_ids.insert(security.id, id);
+        }
+
+        for price in &document.security_prices {
+            sqlx::query(
+                "INSERT INTO security_prices (security_id, price_date, price) VALUES (?, ?, ?)
+                 ON CONFLICT (security_id, price_date) DO UPDATE SET price = excluded.price",
+            )
+            .bind(security_ids[&price.security_id])
+            .bind(&price.price_date)
+            .bind(price.price)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        let mut account_ids = HashMap::new();
+        for account in &document.accounts {
+            let currency = account.currency.as_deref().map(currency_code).transpose()?;
+            let custom_fields = values_json(
+                &mut *conn,
+                CustomFieldEntity::Account,
+                &remap_values(&account.custom_fields, &field_ids),
+            )
+            .await?;
+            let id = sqlx::query(
+                &format!(
+                    "INSERT INTO accounts (id, name, account_type, currency, archived, opening_balance_cents, closed_date, display_order, memo, custom_fields,
+                                           cost_basis_method, created_at)
+                     VALUES (?, ?, ?, COALESCE(?, {}), ?, ?, ?,
+                             COALESCE(?, (SELECT COALESCE(MAX(display_order), 0) + 1 FROM accounts)),
+                             ?, ?, ?, COALESCE(?, CURRENT_TIMESTAMP))",
+                    BASE_CURRENCY
+                ),
+            )
+            .bind(restored_id(account.id, mode))
+            .bind(&account.name)
+            .bind(&account.account_type)
+            .bind(currency)
+            .bind(account.archived)
+            .bind(account.opening_balance_cents)
+            .bind(&account.closed_date)
+            // Merged accounts go after the ledger's own ones
+            .bind(account.display_order.filter(|_| mode == ImportMode::Restore))
+            .bind(&account.memo)
+            .bind(custom_fields)
+            .bind(account.cost_basis_method.as_str())
+            .bind(&account.created_at)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+            account_ids.insert(account.id, id);
+        }
+
+        let mut transaction_ids = HashMap::new();
+        for transaction in &document.transactions {
+            let (description, payee_id, original_description) = match transaction.payee_id {
+                Some(payee_id) => (
+                    transaction.description.clone(),
+                    Some(payee_ids[&payee_id]),
+                    transaction.original_description.clone(),
+                ),
+                // Since version 5 a missing payee is deliberate, e.g. on transfers
+                None if document.version >= 5 => (
+                    transaction.description.clone(),
+                    None,
+                    transaction.original_description.clone(),
+                ),
+                None => {
+                    let payee = resolve_payee(&mut *conn, &transaction.description).await?;
+                    (payee.description, Some(payee.payee_id), Some(transaction.description.clone()))
+                }
+            };
+
+            let custom_fields = values_json(
+                &mut *conn,
+                CustomFieldEntity::Transaction,
+                &remap_values(&transaction.custom_fields, &field_ids),
+            )
+            .await?;
+
+            let id = sqlx::query(
+                "INSERT INTO transactions (id, account_id, amount_cents, transaction_type, description, transaction_date, category_id, cleared,
+                                           payee_id, original_description, memo, check_number, custom_fields, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, datetime('now')))",
+            )
+            .bind(restored_id(transaction.id, mode))
+            .bind(account_ids[&transaction.account_id])
+            .bind(transaction.amount_cents)
+            .bind(&transaction.transaction_type)
+            .bind(description)
+            .bind(&transaction.transaction_date)
+            .bind(category_ids[&transaction.category_id])
+            .bind(transaction.cleared)
+            .bind(payee_id)
+            .bind(original_description)
+            .bind(&transaction.memo)
+            .bind(&transaction.check_number)
+            .bind(custom_fields)
+            .bind(&transaction.created_at)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+            transaction_ids.insert(transaction.id, id);
+
+            for tag in &transaction.tags {
+                let tag_id = ensure_tag(&mut *conn, tag).await?;
+                sqlx::query("INSERT OR IGNORE INTO transaction_tags (transaction_id, tag_id) VALUES (?, ?)")
+                    .bind(id)
+                    .bind(tag_id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+
+        // Transfer legs point at each other, so they are linked once both exist
+        for transaction in document.transactions.iter().filter(|t| t.transfer_id.is_some()) {
+            sqlx::query("UPDATE transactions SET transfer_id = ? WHERE id = ?")
+                .bind(transaction.transfer_id.map(|id| transaction_ids[&id]))
+                .bind(transaction_ids[&transaction.id])
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        let mut trade_ids = HashMap::new();
+        let mut splits = HashSet::new();
+        for trade in &document.investment_transactions {
+            // Older versions recorded a split in every account holding the security
+            if trade.action == "split" && !splits.insert((trade.security_id, trade.trade_date.as_str())) {
+                continue;
+            }
+            let id = sqlx::query(
+                "INSERT INTO investment_transactions (id, account_id, security_id, action, trade_date, quantity_units, amount_cents,
+                                                      fee_cents, split_from, split_to, transaction_id, cost_basis_method, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11,
+                         CASE WHEN ?4 = 'sell' THEN COALESCE(?12, (SELECT cost_basis_method FROM accounts WHERE id = ?2)) END,
+                         COALESCE(?13, datetime('now')))",
+            )
+            .bind(restored_id(trade.id, mode))
+            .bind(account_ids[&trade.account_id])
+            .bind(security_ids[&trade.security_id])
+            .bind(&trade.action)
+            .bind(&trade.trade_date)
+            .bind(trade.quantity_units)
+            .bind(trade.amount_cents)
+            .bind(trade.fee_cents)
+            .bind(trade.split_from)
+            .bind(trade.split_to)
+            .bind(trade.transaction_id.map(|id| transaction_ids[&id]))
+            .bind(trade.cost_basis_method.map(CostBasisMethod::as_str))
+            .bind(&trade.created_at)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+            trade_ids.insert(trade.id, id);
+        }
+
+        for selection in &document.lot_selections {
+            sqlx::query("INSERT INTO lot_selections (sale_id, lot_id, quantity_units) VALUES (?, ?, ?)")
+                .bind(trade_ids[&selection.sale_id])
+                .bind(trade_ids[&selection.lot_id])
+                .bind(selection.quantity_units)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        // A restored document may predate or omit the system categories
+        sqlx::query(
+            "INSERT OR IGNORE INTO categories (name, parent_id) VALUES ('Uncategorized', NULL)",
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(serde_json::json!({
+            "accounts": document.accounts.len(),
+            "categories": categories_created,
+            "categories_matched": document.categories.len() - categories_created,
+            "payees": payees_created,
+            "tags": tags_created,
+            "custom_fields": fields_created,
+            "securities": securities_created,
+            "transactions": document.transactions.len(),
+            "investment_transactions": document.investment_transactions.len()
+        }))
+    })
+    .await
+}
+
+/// Writes a document to a file as pretty-printed JSON.
+pub fn write_document(path: &Path, document: &LedgerDocument) -> Result<(), sqlx::Error> {
+    let json = serde_json::to_string_pretty(document)
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+    std::fs::write(path, json + "\n")?;
+
+    Ok(())
+}
+
+/// Reads a document from a file, checking its format marker and version.
+///
+/// The version is checked before the records are deserialized, so a document
+/// from a newer app version fails with a clear message rather than a field error.
+pub fn read_document(path: &Path) -> Result<LedgerDocument, sqlx::Error> {
+    let contents = std::fs::read_to_string(path)?;
+    let value: serde_json::Value =
+        serde_json::from_str(&contents).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+    if value["format"] != FORMAT_NAME {
+        return Err(sqlx::Error::Protocol(format!(
+            "{} is not a finsight ledger export",
+            path.display()
+        )));
+    }
+    let version = value["version"].as_u64().unwrap_or(0);
+    check_version(u32::try_from(version).unwrap_or(u32::MAX))?;
+
+    serde_json::from_value(value).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+fn check_version(version: u32) -> Result<(), sqlx::Error> {
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(sqlx::Error::Protocol(format!(
+            "unsupported ledger export version {} (this version reads 1 to {})",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    Ok(())
+}
+
+/// Orders categories so every parent precedes its children.
+///
+/// Foreign keys are enforced on insert, and merged categories need their
+/// parent's new id, so the tree is imported top-down.
+fn categories_parents_first(
+    categories: &[CategoryRecord],
+) -> Result<Vec<&CategoryRecord>, sqlx::Error> {
+    let known: HashMap<i64, &CategoryRecord> = categories.iter().map(|c| (c.id, c)).collect();
+    if known.len() != categories.len() {
+        return Err(sqlx::Error::Protocol(
+            "the export contains duplicate category ids".to_string(),
+        ));
+    }
+
+    let mut ordered: Vec<&CategoryRecord> = Vec::with_capacity(categories.len());
+    let mut placed = std::collections::HashSet::new();
+    while ordered.len() < categories.len() {
+        let before = ordered.len();
+        for category in categories {
+            if placed.contains(&category.id) {
+                continue;
+            }
+            match category.parent_id {
+                Some(parent) if !known.contains_key(&parent) => {
+                    return Err(sqlx::Error::Protocol(format!(
+                        "category {} references unknown parent {}",
+                        category.id, parent
+                    )));
+                }
+                Some(parent) if !placed.contains(&parent) => {}
+                _ => {
+                    placed.insert(category.id);
+                    ordered.push(category);
+                }
+            }
+        }
+        if ordered.len() == before {
+            return Err(sqlx::Error::Protocol(
+                "the export's category tree contains a cycle".to_string(),
+            ));
+        }
+    }
+
+    Ok(ordered)
+}
+
+fn check_references(document: &LedgerDocument) -> Result<(), sqlx::Error> {
+    let accounts: HashMap<i64, ()> = document.accounts.iter().map(|a| (a.id, ())).collect();
+    let categories: HashMap<i64, ()> = document.categories.iter().map(|c| (c.id, ())).collect();
+    let payees: HashMap<i64, ()> = document.payees.iter().map(|p| (p.id, ())).collect();
+    if accounts.len() != document.accounts.len() {
+        return Err(sqlx::Error::Protocol(
+            "the export contains duplicate account ids".to_string(),
+        ));
+    }
+
+    let transactions: HashMap<i64, ()> = document.transactions.iter().map(|t| (t.id, ())).collect();
+    for transaction in &document.transactions {
+        if let Some(transfer_id) = transaction.transfer_id.filter(|id| !transactions.contains_key(id)) {
+            return Err(sqlx::Error::Protocol(format!(
+                "transaction {} references unknown transfer leg {}",
+                transaction.id, transfer_id
+            )));
+        }
+        if !accounts.contains_key(&transaction.account_id) {
+            return Err(sqlx::Error::Protocol(format!(
+                "transaction {} references unknown account {}",
+                transaction.id, transaction.account_id
+            )));
+        }
+        if !categories.contains_key(&transaction.category_id) {
+            return Err(sqlx::Error::Protocol(format!(
+                "transaction {} references unknown category {}",
+                transaction.id, transaction.category_id
+            )));
+        }
+        if let Some(payee_id) = transaction.payee_id.filter(|id| !payees.contains_key(id)) {
+            return Err(sqlx::Error::Protocol(format!(
+                "transaction {} references unknown payee {}",
+                transaction.id, payee_id
+            )));
+        }
+    }
+    let securities: HashMap<i64, ()> = document.securities.iter().map(|s| (s.id, ())).collect();
+    if let Some(price) = document.security_prices.iter().find(|p| !securities.contains_key(&p.security_id)) {
+        return Err(sqlx::Error::Protocol(format!(
+            "a price on {} references unknown security {}",
+            price.price_date, price.security_id
+        )));
+    }
+    for trade in &document.investment_transactions {
+        if !["buy", "sell", "dividend", "split"].contains(&trade.action.as_str()) {
+            return Err(sqlx::Error::Protocol(format!(
+                "investment transaction {} has unknown action {}",
+                trade.id, trade.action
+            )));
+        }
+        if !accounts.contains_key(&trade.account_id) {
+            return Err(sqlx::Error::Protocol(format!(
+                "investment transaction {} references unknown account {}",
+                trade.id, trade.account_id
+            )));
+        }
+        if !securities.contains_key(&trade.security_id) {
+            return Err(sqlx::Error::Protocol(format!(
+                "investment transaction {} references unknown security {}",
+                trade.id, trade.security_id
+            )));
+        }
+        if let Some(transaction_id) = trade.transaction_id.filter(|id| !transactions.contains_key(id)) {
+            return Err(sqlx::Error::Protocol(format!(
+                "investment transaction {} references unknown transaction {}",
+                trade.id, transaction_id
+            )));
+        }
+    }
+    let trades: HashMap<i64, &str> = document.investment_transactions.iter().map(|i| (i.id, i.action.as_str())).collect();
+    for selection in &document.lot_selections {
+        let expected = [(selection.sale_id, "sell"), (selection.lot_id, "buy")];
+        if let Some((trade_id, action)) = expected.into_iter().find(|(id, action)| trades.get(id) != Some(action)) {
+            return Err(sqlx::Error::Protocol(format!(
+                "a lot selection references investment transaction {}, which is not a {}",
+                trade_id, action
+            )));
+        }
+    }
+    for payee in &document.payees {
+        if let Some(category_id) = payee.default_category_id.filter(|id| !categories.contains_key(id)) {
+            return Err(sqlx::Error::Protocol(format!(
+                "payee {} references unknown category {}",
+                payee.id, category_id
+            )));
+        }
+    }
+
+    // Values are type-checked on insert, once the fields exist in the ledger
+    let fields: HashMap<i64, CustomFieldEntity> =
+        document.custom_fields.iter().map(|f| (f.id, f.entity)).collect();
+    let values = document
+        .accounts
+        .iter()
+        .map(|a| ("account", a.id, CustomFieldEntity::Account, &a.custom_fields))
+        .chain(document.transactions.iter().map(|t| {
+            ("transaction", t.id, CustomFieldEntity::Transaction, &t.custom_fields)
+        }));
+    for (kind, id, entity, values) in values {
+        if let Some(field_id) = values.keys().find(|field_id| fields.get(field_id) != Some(&entity)) {
+            return Err(sqlx::Error::Protocol(format!(
+                "{} {} references unknown custom field {}",
+                kind, id, field_id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Rekeys custom field values from document field ids to ledger field ids.
+fn remap_values(values: &CustomFieldValues, field_ids: &HashMap<i64, i64>) -> CustomFieldValues {
+    values
+        .iter()
+        .map(|(field_id, value)| (field_ids[field_id], value.clone()))
+        .collect()
+}
+
+fn parse_values(json: String) -> Result<CustomFieldValues, sqlx::Error> {
+    serde_json::from_str(&json).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+fn parse_enum<T: serde::de::DeserializeOwned>(text: String) -> Result<T, sqlx::Error> {
+    serde_json::from_value(serde_json::Value::String(text)).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Fails unless the ledger holds no accounts, transactions, payees, tags,
+/// custom fields, securities or user categories.
+async fn ensure_empty(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let records: i64 = sqlx::query_scalar(
+        "SELECT (SELECT COUNT(*) FROM accounts)
+              + (SELECT COUNT(*) FROM transactions)
+              + (SELECT COUNT(*) FROM categories WHERE name != 'Uncategorized')
+              + (SELECT COUNT(*) FROM payees)
+              + (SELECT COUNT(*) FROM tags)
+              + (SELECT COUNT(*) FROM custom_fields)
+              + (SELECT COUNT(*) FROM securities)",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if records > 0 {
+        return Err(sqlx::Error::Protocol(
+            "restoring requires an empty ledger; create a new ledger or merge instead".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn insert_category(
+    conn: &mut SqliteConnection,
+    category: &CategoryRecord,
+    parent_id: Option<i64>,
+    mode: ImportMode,
+) -> Result<i64, sqlx::Error> {
+    let id = sqlx::query(
+        "INSERT INTO categories (id, name, parent_id, created_at)
+         VALUES (?, ?, ?, COALESCE(?, datetime('now')))",
+    )
+    .bind(restored_id(category.id, mode))
+    .bind(&category.name)
+    .bind(parent_id)
+    .bind(&category.created_at)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    Ok(id)
+}
+
+async fn insert_payee(
+    conn: &mut SqliteConnection,
+    payee: &PayeeRecord,
+    category_ids: &HashMap<i64, i64>,
+    mode: ImportMode,
+) -> Result<i64, sqlx::Error> {
+    let aliases = serde_json::to_string(&payee.aliases).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+    let patterns = serde_json::to_string(&payee.patterns).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+    let id = sqlx::query(
+        "INSERT INTO payees (id, name, aliases, patterns, default_category_id, created_at)
+         VALUES (?, ?, ?, ?, ?, COALESCE(?, datetime('now')))",
+    )
+    .bind(restored_id(payee.id, mode))
+    .bind(&payee.name)
+    .bind(aliases)
+    .bind(patterns)
+    .bind(payee.default_category_id.map(|id| category_ids[&id]))
+    .bind(&payee.created_at)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    Ok(id)
+}
+
+/// Explicit id to insert with: the document's id when restoring, a fresh one when merging.
+fn restored_id(id: i64, mode: ImportMode) -> Option<i64> {
+    match mode {
+        ImportMode::Restore => Some(id),
+        ImportMode::Merge => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{AccountStatus, Currency, Money};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+        pool
+    }
+
+    async fn populated_ledger() -> SqlitePool {
+        let pool = setup_test_db().await;
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_account(&pool, "Old Savings".to_string(), "savings".to_string())
+            .await
+            .unwrap();
+        crate::database::update_account(&pool, 2, "Old Savings".to_string(), "savings".to_string(), true, None)
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Food".to_string(), None).await.unwrap();
+        crate::database::add_category(&pool, "Groceries".to_string(), Some(2)).await.unwrap();
+        let shared = crate::database::add_custom_field(&pool, CustomFieldEntity::Transaction, "Shared".to_string(), CustomFieldType::Boolean)
+            .await
+            .unwrap();
+        let details = crate::database::TransactionDetails {
+            memo: Some("Split with roommate".to_string()),
+            check_number: None,
+            custom_fields: CustomFieldValues::from([(shared, serde_json::json!(true))]),
+        };
+        crate::database::add_transaction_with_details(&pool, 1, Money::new(-4250, Currency::USD), "debit".to_string(), "Market".to_string(), "2025-08-15".to_string(), 3, &details)
+            .await
+            .unwrap();
+        let selection = crate::database::TransactionSelection::Ids(vec![1]);
+        crate::database::bulk_tag_transactions(&pool, &selection, &["summer".to_string()])
+            .await
+            .unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_export_and_restore_round_trip() {
+        let source = populated_ledger().await;
+        let euro = crate::database::AccountDetails {
+            currency: Some("EUR".to_string()),
+            ..Default::default()
+        };
+        crate::database::update_account(&source, 2, "Old Savings".to_string(), "savings".to_string(), true, Some(&euro))
+            .await
+            .unwrap();
+        crate::database::set_exchange_rate(&source, "2025-08-01".to_string(), "EUR".to_string(), "USD".to_string(), 1.1)
+            .await
+            .unwrap();
+        crate::database::add_transfer(&source, 1, 2, Money::new(1100, Currency::USD), None, "2025-08-20".to_string(), None)
+            .await
+            .unwrap();
+        let vti = crate::database::add_security(&source, "VTI".to_string(), "Vanguard Total Stock Market".to_string(), SecurityType::Etf, None)
+            .await
+            .unwrap();
+        crate::database::set_security_price(&source, vti, "2025-08-29".to_string(), 301.5).await.unwrap();
+        let buy = crate::database::InvestmentTrade::Buy {
+            quantity: 2.5,
+            amount: Money::new(75000, Currency::USD),
+            fee: None,
+        };
+        let lot = crate::database::add_investment_transaction(&source, 1, vti, "2025-08-25".to_string(), &buy, None)
+            .await
+            .unwrap();
+        crate::database::set_cost_basis_method(&source, 1, CostBasisMethod::Specific).await.unwrap();
+        let sell = crate::database::InvestmentTrade::Sell {
+            quantity: 1.0,
+            amount: Money::new(30500, Currency::USD),
+            fee: None,
+            lots: vec![crate::database::LotSelection { lot_id: lot, quantity: 1.0 }],
+        };
+        crate::database::add_investment_transaction(&source, 1, vti, "2025-08-28".to_string(), &sell, None)
+            .await
+            .unwrap();
+        let document = export_ledger(&source).await.unwrap();
+        assert_eq!(document.accounts.len(), 2);
+        assert!(document.accounts[1].archived);
+        assert_eq!(document.accounts[1].currency.as_deref(), Some("EUR"));
+        assert_eq!(document.transactions[1].transfer_id, Some(3));
+        assert_eq!(document.security_prices.len(), 1);
+        assert_eq!(document.investment_transactions[0].quantity_units, 250_000_000);
+        assert_eq!(document.investment_transactions[0].transaction_id, Some(4));
+        assert_eq!(document.accounts[0].cost_basis_method, CostBasisMethod::Specific);
+        assert_eq!(document.investment_transactions[0].cost_basis_method, None);
+        assert_eq!(document.investment_transactions[1].cost_basis_method, Some(CostBasisMethod::Specific));
+        assert_eq!(document.lot_selections, vec![LotSelectionRecord { sale_id: 2, lot_id: 1, quantity_units: 100_000_000 }]);
+
+        let target = setup_test_db().await;
+        import_ledger(&target, &document, ImportMode::Restore).await.unwrap();
+
+        let mut restored = export_ledger(&target).await.unwrap();
+        restored.exported_at = document.exported_at.clone();
+        assert_eq!(restored, document);
+
+        // A second restore into the now non-empty ledger is refused
+        assert!(import_ledger(&target, &document, ImportMode::Restore).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_merge_remaps_ids() {
+        let document = export_ledger(&populated_ledger().await).await.unwrap();
+
+        let target = setup_test_db().await;
+        crate::database::add_account(&target, "Credit Card".to_string(), "credit".to_string())
+            .await
+            .unwrap();
+        crate::database::add_category(&target, "Groceries".to_string(), None).await.unwrap();
+        crate::database::add_custom_field(&target, CustomFieldEntity::Transaction, "Receipt".to_string(), CustomFieldType::Text)
+            .await
+            .unwrap();
+
+        let summary = import_ledger(&target, &document, ImportMode::Merge).await.unwrap();
+        assert_eq!(summary["accounts"], 2);
+        assert_eq!(summary["custom_fields"], 1);
+        assert_eq!(summary["categories"], 1); // Only Food is new
+        assert_eq!(summary["categories_matched"], 2); // Uncategorized and Groceries
+
+        // The imported checking account got id 2; its transaction follows it
+        let transactions = crate::database::get_transactions(&target, 2).await.unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0]["category_id"], 2); // Existing Groceries
+        assert_eq!(transactions[0]["memo"], "Split with roommate");
+        assert_eq!(transactions[0]["custom_fields"], serde_json::json!({ "2": true })); // Shared got id 2
+    }
+
+    #[tokio::test]
+    async fn test_merge_links_payees_of_older_documents() {
+        let mut document = export_ledger(&populated_ledger().await).await.unwrap();
+        assert_eq!(document.payees.len(), 1);
+
+        // Version 4 documents carry no payees
+        document.version = 4;
+        document.payees.clear();
+        document.transactions[0].payee_id = None;
+        document.transactions[0].description = "SQ *MARKET".to_string();
+
+        let target = setup_test_db().await;
+        let summary = import_ledger(&target, &document, ImportMode::Merge).await.unwrap();
+        assert_eq!(summary["payees"], 0);
+
+        let payees = crate::database::get_payees(&target).await.unwrap();
+        assert_eq!(payees.len(), 1);
+        assert_eq!(payees[0]["name"], "Market");
+        assert_eq!(payees[0]["transactions"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_invalid_documents() {
+        let mut document = export_ledger(&populated_ledger().await).await.unwrap();
+        let target = setup_test_db().await;
+
+        document.version = FORMAT_VERSION + 1;
+        assert!(import_ledger(&target, &document, ImportMode::Merge).await.is_err());
+
+        document.version = FORMAT_VERSION;
+        document.transactions[0].account_id = 99;
+        assert!(import_ledger(&target, &document, ImportMode::Merge).await.is_err());
+
+        document.transactions[0].account_id = 1;
+        document.categories[1].parent_id = Some(3); // Food <-> Groceries
+        assert!(import_ledger(&target, &document, ImportMode::Merge).await.is_err());
+
+        document.categories[1].parent_id = None;
+        document.custom_fields[0].field_type = CustomFieldType::Number; // true is no number
+        assert!(import_ledger(&target, &document, ImportMode::Merge).await.is_err());
+
+        // Nothing was written by the failed imports
+        assert_eq!(crate::database::get_all_accounts(&target, AccountStatus::All).await.unwrap().len(), 0);
+    }
+}
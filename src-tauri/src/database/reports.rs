@@ -0,0 +1,347 @@
+//! Ledger-wide reports in the base currency.
+//!
+//! Amounts are converted with the rules described in
+//! [`currencies`](super::currencies): balances at the rate of the as-of date,
+//! income and expenses at the rate of each transaction's date.
+
+use super::currencies::{BASE_CURRENCY, convert, ensure_rates, to_base_sql};
+use super::investments::holdings_of;
+use super::money::{Currency, Money};
+use super::unit_of_work;
+use sqlx::{Row, SqlitePool};
+use std::collections::BTreeMap;
+
+/// Reports every account's balance and the household's net worth on a date.
+///
+/// Securities held in an account count at their market value on the date,
+/// see [`get_holdings()`](super::get_holdings).
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `as_of` - Last date (YYYY-MM-DD) whose transactions count; defaults to today
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `base_currency`, `as_of`, `accounts`
+///   (id, name, currency, archived, `balance_cents` and `holdings_value_cents`
+///   in the account's currency, `base_balance_cents` and
+///   `base_holdings_value_cents`, in sidebar order) and `net_worth_cents`
+/// * `Err(sqlx::Error)` - No exchange rate for an account with a balance, a
+///   total too large to represent, or database failure
+///
+/// # Examples
+/// ```no_run
+/// let report = get_net_worth(&pool, Some("2025-12-31".to_string())).await?;
+/// println!("Net worth: {} {}", report["net_worth_cents"], report["base_currency"]);
+/// ```
+pub async fn get_net_worth(pool: &SqlitePool, as_of: Option<String>) -> Result<serde_json::Value, sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        let as_of: String = match as_of {
+            Some(date) => date,
+            None => sqlx::query_scalar("SELECT date('now')").fetch_one(&mut *conn).await?,
+        };
+        let base_currency: String = sqlx::query_scalar(&format!("SELECT {}", BASE_CURRENCY))
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT b.*, CASE WHEN b.balance_cents = 0 THEN 0 ELSE {} END AS base_balance_cents
+             FROM (
+                 SELECT a.id, a.name, a.currency, a.archived, a.display_order,
+                        a.opening_balance_cents + COALESCE(SUM(t.amount_cents), 0) AS balance_cents
+                 FROM accounts a
+                 LEFT JOIN transactions t ON t.account_id = a.id AND t.deleted_at IS NULL AND t.transaction_date <= ?1
+                 GROUP BY a.id
+             ) b
+             ORDER BY b.display_order, b.id",
+            to_base_sql("b.balance_cents", "b.currency", "?1")
+        ))
+        .bind(&as_of)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut holdings: BTreeMap<i64, Money> = BTreeMap::new();
+        for holding in holdings_of(&mut *conn, None, Some(&as_of)).await? {
+            let value = holding.market_value;
+            let total = holdings.entry(holding.account_id).or_insert(Money::zero(value.currency()));
+            *total = total.checked_add(value)?;
+        }
+        let base = Currency::new(&base_currency)?;
+
+        let mut net_worth = Money::zero(base);
+        let mut accounts = Vec::new();
+        for row in rows {
+            let currency: String = row.get("currency");
+            let base_balance: i64 = row.get::<Option<i64>, _>("base_balance_cents").ok_or_else(|| {
+                sqlx::Error::Protocol(format!(
+                    "no exchange rate from {} to {} on or before {}; add or import rates first",
+                    currency, base_currency, as_of
+                ))
+            })?;
+            let account_id: i64 = row.get("id");
+            let (holdings_value, base_holdings_value) = match holdings.get(&account_id) {
+                Some(&value) => (value, convert(&mut *conn, value, base, &as_of).await?),
+                None => (Money::zero(Currency::new(&currency)?), Money::zero(base)),
+            };
+            net_worth = net_worth.checked_add(Money::new(base_balance, base))?.checked_add(base_holdings_value)?;
+            accounts.push(serde_json::json!({
+                "account_id": account_id,
+                "name": row.get::<String, _>("name"),
+                "currency": currency,
+                "archived": row.get::<bool, _>("archived"),
+                "balance_cents": row.get::<i64, _>("balance_cents"),
+                "holdings_value_cents": holdings_value.amount_cents(),
+                "base_balance_cents": base_balance,
+                "base_holdings_value_cents": base_holdings_value.amount_cents()
+            }));
+        }
+
+        Ok(serde_json::json!({
+            "base_currency": base_currency,
+            "as_of": as_of,
+            "accounts": accounts,
+            "net_worth_cents": net_worth.amount_cents()
+        }))
+    })
+    .await
+}
+
+/// Reports income and expenses per category over a period, with the realized
+/// exchange differences of transfers between accounts in different currencies.
+///
+/// Transfer legs and the cash side of buying or selling securities are not
+/// income or expenses; dividends are. When a transfer's two legs are
+/// worth different amounts in the base currency at the transfer date's rates,
+/// for example because the bank used a worse rate, the difference is a realized
+/// FX gain (positive) or loss (negative).
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `date_from` - Optional first date (YYYY-MM-DD) included
+/// * `date_to` - Optional last date (YYYY-MM-DD) included
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `base_currency`, `categories` (id,
+///   name, `income_cents`, `expense_cents`, by name), `total_income_cents`,
+///   `total_expense_cents`, `fx_differences` (per transfer: both transaction
+///   ids, date, currencies, amounts and `fx_difference_cents`),
+///   `total_fx_difference_cents` and `net_cents`
+/// * `Err(sqlx::Error)` - A transaction in the period cannot be converted, a
+///   total too large to represent, or database failure
+pub async fn get_income_statement(
+    pool: &SqlitePool,
+    date_from: Option<String>,
+    date_to: Option<String>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    unit_of_work(pool, async |conn| {
+        ensure_rates(&mut *conn, &date_from, &date_to).await?;
+        let base_currency: String = sqlx::query_scalar(&format!("SELECT {}", BASE_CURRENCY))
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let categories = sqlx::query(&format!(
+            "SELECT c.id, c.name,
+                    COALESCE(SUM(CASE WHEN t.base_cents > 0 THEN t.base_cents END), 0) AS income_cents,
+                    COALESCE(SUM(CASE WHEN t.base_cents < 0 THEN -t.base_cents END), 0) AS expense_cents
+             FROM (
+                 SELECT t.category_id, {} AS base_cents
+                 FROM transactions t
+                 JOIN accounts a ON a.id = t.account_id
+                 WHERE t.deleted_at IS NULL AND t.transfer_id IS NULL
+                   AND t.id NOT IN (
+                       SELECT transaction_id FROM investment_transactions
+                       WHERE action IN ('buy', 'sell') AND transaction_id IS NOT NULL
+                   )
+                   AND (?1 IS NULL OR t.transaction_date >= ?1)
+                   AND (?2 IS NULL OR t.transaction_date <= ?2)
+             ) t
+             JOIN categories c ON c.id = t.category_id
+             GROUP BY c.id
+             ORDER BY c.name",
+            to_base_sql("t.amount_cents", "a.currency", "t.transaction_date")
+        ))
+        .bind(&date_from)
+        .bind(&date_to)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let transfers = sqlx::query(&format!(
+            "SELECT t.id AS from_transaction_id, p.id AS to_transaction_id, t.transaction_date, t.description,
+                    a.currency AS from_currency, pa.currency AS to_currency,
+                    -t.amount_cents AS sent_cents, p.amount_cents AS received_cents,
+                    {} + {} AS fx_difference_cents
+             FROM transactions t
+             JOIN transactions p ON p.id = t.transfer_id AND p.deleted_at IS NULL
+             JOIN accounts a ON a.id = t.account_id
+             JOIN accounts pa ON pa.id = p.account_id
+             WHERE t.deleted_at IS NULL AND t.amount_cents < 0 AND a.currency != pa.currency
+               AND (?1 IS NULL OR t.transaction_date >= ?1)
+               AND (?2 IS NULL OR t.transaction_date <= ?2)
+             ORDER BY t.transaction_date, t.id",
+            to_base_sql("p.amount_cents", "pa.currency", "p.transaction_date"),
+            to_base_sql("t.amount_cents", "a.currency", "t.transaction_date")
+        ))
+        .bind(&date_from)
+        .bind(&date_to)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let base = Currency::new(&base_currency)?;
+        let (mut income, mut expenses, mut fx) = (Money::zero(base), Money::zero(base), Money::zero(base));
+        let categories = categories
+            .into_iter()
+            .map(|row| {
+                let (category_income, category_expense): (i64, i64) = (row.get("income_cents"), row.get("expense_cents"));
+                income = income.checked_add(Money::new(category_income, base))?;
+                expenses = expenses.checked_add(Money::new(category_expense, base))?;
+                Ok(serde_json::json!({
+                    "category_id": row.get::<i64, _>("id"),
+                    "name": row.get::<String, _>("name"),
+                    "income_cents": category_income,
+                    "expense_cents": category_expense
+                }))
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+        let fx_differences = transfers
+            .into_iter()
+            .map(|row| {
+                let difference: i64 = row.get("fx_difference_cents");
+                fx = fx.checked_add(Money::new(difference, base))?;
+                Ok(serde_json::json!({
+                    "from_transaction_id": row.get::<i64, _>("from_transaction_id"),
+                    "to_transaction_id": row.get::<i64, _>("to_transaction_id"),
+                    "transaction_date": row.get::<String, _>("transaction_date"),
+                    "description": row.get::<String, _>("description"),
+                    "from_currency": row.get::<String, _>("from_currency"),
+                    "to_currency": row.get::<String, _>("to_currency"),
+                    "sent_cents": row.get::<i64, _>("sent_cents"),
+                    "received_cents": row.get::<i64, _>("received_cents"),
+                    "fx_difference_cents": difference
+                }))
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+        Ok(serde_json::json!({
+            "base_currency": base_currency,
+            "categories": categories,
+            "total_income_cents": income.amount_cents(),
+            "total_expense_cents": expenses.amount_cents(),
+            "fx_differences": fx_differences,
+            "total_fx_difference_cents": fx.amount_cents(),
+            "net_cents": income.checked_sub(expenses)?.checked_add(fx)?.amount_cents()
+        }))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{AccountDetails, Currency, Money, TransactionSelection};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        let euro = AccountDetails {
+            currency: Some("EUR".to_string()),
+            ..Default::default()
+        };
+        crate::database::add_account_with_details(&pool, "Girokonto".to_string(), "checking".to_string(), &euro)
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Salary".to_string(), None).await.unwrap();
+        crate::database::add_category(&pool, "Rent".to_string(), None).await.unwrap();
+        crate::database::import_exchange_rates(&pool, "Date,USD\n2025-01-02,1.10\n2025-02-03,1.20\n", "EUR".to_string())
+            .await
+            .unwrap();
+
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_reports_convert_at_transaction_and_as_of_rates() {
+        let pool = setup_test_db().await;
+        crate::database::add_transaction(&pool, 2, Money::new(300000, Currency::EUR), "credit".to_string(), "Gehalt".to_string(), "2025-01-15".to_string(), 2)
+            .await
+            .unwrap();
+        crate::database::add_transaction(&pool, 2, Money::new(-100000, Currency::EUR), "debit".to_string(), "Miete".to_string(), "2025-02-05".to_string(), 3)
+            .await
+            .unwrap();
+
+        // Income at January's rate, rent at February's
+        let statement = get_income_statement(&pool, None, None).await.unwrap();
+        assert_eq!(statement["base_currency"], "USD");
+        assert_eq!(statement["total_income_cents"], 330000);
+        assert_eq!(statement["total_expense_cents"], 120000);
+        assert_eq!(statement["net_cents"], 210000);
+
+        // The EUR 2,000 balance is valued at the as-of date's rate
+        let january = get_net_worth(&pool, Some("2025-01-31".to_string())).await.unwrap();
+        assert_eq!(january["accounts"][1]["balance_cents"], 300000);
+        assert_eq!(january["net_worth_cents"], 330000);
+        let february = get_net_worth(&pool, Some("2025-02-28".to_string())).await.unwrap();
+        assert_eq!(february["accounts"][1]["base_balance_cents"], 240000);
+
+        // Reports fail rather than skip amounts they cannot convert
+        assert!(get_net_worth(&pool, Some("2024-12-31".to_string())).await.is_ok()); // Nothing held yet
+        crate::database::add_transaction(&pool, 2, Money::new(-5000, Currency::EUR), "debit".to_string(), "Bäckerei".to_string(), "2024-12-30".to_string(), 1)
+            .await
+            .unwrap();
+        assert!(get_income_statement(&pool, None, None).await.is_err());
+        assert!(get_net_worth(&pool, Some("2024-12-31".to_string())).await.is_err());
+        crate::database::bulk_delete_transactions(&pool, &TransactionSelection::Ids(vec![3])).await.unwrap();
+
+        // Changing the base currency revalues everything in EUR
+        crate::database::set_base_currency(&pool, "eur".to_string()).await.unwrap();
+        let statement = get_income_statement(&pool, None, None).await.unwrap();
+        assert_eq!(statement["total_income_cents"], 300000);
+    }
+
+    #[tokio::test]
+    async fn test_net_worth_overflow_is_an_error() {
+        let pool = setup_test_db().await;
+        crate::database::add_account(&pool, "Savings".to_string(), "savings".to_string()).await.unwrap();
+        for account_id in [1, 3] {
+            crate::database::set_opening_balance(&pool, account_id, Money::new(i64::MAX / 2 + 1, Currency::USD)).await.unwrap();
+        }
+
+        assert!(get_net_worth(&pool, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transfers_realize_fx_differences() {
+        let pool = setup_test_db().await;
+        crate::database::add_transaction(&pool, 1, Money::new(500000, Currency::USD), "credit".to_string(), "Paycheck".to_string(), "2025-01-10".to_string(), 2)
+            .await
+            .unwrap();
+
+        // USD 1,200 buys EUR 1,050 at the bank, worth USD 1,155 at the reference rate
+        let transfer = crate::database::add_transfer(&pool, 1, 2, Money::new(120000, Currency::USD), Some(Money::new(105000, Currency::EUR)), "2025-01-20".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(transfer["received_cents"], 105000);
+
+        // Without a received amount, the reference rate applies
+        let converted = crate::database::add_transfer(&pool, 2, 1, Money::new(10000, Currency::EUR), None, "2025-02-10".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(converted["received_cents"], 12000);
+
+        let statement = get_income_statement(&pool, None, None).await.unwrap();
+        // Transfers are neither income nor expenses
+        assert_eq!(statement["total_income_cents"], 500000);
+        assert_eq!(statement["total_expense_cents"], 0);
+        assert_eq!(statement["fx_differences"].as_array().unwrap().len(), 2);
+        assert_eq!(statement["fx_differences"][0]["fx_difference_cents"], -4500);
+        assert_eq!(statement["fx_differences"][1]["fx_difference_cents"], 0);
+        assert_eq!(statement["net_cents"], 495500);
+
+        let worth = get_net_worth(&pool, Some("2025-01-31".to_string())).await.unwrap();
+        assert_eq!(worth["net_worth_cents"], 380000 + 115500);
+    }
+}
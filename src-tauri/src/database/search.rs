@@ -0,0 +1,736 @@
+//! Transaction search and the shared transaction filter.
+//!
+//! [`TransactionFilter`] describes a set of transactions by free text and
+//! structured criteria. It is deserialized straight from command arguments, so
+//! the frontend sends a plain object with only the fields it needs:
+//!
+//! ```javascript
+//! { query: "amazon", date_from: "2025-03-01", date_to: "2025-05-31" }
+//! ```
+//!
+//! The free-text part runs against the `transactions_fts` FTS5 index, which
+//! triggers keep in sync with the `transactions` table.
+//!
+//! [`search_transactions()`] returns the best matches for a filter, while
+//! [`query_transactions()`] pages through a filtered account register.
+
+use super::custom_fields::{CustomFieldValues, parse_values};
+use super::tags::{TAG_NAMES, clean_tag_name, tag_names};
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+
+/// Maximum number of results returned by [`search_transactions()`] when no limit is given.
+pub const DEFAULT_SEARCH_LIMIT: i64 = 100;
+
+/// Page size of [`query_transactions()`] when no limit is given.
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// Largest page [`query_transactions()`] returns, whatever limit is requested.
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+/// Transactions with each row's account balance after it, in register order.
+///
+/// The balance starts from the account's opening balance and accumulates in
+/// date order, with the id breaking ties between same-day transactions. The
+/// window runs over the whole account, before any other filter applies, so a
+/// filtered or paginated result still shows the true balance. SQLite pushes
+/// `account_id` conditions into the subquery since it is the partition key.
+pub(crate) const REGISTER_SOURCE: &str = "(
+    SELECT tr.*, a.opening_balance_cents + SUM(tr.amount_cents) OVER (
+        PARTITION BY tr.account_id ORDER BY tr.transaction_date, tr.id
+    ) AS running_balance
+    FROM transactions tr
+    JOIN accounts a ON a.id = tr.account_id
+    WHERE tr.deleted_at IS NULL
+)";
+
+/// Criteria selecting transactions. Empty fields do not restrict the result.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionFilter {
+    /// Free text matched against description, memo and payee. Every word must
+    /// match, as a prefix and ignoring case and accents ("amaz caf" finds
+    /// "Amazon Café").
+    pub query: Option<String>,
+    pub account_id: Option<i64>,
+    /// Category to match, including all of its subcategories
+    pub category_id: Option<i64>,
+    /// First date to include (YYYY-MM-DD)
+    pub date_from: Option<String>,
+    /// Last date to include (YYYY-MM-DD)
+    pub date_to: Option<String>,
+    /// Smallest amount to include, compared against the absolute value in cents
+    pub min_amount_cents: Option<i64>,
+    /// Largest amount to include, compared against the absolute value in cents
+    pub max_amount_cents: Option<i64>,
+    /// "debit" or "credit"
+    pub transaction_type: Option<String>,
+    /// Only cleared (`true`) or only uncleared (`false`) transactions
+    pub cleared: Option<bool>,
+    /// Tag names the transaction must all carry, ignoring case and a leading `#`
+    pub tags: Vec<String>,
+    /// Exact check number
+    pub check_number: Option<String>,
+    /// Custom field values the transaction must all have, keyed by field id;
+    /// `null` matches transactions without a value for the field
+    pub custom_fields: CustomFieldValues,
+}
+
+impl TransactionFilter {
+    /// Full-text query in FTS5 syntax, or `None` when there is nothing to match.
+    ///
+    /// User input is never passed to FTS5 verbatim: each word is quoted, so
+    /// characters such as `"`, `-` or `:` cannot form (invalid) query syntax,
+    /// and turned into a prefix match.
+    fn fts_query(&self) -> Option<String> {
+        let terms: Vec<String> = self
+            .query
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|word| word.replace('"', ""))
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("\"{}\"*", word))
+            .collect();
+
+        (!terms.is_empty()).then(|| terms.join(" "))
+    }
+
+    /// Appends `FROM` and `WHERE` clauses selecting the matching transactions as `t`.
+    ///
+    /// `source` is the table or subquery the transactions are read from, either
+    /// `transactions` or [`REGISTER_SOURCE`]. When the filter has a full-text
+    /// query, `transactions_fts` is joined so callers can rank by
+    /// `bm25(transactions_fts)`. Fails for an empty tag name.
+    pub(crate) fn push_from_where(&self, builder: &mut QueryBuilder<'_, Sqlite>, source: &str) -> Result<(), sqlx::Error> {
+        builder.push(format!(" FROM {} t", source));
+
+        let fts_query = self.fts_query();
+        if fts_query.is_some() {
+            builder.push(" JOIN transactions_fts ON transactions_fts.rowid = t.id");
+        }
+
+        builder.push(" WHERE t.deleted_at IS NULL");
+
+        if let Some(fts_query) = fts_query {
+            builder.push(" AND transactions_fts MATCH ").push_bind(fts_query);
+        }
+        if let Some(account_id) = self.account_id {
+            builder.push(" AND t.account_id = ").push_bind(account_id);
+        }
+        if let Some(category_id) = self.category_id {
+            builder
+                .push(
+                    " AND t.category_id IN (
+                        WITH RECURSIVE subtree(id) AS (
+                            SELECT ",
+                )
+                .push_bind(category_id)
+                .push(
+                    "
+                            UNION ALL
+                            SELECT c.id FROM categories c JOIN subtree s ON c.parent_id = s.id
+                        )
+                        SELECT id FROM subtree
+                    )",
+                );
+        }
+        if let Some(date_from) = &self.date_from {
+            builder.push(" AND t.transaction_date >= ").push_bind(date_from.clone());
+        }
+        if let Some(date_to) = &self.date_to {
+            builder.push(" AND t.transaction_date <= ").push_bind(date_to.clone());
+        }
+        if let Some(min) = self.min_amount_cents {
+            builder.push(" AND ABS(t.amount_cents) >= ").push_bind(min);
+        }
+        if let Some(max) = self.max_amount_cents {
+            builder.push(" AND ABS(t.amount_cents) <= ").push_bind(max);
+        }
+        if let Some(transaction_type) = &self.transaction_type {
+            builder
+                .push(" AND t.transaction_type = ")
+                .push_bind(transaction_type.clone());
+        }
+        if let Some(cleared) = self.cleared {
+            builder.push(" AND t.cleared = ").push_bind(cleared);
+        }
+        for tag in &self.tags {
+            let tag = clean_tag_name(tag)?;
+            builder
+                .push(
+                    " AND EXISTS (SELECT 1 FROM transaction_tags tt JOIN tags g ON g.id = tt.tag_id
+                                  WHERE tt.transaction_id = t.id AND g.name = ",
+                )
+                .push_bind(tag)
+                .push(")");
+        }
+        if let Some(check_number) = &self.check_number {
+            builder.push(" AND t.check_number = ").push_bind(check_number.trim().to_string());
+        }
+        for (field_id, value) in &self.custom_fields {
+            builder.push(format!(" AND json_extract(t.custom_fields, '$.\"{}\"')", field_id));
+            match value {
+                serde_json::Value::Null => {
+                    builder.push(" IS NULL");
+                }
+                serde_json::Value::Bool(flag) => {
+                    builder.push(" = ").push_bind(*flag);
+                }
+                serde_json::Value::Number(number) => {
+                    builder.push(" = ");
+                    match number.as_i64() {
+                        Some(integer) => builder.push_bind(integer),
+                        None => builder.push_bind(number.as_f64()),
+                    };
+                }
+                other => {
+                    let text = other.as_str().map_or_else(|| other.to_string(), str::to_string);
+                    builder.push(" = ").push_bind(text);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Column [`query_transactions()`] sorts by. Ties are broken by id.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionSort {
+    #[default]
+    Date,
+    /// Signed amount, so with ascending order the largest debits come first
+    Amount,
+    /// Case-insensitive description
+    Description,
+}
+
+impl TransactionSort {
+    fn column(self) -> &'static str {
+        match self {
+            TransactionSort::Date => "t.transaction_date",
+            TransactionSort::Amount => "t.amount_cents",
+            TransactionSort::Description => "t.description COLLATE NOCASE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// One page request for [`query_transactions()`].
+///
+/// The filter fields sit at the top level next to the paging fields, e.g.
+/// `{ account_id: 1, cleared: false, sort: "amount", direction: "asc", limit: 50 }`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionQuery {
+    #[serde(flatten)]
+    pub filter: TransactionFilter,
+    pub sort: TransactionSort,
+    pub direction: SortDirection,
+    /// Page size; defaults to [`DEFAULT_PAGE_SIZE`], capped at [`MAX_PAGE_SIZE`]
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page; `None` for the first page
+    pub after: Option<String>,
+}
+
+/// Searches transactions by text and structured filters, best matches first.
+///
+/// With a full-text query, results are ranked by BM25 relevance, with the most
+/// recent transaction first among equally relevant ones. Without one, results
+/// are simply ordered newest first.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference for executing the query
+/// * `filter` - Text and criteria to match, see [`TransactionFilter`]
+/// * `limit` - Maximum number of results
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Matching transactions with id, account_id,
+///   amount_cents, transaction_type, description, original_description,
+///   transaction_date, category_id, payee_id, memo, check_number, custom_fields,
+///   `tags` (names) and `rank` (BM25 score, lower is better; null without a
+///   text query)
+/// * `Err(sqlx::Error)` - Database query failure
+///
+/// # Examples
+/// ```no_run
+/// // "That Amazon charge from last spring"
+/// let filter = TransactionFilter {
+///     query: Some("amazon".to_string()),
+///     date_from: Some("2025-03-01".to_string()),
+///     date_to: Some("2025-05-31".to_string()),
+///     ..Default::default()
+/// };
+/// let results = search_transactions(&pool, &filter, DEFAULT_SEARCH_LIMIT).await?;
+/// ```
+pub async fn search_transactions(
+    pool: &SqlitePool,
+    filter: &TransactionFilter,
+    limit: i64,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let ranked = filter.fts_query().is_some();
+
+    let mut builder = QueryBuilder::<Sqlite>::new(
+        "SELECT t.id, t.account_id, t.amount_cents, t.transaction_type, t.description, t.original_description, t.transaction_date, t.category_id, t.payee_id, t.memo, t.check_number, t.custom_fields",
+    );
+    builder.push(format!(", {} AS tags", TAG_NAMES));
+    builder.push(if ranked {
+        ", bm25(transactions_fts) AS rank"
+    } else {
+        ", NULL AS rank"
+    });
+    filter.push_from_where(&mut builder, "transactions")?;
+    builder.push(if ranked {
+        " ORDER BY rank, t.transaction_date DESC, t.id DESC"
+    } else {
+        " ORDER BY t.transaction_date DESC, t.id DESC"
+    });
+    builder.push(" LIMIT ").push_bind(limit);
+
+    let rows = builder.build().fetch_all(pool).await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "account_id": row.get::<i64, _>("account_id"),
+                "amount_cents": row.get::<i64, _>("amount_cents"),
+                "transaction_type": row.get::<String, _>("transaction_type"),
+                "description": row.get::<String, _>("description"),
+                "original_description": row.get::<Option<String>, _>("original_description"),
+                "transaction_date": row.get::<String, _>("transaction_date"),
+                "category_id": row.get::<i64, _>("category_id"),
+                "payee_id": row.get::<Option<i64>, _>("payee_id"),
+                "memo": row.get::<Option<String>, _>("memo"),
+                "check_number": row.get::<Option<String>, _>("check_number"),
+                "custom_fields": parse_values(row.get("custom_fields"))?,
+                "tags": tag_names(row.get("tags"))?,
+                "rank": row.get::<Option<f64>, _>("rank")
+            }))
+        })
+        .collect()
+}
+
+/// Returns one page of transactions, sorted and filtered, with running balances.
+///
+/// Uses keyset pagination: each page ends with a `next_cursor` naming the last
+/// row's sort key and id, and the next page continues strictly after it. Unlike
+/// `OFFSET`, this stays fast deep into large accounts and does not skip or
+/// repeat rows when transactions are added between page loads.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference for executing the query
+/// * `query` - Filter, sort order, page size and cursor, see [`TransactionQuery`]
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `transactions` (the page, each with a
+///   `running_balance` of its account after that transaction), `total_count`
+///   (all matching transactions across pages) and `next_cursor` (null on the
+///   last page)
+/// * `Err(sqlx::Error)` - Malformed cursor or database query failure
+///
+/// # Examples
+/// ```no_run
+/// let mut query = TransactionQuery {
+///     filter: TransactionFilter { account_id: Some(1), ..Default::default() },
+///     ..Default::default()
+/// };
+/// loop {
+///     let page = query_transactions(&pool, &query).await?;
+///     // ... render page["transactions"] ...
+///     match page["next_cursor"].as_str() {
+///         Some(cursor) => query.after = Some(cursor.to_string()),
+///         None => break,
+///     }
+/// }
+/// ```
+pub async fn query_transactions(
+    pool: &SqlitePool,
+    query: &TransactionQuery,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let column = query.sort.column();
+    let (comparison, direction) = match query.direction {
+        SortDirection::Asc => (">", "ASC"),
+        SortDirection::Desc => ("<", "DESC"),
+    };
+
+    let mut builder = QueryBuilder::<Sqlite>::new(
+        "SELECT t.id, t.account_id, t.amount_cents, t.transaction_type, t.description, t.original_description, t.transaction_date, t.category_id, t.payee_id, t.memo, t.check_number, t.custom_fields, t.transfer_id, t.cleared, t.running_balance",
+    );
+    builder.push(format!(", {} AS tags", TAG_NAMES));
+    query.filter.push_from_where(&mut builder, REGISTER_SOURCE)?;
+
+    if let Some(cursor) = &query.after {
+        let (key, id) = cursor
+            .rsplit_once('|')
+            .and_then(|(key, id)| Some((key, id.parse::<i64>().ok()?)))
+            .ok_or_else(|| sqlx::Error::Protocol(format!("invalid cursor: {}", cursor)))?;
+
+        builder.push(format!(" AND ({} {} ", column, comparison));
+        push_sort_key(&mut builder, query.sort, key)?;
+        builder.push(format!(" OR ({} = ", column));
+        push_sort_key(&mut builder, query.sort, key)?;
+        builder
+            .push(format!(" AND t.id {} ", comparison))
+            .push_bind(id)
+            .push("))");
+    }
+
+    builder
+        .push(format!(" ORDER BY {} {}, t.id {} LIMIT ", column, direction, direction))
+        .push_bind(limit + 1);
+
+    let mut rows = builder.build().fetch_all(pool).await?;
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+
+    let next_cursor = match rows.last() {
+        Some(row) if has_more => {
+            let key = match query.sort {
+                TransactionSort::Date => row.get::<String, _>("transaction_date"),
+                TransactionSort::Amount => row.get::<i64, _>("amount_cents").to_string(),
+                TransactionSort::Description => row.get::<String, _>("description"),
+            };
+            Some(format!("{}|{}", key, row.get::<i64, _>("id")))
+        }
+        _ => None,
+    };
+
+    let transactions = rows
+        .into_iter()
+        .map(|row| {
+            Ok(serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "account_id": row.get::<i64, _>("account_id"),
+                "amount_cents": row.get::<i64, _>("amount_cents"),
+                "transaction_type": row.get::<String, _>("transaction_type"),
+                "description": row.get::<String, _>("description"),
+                "original_description": row.get::<Option<String>, _>("original_description"),
+                "transaction_date": row.get::<String, _>("transaction_date"),
+                "category_id": row.get::<i64, _>("category_id"),
+                "payee_id": row.get::<Option<i64>, _>("payee_id"),
+                "memo": row.get::<Option<String>, _>("memo"),
+                "check_number": row.get::<Option<String>, _>("check_number"),
+                "custom_fields": parse_values(row.get("custom_fields"))?,
+                "transfer_id": row.get::<Option<i64>, _>("transfer_id"),
+                "tags": tag_names(row.get("tags"))?,
+                "cleared": row.get::<bool, _>("cleared"),
+                "running_balance": row.get::<i64, _>("running_balance")
+            }))
+        })
+        .collect::<Result<Vec<serde_json::Value>, sqlx::Error>>()?;
+
+    let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*)");
+    query.filter.push_from_where(&mut count, "transactions")?;
+    let total_count: i64 = count.build_query_scalar().fetch_one(pool).await?;
+
+    Ok(serde_json::json!({
+        "transactions": transactions,
+        "total_count": total_count,
+        "next_cursor": next_cursor
+    }))
+}
+
+/// Binds a cursor's sort key with the type of the sort column.
+fn push_sort_key(
+    builder: &mut QueryBuilder<'_, Sqlite>,
+    sort: TransactionSort,
+    key: &str,
+) -> Result<(), sqlx::Error> {
+    match sort {
+        TransactionSort::Amount => {
+            let amount = key
+                .parse::<i64>()
+                .map_err(|_| sqlx::Error::Protocol(format!("invalid cursor amount: {}", key)))?;
+            builder.push_bind(amount);
+        }
+        TransactionSort::Date | TransactionSort::Description => {
+            builder.push_bind(key.to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves a filter under a name, replacing any filter saved under that name.
+///
+/// Saved filters can be re-run from the UI and used to select transactions for
+/// bulk operations.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `name` - Unique display name, e.g. "Unreviewed Amazon"
+/// * `filter` - Filter to save
+///
+/// # Returns
+/// * `Ok(i64)` - Id of the saved filter
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn save_filter(
+    pool: &SqlitePool,
+    name: String,
+    filter: &TransactionFilter,
+) -> Result<i64, sqlx::Error> {
+    let json = serde_json::to_string(filter).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+    sqlx::query_scalar(
+        "INSERT INTO saved_filters (name, filter) VALUES (?, ?)
+         ON CONFLICT (name) DO UPDATE SET filter = excluded.filter
+         RETURNING id",
+    )
+    .bind(name)
+    .bind(json)
+    .fetch_one(pool)
+    .await
+}
+
+/// Lists saved filters by name.
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with id, name and the `filter` object
+/// * `Err(sqlx::Error)` - Database failure or unreadable stored filter
+pub async fn get_saved_filters(pool: &SqlitePool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query("SELECT id, name, filter FROM saved_filters ORDER BY name")
+        .fetch_all(pool)
+        .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let filter: serde_json::Value = serde_json::from_str(row.get("filter"))
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            Ok(serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "name": row.get::<String, _>("name"),
+                "filter": filter
+            }))
+        })
+        .collect()
+}
+
+/// Deletes a saved filter.
+pub async fn delete_saved_filter(pool: &SqlitePool, filter_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM saved_filters WHERE id = ?")
+        .bind(filter_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Loads a saved filter by id.
+pub(crate) async fn load_saved_filter(
+    conn: &mut SqliteConnection,
+    filter_id: i64,
+) -> Result<TransactionFilter, sqlx::Error> {
+    let json: String = sqlx::query_scalar("SELECT filter FROM saved_filters WHERE id = ?")
+        .bind(filter_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| sqlx::Error::Protocol(format!("saved filter {} not found", filter_id)))?;
+
+    serde_json::from_str(&json).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_account(&pool, "Credit Card".to_string(), "credit".to_string())
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Shopping".to_string(), None).await.unwrap();
+        crate::database::add_category(&pool, "Books".to_string(), Some(2)).await.unwrap();
+
+        let transactions = [
+            (2, -2599, "debit", "AMAZON MKTPLACE PMTS", "2025-04-12", 3),
+            (2, -1499, "debit", "Amazon Prime membership", "2025-09-01", 2),
+            (1, -450, "debit", "Café Central", "2025-04-14", 1),
+            (1, 2599, "credit", "Amazon refund", "2025-04-20", 2),
+            (1, -8000, "debit", "Grocery market", "2025-04-21", 1),
+        ];
+        for (account_id, amount, kind, description, date, category_id) in transactions {
+            crate::database::add_transaction(&pool, account_id, Money::new(amount, Currency::USD), kind.to_string(), description.to_string(), date.to_string(), category_id)
+                .await
+                .unwrap();
+        }
+
+        pool
+    }
+
+    fn ids(results: &[serde_json::Value]) -> Vec<i64> {
+        results.iter().map(|r| r["id"].as_i64().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_combines_text_and_filters() {
+        let pool = setup_test_db().await;
+
+        let filter = TransactionFilter {
+            query: Some("amazon".to_string()),
+            date_from: Some("2025-03-01".to_string()),
+            date_to: Some("2025-05-31".to_string()),
+            ..Default::default()
+        };
+        let results = search_transactions(&pool, &filter, DEFAULT_SEARCH_LIMIT).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0]["rank"].is_f64());
+
+        let debits = TransactionFilter {
+            transaction_type: Some("debit".to_string()),
+            ..filter.clone()
+        };
+        let results = search_transactions(&pool, &debits, DEFAULT_SEARCH_LIMIT).await.unwrap();
+        assert_eq!(ids(&results), vec![1]);
+
+        // Category filters include subcategories: Books (3) is under Shopping (2)
+        let shopping = TransactionFilter {
+            category_id: Some(2),
+            ..Default::default()
+        };
+        let results = search_transactions(&pool, &shopping, DEFAULT_SEARCH_LIMIT).await.unwrap();
+        assert_eq!(ids(&results), vec![2, 4, 1]); // Newest first without a text query
+    }
+
+    #[tokio::test]
+    async fn test_search_amount_range_and_text_normalization() {
+        let pool = setup_test_db().await;
+
+        // Amount bounds apply to the absolute value of debits and credits alike
+        let filter = TransactionFilter {
+            min_amount_cents: Some(2000),
+            max_amount_cents: Some(3000),
+            ..Default::default()
+        };
+        let results = search_transactions(&pool, &filter, DEFAULT_SEARCH_LIMIT).await.unwrap();
+        assert_eq!(ids(&results), vec![4, 1]);
+
+        // Prefix, case and accent insensitive; stray quotes are not syntax errors
+        let filter = TransactionFilter {
+            query: Some("CAF \"centr".to_string()),
+            ..Default::default()
+        };
+        let results = search_transactions(&pool, &filter, DEFAULT_SEARCH_LIMIT).await.unwrap();
+        assert_eq!(ids(&results), vec![3]);
+
+        // Index follows updates and deletes
+        crate::database::update_transaction(&pool, 3, 1, Money::new(-450, Currency::USD), "debit".to_string(), "Coffee shop".to_string(), "2025-04-14".to_string(), 1, None)
+            .await
+            .unwrap();
+        crate::database::delete_transaction(&pool, 5).await.unwrap();
+        let coffee = TransactionFilter {
+            query: Some("coffee".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(search_transactions(&pool, &filter, 10).await.unwrap().len(), 0);
+        assert_eq!(search_transactions(&pool, &coffee, 10).await.unwrap().len(), 1);
+        let grocery = TransactionFilter {
+            query: Some("grocery".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(search_transactions(&pool, &grocery, 10).await.unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_transactions_pages_without_gaps() {
+        let pool = setup_test_db().await;
+        crate::database::set_transaction_cleared(&pool, 3, true).await.unwrap();
+
+        let mut query = TransactionQuery {
+            sort: TransactionSort::Amount,
+            direction: SortDirection::Asc,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        loop {
+            let page = query_transactions(&pool, &query).await.unwrap();
+            assert_eq!(page["total_count"], 5);
+            seen.extend(ids(page["transactions"].as_array().unwrap()));
+            match page["next_cursor"].as_str() {
+                Some(cursor) => query.after = Some(cursor.to_string()),
+                None => break,
+            }
+        }
+        // -8000, -2599, -1499, -450, 2599
+        assert_eq!(seen, vec![5, 1, 2, 3, 4]);
+
+        query.after = Some("not a cursor".to_string());
+        assert!(query_transactions(&pool, &query).await.is_err());
+
+        let uncleared = TransactionQuery {
+            filter: TransactionFilter {
+                account_id: Some(1),
+                cleared: Some(false),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let page = query_transactions(&pool, &uncleared).await.unwrap();
+        assert_eq!(ids(page["transactions"].as_array().unwrap()), vec![5, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_query_transactions_running_balance_ignores_filters() {
+        let pool = setup_test_db().await;
+
+        // Checking: -450 (04-14), +2599 (04-20), -8000 (04-21)
+        let query = TransactionQuery {
+            filter: TransactionFilter {
+                account_id: Some(1),
+                transaction_type: Some("debit".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let page = query_transactions(&pool, &query).await.unwrap();
+        let transactions = page["transactions"].as_array().unwrap();
+        assert_eq!(transactions[0]["running_balance"], -5851);
+        assert_eq!(transactions[1]["running_balance"], -450);
+    }
+
+    #[tokio::test]
+    async fn test_query_transactions_running_balance_across_pages() {
+        let pool = setup_test_db().await;
+        crate::database::set_opening_balance(&pool, 1, Money::new(100000, Currency::USD)).await.unwrap();
+
+        let mut query = TransactionQuery {
+            filter: TransactionFilter {
+                account_id: Some(1),
+                ..Default::default()
+            },
+            direction: SortDirection::Asc,
+            limit: Some(1),
+            ..Default::default()
+        };
+        let mut balances = Vec::new();
+        loop {
+            let page = query_transactions(&pool, &query).await.unwrap();
+            balances.push(page["transactions"][0]["running_balance"].as_i64().unwrap());
+            match page["next_cursor"].as_str() {
+                Some(cursor) => query.after = Some(cursor.to_string()),
+                None => break,
+            }
+        }
+        assert_eq!(balances, vec![99550, 102149, 94149]);
+    }
+}
+
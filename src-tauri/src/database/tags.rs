@@ -0,0 +1,352 @@
+//! Tags: free-form labels cutting across accounts and categories.
+//!
+//! Where a category says what kind of spending a transaction is, tags group
+//! transactions by trip, project or event, such as `#italy-2025` or
+//! `#kitchen-remodel`. A transaction can carry any number of tags. Tags are
+//! created on first use; a leading `#` is not part of the name, and names are
+//! unique ignoring case.
+//!
+//! Transactions are tagged and untagged in bulk with
+//! [`bulk_tag_transactions()`](super::bulk_tag_transactions) and
+//! [`bulk_untag_transactions()`](super::bulk_untag_transactions), and filtered by
+//! tag through [`TransactionFilter::tags`](super::TransactionFilter::tags).
+
+use super::currencies::{ensure_rates, to_base_sql};
+use super::journal::journaled;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+/// Column expression with the JSON array of a transaction `t`'s tag names, sorted.
+pub(crate) const TAG_NAMES: &str = "(SELECT json_group_array(name) FROM (
+    SELECT g.name FROM transaction_tags tt JOIN tags g ON g.id = tt.tag_id
+    WHERE tt.transaction_id = t.id ORDER BY g.name
+))";
+
+/// Cleans up a tag name as typed by the user: trimmed, without leading `#`.
+pub(crate) fn clean_tag_name(name: &str) -> Result<String, sqlx::Error> {
+    let name = name.trim().trim_start_matches('#').trim();
+    if name.is_empty() {
+        return Err(sqlx::Error::Protocol("tag name cannot be empty".to_string()));
+    }
+
+    Ok(name.to_string())
+}
+
+/// Returns the id of the tag with this name, creating the tag if needed.
+pub(crate) async fn ensure_tag(conn: &mut SqliteConnection, name: &str) -> Result<i64, sqlx::Error> {
+    let name = clean_tag_name(name)?;
+
+    let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+        .bind(&name)
+        .fetch_optional(&mut *conn)
+        .await?;
+    match existing {
+        Some(id) => Ok(id),
+        None => Ok(sqlx::query("INSERT INTO tags (name) VALUES (?)")
+            .bind(&name)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid()),
+    }
+}
+
+/// Parses a [`TAG_NAMES`] column.
+pub(crate) fn tag_names(json: &str) -> Result<Vec<String>, sqlx::Error> {
+    serde_json::from_str(json).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Retrieves all tags, sorted by name.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with id, name and the number of
+///   tagged `transactions`
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_tags(pool: &SqlitePool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT g.id, g.name,
+                (SELECT COUNT(*) FROM transaction_tags tt JOIN transactions t ON t.id = tt.transaction_id
+                 WHERE tt.tag_id = g.id AND t.deleted_at IS NULL) AS transactions
+         FROM tags g
+         ORDER BY g.name",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let result: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "id": row.get::<i64, _>("id"),
+                "name": row.get::<String, _>("name"),
+                "transactions": row.get::<i64, _>("transactions")
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+/// Creates a tag without tagging any transaction yet.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `name` - Tag name; a leading `#` is dropped
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new tag, or of the existing tag with that name
+/// * `Err(sqlx::Error)` - Empty name or database failure
+pub async fn add_tag(pool: &SqlitePool, name: String) -> Result<i64, sqlx::Error> {
+    journaled(pool, "Add tag", async |conn| ensure_tag(&mut *conn, &name).await).await
+}
+
+/// Renames a tag on every transaction carrying it.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `tag_id` - Tag to rename
+/// * `name` - New name; a leading `#` is dropped
+///
+/// # Returns
+/// * `Ok(())` - Tag renamed
+/// * `Err(sqlx::Error)` - Empty name, name taken by another tag, or unknown tag
+pub async fn rename_tag(pool: &SqlitePool, tag_id: i64, name: String) -> Result<(), sqlx::Error> {
+    let name = clean_tag_name(&name)?;
+
+    journaled(pool, "Rename tag", async |conn| {
+        let result = sqlx::query("UPDATE tags SET name = ? WHERE id = ?")
+            .bind(&name)
+            .bind(tag_id)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Deletes a tag and removes it from all transactions.
+pub async fn delete_tag(pool: &SqlitePool, tag_id: i64) -> Result<(), sqlx::Error> {
+    journaled(pool, "Delete tag", async |conn| {
+        // Explicitly rather than by cascade, so undo restores the tag before its links
+        sqlx::query("DELETE FROM transaction_tags WHERE tag_id = ?")
+            .bind(tag_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM tags WHERE id = ?")
+            .bind(tag_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Reports spending per tag across all accounts and categories.
+///
+/// A transaction carrying several tags counts towards each of them, so the
+/// totals of different tags may overlap. Amounts are converted into the base
+/// currency at each transaction date's rate.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `date_from` - Optional first date (YYYY-MM-DD) included
+/// * `date_to` - Optional last date (YYYY-MM-DD) included
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with tag_id, name, `spent_cents`
+///   (total of debits, positive), `net_cents`, transaction `count` and
+///   `categories` (the same totals per category, highest spending first), for
+///   every tag used in the period, highest spending first
+/// * `Err(sqlx::Error)` - A transaction in the period cannot be converted, or
+///   database failure
+///
+/// # Examples
+/// ```no_run
+/// // What did the Italy trip cost, and on what?
+/// let report = get_tag_spending(&pool, None, None).await?;
+/// let italy = report.iter().find(|tag| tag["name"] == "italy-2025");
+/// ```
+pub async fn get_tag_spending(
+    pool: &SqlitePool,
+    date_from: Option<String>,
+    date_to: Option<String>,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    ensure_rates(&mut conn, &date_from, &date_to).await?;
+
+    let rows = sqlx::query(&format!(
+        "SELECT g.id AS tag_id, g.name AS tag_name, c.id AS category_id, c.name AS category_name,
+                SUM(CASE WHEN t.base_cents < 0 THEN -t.base_cents ELSE 0 END) AS spent_cents,
+                SUM(t.base_cents) AS net_cents,
+                COUNT(*) AS count
+         FROM transaction_tags tt
+         JOIN tags g ON g.id = tt.tag_id
+         JOIN (
+             SELECT t.id, t.category_id, {} AS base_cents
+             FROM transactions t
+             JOIN accounts a ON a.id = t.account_id
+             WHERE t.deleted_at IS NULL
+               AND (? IS NULL OR t.transaction_date >= ?)
+               AND (? IS NULL OR t.transaction_date <= ?)
+         ) t ON t.id = tt.transaction_id
+         JOIN categories c ON c.id = t.category_id
+         GROUP BY g.id, c.id
+         ORDER BY g.id, spent_cents DESC, c.name",
+        to_base_sql("t.amount_cents", "a.currency", "t.transaction_date")
+    ))
+    .bind(&date_from)
+    .bind(&date_from)
+    .bind(&date_to)
+    .bind(&date_to)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut report: Vec<serde_json::Value> = Vec::new();
+    for row in rows {
+        let tag_id: i64 = row.get("tag_id");
+        let (spent, net, count): (i64, i64, i64) = (row.get("spent_cents"), row.get("net_cents"), row.get("count"));
+
+        if report.last().is_none_or(|tag| tag["tag_id"] != tag_id) {
+            report.push(serde_json::json!({
+                "tag_id": tag_id,
+                "name": row.get::<String, _>("tag_name"),
+                "spent_cents": 0,
+                "net_cents": 0,
+                "count": 0,
+                "categories": []
+            }));
+        }
+        let tag = report.last_mut().expect("pushed above");
+        for (field, value) in [("spent_cents", spent), ("net_cents", net), ("count", count)] {
+            tag[field] = (tag[field].as_i64().unwrap_or_default() + value).into();
+        }
+        if let Some(categories) = tag["categories"].as_array_mut() {
+            categories.push(serde_json::json!({
+                "category_id": row.get::<i64, _>("category_id"),
+                "name": row.get::<String, _>("category_name"),
+                "spent_cents": spent,
+                "net_cents": net,
+                "count": count
+            }));
+        }
+    }
+
+    report.sort_by_key(|tag| std::cmp::Reverse(tag["spent_cents"].as_i64().unwrap_or_default()));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{BulkEdit, Currency, Money, TransactionFilter, TransactionQuery, TransactionSelection};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_account(&pool, "Credit Card".to_string(), "credit".to_string())
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Travel".to_string(), None).await.unwrap();
+        crate::database::add_category(&pool, "Dining".to_string(), None).await.unwrap();
+        for (account_id, amount, description, category_id) in [
+            (1, -90000, "Alitalia", 2),
+            (2, -4500, "Trattoria", 3),
+            (2, -2000, "Gelato", 3),
+            (1, -6000, "Hardware Store", 1),
+        ] {
+            crate::database::add_transaction(&pool, account_id, Money::new(amount, Currency::USD), "debit".to_string(), description.to_string(), "2025-06-01".to_string(), category_id)
+                .await
+                .unwrap();
+        }
+
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_bulk_tagging_and_tag_filter() {
+        let pool = setup_test_db().await;
+
+        let results = crate::database::bulk_tag_transactions(
+            &pool,
+            &TransactionSelection::Ids(vec![1, 2, 3, 99]),
+            &["#italy-2025".to_string(), "vacation".to_string()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(results[0]["status"], "tagged");
+        assert_eq!(results[3]["status"], "not_found");
+
+        // Tagging again is harmless; names match ignoring case and the leading #
+        crate::database::bulk_tag_transactions(&pool, &TransactionSelection::Ids(vec![1]), &["Italy-2025".to_string()])
+            .await
+            .unwrap();
+        let tags = get_tags(&pool).await.unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0]["name"], "italy-2025");
+        assert_eq!(tags[0]["transactions"], 3);
+
+        crate::database::bulk_untag_transactions(&pool, &TransactionSelection::Ids(vec![3]), &["vacation".to_string()])
+            .await
+            .unwrap();
+
+        let query = TransactionQuery {
+            filter: TransactionFilter {
+                tags: vec!["italy-2025".to_string(), "vacation".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let page = crate::database::query_transactions(&pool, &query).await.unwrap();
+        assert_eq!(page["total_count"], 2);
+        assert_eq!(page["transactions"][0]["tags"], serde_json::json!(["italy-2025", "vacation"]));
+
+        // Bulk edits through a tag filter
+        let selection = TransactionSelection::Filter(Box::new(TransactionFilter {
+            tags: vec!["vacation".to_string()],
+            ..Default::default()
+        }));
+        let edit = BulkEdit {
+            category_id: Some(2),
+            ..Default::default()
+        };
+        let results = crate::database::bulk_update_transactions(&pool, &selection, &edit).await.unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tag_spending_report_and_delete() {
+        let pool = setup_test_db().await;
+        crate::database::bulk_tag_transactions(&pool, &TransactionSelection::Ids(vec![1, 2, 3]), &["italy-2025".to_string()])
+            .await
+            .unwrap();
+        crate::database::bulk_tag_transactions(&pool, &TransactionSelection::Ids(vec![4]), &["kitchen-remodel".to_string()])
+            .await
+            .unwrap();
+
+        let report = get_tag_spending(&pool, None, None).await.unwrap();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0]["name"], "italy-2025");
+        assert_eq!(report[0]["spent_cents"], 96500);
+        assert_eq!(report[0]["count"], 3);
+        assert_eq!(report[0]["categories"][0]["name"], "Travel");
+        assert_eq!(report[0]["categories"][1]["spent_cents"], 6500);
+
+        // Deleting a tag untags its transactions; undo brings both back
+        delete_tag(&pool, 1).await.unwrap();
+        assert_eq!(get_tag_spending(&pool, None, None).await.unwrap().len(), 1);
+        crate::database::undo(&pool).await.unwrap();
+        assert_eq!(get_tag_spending(&pool, None, None).await.unwrap()[0]["count"], 3);
+    }
+}
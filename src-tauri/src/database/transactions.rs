@@ -1,4 +1,23 @@
-use sqlx::{Row, SqlitePool};
+use super::accounts::{account_amount, account_currency};
+use super::categories::check_category_assignable;
+use super::currencies::convert;
+use super::custom_fields::{CustomFieldEntity, CustomFieldValues, parse_values, values_json};
+use super::journal::journaled;
+use super::money::Money;
+use super::payees::resolve_payee;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+/// Notes and user-defined values recorded with a transaction.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionDetails {
+    /// Free-text note, e.g. "Reimbursable by work"; searchable like the description
+    pub memo: Option<String>,
+    pub check_number: Option<String>,
+    /// Values by custom field id, see [`custom_fields`](super::custom_fields)
+    pub custom_fields: CustomFieldValues,
+}
 
 /// Retrieves all transactions for a specific financial account.
 ///
@@ -6,12 +25,16 @@ use sqlx::{Row, SqlitePool};
 /// JSON-serializable objects for frontend display. Results include core transaction
 /// data but exclude internal metadata like creation timestamps to keep the API clean.
 ///
+/// Each row carries the account's `running_balance` after that transaction, as a
+/// bank register shows it: the opening balance plus all amounts up to and
+/// including the row in date order (same-day rows in id order).
+///
 /// # Arguments
 /// * `pool` - SQLite connection pool reference for executing the query
 /// * `account_id` - Database ID of the account to retrieve transactions for
 ///
 /// # Returns
-/// * `Ok(Vec<serde_json::Value>)` - Array of transaction objects with id, account_id, amount_cents, description, and transaction_date
+/// * `Ok(Vec<serde_json::Value>)` - Array of transaction objects with id, account_id, amount_cents, description, original_description (the bank's text, when cleaned up), transaction_date, payee_id, memo, check_number, custom_fields, transfer_id (the other leg of a transfer), tags, cleared flag and running_balance, most recent first
 /// * `Err(sqlx::Error)` - Database query or data extraction failure
 ///
 /// # Errors
@@ -40,24 +63,59 @@ pub async fn get_transactions(
     pool: &SqlitePool,
     account_id: i64,
 ) -> Result<Vec<serde_json::Value>, sqlx::Error> {
-    let transactions = sqlx::query("SELECT id, account_id, amount_cents, transaction_type, description, transaction_date, category_id FROM transactions WHERE account_id = ?").bind(account_id).fetch_all(pool).await?;
+    let transactions = sqlx::query(&format!(
+        r#"
+        SELECT
+            id,
+            account_id,
+            amount_cents,
+            transaction_type,
+            description,
+            original_description,
+            transaction_date,
+            category_id,
+            payee_id,
+            memo,
+            check_number,
+            custom_fields,
+            transfer_id,
+            {} AS tags,
+            cleared,
+            running_balance
+        FROM {} t
+        WHERE account_id = ?
+        ORDER BY transaction_date DESC, id DESC
+        "#,
+        super::tags::TAG_NAMES,
+        super::search::REGISTER_SOURCE
+    ))
+    .bind(account_id)
+    .fetch_all(pool)
+    .await?;
 
-    let result: Vec<serde_json::Value> = transactions
+    transactions
         .into_iter()
         .map(|row| {
-            serde_json::json!({
+            Ok(serde_json::json!({
                 "id": row.get::<i64, _>("id"),
                 "account_id": row.get::<i64, _>("account_id"),
                 "amount_cents": row.get::<i64, _>("amount_cents"),
                 "transaction_type": row.get::<String, _>("transaction_type"),
                 "description": row.get::<String, _>("description"),
+                "original_description": row.get::<Option<String>, _>("original_description"),
                 "transaction_date": row.get::<String, _>("transaction_date"),
-                "category_id": row.get::<i64, _>("category_id")
-            })
+                "category_id": row.get::<i64, _>("category_id"),
+                "payee_id": row.get::<Option<i64>, _>("payee_id"),
+                "memo": row.get::<Option<String>, _>("memo"),
+                "check_number": row.get::<Option<String>, _>("check_number"),
+                "custom_fields": parse_values(row.get("custom_fields"))?,
+                "transfer_id": row.get::<Option<i64>, _>("transfer_id"),
+                "tags": super::tags::tag_names(row.get("tags"))?,
+                "cleared": row.get::<bool, _>("cleared"),
+                "running_balance": row.get::<i64, _>("running_balance")
+            }))
         })
-        .collect();
-
-    Ok(result)
+        .collect()
 }
 
 /// Creates a new financial transaction record for the specified account.
@@ -66,10 +124,17 @@ pub async fn get_transactions(
 /// financial calculations. The creation timestamp is automatically set by the database.
 /// Transaction types determine how amounts affect account balances in future calculations.
 ///
+/// A bank-formatted description is cleaned up with
+/// [`normalize_description()`](crate::database::normalize_description), while
+/// typed text is stored as typed, and the transaction is linked to a matching
+/// payee, which is created if none matches. The raw text is kept as
+/// `original_description`. An "Uncategorized"
+/// transaction takes the payee's default category, if it has one.
+///
 /// # Arguments
 /// * `pool` - SQLite connection pool reference for executing the insertion
 /// * `account_id` - Database ID of the account this transaction belongs to
-/// * `amount_cents` - Transaction amount in cents (always positive, e.g., 2550 for $25.50)
+/// * `amount` - Signed amount in the account's currency: negative for a debit, positive for a credit
 /// * `transaction_type` - Either "debit" (reduces balance) or "credit" (increases balance)
 /// * `description` - Human-readable transaction description from bank data or user input
 /// * `transaction_date` - Transaction date in ISO 8601 format (YYYY-MM-DD)
@@ -83,7 +148,9 @@ pub async fn get_transactions(
 /// - Database connection cannot be established (pool exhaustion, file locks)
 /// - Account ID does not exist (invalid foreign key reference)
 /// - Transaction type is invalid (must be "debit" or "credit")
+/// - Amount is in another currency than the account, or signed against its type
 /// - Date format is malformed (must be valid ISO 8601 YYYY-MM-DD)
+/// - The category is in the trash
 /// - Database insertion fails (disk space, permissions, corruption)
 /// - Parameter binding fails (invalid UTF-8 in strings, integer overflow)
 ///
@@ -93,7 +160,7 @@ pub async fn get_transactions(
 /// add_transaction(
 ///     &pool,
 ///     1,
-///     4275,  // $42.75
+///     Money::new(-4275, Currency::USD),  // $42.75
 ///     "debit".to_string(),
 ///     "Whole Foods Market".to_string(),
 ///     "2025-08-15".to_string()
@@ -103,7 +170,7 @@ pub async fn get_transactions(
 /// add_transaction(
 ///     &pool,
 ///     1,
-///     250000,  // $2,500.00
+///     Money::new(250000, Currency::USD),  // $2,500.00
 ///     "credit".to_string(),
 ///     "Payroll Deposit".to_string(),
 ///     "2025-08-15".to_string()
@@ -112,41 +179,137 @@ pub async fn get_transactions(
 pub async fn add_transaction(
     pool: &SqlitePool,
     account_id: i64,
-    amount_cents: i64,
+    amount: Money,
     transaction_type: String,
     description: String,
     transaction_date: String,
     category_id: i64,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        r#"
-        INSERT INTO transactions (
+    add_transaction_with_details(
+        pool,
         account_id,
-        amount_cents,
+        amount,
         transaction_type,
         description,
         transaction_date,
-        category_id) 
-        VALUES (?, ?, ?, ?, ?, ?)
-    "#,
+        category_id,
+        &TransactionDetails::default(),
     )
-    .bind(account_id)
-    .bind(amount_cents)
-    .bind(transaction_type)
-    .bind(description)
-    .bind(transaction_date)
-    .bind(category_id)
-    .execute(pool)
     .await?;
 
     Ok(())
 }
 
-/// Removes a transaction record from the database.
+/// Creates a transaction with a memo, check number or custom field values.
+///
+/// Works like [`add_transaction()`], which is a shorthand for this function
+/// without details.
+///
+/// # Arguments
+/// * `details` - Memo, check number and custom field values to record
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new transaction
+/// * `Err(sqlx::Error)` - See [`add_transaction()`]; also fails for an unknown
+///   custom field or a value of the wrong type
+///
+/// # Examples
+/// ```no_run
+/// let details = TransactionDetails {
+///     memo: Some("Reimbursable by work".to_string()),
+///     check_number: Some("1042".to_string()),
+///     ..Default::default()
+/// };
+/// let id = add_transaction_with_details(&pool, 1, Money::new(-8900, Currency::USD), "debit".to_string(), "Hotel".to_string(), "2025-05-20".to_string(), 1, &details).await?;
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub async fn add_transaction_with_details(
+    pool: &SqlitePool,
+    account_id: i64,
+    amount: Money,
+    transaction_type: String,
+    description: String,
+    transaction_date: String,
+    category_id: i64,
+    details: &TransactionDetails,
+) -> Result<i64, sqlx::Error> {
+    journaled(pool, "Add transaction", async |conn| {
+        let amount_cents = signed_amount(&mut *conn, account_id, amount, &transaction_type).await?;
+        let payee = resolve_payee(&mut *conn, &description).await?;
+        let category_id = payee.category(&mut *conn, category_id).await?;
+        check_category_assignable(&mut *conn, category_id).await?;
+        let custom_fields = values_json(&mut *conn, CustomFieldEntity::Transaction, &details.custom_fields).await?;
+
+        let id = sqlx::query(
+            r#"
+            INSERT INTO transactions (
+            account_id,
+            amount_cents,
+            transaction_type,
+            description,
+            transaction_date,
+            category_id,
+            payee_id,
+            original_description,
+            memo,
+            check_number,
+            custom_fields) 
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        )
+        .bind(account_id)
+        .bind(amount_cents)
+        .bind(transaction_type)
+        .bind(&payee.description)
+        .bind(transaction_date)
+        .bind(category_id)
+        .bind(payee.payee_id)
+        .bind(&description)
+        .bind(&details.memo)
+        .bind(&details.check_number)
+        .bind(custom_fields)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+        Ok(id)
+    })
+    .await
+}
+
+/// Minor units of a transaction amount, which must be in the account's
+/// currency and signed as its type says: debits negative, credits positive.
+async fn signed_amount(
+    conn: &mut SqliteConnection,
+    account_id: i64,
+    amount: Money,
+    transaction_type: &str,
+) -> Result<i64, sqlx::Error> {
+    let amount_cents = account_amount(&mut *conn, account_id, amount).await?;
+    let wrong_sign = match transaction_type {
+        "debit" => amount.is_positive(),
+        "credit" => amount.is_negative(),
+        _ => false,
+    };
+    if wrong_sign {
+        return Err(sqlx::Error::Protocol(format!(
+            "a {} of {} has the wrong sign; debits are negative and credits positive",
+            transaction_type, amount
+        )));
+    }
+
+    Ok(amount_cents)
+}
+
+/// Moves a transaction to the trash.
 ///
-/// Permanently deletes the transaction with the specified ID. This operation
-/// cannot be undone, so the transaction data will be completely removed from
-/// the database. Use with caution as this affects historical financial records.
+/// The transaction disappears from every listing and balance but is kept with a
+/// `deleted_at` timestamp until the trash is purged; restore it with
+/// [`restore_transaction()`](crate::database::restore_transaction). The deletion
+/// is also recorded in the change journal, so [`undo()`](crate::database::undo)
+/// reverts it too. Deleting either leg of a transfer trashes both. The cash
+/// side of a trade is deleted with its trade through
+/// [`delete_investment_transaction()`](crate::database::delete_investment_transaction).
 ///
 /// # Arguments
 /// * `pool` - SQLite connection pool reference for executing the deletion
@@ -160,6 +323,7 @@ pub async fn add_transaction(
 /// Fails if:
 /// - Database connection cannot be established (pool exhaustion, file locks)
 /// - Transaction ID does not exist (no matching record to delete)
+/// - The transaction is the cash side of a trade
 /// - Database deletion fails (permissions, corruption, foreign key constraints)
 /// - Connection pool is exhausted or disconnected
 ///
@@ -168,14 +332,73 @@ pub async fn add_transaction(
 /// // Remove an incorrect transaction entry
 /// delete_transaction(&pool, 123).await?;
 ///
-/// // Note: No error if transaction ID doesn't exist - SQLite DELETE succeeds
-/// // with 0 rows affected when no matching records are found
+/// // Note: No error if transaction ID doesn't exist or is already in the trash -
+/// // the UPDATE succeeds with 0 rows affected when no matching records are found
 /// ```
 pub async fn delete_transaction(pool: &SqlitePool, transaction_id: i64) -> Result<(), sqlx::Error> {
-    sqlx::query("DELETE FROM transactions WHERE id = ?")
+    journaled(pool, "Delete transaction", async |conn| {
+        trash_transaction(&mut *conn, transaction_id).await?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Moves a live transaction to the trash together with the other leg of its
+/// transfer, inside an open unit of work.
+///
+/// # Returns
+/// * `Ok(Vec<i64>)` - IDs of the trashed transactions; empty when the
+///   transaction does not exist or is already in the trash
+/// * `Err(sqlx::Error)` - The transaction is the cash side of a trade, or
+///   database failure
+pub(crate) async fn trash_transaction(conn: &mut SqliteConnection, transaction_id: i64) -> Result<Vec<i64>, sqlx::Error> {
+    check_trade_cash_leg(&mut *conn, transaction_id).await?;
+    sqlx::query_scalar(
+        "UPDATE transactions SET deleted_at = datetime('now')
+         WHERE deleted_at IS NULL
+           AND (id = ?1 OR id = (SELECT transfer_id FROM transactions WHERE id = ?1 AND deleted_at IS NULL))
+         RETURNING id",
+    )
+    .bind(transaction_id)
+    .fetch_all(&mut *conn)
+    .await
+}
+
+/// Fails if a transaction is a leg of a transfer and `changes_amounts` would
+/// change what it moves; such an edit would unbalance the two accounts.
+pub(crate) async fn check_transfer_edit(
+    conn: &mut SqliteConnection,
+    transaction_id: i64,
+    changes_amounts: bool,
+) -> Result<(), sqlx::Error> {
+    let transfer: Option<i64> = sqlx::query_scalar("SELECT transfer_id FROM transactions WHERE id = ? AND deleted_at IS NULL")
         .bind(transaction_id)
-        .execute(pool)
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten();
+    if transfer.is_some() && changes_amounts {
+        return Err(sqlx::Error::Protocol(
+            "the account, amount and date of a transfer leg cannot be changed on their own; delete the transfer and add it again".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fails if a transaction is the cash side of a trade, which only changes
+/// together with its investment transaction.
+pub(crate) async fn check_trade_cash_leg(conn: &mut SqliteConnection, transaction_id: i64) -> Result<(), sqlx::Error> {
+    let trade: Option<i64> = sqlx::query_scalar("SELECT id FROM investment_transactions WHERE transaction_id = ?")
+        .bind(transaction_id)
+        .fetch_optional(&mut *conn)
         .await?;
+    if let Some(trade) = trade {
+        return Err(sqlx::Error::Protocol(format!(
+            "this is the cash side of investment transaction {}; delete the investment transaction and record it again instead",
+            trade
+        )));
+    }
 
     Ok(())
 }
@@ -183,6 +406,9 @@ pub async fn delete_transaction(pool: &SqlitePool, transaction_id: i64) -> Resul
 /// Updates an existing transaction record with new values.
 ///
 /// Modifies all fields of the specified transaction with the provided data.
+/// A changed description is stored as given and relinks the transaction to the
+/// payee it matches. On a leg of a transfer only the description, category and
+/// details can change. The cash side of a trade cannot be edited.
 /// This replaces the entire transaction record, ensuring consistency across
 /// all transaction attributes. The transaction ID cannot be changed through
 /// this operation as it serves as the immutable record identifier.
@@ -191,10 +417,13 @@ pub async fn delete_transaction(pool: &SqlitePool, transaction_id: i64) -> Resul
 /// * `pool` - SQLite connection pool reference for executing the update
 /// * `transaction_id` - Database ID of the transaction to modify
 /// * `account_id` - New account ID this transaction belongs to
-/// * `amount_cents` - New transaction amount in cents (always positive, e.g., 2550 for $25.50)
+/// * `amount` - New signed amount in the account's currency, negative for a debit
 /// * `transaction_type` - New transaction type ("debit" or "credit")
 /// * `description` - New human-readable transaction description
 /// * `transaction_date` - New transaction date in ISO 8601 format (YYYY-MM-DD)
+/// * `category_id` - New category ID
+/// * `details` - New memo, check number and custom field values, replacing the
+///   current ones; None leaves them unchanged
 ///
 /// # Returns
 /// * `Ok(())` - Transaction updated successfully
@@ -206,7 +435,11 @@ pub async fn delete_transaction(pool: &SqlitePool, transaction_id: i64) -> Resul
 /// - Transaction ID does not exist (no matching record to update)
 /// - New account ID does not exist (invalid foreign key reference)
 /// - Transaction type is invalid (must be "debit" or "credit")
+/// - Amount is in another currency than the account, or signed against its type
 /// - Date format is malformed (must be valid ISO 8601 YYYY-MM-DD)
+/// - The account, amount or date of a transfer leg would change
+/// - The transaction is the cash side of a trade
+/// - The category is in the trash
 /// - Database update fails (permissions, corruption, constraint violations)
 /// - Parameter binding fails (invalid UTF-8 in strings, integer overflow)
 ///
@@ -217,7 +450,7 @@ pub async fn delete_transaction(pool: &SqlitePool, transaction_id: i64) -> Resul
 ///     &pool,
 ///     123,
 ///     1,
-///     5275,  // Updated to $52.75
+///     Money::new(-5275, Currency::USD),  // Updated to $52.75
 ///     "debit".to_string(),
 ///     "Whole Foods Market - Corrected".to_string(),
 ///     "2025-08-15".to_string()
@@ -228,55 +461,276 @@ pub async fn delete_transaction(pool: &SqlitePool, transaction_id: i64) -> Resul
 ///     &pool,
 ///     456,
 ///     2,      // New account_id
-///     250000,
+///     Money::new(250000, Currency::USD),
 ///     "credit".to_string(),
 ///     "Payroll Deposit".to_string(),
 ///     "2025-08-15".to_string()
 /// ).await?;
 /// ```
+#[allow(clippy::too_many_arguments)]
 pub async fn update_transaction(
     pool: &SqlitePool,
     transaction_id: i64,
     account_id: i64,
-    amount_cents: i64,
+    amount: Money,
     transaction_type: String,
     description: String,
     transaction_date: String,
     category_id: i64,
+    details: Option<&TransactionDetails>,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        r#"
-        UPDATE transactions SET 
-            account_id = ?,
-            amount_cents = ?,
-            transaction_type = ?,
-            description = ?,
-            transaction_date = ?,
-            category_id = ?
-            WHERE id = ?
-            "#,
-    )
-    .bind(account_id)
-    .bind(amount_cents)
-    .bind(transaction_type)
-    .bind(description)
-    .bind(transaction_date)
-    .bind(category_id)
-    .bind(transaction_id)
-    .execute(pool)
-    .await?;
+    journaled(pool, "Update transaction", async |conn| {
+        let amount_cents = signed_amount(&mut *conn, account_id, amount, &transaction_type).await?;
+        check_category_assignable(&mut *conn, category_id).await?;
+        check_trade_cash_leg(&mut *conn, transaction_id).await?;
+        let current = sqlx::query(
+            "SELECT account_id, amount_cents, transaction_date, description FROM transactions
+             WHERE id = ? AND deleted_at IS NULL",
+        )
+        .bind(transaction_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some(current) = &current {
+            let changes_amounts = current.get::<i64, _>("account_id") != account_id
+                || current.get::<i64, _>("amount_cents") != amount_cents
+                || current.get::<&str, _>("transaction_date") != transaction_date;
+            check_transfer_edit(&mut *conn, transaction_id, changes_amounts).await?;
+        }
+        // An edited description is kept as typed but may mean another payee
+        let payee_id = match current.map(|current| current.get::<String, _>("description")) {
+            Some(current) if current != description => {
+                Some(resolve_payee(&mut *conn, &description).await?.payee_id)
+            }
+            _ => None,
+        };
+        let custom_fields = match details {
+            Some(details) => {
+                Some(values_json(&mut *conn, CustomFieldEntity::Transaction, &details.custom_fields).await?)
+            }
+            None => None,
+        };
 
-    Ok(())
+        sqlx::query(
+            r#"
+            UPDATE transactions SET 
+                account_id = ?,
+                amount_cents = ?,
+                transaction_type = ?,
+                description = ?,
+                transaction_date = ?,
+                category_id = ?,
+                payee_id = COALESCE(?, payee_id),
+                memo = CASE WHEN ? THEN ? ELSE memo END,
+                check_number = CASE WHEN ? THEN ? ELSE check_number END,
+                custom_fields = COALESCE(?, custom_fields)
+                WHERE id = ? AND deleted_at IS NULL
+                "#,
+        )
+        .bind(account_id)
+        .bind(amount_cents)
+        .bind(transaction_type)
+        .bind(description)
+        .bind(transaction_date)
+        .bind(category_id)
+        .bind(payee_id)
+        .bind(details.is_some())
+        .bind(details.and_then(|details| details.memo.as_deref()))
+        .bind(details.is_some())
+        .bind(details.and_then(|details| details.check_number.as_deref()))
+        .bind(custom_fields)
+        .bind(transaction_id)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Moves money between two of the household's accounts.
+///
+/// Records a debit in the source account and a credit in the destination
+/// account, linked to each other so reports treat them as a transfer rather
+/// than as an expense and income. Between accounts in different currencies,
+/// `received` is what actually arrived; any difference to the reference
+/// rate shows as a realized FX difference in
+/// [`get_income_statement()`](crate::database::get_income_statement).
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `from_account_id` - Account the money leaves
+/// * `to_account_id` - Account the money arrives in
+/// * `amount` - Positive amount leaving, in the source account's currency
+/// * `received` - Amount arriving, in the destination account's currency;
+///   defaults to `amount` converted at the transfer date's rate
+/// * `transaction_date` - Transfer date (YYYY-MM-DD)
+/// * `description` - Description of both legs; defaults to "Transfer to/from <account>"
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `from_transaction_id`,
+///   `to_transaction_id` and `received_cents`
+/// * `Err(sqlx::Error)` - Same account on both sides, non-positive amount, an
+///   amount in another currency than its account, a received amount differing
+///   from the sent one in the same currency, no exchange rate, or database failure
+///
+/// # Examples
+/// ```no_run
+/// // USD 1,200.00 bought EUR 1,050.00
+/// let received = Money::new(105000, Currency::EUR);
+/// add_transfer(&pool, 1, 2, Money::new(120000, Currency::USD), Some(received), "2025-01-20".to_string(), None).await?;
+/// ```
+pub async fn add_transfer(
+    pool: &SqlitePool,
+    from_account_id: i64,
+    to_account_id: i64,
+    amount: Money,
+    received: Option<Money>,
+    transaction_date: String,
+    description: Option<String>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    journaled(pool, "Add transfer", async |conn| {
+        let (from_name, to_name) = (account_name(&mut *conn, from_account_id).await?, account_name(&mut *conn, to_account_id).await?);
+        let descriptions = match &description {
+            Some(description) => [description.clone(), description.clone()],
+            None => [format!("Transfer to {}", to_name), format!("Transfer from {}", from_name)],
+        };
+        let (from_id, to_id, received) = insert_transfer(
+            &mut *conn,
+            from_account_id,
+            to_account_id,
+            amount,
+            received,
+            &transaction_date,
+            descriptions,
+        )
+        .await?;
+
+        Ok(serde_json::json!({
+            "from_transaction_id": from_id,
+            "to_transaction_id": to_id,
+            "received_cents": received.amount_cents()
+        }))
+    })
+    .await
+}
+
+async fn account_name(conn: &mut SqliteConnection, account_id: i64) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar("SELECT name FROM accounts WHERE id = ?")
+        .bind(account_id)
+        .fetch_one(&mut *conn)
+        .await
+}
+
+/// Records a transfer as two linked, uncategorized transactions inside an open
+/// unit of work, see [`add_transfer()`].
+///
+/// # Returns
+/// * `Ok((i64, i64, Money))` - IDs of the debit and the credit leg, and the amount received
+pub(crate) async fn insert_transfer(
+    conn: &mut SqliteConnection,
+    from_account_id: i64,
+    to_account_id: i64,
+    amount: Money,
+    received: Option<Money>,
+    date: &str,
+    [from_description, to_description]: [String; 2],
+) -> Result<(i64, i64, Money), sqlx::Error> {
+    if from_account_id == to_account_id {
+        return Err(sqlx::Error::Protocol("cannot transfer to the same account".to_string()));
+    }
+    if !amount.is_positive() || received.is_some_and(|received| !received.is_positive()) {
+        return Err(sqlx::Error::Protocol("transfer amounts must be positive".to_string()));
+    }
+
+    let sent_cents = account_amount(&mut *conn, from_account_id, amount).await?;
+    let to_currency = account_currency(&mut *conn, to_account_id).await?;
+    let received = match received {
+        Some(received) if amount.currency() == to_currency && received != amount => {
+            return Err(sqlx::Error::Protocol(format!(
+                "both accounts are in {}; the received amount must equal the amount sent",
+                to_currency
+            )));
+        }
+        Some(received) => received,
+        None => convert(&mut *conn, amount, to_currency, date).await?,
+    };
+    let received_cents = account_amount(&mut *conn, to_account_id, received).await?;
+
+    let uncategorized_id: i64 = sqlx::query_scalar("SELECT id FROM categories WHERE name = 'Uncategorized'")
+        .fetch_one(&mut *conn)
+        .await?;
+    let mut ids = Vec::with_capacity(2);
+    for (account_id, amount, description) in [
+        (from_account_id, -sent_cents, from_description),
+        (to_account_id, received_cents, to_description),
+    ] {
+        let id = sqlx::query(
+            "INSERT INTO transactions (account_id, amount_cents, transaction_type, description, transaction_date, category_id)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(account_id)
+        .bind(amount)
+        .bind(if amount < 0 { "debit" } else { "credit" })
+        .bind(description)
+        .bind(date)
+        .bind(uncategorized_id)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+        ids.push(id);
+    }
+    for (id, peer_id) in [(ids[0], ids[1]), (ids[1], ids[0])] {
+        sqlx::query("UPDATE transactions SET transfer_id = ? WHERE id = ?")
+            .bind(peer_id)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok((ids[0], ids[1], received))
+}
+
+/// Marks a transaction as cleared (seen on the bank statement) or uncleared.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference for executing the update
+/// * `transaction_id` - Database ID of the transaction
+/// * `cleared` - New cleared state
+///
+/// # Returns
+/// * `Ok(())` - Cleared state updated
+/// * `Err(sqlx::Error)` - Transaction not found or database failure
+pub async fn set_transaction_cleared(
+    pool: &SqlitePool,
+    transaction_id: i64,
+    cleared: bool,
+) -> Result<(), sqlx::Error> {
+    let label = if cleared { "Clear transaction" } else { "Unclear transaction" };
+
+    journaled(pool, label, async |conn| {
+        let result = sqlx::query("UPDATE transactions SET cleared = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(cleared)
+            .bind(transaction_id)
+            .execute(&mut *conn)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::Currency;
     use sqlx::SqlitePool;
 
     async fn setup_test_db() -> SqlitePool {
         let pool = SqlitePool::connect(":memory:").await.unwrap();
-        crate::database::create_tables(&pool).await.unwrap();
         crate::database::migrations::run_migrations(&pool)
             .await
             .unwrap();
@@ -294,7 +748,7 @@ mod tests {
         add_transaction(
             &pool,
             1,
-            1000,
+            Money::new(-1000, Currency::USD),
             "debit".to_string(),
             "Groceries".to_string(),
             "2025-01-01".to_string(),
@@ -305,8 +759,14 @@ mod tests {
 
         let transactions = get_transactions(&pool, 1).await.unwrap();
         assert_eq!(transactions.len(), 1);
-        assert_eq!(transactions[0]["amount_cents"], 1000);
+        assert_eq!(transactions[0]["amount_cents"], -1000);
         assert_eq!(transactions[0]["description"], "Groceries");
+
+        // Amounts must be in the account's currency and signed as their type says
+        for (amount, kind) in [(Money::new(-1000, Currency::EUR), "debit"), (Money::new(1000, Currency::USD), "debit"), (Money::new(-1000, Currency::USD), "credit")] {
+            assert!(add_transaction(&pool, 1, amount, kind.to_string(), "Groceries".to_string(), "2025-01-01".to_string(), 1).await.is_err());
+        }
+        assert_eq!(get_transactions(&pool, 1).await.unwrap().len(), 1);
         assert_eq!(transactions[0]["category_id"], 1);
     }
 
@@ -322,7 +782,7 @@ mod tests {
         add_transaction(
             &pool,
             1,
-            1000,
+            Money::new(-1000, Currency::USD),
             "debit".to_string(),
             "Original Description".to_string(),
             "2025-01-01".to_string(),
@@ -336,11 +796,15 @@ mod tests {
             &pool,
             1,
             1,
-            2000,
+            Money::new(2000, Currency::USD),
             "credit".to_string(),
             "Updated Description".to_string(),
             "2025-01-02".to_string(),
             1,
+            Some(&TransactionDetails {
+                memo: Some("Refund for returned shoes".to_string()),
+                ..Default::default()
+            }),
         )
         .await
         .unwrap();
@@ -351,6 +815,14 @@ mod tests {
         assert_eq!(transactions[0]["transaction_type"], "credit");
         assert_eq!(transactions[0]["description"], "Updated Description");
         assert_eq!(transactions[0]["transaction_date"], "2025-01-02");
+        assert_eq!(transactions[0]["memo"], "Refund for returned shoes");
+
+        // Without details, the memo is kept
+        update_transaction(&pool, 1, 1, Money::new(2100, Currency::USD), "credit".to_string(), "Updated Description".to_string(), "2025-01-02".to_string(), 1, None)
+            .await
+            .unwrap();
+        let transactions = get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(transactions[0]["memo"], "Refund for returned shoes");
     }
 
     #[tokio::test]
@@ -364,7 +836,7 @@ mod tests {
         add_transaction(
             &pool,
             1,
-            1000,
+            Money::new(-1000, Currency::USD),
             "debit".to_string(),
             "Groceries".to_string(),
             "2025-01-01".to_string(),
@@ -378,4 +850,94 @@ mod tests {
         let transactions = get_transactions(&pool, 1).await.unwrap();
         assert_eq!(transactions.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_transfer_legs_change_together() {
+        let pool = setup_test_db().await;
+        for name in ["Checking", "Savings"] {
+            crate::database::add_account(&pool, name.to_string(), "checking".to_string()).await.unwrap();
+        }
+        add_transfer(&pool, 1, 2, Money::new(1000, Currency::USD), None, "2025-03-01".to_string(), None)
+            .await
+            .unwrap();
+
+        // Only what does not unbalance the accounts can change on one leg
+        let amount = update_transaction(&pool, 1, 1, Money::new(-900, Currency::USD), "debit".to_string(), "Transfer to Savings".to_string(), "2025-03-01".to_string(), 1, None);
+        assert!(amount.await.is_err());
+        update_transaction(&pool, 1, 1, Money::new(-1000, Currency::USD), "debit".to_string(), "Rainy day fund".to_string(), "2025-03-01".to_string(), 1, None)
+            .await
+            .unwrap();
+        let shift = crate::database::BulkEdit { date_shift_days: Some(1), ..Default::default() };
+        assert!(crate::database::bulk_update_transactions(&pool, &crate::database::TransactionSelection::Ids(vec![2]), &shift).await.is_err());
+
+        // Deleting and restoring either leg takes the other along
+        delete_transaction(&pool, 2).await.unwrap();
+        assert_eq!(get_transactions(&pool, 1).await.unwrap().len(), 0);
+        crate::database::restore_transaction(&pool, 1).await.unwrap();
+        assert_eq!(get_transactions(&pool, 2).await.unwrap().len(), 1);
+        let results = crate::database::bulk_delete_transactions(&pool, &crate::database::TransactionSelection::Ids(vec![2, 1]))
+            .await
+            .unwrap();
+        assert!(results.iter().all(|result| result["status"] == "deleted"));
+        assert_eq!(get_transactions(&pool, 1).await.unwrap().len(), 0);
+
+        // A leg trashed on its own by an older version exports without its link
+        crate::database::restore_transaction(&pool, 2).await.unwrap();
+        sqlx::query("UPDATE transactions SET deleted_at = datetime('now') WHERE id = 2").execute(&pool).await.unwrap();
+        let document = crate::database::portable::export_ledger(&pool).await.unwrap();
+        assert_eq!(document.transactions[0].transfer_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_trashed_category_cannot_be_assigned() {
+        let pool = setup_test_db().await;
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string()).await.unwrap();
+        add_transaction(&pool, 1, Money::new(-1500, Currency::USD), "debit".to_string(), "Lunch".to_string(), "2025-03-01".to_string(), 1)
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Dining".to_string(), None).await.unwrap();
+        let dining: i64 = sqlx::query_scalar("SELECT id FROM categories WHERE name = 'Dining'").fetch_one(&pool).await.unwrap();
+        crate::database::delete_category(&pool, dining).await.unwrap();
+
+        assert!(add_transaction(&pool, 1, Money::new(-900, Currency::USD), "debit".to_string(), "Coffee".to_string(), "2025-03-02".to_string(), dining).await.is_err());
+        assert!(update_transaction(&pool, 1, 1, Money::new(-1500, Currency::USD), "debit".to_string(), "Lunch".to_string(), "2025-03-01".to_string(), dining, None).await.is_err());
+        let selection = crate::database::TransactionSelection::Ids(vec![1]);
+        assert!(crate::database::bulk_recategorize_transactions(&pool, &selection, dining).await.is_err());
+        let transactions = get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0]["category_id"], 1);
+
+        // Restored, it can be used again
+        crate::database::restore_category(&pool, dining).await.unwrap();
+        crate::database::bulk_recategorize_transactions(&pool, &selection, dining).await.unwrap();
+        assert_eq!(get_transactions(&pool, 1).await.unwrap()[0]["category_id"], dining);
+    }
+
+    #[tokio::test]
+    async fn test_get_transactions_running_balance() {
+        let pool = setup_test_db().await;
+
+        crate::database::add_account(&pool, "Test Account".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::set_opening_balance(&pool, 1, Money::new(10000, Currency::USD)).await.unwrap();
+
+        // Inserted out of date order; the second and third share a date
+        for (amount, date) in [(-2500, "2025-02-01"), (5000, "2025-01-15"), (-1000, "2025-01-15")] {
+            let kind = if amount < 0 { "debit" } else { "credit" };
+            add_transaction(&pool, 1, Money::new(amount, Currency::USD), kind.to_string(), "Test".to_string(), date.to_string(), 1)
+                .await
+                .unwrap();
+        }
+
+        let transactions = get_transactions(&pool, 1).await.unwrap();
+        let balances: Vec<i64> = transactions
+            .iter()
+            .map(|tx| tx["running_balance"].as_i64().unwrap())
+            .collect();
+        // Most recent first: 10000 + 5000 - 1000 - 2500
+        assert_eq!(balances, vec![11500, 14000, 15000]);
+        assert_eq!(crate::database::get_balance(&pool, 1).await.unwrap().amount_cents(), 11500);
+    }
 }
+
@@ -0,0 +1,344 @@
+//! Trash bin for deleted transactions and categories.
+//!
+//! [`delete_transaction()`](super::delete_transaction),
+//! [`bulk_delete_transactions()`](super::bulk_delete_transactions) and
+//! [`delete_category()`](super::delete_category) only stamp `deleted_at` on the
+//! row. Trashed rows are invisible to every listing, search and balance, and
+//! stay in the database until they are restored or purged here.
+
+use super::journal::journaled;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+/// Lists everything in the trash, most recently deleted first.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `transactions` and `categories` arrays;
+///   every item carries its `deleted_at` timestamp
+/// * `Err(sqlx::Error)` - Database failure
+pub async fn get_trash(pool: &SqlitePool) -> Result<serde_json::Value, sqlx::Error> {
+    let transactions: Vec<serde_json::Value> = sqlx::query(
+        "SELECT id, account_id, amount_cents, transaction_type, description, transaction_date, category_id, deleted_at
+         FROM transactions
+         WHERE deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC, id DESC",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        serde_json::json!({
+            "id": row.get::<i64, _>("id"),
+            "account_id": row.get::<i64, _>("account_id"),
+            "amount_cents": row.get::<i64, _>("amount_cents"),
+            "transaction_type": row.get::<String, _>("transaction_type"),
+            "description": row.get::<String, _>("description"),
+            "transaction_date": row.get::<String, _>("transaction_date"),
+            "category_id": row.get::<i64, _>("category_id"),
+            "deleted_at": row.get::<String, _>("deleted_at")
+        })
+    })
+    .collect();
+
+    let categories: Vec<serde_json::Value> = sqlx::query(
+        "SELECT id, name, parent_id, deleted_at
+         FROM categories
+         WHERE deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC, id DESC",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        serde_json::json!({
+            "id": row.get::<i64, _>("id"),
+            "name": row.get::<String, _>("name"),
+            "parent_id": row.get::<Option<i64>, _>("parent_id"),
+            "deleted_at": row.get::<String, _>("deleted_at")
+        })
+    })
+    .collect();
+
+    Ok(serde_json::json!({
+        "transactions": transactions,
+        "categories": categories
+    }))
+}
+
+/// Brings a trashed transaction back into its account, together with the
+/// other leg of its transfer.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `transaction_id` - Database ID of the trashed transaction
+///
+/// # Returns
+/// * `Ok(())` - Transaction restored
+/// * `Err(sqlx::Error)` - `RowNotFound` if the transaction is not in the trash
+pub async fn restore_transaction(pool: &SqlitePool, transaction_id: i64) -> Result<(), sqlx::Error> {
+    journaled(pool, "Restore transaction", async |conn| {
+        let result = sqlx::query(
+            "UPDATE transactions SET deleted_at = NULL
+             WHERE deleted_at IS NOT NULL
+               AND (id = ?1 OR id = (SELECT transfer_id FROM transactions WHERE id = ?1 AND deleted_at IS NOT NULL))",
+        )
+        .bind(transaction_id)
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Brings a trashed category back into the category tree.
+///
+/// The category returns under its original parent if that parent still exists
+/// outside the trash, and at root level otherwise. Transactions and child
+/// categories re-homed when it was deleted stay where they are.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `category_id` - Database ID of the trashed category
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The restored category's id, name and parent_id
+/// * `Err(sqlx::Error)` - `RowNotFound` if the category is not in the trash
+pub async fn restore_category(
+    pool: &SqlitePool,
+    category_id: i64,
+) -> Result<serde_json::Value, sqlx::Error> {
+    journaled(pool, "Restore category", async |conn| {
+        if restore_category_row(&mut *conn, category_id).await? == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        let row = sqlx::query("SELECT id, name, parent_id FROM categories WHERE id = ?")
+            .bind(category_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        Ok(serde_json::json!({
+            "id": row.get::<i64, _>("id"),
+            "name": row.get::<String, _>("name"),
+            "parent_id": row.get::<Option<i64>, _>("parent_id")
+        }))
+    })
+    .await
+}
+
+/// Clears `deleted_at` on a category, re-linking it to its original parent
+/// when that parent is not trashed itself. Returns the number of rows restored.
+pub(crate) async fn restore_category_row(
+    conn: &mut SqliteConnection,
+    category_id: i64,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE categories SET
+            deleted_at = NULL,
+            parent_id = (SELECT p.id FROM categories p WHERE p.id = categories.parent_id AND p.deleted_at IS NULL)
+         WHERE id = ? AND deleted_at IS NOT NULL",
+    )
+    .bind(category_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Permanently deletes trash items deleted at least `older_than_days` days ago.
+///
+/// Trashed categories still referenced by other trashed rows are detached
+/// first: their trashed children move to root level and any transaction left
+/// on them moves to "Uncategorized". The purge is a journaled operation, so it
+/// can be undone like any other change while the journal holds it.
+///
+/// # Arguments
+/// * `pool` - SQLite connection pool reference
+/// * `older_than_days` - Minimum age in days; `0` empties the whole trash
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Numbers of purged `transactions` and `categories`
+/// * `Err(sqlx::Error)` - Negative age or database failure; nothing is purged
+///
+/// # Examples
+/// ```no_run
+/// // Keep the last 30 days of deletions restorable
+/// let purged = purge_trash(&pool, 30).await?;
+/// println!("Purged {} transactions", purged["transactions"]);
+/// ```
+pub async fn purge_trash(
+    pool: &SqlitePool,
+    older_than_days: i64,
+) -> Result<serde_json::Value, sqlx::Error> {
+    if older_than_days < 0 {
+        return Err(sqlx::Error::Protocol(
+            "purge age cannot be negative".to_string(),
+        ));
+    }
+    let cutoff = format!("-{} days", older_than_days);
+
+    journaled(pool, "Empty trash", async |conn| {
+        // Tag links and attachments first rather than by cascade, so undo restores
+        // them after their transactions. Attachment files stay until garbage collection.
+        for linked in ["transaction_tags", "attachments"] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE transaction_id IN (
+                    SELECT id FROM transactions WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?)
+                )",
+                linked
+            ))
+            .bind(&cutoff)
+            .execute(&mut *conn)
+            .await?;
+        }
+        // Unlink transfer legs and trades explicitly too, rather than through ON DELETE SET NULL
+        for (linking, column) in [("transactions", "transfer_id"), ("investment_transactions", "transaction_id")] {
+            sqlx::query(&format!(
+                "UPDATE {linking} SET {column} = NULL WHERE {column} IN (
+                    SELECT id FROM transactions WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?)
+                )"
+            ))
+            .bind(&cutoff)
+            .execute(&mut *conn)
+            .await?;
+        }
+        let transactions = sqlx::query(
+            "DELETE FROM transactions WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?)",
+        )
+        .bind(&cutoff)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        let purged = "SELECT id FROM categories WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?)";
+        sqlx::query(&format!("UPDATE categories SET parent_id = NULL WHERE parent_id IN ({})", purged))
+            .bind(&cutoff)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(&format!(
+            "UPDATE transactions SET category_id = (SELECT id FROM categories WHERE name = 'Uncategorized')
+             WHERE category_id IN ({})",
+            purged
+        ))
+        .bind(&cutoff)
+        .execute(&mut *conn)
+        .await?;
+        let categories = sqlx::query(&format!("DELETE FROM categories WHERE id IN ({})", purged))
+            .bind(&cutoff)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+        Ok(serde_json::json!({
+            "transactions": transactions,
+            "categories": categories
+        }))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Currency, Money};
+    use sqlx::SqlitePool;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect(":memory:").await.unwrap();
+        crate::database::migrations::run_migrations(&pool).await.unwrap();
+        crate::database::seed_system_data(&pool).await.unwrap();
+
+        crate::database::add_account(&pool, "Checking".to_string(), "checking".to_string())
+            .await
+            .unwrap();
+        crate::database::add_category(&pool, "Household".to_string(), None).await.unwrap();
+        crate::database::add_category(&pool, "Cleaning".to_string(), Some(2)).await.unwrap();
+        for (amount, description) in [(-1500, "Detergent"), (-800, "Sponges")] {
+            crate::database::add_transaction(&pool, 1, Money::new(amount, Currency::USD), "debit".to_string(), description.to_string(), "2025-05-01".to_string(), 3)
+                .await
+                .unwrap();
+        }
+
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_trashed_transactions_leave_reads_and_restore() {
+        let pool = setup_test_db().await;
+
+        crate::database::delete_transaction(&pool, 1).await.unwrap();
+        assert_eq!(crate::database::get_balance(&pool, 1).await.unwrap().amount_cents(), -800);
+        let register = crate::database::get_transactions(&pool, 1).await.unwrap();
+        assert_eq!(register.len(), 1);
+        assert_eq!(register[0]["running_balance"], -800);
+        let filter = crate::database::TransactionFilter {
+            query: Some("detergent".to_string()),
+            ..Default::default()
+        };
+        assert!(crate::database::search_transactions(&pool, &filter, 10).await.unwrap().is_empty());
+        // Trashed rows cannot be edited
+        assert!(crate::database::set_transaction_cleared(&pool, 1, true).await.is_err());
+
+        let trash = get_trash(&pool).await.unwrap();
+        assert_eq!(trash["transactions"][0]["description"], "Detergent");
+
+        restore_transaction(&pool, 1).await.unwrap();
+        assert_eq!(crate::database::get_balance(&pool, 1).await.unwrap().amount_cents(), -2300);
+        assert!(restore_transaction(&pool, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restore_category_relinks_parent_when_present() {
+        let pool = setup_test_db().await;
+
+        crate::database::delete_category(&pool, 3).await.unwrap();
+        let restored = restore_category(&pool, 3).await.unwrap();
+        assert_eq!(restored["parent_id"], 2);
+
+        crate::database::delete_category(&pool, 3).await.unwrap();
+        crate::database::delete_category(&pool, 2).await.unwrap();
+        assert!(crate::database::add_category(&pool, "Household".to_string(), None).await.is_err());
+        let restored = restore_category(&pool, 3).await.unwrap();
+        assert_eq!(restored["parent_id"], serde_json::Value::Null);
+
+        assert!(crate::database::delete_category(&pool, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_purge_trash_respects_age() {
+        let pool = setup_test_db().await;
+
+        crate::database::delete_transaction(&pool, 1).await.unwrap();
+        crate::database::delete_transaction(&pool, 2).await.unwrap();
+        crate::database::delete_category(&pool, 3).await.unwrap();
+        crate::database::delete_category(&pool, 2).await.unwrap();
+        sqlx::query("UPDATE transactions SET deleted_at = datetime('now', '-40 days') WHERE id = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE categories SET deleted_at = datetime('now', '-40 days') WHERE id = 2")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let purged = purge_trash(&pool, 30).await.unwrap();
+        assert_eq!(purged["transactions"], 1);
+        assert_eq!(purged["categories"], 1);
+
+        let trash = get_trash(&pool).await.unwrap();
+        assert_eq!(trash["transactions"].as_array().unwrap().len(), 1);
+        assert_eq!(trash["categories"][0]["name"], "Cleaning");
+        assert_eq!(trash["categories"][0]["parent_id"], serde_json::Value::Null);
+
+        let purged = purge_trash(&pool, 0).await.unwrap();
+        assert_eq!(purged["transactions"], 1);
+        assert!(purge_trash(&pool, -1).await.is_err());
+    }
+}
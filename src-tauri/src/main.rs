@@ -16,7 +16,16 @@
 //! - **Database**: SQLite with manual transaction and account management
 
 mod database;
-use sqlx::SqlitePool;
+use database::{CostBasisMethod, Currency, InvestmentTrade, LedgerManager, Locale, Money, SecurityType};
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+
+/// Interval between automatic backups of the active ledger.
+const BACKUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Event emitted with the error message when a scheduled backup fails.
+const BACKUP_FAILED_EVENT: &str = "backup-failed";
 
 /// Application entry point for the finsight personal finance desktop application.
 ///
@@ -25,8 +34,16 @@ use sqlx::SqlitePool;
 ///
 /// # Database Initialization
 ///
-/// Creates a SQLite connection pool using the configured database URL. The database
-/// schema is automatically created if it doesn't exist.
+/// Opens the database during Tauri setup, once the app data directory is known.
+/// The file location is resolved from `FINSIGHT_DB_PATH`, the settings file, or
+/// the app data directory default (see [`database::location`]), and the schema is
+/// automatically created if it doesn't exist. Further ledgers can be opened at
+/// runtime; every command operates on the active ledger.
+///
+/// # Backups
+///
+/// A background task snapshots the active ledger on startup and every 24 hours,
+/// pruning old backups according to the configured retention policy.
 ///
 /// # Command Registration
 ///
@@ -36,7 +53,8 @@ use sqlx::SqlitePool;
 /// - `update_account` - Updates existing account details and archived status
 /// - `get_transactions` - Fetches transactions for a specific account
 /// - `add_transaction` - Creates a new transaction record
-/// - `delete_transaction` - Permanently removes a transaction record
+/// - `delete_transaction` - Moves a transaction record to the trash
+/// - `undo` / `redo` - Reverts or re-applies the latest data change
 ///
 /// # Runtime Behavior
 ///
@@ -59,32 +77,134 @@ use sqlx::SqlitePool;
 ///
 /// ```no_run
 /// // Entry point is called automatically by the Rust runtime
-/// #[tokio::main]
-/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     // Application initialization and startup...
 /// }
 /// ```
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize the database
-    let pool = database::init_db().await?;
-
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     tauri::Builder::default()
-        .manage(pool)
+        .setup(|app| {
+            // Open the startup ledger in the OS app data directory
+            let data_dir = app.path().app_data_dir()?;
+            let ledgers = tauri::async_runtime::block_on(LedgerManager::open(data_dir))?;
+            app.manage(ledgers);
+
+            // Back up on startup and then once a day while the app is running;
+            // failures are pushed to the frontend and kept for `list_backups`
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    if let Err(e) = handle.state::<LedgerManager>().backup().await {
+                        let _ = handle.emit(BACKUP_FAILED_EVENT, e.to_string());
+                    }
+                    tokio::time::sleep(BACKUP_INTERVAL).await;
+                }
+            });
+
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             get_accounts,
             get_account,
             add_account,
             update_account,
+            set_opening_balance,
+            close_account,
+            reorder_accounts,
             get_balance,
+            parse_money,
+            format_money,
             get_transactions,
             add_transaction,
             delete_transaction,
             update_transaction,
+            search_transactions,
+            query_transactions,
+            set_transaction_cleared,
+            bulk_update_transactions,
+            bulk_recategorize_transactions,
+            bulk_delete_transactions,
+            bulk_tag_transactions,
+            bulk_untag_transactions,
+            save_filter,
+            get_saved_filters,
+            delete_saved_filter,
+            undo,
+            redo,
+            get_undo_history,
+            set_audit_actor,
+            get_record_history,
+            get_activity_feed,
+            get_trash,
+            restore_transaction,
+            restore_category,
+            purge_trash,
             get_categories,
             add_category,
             update_category,
-            delete_category
+            delete_category,
+            merge_categories,
+            move_category_subtree,
+            attach_file,
+            get_attachments,
+            open_attachment,
+            detach_file,
+            collect_attachment_garbage,
+            get_payees,
+            save_payee,
+            delete_payee,
+            merge_payees,
+            link_payees,
+            get_top_payees,
+            get_tags,
+            add_tag,
+            rename_tag,
+            delete_tag,
+            get_tag_spending,
+            get_custom_fields,
+            add_custom_field,
+            rename_custom_field,
+            delete_custom_field,
+            add_transfer,
+            get_base_currency,
+            set_base_currency,
+            set_exchange_rate,
+            get_exchange_rates,
+            delete_exchange_rate,
+            get_exchange_rate,
+            import_exchange_rates,
+            get_net_worth,
+            get_income_statement,
+            get_securities,
+            add_security,
+            update_security,
+            delete_security,
+            add_investment_transaction,
+            delete_investment_transaction,
+            get_investment_transactions,
+            set_security_price,
+            get_security_prices,
+            import_security_prices,
+            get_lots,
+            get_holdings,
+            set_cost_basis_method,
+            get_realized_gains,
+            export_realized_gains,
+            get_database_location,
+            move_database,
+            list_ledgers,
+            create_ledger,
+            open_ledger,
+            switch_ledger,
+            close_ledger,
+            unlock_ledger,
+            change_passphrase,
+            create_backup,
+            list_backups,
+            restore_backup,
+            set_backup_policy,
+            export_ledger,
+            import_ledger
         ])
         .run(tauri::generate_context!())
         .expect("Error while running tauri application");
@@ -92,13 +212,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// Retrieves all financial accounts from the database.
+/// Retrieves financial accounts from the database in sidebar order.
 ///
 /// Returns account records as JSON-serializable values for frontend consumption.
-/// Each account includes id, name, account type, and creation timestamp.
+/// Each account includes id, name, account type, archived flag, closing date,
+/// display order and opening balance.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `status` - `"active"`, `"archived"` or `"all"` (the default)
 ///
 /// # Returns
 /// * `Ok(Vec<serde_json::Value>)` - Array of account objects
@@ -112,25 +234,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///
 /// # Examples
 /// ```javascript
-/// // Retrieve all accounts for display in accounts list
-/// const accounts = await invoke('get_accounts');
+/// // Retrieve the accounts shown in the sidebar
+/// const accounts = await invoke('get_accounts', { status: 'active' });
 /// accounts.forEach(account => {
 ///     console.log(`${account.name} (${account.account_type})`);
 /// });
 /// ```
 #[tauri::command]
-async fn get_accounts(db: tauri::State<'_, SqlitePool>) -> Result<Vec<serde_json::Value>, String> {
-    database::get_all_accounts(&*db)
+async fn get_accounts(
+    db: tauri::State<'_, LedgerManager>,
+    status: Option<database::AccountStatus>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_all_accounts(&pool, status.unwrap_or_default())
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn get_account(
-    db: tauri::State<'_, SqlitePool>,
+    db: tauri::State<'_, LedgerManager>,
     account_id: i64,
 ) -> Result<serde_json::Value, String> {
-    database::get_account(&*db, account_id)
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_account(&pool, account_id)
         .await
         .map_err(|e| e.to_string())
 }
@@ -141,9 +268,11 @@ async fn get_account(
 /// timestamp. Account names should be descriptive and unique within the household.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
+/// * `db` - Ledger manager routing to the active ledger's database
 /// * `name` - Human-readable account name (e.g., "Chase Checking", "Emergency Savings")
 /// * `account_type` - Account classification, must be "checking" or "savings"
+/// * `details` - Optional currency (defaults to the base currency), memo and
+///   custom field values keyed by field id
 ///
 /// # Returns
 /// * `Ok(())` - Account created successfully
@@ -164,6 +293,20 @@ async fn get_account(
 ///     accountType: 'checking'
 /// });
 ///
+/// // With a memo and a value for custom field 2 ("Branch")
+/// await invoke('add_account', {
+///     name: 'Joint Checking',
+///     accountType: 'checking',
+///     details: { memo: 'Shared with Sam', custom_fields: { 2: 'Main St' } }
+/// });
+///
+/// // A euro account
+/// await invoke('add_account', {
+///     name: 'Girokonto',
+///     accountType: 'checking',
+///     details: { currency: 'EUR' }
+/// });
+///
 /// // Handle validation errors
 /// try {
 ///     await invoke('add_account', { name: '', accountType: 'invalid' });
@@ -173,12 +316,15 @@ async fn get_account(
 /// ```
 #[tauri::command]
 async fn add_account(
-    db: tauri::State<'_, SqlitePool>,
+    db: tauri::State<'_, LedgerManager>,
     name: String,
     account_type: String,
+    details: Option<database::AccountDetails>,
 ) -> Result<(), String> {
-    database::add_account(&*db, name, account_type)
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::add_account_with_details(&pool, name, account_type, &details.unwrap_or_default())
         .await
+        .map(|_| ())
         .map_err(|e| e.to_string())
 }
 
@@ -189,11 +335,12 @@ async fn add_account(
 /// through the archived flag. All parameters are required to ensure data consistency.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
+/// * `db` - Ledger manager routing to the active ledger's database
 /// * `account_id` - Database ID of the account to modify
 /// * `name` - New human-readable account name
 /// * `account_type` - New account classification ("checking" or "savings")
 /// * `archived` - New archived status (true hides account, false shows it)
+/// * `details` - New currency, memo and custom field values; omit to keep the current ones
 ///
 /// # Returns
 /// * `Ok(())` - Account updated successfully
@@ -205,6 +352,7 @@ async fn add_account(
 /// - Account ID does not exist (no matching record to update)
 /// - Account name violates constraints (empty string, potential duplicates)
 /// - Invalid account type provided (must be "checking" or "savings")
+/// - A new currency for an account with transactions, an opening balance or trades
 /// - Database update fails (permissions, corruption, constraint violations)
 ///
 /// # Examples
@@ -227,24 +375,170 @@ async fn add_account(
 /// ```
 #[tauri::command]
 async fn update_account(
-    db: tauri::State<'_, SqlitePool>,
+    db: tauri::State<'_, LedgerManager>,
     account_id: i64,
     name: String,
     account_type: String,
     archived: bool,
+    details: Option<database::AccountDetails>,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::update_account(&pool, account_id, name, account_type, archived, details.as_ref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Sets the balance an account had before its first recorded transaction.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_id` - Database ID of the account
+/// * `opening_balance` - Signed opening balance in the account's currency
+///
+/// # Returns
+/// * `Ok(())` - Opening balance saved; running balances and totals include it
+/// * `Err(String)` - Database error message for frontend display
+///
+/// # Examples
+/// ```javascript
+/// await invoke('set_opening_balance', {
+///     accountId: 1,
+///     openingBalance: { amount_cents: 123456, currency: 'USD' }
+/// });
+/// ```
+#[tauri::command]
+async fn set_opening_balance(
+    db: tauri::State<'_, LedgerManager>,
+    account_id: i64,
+    opening_balance: Money,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::set_opening_balance(&pool, account_id, opening_balance)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Closes an account, optionally moving its remaining balance elsewhere first.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_id` - Database ID of the account to close
+/// * `transfer_to` - Active account receiving a non-zero remainder
+/// * `closed_date` - Closing date (YYYY-MM-DD), defaults to today
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The closed account with `transferred_cents`
+/// * `Err(String)` - Non-zero balance without `transfer_to`, invalid target,
+///   already closed, or a database error
+///
+/// # Examples
+/// ```javascript
+/// try {
+///     await invoke('close_account', { accountId: 5 });
+/// } catch (error) {
+///     // Balance left: ask where to move it
+///     await invoke('close_account', { accountId: 5, transferTo: 1 });
+/// }
+/// ```
+#[tauri::command]
+async fn close_account(
+    db: tauri::State<'_, LedgerManager>,
+    account_id: i64,
+    transfer_to: Option<i64>,
+    closed_date: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::close_account(&pool, account_id, transfer_to, closed_date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Saves the order in which accounts appear in the sidebar.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_ids` - Account IDs in their new order; omitted accounts follow them
+///
+/// # Returns
+/// * `Ok(())` - Order saved
+/// * `Err(String)` - Unknown or repeated account, or a database error
+#[tauri::command]
+async fn reorder_accounts(
+    db: tauri::State<'_, LedgerManager>,
+    account_ids: Vec<i64>,
 ) -> Result<(), String> {
-    database::update_account(&*db, account_id, name, account_type, archived)
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::reorder_accounts(&pool, &account_ids)
         .await
         .map_err(|e| e.to_string())
 }
 
+/// Returns the current balance of an account.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_id` - Database ID of the account
+///
+/// # Returns
+/// * `Ok(Money)` - Opening balance plus all transactions, in the account's currency
+/// * `Err(String)` - Unknown account, a balance too large to represent, or a database error
+///
+/// # Examples
+/// ```javascript
+/// const balance = await invoke('get_balance', { accountId: 1 });
+/// // { amount_cents: -4275, currency: 'USD' }
+/// ```
 #[tauri::command]
-async fn get_balance(db: tauri::State<'_, SqlitePool>, account_id: i64) -> Result<i64, String> {
-    database::get_balance(&*db, account_id)
+async fn get_balance(db: tauri::State<'_, LedgerManager>, account_id: i64) -> Result<Money, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_balance(&pool, account_id)
         .await
         .map_err(|e| e.to_string())
 }
 
+/// Reads an amount as the user typed it, e.g. "1.234,56 €" or "($42.75)".
+///
+/// # Arguments
+/// * `text` - Amount with optional currency symbol or code, sign or accounting parentheses
+/// * `currency` - Currency of the account the amount is for; a different symbol is rejected
+/// * `locale` - Locale tag such as "de-DE" for separators, defaults to "en-US"
+///
+/// # Returns
+/// * `Ok(Money)` - The amount in minor units of `currency`
+/// * `Err(String)` - Unreadable text, too many decimal places, another currency or unsupported locale
+///
+/// # Examples
+/// ```javascript
+/// const amount = await invoke('parse_money', {
+///     text: '1.234,56 €',
+///     currency: account.currency,
+///     locale: navigator.language
+/// });
+/// // { amount_cents: 123456, currency: 'EUR' }
+/// ```
+#[tauri::command]
+fn parse_money(text: String, currency: Currency, locale: Option<Locale>) -> Result<Money, String> {
+    Money::parse(&text, currency, locale.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+/// Formats an amount for display, e.g. "-$1,234.56" or "1.234,56 €".
+///
+/// # Arguments
+/// * `amount` - Amount to format
+/// * `locale` - Locale tag such as "fr-FR", defaults to "en-US"
+/// * `accounting` - Show negative amounts in parentheses, e.g. "($42.75)"
+///
+/// # Returns
+/// * `String` - The formatted amount
+#[tauri::command]
+fn format_money(amount: Money, locale: Option<Locale>, accounting: Option<bool>) -> String {
+    let locale = locale.unwrap_or_default();
+    match accounting {
+        Some(true) => amount.format_accounting(locale),
+        _ => amount.format(locale),
+    }
+}
+
 /// Retrieves all transactions for a specific financial account.
 ///
 /// Returns transaction records ordered by date (most recent first) as JSON-serializable
@@ -253,7 +547,7 @@ async fn get_balance(db: tauri::State<'_, SqlitePool>, account_id: i64) -> Resul
 ///
 /// # Arguments
 /// * `account_id` - Database ID of the account to query transactions for
-/// * `db` - SQLite connection pool managed by Tauri state
+/// * `db` - Ledger manager routing to the active ledger's database
 ///
 /// # Returns
 /// * `Ok(Vec<serde_json::Value>)` - Array of transaction objects ordered by date
@@ -279,10 +573,11 @@ async fn get_balance(db: tauri::State<'_, SqlitePool>, account_id: i64) -> Resul
 /// ```
 #[tauri::command]
 async fn get_transactions(
-    db: tauri::State<'_, SqlitePool>,
+    db: tauri::State<'_, LedgerManager>,
     account_id: i64,
 ) -> Result<Vec<serde_json::Value>, String> {
-    database::get_transactions(&*db, account_id)
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_transactions(&pool, account_id)
         .await
         .map_err(|e| e.to_string())
 }
@@ -295,11 +590,12 @@ async fn get_transactions(
 ///
 /// # Arguments
 /// * `account_id` - Database ID of the account this transaction belongs to
-/// * `amount_cents` - Transaction amount in cents (always positive, e.g., 2550 for $25.50)
+/// * `amount` - Signed amount in the account's currency, negative for a debit
 /// * `transaction_type` - Either "debit" (reduces balance) or "credit" (increases balance)
 /// * `description` - Human-readable transaction description from bank or user input
 /// * `transaction_date` - Transaction date in ISO 8601 format (YYYY-MM-DD)
-/// * `db` - SQLite connection pool managed by Tauri state
+/// * `details` - Optional memo, check number and custom field values keyed by field id
+/// * `db` - Ledger manager routing to the active ledger's database
 ///
 /// # Returns
 /// * `Ok(())` - Transaction created successfully
@@ -310,6 +606,7 @@ async fn get_transactions(
 /// - Database connection cannot be established (pool exhaustion, file locks)
 /// - Account ID does not exist (invalid foreign key reference)
 /// - Invalid transaction type provided (must be "debit" or "credit")
+/// - Amount is in another currency than the account, or signed against its type
 /// - Date format is invalid (must be YYYY-MM-DD ISO 8601)
 /// - Database insertion fails (disk space, permissions, corruption)
 ///
@@ -318,7 +615,7 @@ async fn get_transactions(
 /// // Add a grocery store purchase
 /// await invoke('add_transaction', {
 ///     accountId: 1,
-///     amountCents: 4275,  // $42.75
+///     amount: { amount_cents: -4275, currency: 'USD' },  // $42.75
 ///     transactionType: 'debit',
 ///     description: 'Whole Foods Market',
 ///     transactionDate: '2025-08-15'
@@ -327,213 +624,2241 @@ async fn get_transactions(
 /// // Add a paycheck deposit
 /// await invoke('add_transaction', {
 ///     accountId: 1,
-///     amountCents: 250000,  // $2,500.00
+///     amount: { amount_cents: 250000, currency: 'USD' },  // $2,500.00
 ///     transactionType: 'credit',
 ///     description: 'Salary Deposit',
 ///     transactionDate: '2025-08-15'
 /// });
+///
+/// // A check with a memo, flagged through custom field 1 ("Reimbursable")
+/// await invoke('add_transaction', {
+///     accountId: 1,
+///     amount: { amount_cents: -12000, currency: 'USD' },
+///     transactionType: 'debit',
+///     description: 'Check 1042',
+///     transactionDate: '2025-08-15',
+///     details: { memo: 'Reimbursable by work', check_number: '1042', custom_fields: { 1: true } }
+/// });
 /// ```
 #[tauri::command]
 async fn add_transaction(
-    db: tauri::State<'_, SqlitePool>,
+    db: tauri::State<'_, LedgerManager>,
     account_id: i64,
-    amount_cents: i64,
+    amount: Money,
     transaction_type: String,
     description: String,
     transaction_date: String,
     category_id: i64,
+    details: Option<database::TransactionDetails>,
 ) -> Result<(), String> {
-    database::add_transaction(
-        &*db,
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::add_transaction_with_details(
+        &pool,
         account_id,
-        amount_cents,
+        amount,
         transaction_type,
         description,
         transaction_date,
         category_id,
+        &details.unwrap_or_default(),
     )
     .await
+    .map(|_| ())
     .map_err(|e| e.to_string())
 }
 
-/// Permanently removes a transaction record from the database.
+/// Returns one page of an account register: filtered, sorted transactions with
+/// running balances.
 ///
-/// Deletes the transaction with the specified ID from the database. This operation
-/// cannot be undone and will completely remove the transaction from financial records.
-/// Use with caution as this affects historical data and account balance calculations.
+/// Pages are chained with keyset cursors, so even accounts with tens of
+/// thousands of transactions load one screen at a time. Pass the previous
+/// page's `next_cursor` as `after` to continue.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
-/// * `transaction_id` - Database ID of the transaction to remove
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `query` - Object with the filter fields of `search_transactions` (plus
+///   `cleared`), `sort` (`"date"`, `"amount"` or `"description"`), `direction`
+///   (`"asc"` or `"desc"`, default), `limit` (default 50, at most 500) and `after`
 ///
 /// # Returns
-/// * `Ok(())` - Transaction deleted successfully
+/// * `Ok(serde_json::Value)` - Object with `transactions` (each with
+///   `running_balance`), `total_count` and `next_cursor` (null on the last page)
 /// * `Err(String)` - Database error message for frontend display
 ///
-/// # Errors
-/// Fails if:
-/// - Database connection cannot be established (pool exhaustion, file locks)
-/// - Transaction ID does not exist (no matching record to delete)
-/// - Database deletion fails (permissions, corruption, foreign key constraints)
-/// - Connection pool is exhausted or disconnected
-///
 /// # Examples
 /// ```javascript
-/// // Remove an incorrect transaction entry
-/// await invoke('delete_transaction', {
-///     transactionId: 123
+/// let page = await invoke('query_transactions', {
+///     query: { account_id: 1, date_from: '2025-01-01', cleared: false }
 /// });
-///
-/// // Handle deletion errors
-/// try {
-///     await invoke('delete_transaction', { transactionId: 999 });
-/// } catch (error) {
-///     console.error('Failed to delete transaction:', error);
+/// while (page.next_cursor) {
+///     page = await invoke('query_transactions', {
+///         query: { account_id: 1, date_from: '2025-01-01', cleared: false, after: page.next_cursor }
+///     });
 /// }
 /// ```
 #[tauri::command]
-async fn delete_transaction(
-    db: tauri::State<'_, SqlitePool>,
-    transaction_id: i64,
-) -> Result<(), String> {
-    database::delete_transaction(&*db, transaction_id)
+async fn query_transactions(
+    db: tauri::State<'_, LedgerManager>,
+    query: database::TransactionQuery,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::query_transactions(&pool, &query)
         .await
         .map_err(|e| e.to_string())
 }
 
-/// Updates an existing transaction record with new values.
-///
-/// Modifies all fields of the specified transaction with the provided data.
-/// This replaces the entire transaction record, ensuring consistency across
-/// all transaction attributes. Useful for correcting transaction details or
-/// moving transactions between accounts.
+/// Marks a transaction as cleared or uncleared during reconciliation.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
-/// * `transaction_id` - Database ID of the transaction to modify
-/// * `account_id` - New account ID this transaction belongs to
-/// * `amount_cents` - New transaction amount in cents (always positive)
-/// * `transaction_type` - New transaction type ("debit" or "credit")
-/// * `description` - New human-readable transaction description
-/// * `transaction_date` - New transaction date in ISO 8601 format (YYYY-MM-DD)
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `transaction_id` - Database ID of the transaction
+/// * `cleared` - Whether the transaction appears on the bank statement
 ///
 /// # Returns
-/// * `Ok(())` - Transaction updated successfully
+/// * `Ok(())` - Cleared state updated
 /// * `Err(String)` - Database error message for frontend display
 #[tauri::command]
-async fn update_transaction(
-    db: tauri::State<'_, SqlitePool>,
+async fn set_transaction_cleared(
+    db: tauri::State<'_, LedgerManager>,
     transaction_id: i64,
-    account_id: i64,
-    amount_cents: i64,
-    transaction_type: String,
-    description: String,
-    transaction_date: String,
-    category_id: i64,
+    cleared: bool,
 ) -> Result<(), String> {
-    database::update_transaction(
-        &*db,
-        transaction_id,
-        account_id,
-        amount_cents,
-        transaction_type,
-        description,
-        transaction_date,
-        category_id,
-    )
-    .await
-    .map_err(|e| e.to_string())
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::set_transaction_cleared(&pool, transaction_id, cleared)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-/// Retrieves all categories from the database for transaction categorization.
+/// Applies the same changes to many transactions in one atomic, undoable step.
 ///
-/// Returns category records as JSON-serializable values including hierarchical
-/// relationships. Categories include system categories like "Uncategorized" and
-/// user-defined categories for organizing transactions.
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `selection` - One of `{ ids: [...] }`, `{ filter: {...} }` or `{ saved_filter: id }`
+/// * `edit` - Object with any of `category_id`, `account_id`, `date_shift_days`
+///   and `description`
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - `{id, status}` per transaction, status
+///   `"updated"` or `"not_found"`
+/// * `Err(String)` - Error message naming the failing row; nothing was changed
+///
+/// # Examples
+/// ```javascript
+/// await invoke('bulk_update_transactions', {
+///     selection: { ids: [12, 13, 14] },
+///     edit: { date_shift_days: 1, description: 'Rent' }
+/// });
+/// ```
+#[tauri::command]
+async fn bulk_update_transactions(
+    db: tauri::State<'_, LedgerManager>,
+    selection: database::TransactionSelection,
+    edit: database::BulkEdit,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::bulk_update_transactions(&pool, &selection, &edit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Adds tags to many transactions in one atomic, undoable step.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `selection` - One of `{ ids: [...] }`, `{ filter: {...} }` or `{ saved_filter: id }`
+/// * `tags` - Tag names; tags that do not exist yet are created
 ///
 /// # Returns
-/// * `Ok(Vec<serde_json::Value>)` - Array of category objects with id, name, and parent_id
-/// * `Err(String)` - Database error message for frontend display
+/// * `Ok(Vec<serde_json::Value>)` - `{id, status}` per transaction, status
+///   `"tagged"` or `"not_found"`
+/// * `Err(String)` - Error message; nothing was changed
+///
+/// # Examples
+/// ```javascript
+/// await invoke('bulk_tag_transactions', {
+///     selection: { filter: { date_from: '2025-06-01', date_to: '2025-06-14' } },
+///     tags: ['#italy-2025']
+/// });
+/// ```
 #[tauri::command]
-async fn get_categories(
-    db: tauri::State<'_, SqlitePool>,
+async fn bulk_tag_transactions(
+    db: tauri::State<'_, LedgerManager>,
+    selection: database::TransactionSelection,
+    tags: Vec<String>,
 ) -> Result<Vec<serde_json::Value>, String> {
-    database::get_all_categories(&*db)
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::bulk_tag_transactions(&pool, &selection, &tags)
         .await
         .map_err(|e| e.to_string())
 }
 
-/// Creates a new category for transaction organization.
+/// Removes tags from many transactions in one atomic, undoable step.
 ///
-/// Inserts a category record with optional parent relationship for hierarchical
-/// organization. Category names must be unique across the entire system to prevent
-/// confusion in transaction categorization.
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `selection` - One of `{ ids: [...] }`, `{ filter: {...} }` or `{ saved_filter: id }`
+/// * `tags` - Tag names to remove
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - `{id, status}` per transaction, status
+///   `"untagged"` or `"not_found"`
+/// * `Err(String)` - Error message; nothing was changed
+#[tauri::command]
+async fn bulk_untag_transactions(
+    db: tauri::State<'_, LedgerManager>,
+    selection: database::TransactionSelection,
+    tags: Vec<String>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::bulk_untag_transactions(&pool, &selection, &tags)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Moves many transactions to one category in one atomic, undoable step.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
-/// * `name` - Unique category name (e.g., "Groceries", "Utilities")
-/// * `parent_id` - Optional parent category ID for hierarchical organization
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `selection` - One of `{ ids: [...] }`, `{ filter: {...} }` or `{ saved_filter: id }`
+/// * `category_id` - Category to assign
 ///
 /// # Returns
-/// * `Ok(())` - Category created successfully
-/// * `Err(String)` - Database error message for frontend display
+/// * `Ok(Vec<serde_json::Value>)` - `{id, status}` per transaction
+/// * `Err(String)` - Error message for frontend display; nothing was changed
+///
+/// # Examples
+/// ```javascript
+/// // Clean up an import
+/// await invoke('bulk_recategorize_transactions', {
+///     selection: { filter: { query: 'whole foods', category_id: 1 } },
+///     categoryId: 7
+/// });
+/// ```
 #[tauri::command]
-async fn add_category(
-    db: tauri::State<'_, SqlitePool>,
-    name: String,
-    parent_id: Option<i64>,
-) -> Result<(), String> {
-    database::add_category(&*db, name, parent_id)
+async fn bulk_recategorize_transactions(
+    db: tauri::State<'_, LedgerManager>,
+    selection: database::TransactionSelection,
+    category_id: i64,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::bulk_recategorize_transactions(&pool, &selection, category_id)
         .await
         .map_err(|e| e.to_string())
 }
 
-/// Updates an existing category with new values.
+/// Moves many transactions to the trash in one atomic, undoable step.
 ///
-/// Modifies category name and parent relationship. Useful for reorganizing
-/// category hierarchies or correcting category names. All transactions
-/// using this category remain properly linked.
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `selection` - One of `{ ids: [...] }`, `{ filter: {...} }` or `{ saved_filter: id }`
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - `{id, status}` per transaction, status
+///   `"deleted"` or `"not_found"`
+/// * `Err(String)` - Error message for frontend display; nothing was deleted
+#[tauri::command]
+async fn bulk_delete_transactions(
+    db: tauri::State<'_, LedgerManager>,
+    selection: database::TransactionSelection,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::bulk_delete_transactions(&pool, &selection)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Saves a transaction filter under a name for reuse in searches and bulk commands.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
-/// * `category_id` - Database ID of the category to modify
-/// * `name` - New unique category name
-/// * `parent_id` - New parent category ID or None for root level
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `name` - Unique name; saving under an existing name replaces that filter
+/// * `filter` - Filter object as accepted by `search_transactions`
 ///
 /// # Returns
-/// * `Ok(())` - Category updated successfully
+/// * `Ok(i64)` - Id of the saved filter
 /// * `Err(String)` - Database error message for frontend display
 #[tauri::command]
-async fn update_category(
-    db: tauri::State<'_, SqlitePool>,
-    category_id: i64,
+async fn save_filter(
+    db: tauri::State<'_, LedgerManager>,
     name: String,
-    parent_id: Option<i64>,
-) -> Result<(), String> {
-    database::update_category(&*db, category_id, name, parent_id)
+    filter: database::TransactionFilter,
+) -> Result<i64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::save_filter(&pool, name, &filter)
         .await
         .map_err(|e| e.to_string())
 }
 
-/// Removes a category with automatic cleanup of dependent data.
+/// Lists saved transaction filters by name.
 ///
-/// Deletes the category and handles orphaned data by moving child categories
-/// up one level in the hierarchy and reassigning all transactions to the
-/// "Uncategorized" system category. Cannot delete the "Uncategorized" category itself.
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with id, name and filter
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_saved_filters(
+    db: tauri::State<'_, LedgerManager>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_saved_filters(&pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes a saved transaction filter.
 ///
 /// # Arguments
-/// * `db` - SQLite connection pool managed by Tauri state
-/// * `category_id` - Database ID of the category to remove
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `filter_id` - Id of the saved filter
 ///
 /// # Returns
-/// * `Ok(())` - Category deleted successfully with cleanup completed
+/// * `Ok(())` - Filter deleted (or did not exist)
 /// * `Err(String)` - Database error message for frontend display
 #[tauri::command]
-async fn delete_category(db: tauri::State<'_, SqlitePool>, category_id: i64) -> Result<(), String> {
-    database::delete_category(&*db, category_id)
+async fn delete_saved_filter(
+    db: tauri::State<'_, LedgerManager>,
+    filter_id: i64,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_saved_filter(&pool, filter_id)
         .await
         .map_err(|e| e.to_string())
 }
+
+/// Reverts the most recent change to the ledger's data.
+///
+/// Every data change (adding, editing and deleting accounts, categories, payees,
+/// tags and transactions, including bulk edits) is one entry in the undo history.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The undone operation's `id` and `label`
+/// * `Err(String)` - Nothing to undo, or the affected records were changed since
+///
+/// # Examples
+/// ```javascript
+/// const { label } = await invoke('undo');
+/// showToast(`Undid "${label}"`);
+/// ```
+#[tauri::command]
+async fn undo(db: tauri::State<'_, LedgerManager>) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::undo(&pool).await.map_err(|e| e.to_string())
+}
+
+/// Re-applies the most recently undone change.
+///
+/// Redo is only available until the next data change is made.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The redone operation's `id` and `label`
+/// * `Err(String)` - Nothing to redo, or the affected records were changed since
+#[tauri::command]
+async fn redo(db: tauri::State<'_, LedgerManager>) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::redo(&pool).await.map_err(|e| e.to_string())
+}
+
+/// Lists recent operations in the undo history, newest first.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `limit` - Maximum number of operations to return (defaults to 50)
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Objects with id, label, created_at, undone and changes
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_undo_history(
+    db: tauri::State<'_, LedgerManager>,
+    limit: Option<i64>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_undo_history(&pool, limit.unwrap_or(50))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Sets the actor label recorded in the audit log for following changes.
+///
+/// The label belongs to this app instance and applies to every ledger it
+/// opens; it is not stored in the ledger, so instances sharing one ledger each
+/// record their own. Call this once at startup, or when the user changes.
+///
+/// # Arguments
+/// * `actor` - Who is making changes, for example a household member's name
+///
+/// # Returns
+/// * `Ok(())` - Actor label set
+/// * `Err(String)` - Empty label
+#[tauri::command]
+fn set_audit_actor(actor: String) -> Result<(), String> {
+    database::set_audit_actor(actor).map_err(|e| e.to_string())
+}
+
+/// Retrieves every recorded change to one account, category or transaction.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `table` - Record type: `"accounts"`, `"attachments"`, `"categories"`, `"custom_fields"`,
+///   `"payees"`, `"tags"`, `"transactions"` or `"transaction_tags"`
+/// * `row_id` - Id of the record
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Audit entries, oldest first, with action, actor,
+///   changed_at and the record's `old_values`/`new_values`
+/// * `Err(String)` - Unknown record type or database error message
+///
+/// # Examples
+/// ```javascript
+/// const history = await invoke('get_record_history', { table: 'transactions', rowId: 42 });
+/// history.forEach(e => console.log(`${e.changed_at}: ${e.action} by ${e.actor}`));
+/// ```
+#[tauri::command]
+async fn get_record_history(
+    db: tauri::State<'_, LedgerManager>,
+    table: String,
+    row_id: i64,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_record_history(&pool, &table, row_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Retrieves the latest changes across the whole ledger, newest first.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `limit` - Maximum number of entries to return (defaults to 50)
+/// * `before_id` - Id of the last entry already shown, to load the next page
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Audit entries in the same shape as `get_record_history`
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_activity_feed(
+    db: tauri::State<'_, LedgerManager>,
+    limit: Option<i64>,
+    before_id: Option<i64>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_activity_feed(&pool, limit.unwrap_or(50), before_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Lists deleted transactions and categories waiting in the trash.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - `{ transactions: [...], categories: [...] }`, newest
+///   deletions first, each item with its `deleted_at` timestamp
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_trash(db: tauri::State<'_, LedgerManager>) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_trash(&pool).await.map_err(|e| e.to_string())
+}
+
+/// Restores a transaction from the trash, with the other leg of its transfer.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `transaction_id` - Database ID of the trashed transaction
+///
+/// # Returns
+/// * `Ok(())` - Transaction is back in its account
+/// * `Err(String)` - Transaction is not in the trash, or a database error
+#[tauri::command]
+async fn restore_transaction(
+    db: tauri::State<'_, LedgerManager>,
+    transaction_id: i64,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::restore_transaction(&pool, transaction_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Restores a category from the trash.
+///
+/// The category returns under its original parent when that parent still
+/// exists, and at root level otherwise.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `category_id` - Database ID of the trashed category
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The restored category's id, name and parent_id
+/// * `Err(String)` - Category is not in the trash, or a database error
+#[tauri::command]
+async fn restore_category(
+    db: tauri::State<'_, LedgerManager>,
+    category_id: i64,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::restore_category(&pool, category_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Permanently deletes trash items older than the given number of days.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `older_than_days` - Minimum age of purged items; `0` empties the trash
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Numbers of purged `transactions` and `categories`
+/// * `Err(String)` - Negative age or database error message
+///
+/// # Examples
+/// ```javascript
+/// const purged = await invoke('purge_trash', { olderThanDays: 30 });
+/// ```
+#[tauri::command]
+async fn purge_trash(
+    db: tauri::State<'_, LedgerManager>,
+    older_than_days: i64,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::purge_trash(&pool, older_than_days)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Searches transactions by free text and filters, best matches first.
+///
+/// Matches every word of `filter.query` against description, memo and payee
+/// (prefix, case and accent insensitive) and combines it with the structured
+/// criteria. Without a text query, matching transactions are listed newest first.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `filter` - Object with optional `query`, `account_id`, `category_id`
+///   (includes subcategories), `date_from`, `date_to`, `min_amount_cents`,
+///   `max_amount_cents` (absolute amounts) and `transaction_type`
+/// * `limit` - Maximum number of results; defaults to 100
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Matching transactions with a `rank` score
+/// * `Err(String)` - Database error message for frontend display
+///
+/// # Examples
+/// ```javascript
+/// const results = await invoke('search_transactions', {
+///     filter: { query: 'amazon', date_from: '2025-03-01', date_to: '2025-05-31' },
+///     limit: 20
+/// });
+/// ```
+#[tauri::command]
+async fn search_transactions(
+    db: tauri::State<'_, LedgerManager>,
+    filter: database::TransactionFilter,
+    limit: Option<i64>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::search_transactions(&pool, &filter, limit.unwrap_or(database::DEFAULT_SEARCH_LIMIT))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Moves a transaction to the trash.
+///
+/// The transaction leaves all listings and account balances immediately but can
+/// be brought back with `restore_transaction` until the trash is purged. The
+/// deletion can also be reverted with `undo`. Deleting either leg of a transfer
+/// trashes both. The cash side of a trade is refused; delete it with its trade
+/// through `delete_investment_transaction`.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `transaction_id` - Database ID of the transaction to remove
+///
+/// # Returns
+/// * `Ok(())` - Transaction deleted successfully
+/// * `Err(String)` - Database error message for frontend display
+///
+/// # Errors
+/// Fails if:
+/// - Database connection cannot be established (pool exhaustion, file locks)
+/// - Transaction ID does not exist (no matching record to delete)
+/// - Database deletion fails (permissions, corruption, foreign key constraints)
+/// - Connection pool is exhausted or disconnected
+///
+/// # Examples
+/// ```javascript
+/// // Remove an incorrect transaction entry
+/// await invoke('delete_transaction', {
+///     transactionId: 123
+/// });
+///
+/// // Handle deletion errors
+/// try {
+///     await invoke('delete_transaction', { transactionId: 999 });
+/// } catch (error) {
+///     console.error('Failed to delete transaction:', error);
+/// }
+/// ```
+#[tauri::command]
+async fn delete_transaction(
+    db: tauri::State<'_, LedgerManager>,
+    transaction_id: i64,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_transaction(&pool, transaction_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Updates an existing transaction record with new values.
+///
+/// Modifies all fields of the specified transaction with the provided data.
+/// This replaces the entire transaction record, ensuring consistency across
+/// all transaction attributes. Useful for correcting transaction details or
+/// moving transactions between accounts. The account, amount and date of a
+/// transfer leg cannot change on their own, the cash side of a trade cannot be
+/// edited, and a category in the trash cannot be assigned.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `transaction_id` - Database ID of the transaction to modify
+/// * `account_id` - New account ID this transaction belongs to
+/// * `amount` - New signed amount in the account's currency, negative for a debit
+/// * `transaction_type` - New transaction type ("debit" or "credit")
+/// * `description` - New human-readable transaction description
+/// * `transaction_date` - New transaction date in ISO 8601 format (YYYY-MM-DD)
+/// * `details` - New memo, check number and custom field values; omit to keep the current ones
+///
+/// # Returns
+/// * `Ok(())` - Transaction updated successfully
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn update_transaction(
+    db: tauri::State<'_, LedgerManager>,
+    transaction_id: i64,
+    account_id: i64,
+    amount: Money,
+    transaction_type: String,
+    description: String,
+    transaction_date: String,
+    category_id: i64,
+    details: Option<database::TransactionDetails>,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::update_transaction(
+        &pool,
+        transaction_id,
+        account_id,
+        amount,
+        transaction_type,
+        description,
+        transaction_date,
+        category_id,
+        details.as_ref(),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Retrieves all categories from the database for transaction categorization.
+///
+/// Returns category records as JSON-serializable values including hierarchical
+/// relationships. Categories include system categories like "Uncategorized" and
+/// user-defined categories for organizing transactions.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Array of category objects with id, name, and parent_id
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_categories(
+    db: tauri::State<'_, LedgerManager>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_all_categories(&pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Creates a new category for transaction organization.
+///
+/// Inserts a category record with optional parent relationship for hierarchical
+/// organization. Category names must be unique across the entire system to prevent
+/// confusion in transaction categorization.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `name` - Unique category name (e.g., "Groceries", "Utilities")
+/// * `parent_id` - Optional parent category ID for hierarchical organization
+///
+/// # Returns
+/// * `Ok(())` - Category created successfully
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn add_category(
+    db: tauri::State<'_, LedgerManager>,
+    name: String,
+    parent_id: Option<i64>,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::add_category(&pool, name, parent_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Updates an existing category with new values.
+///
+/// Modifies category name and parent relationship. Useful for reorganizing
+/// category hierarchies or correcting category names. All transactions
+/// using this category remain properly linked.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `category_id` - Database ID of the category to modify
+/// * `name` - New unique category name
+/// * `parent_id` - New parent category ID or None for root level
+///
+/// # Returns
+/// * `Ok(())` - Category updated successfully
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn update_category(
+    db: tauri::State<'_, LedgerManager>,
+    category_id: i64,
+    name: String,
+    parent_id: Option<i64>,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::update_category(&pool, category_id, name, parent_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Moves a category to the trash with automatic cleanup of dependent data.
+///
+/// Trashes the category and handles orphaned data by moving child categories
+/// up one level in the hierarchy and reassigning all transactions to the
+/// "Uncategorized" system category. Cannot delete the "Uncategorized" category itself.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `category_id` - Database ID of the category to remove
+///
+/// # Returns
+/// * `Ok(())` - Category deleted successfully with cleanup completed
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn delete_category(db: tauri::State<'_, LedgerManager>, category_id: i64) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_category(&pool, category_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Merges one category into another and removes the source category.
+///
+/// Moves all transactions, child categories and payee defaults from the source
+/// onto the target in a single SQL transaction. Useful for cleaning up duplicate categories such
+/// as folding "Dining" into "Restaurants".
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `source_id` - Database ID of the category to merge away
+/// * `target_id` - Database ID of the category receiving the source's data
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Summary with repointed row counts per table
+/// * `Err(String)` - Validation or database error message for frontend display
+///
+/// # Examples
+/// ```javascript
+/// const summary = await invoke('merge_categories', { sourceId: 4, targetId: 7 });
+/// console.log(`Moved ${summary.transactions} transactions`);
+/// ```
+#[tauri::command]
+async fn merge_categories(
+    db: tauri::State<'_, LedgerManager>,
+    source_id: i64,
+    target_id: i64,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::merge_categories(&pool, source_id, target_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Moves a category together with all of its descendants under a new parent.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `category_id` - Database ID of the subtree root to move
+/// * `new_parent_id` - Destination parent category ID or None for root level
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Summary with the number of categories in the moved subtree
+/// * `Err(String)` - Validation or database error message for frontend display
+#[tauri::command]
+async fn move_category_subtree(
+    db: tauri::State<'_, LedgerManager>,
+    category_id: i64,
+    new_parent_id: Option<i64>,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::move_category_subtree(&pool, category_id, new_parent_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Attaches a file, such as a receipt PDF or photo, to a transaction.
+///
+/// The file is copied into the active ledger's attachment store, so the
+/// original can be moved or deleted afterwards.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `transaction_id` - Transaction to attach the file to
+/// * `path` - Path of the file to attach, e.g. from a file picker
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The attachment with id, file_name, media_type and size_bytes
+/// * `Err(String)` - Unreadable or too large file, unknown transaction, or database error
+///
+/// # Examples
+/// ```javascript
+/// const path = await open({ filters: [{ name: 'Receipts', extensions: ['pdf', 'jpg', 'png'] }] });
+/// await invoke('attach_file', { transactionId: 42, path });
+/// ```
+#[tauri::command]
+async fn attach_file(
+    db: tauri::State<'_, LedgerManager>,
+    transaction_id: i64,
+    path: String,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    let store = db.attachment_dir().await.map_err(|e| e.to_string())?;
+    database::attachments::attach_file(&pool, &store, transaction_id, &PathBuf::from(path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Lists the files attached to a transaction.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `transaction_id` - Transaction whose attachments to list
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Attachments, oldest first
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_attachments(
+    db: tauri::State<'_, LedgerManager>,
+    transaction_id: i64,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::attachments::get_attachments(&pool, transaction_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Opens an attachment in the operating system's default application.
+///
+/// A copy under the original file name is opened from the temp directory, so
+/// edits in the viewer never change the stored file.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `attachment_id` - Attachment to open
+///
+/// # Returns
+/// * `Ok(String)` - Path of the opened copy
+/// * `Err(String)` - Unknown attachment, missing file, or no application to open it
+#[tauri::command]
+async fn open_attachment(db: tauri::State<'_, LedgerManager>, attachment_id: i64) -> Result<String, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    let store = db.attachment_dir().await.map_err(|e| e.to_string())?;
+    let target_dir = std::env::temp_dir().join("finsight-attachments");
+    let copy = database::attachments::checkout_attachment(&pool, &store, attachment_id, &target_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    open_with_default_app(&copy).map_err(|e| e.to_string())?;
+
+    Ok(copy.to_string_lossy().into_owned())
+}
+
+/// Removes an attachment from its transaction.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `attachment_id` - Attachment to remove
+///
+/// # Returns
+/// * `Ok(())` - Attachment removed; the stored file is kept until garbage collection
+/// * `Err(String)` - Unknown attachment or database error
+#[tauri::command]
+async fn detach_file(db: tauri::State<'_, LedgerManager>, attachment_id: i64) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::attachments::detach_file(&pool, attachment_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes stored attachment files that no transaction refers to any more.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Number of deleted `files` and freed `bytes`
+/// * `Err(String)` - Database or file system error
+#[tauri::command]
+async fn collect_attachment_garbage(db: tauri::State<'_, LedgerManager>) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    let store = db.attachment_dir().await.map_err(|e| e.to_string())?;
+    database::attachments::collect_garbage(&pool, &store)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Opens a file with the operating system's default application for its type.
+fn open_with_default_app(path: &std::path::Path) -> std::io::Result<()> {
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut command = std::process::Command::new("xdg-open");
+
+    command.arg(path).spawn().map(|_| ())
+}
+
+/// Retrieves all payees with their aliases, patterns and default category.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Payees sorted by name, each with the number
+///   of linked `transactions`
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_payees(db: tauri::State<'_, LedgerManager>) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_payees(&pool).await.map_err(|e| e.to_string())
+}
+
+/// Creates a payee or updates an existing one.
+///
+/// Patterns are SQL `LIKE` patterns matched against the raw bank description,
+/// aliases are alternative names matched against the cleaned-up description.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `payee_id` - Payee to update, or None to create one
+/// * `payee` - Object with `name` and optional `aliases`, `patterns` and
+///   `default_category_id`
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the saved payee
+/// * `Err(String)` - Empty or duplicate name, unknown payee or category
+///
+/// # Examples
+/// ```javascript
+/// const payeeId = await invoke('save_payee', {
+///     payee: { name: 'Amazon', patterns: ['AMZN MKTP%'], default_category_id: 4 }
+/// });
+/// ```
+#[tauri::command]
+async fn save_payee(
+    db: tauri::State<'_, LedgerManager>,
+    payee_id: Option<i64>,
+    payee: database::PayeeInput,
+) -> Result<i64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::save_payee(&pool, payee_id, &payee)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes a payee; its transactions keep their descriptions.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `payee_id` - Database ID of the payee to remove
+///
+/// # Returns
+/// * `Ok(())` - Payee deleted
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn delete_payee(db: tauri::State<'_, LedgerManager>, payee_id: i64) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_payee(&pool, payee_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Merges one payee into another, keeping the source's name as an alias.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `source_id` - Payee to merge away
+/// * `target_id` - Payee receiving the source's transactions
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Summary with the number of relinked `transactions`
+/// * `Err(String)` - Validation or database error message for frontend display
+#[tauri::command]
+async fn merge_payees(
+    db: tauri::State<'_, LedgerManager>,
+    source_id: i64,
+    target_id: i64,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::merge_payees(&pool, source_id, target_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Links transactions without a payee to existing payees using the current
+/// aliases and patterns. Transfers, trade cash legs and trashed transactions are skipped.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(u64)` - Number of transactions linked
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn link_payees(db: tauri::State<'_, LedgerManager>) -> Result<u64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::link_payees(&pool).await.map_err(|e| e.to_string())
+}
+
+/// Reports the payees with the most spending across all accounts.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `date_from` - Optional first date (YYYY-MM-DD) included
+/// * `date_to` - Optional last date (YYYY-MM-DD) included
+/// * `limit` - Maximum number of payees, defaults to 10
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Payees with `spent_cents`, `net_cents` and
+///   `count` in the base currency, highest spending first
+/// * `Err(String)` - Database error message for frontend display
+///
+/// # Examples
+/// ```javascript
+/// const top = await invoke('get_top_payees', { dateFrom: '2025-01-01', limit: 5 });
+/// ```
+#[tauri::command]
+async fn get_top_payees(
+    db: tauri::State<'_, LedgerManager>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_top_payees(&pool, date_from, date_to, limit.unwrap_or(10))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Retrieves all tags with the number of transactions carrying each.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Tags sorted by name with id, name and `transactions`
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_tags(db: tauri::State<'_, LedgerManager>) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_tags(&pool).await.map_err(|e| e.to_string())
+}
+
+/// Creates a tag ahead of tagging any transaction with it.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `name` - Tag name, with or without a leading `#`
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new tag, or of the existing tag with that name
+/// * `Err(String)` - Empty name or database error
+#[tauri::command]
+async fn add_tag(db: tauri::State<'_, LedgerManager>, name: String) -> Result<i64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::add_tag(&pool, name).await.map_err(|e| e.to_string())
+}
+
+/// Renames a tag on every transaction carrying it.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `tag_id` - Database ID of the tag to rename
+/// * `name` - New name, with or without a leading `#`
+///
+/// # Returns
+/// * `Ok(())` - Tag renamed
+/// * `Err(String)` - Empty or taken name, unknown tag, or database error
+#[tauri::command]
+async fn rename_tag(db: tauri::State<'_, LedgerManager>, tag_id: i64, name: String) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::rename_tag(&pool, tag_id, name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes a tag and removes it from all transactions.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `tag_id` - Database ID of the tag to remove
+///
+/// # Returns
+/// * `Ok(())` - Tag deleted
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn delete_tag(db: tauri::State<'_, LedgerManager>, tag_id: i64) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_tag(&pool, tag_id).await.map_err(|e| e.to_string())
+}
+
+/// Reports spending per tag across all accounts and categories.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `date_from` - Optional first date (YYYY-MM-DD) included
+/// * `date_to` - Optional last date (YYYY-MM-DD) included
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Tags with `spent_cents`, `net_cents`, `count`
+///   and a per-category breakdown in `categories`, in the base currency and
+///   highest spending first
+/// * `Err(String)` - Database error message for frontend display
+///
+/// # Examples
+/// ```javascript
+/// const report = await invoke('get_tag_spending', { dateFrom: '2025-01-01' });
+/// const trip = report.find(tag => tag.name === 'italy-2025');
+/// ```
+#[tauri::command]
+async fn get_tag_spending(
+    db: tauri::State<'_, LedgerManager>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_tag_spending(&pool, date_from, date_to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Retrieves the ledger's custom field definitions.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `entity` - "account" or "transaction" to list only those fields; all fields when omitted
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Fields with id, entity, name and field_type
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_custom_fields(
+    db: tauri::State<'_, LedgerManager>,
+    entity: Option<database::CustomFieldEntity>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_custom_fields(&pool, entity)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Defines a custom field for accounts or transactions.
+///
+/// Values are then written through the `details` argument of the account and
+/// transaction commands, keyed by the returned field id.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `entity` - "account" or "transaction"
+/// * `name` - Display name, unique per entity
+/// * `field_type` - "text", "number", "date" or "boolean"
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new field
+/// * `Err(String)` - Empty or duplicate name, or database error
+///
+/// # Examples
+/// ```javascript
+/// const fieldId = await invoke('add_custom_field', {
+///     entity: 'transaction',
+///     name: 'Reimbursable',
+///     fieldType: 'boolean'
+/// });
+/// const open = await invoke('search_transactions', { filter: { custom_fields: { [fieldId]: true } } });
+/// ```
+#[tauri::command]
+async fn add_custom_field(
+    db: tauri::State<'_, LedgerManager>,
+    entity: database::CustomFieldEntity,
+    name: String,
+    field_type: database::CustomFieldType,
+) -> Result<i64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::add_custom_field(&pool, entity, name, field_type)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Renames a custom field, keeping its values.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `field_id` - Database ID of the field to rename
+/// * `name` - New display name
+///
+/// # Returns
+/// * `Ok(())` - Field renamed
+/// * `Err(String)` - Empty or taken name, unknown field, or database error
+#[tauri::command]
+async fn rename_custom_field(
+    db: tauri::State<'_, LedgerManager>,
+    field_id: i64,
+    name: String,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::rename_custom_field(&pool, field_id, name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes a custom field and its value on every account or transaction.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `field_id` - Database ID of the field to delete
+///
+/// # Returns
+/// * `Ok(u64)` - Number of records that lost a value
+/// * `Err(String)` - Unknown field or database error
+#[tauri::command]
+async fn delete_custom_field(db: tauri::State<'_, LedgerManager>, field_id: i64) -> Result<u64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_custom_field(&pool, field_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Moves money between two accounts as a linked pair of transactions.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `from_account_id` - Account the money leaves
+/// * `to_account_id` - Account the money arrives in
+/// * `amount` - Positive amount leaving, in the source account's currency
+/// * `received` - Amount arriving in the destination account's currency;
+///   converted at the reference rate when omitted
+/// * `transaction_date` - Transfer date (YYYY-MM-DD)
+/// * `description` - Optional description of both legs
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - `from_transaction_id`, `to_transaction_id` and `received_cents`
+/// * `Err(String)` - Invalid accounts or amounts, missing exchange rate, or database error
+///
+/// # Examples
+/// ```javascript
+/// // Paid USD 1,200.00, the EUR account received EUR 1,050.00
+/// await invoke('add_transfer', {
+///     fromAccountId: 1,
+///     toAccountId: 2,
+///     amount: { amount_cents: 120000, currency: 'USD' },
+///     received: { amount_cents: 105000, currency: 'EUR' },
+///     transactionDate: '2025-01-20'
+/// });
+/// ```
+#[tauri::command]
+async fn add_transfer(
+    db: tauri::State<'_, LedgerManager>,
+    from_account_id: i64,
+    to_account_id: i64,
+    amount: Money,
+    received: Option<Money>,
+    transaction_date: String,
+    description: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::add_transfer(
+        &pool,
+        from_account_id,
+        to_account_id,
+        amount,
+        received,
+        transaction_date,
+        description,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Returns the currency reports are converted into.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(String)` - Three-letter ISO currency code
+/// * `Err(String)` - Database error message for frontend display
+#[tauri::command]
+async fn get_base_currency(db: tauri::State<'_, LedgerManager>) -> Result<String, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_base_currency(&pool).await.map_err(|e| e.to_string())
+}
+
+/// Sets the currency reports are converted into.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `currency` - Three-letter ISO currency code, e.g. "EUR"
+///
+/// # Returns
+/// * `Ok(())` - Base currency stored
+/// * `Err(String)` - Invalid code or database error
+#[tauri::command]
+async fn set_base_currency(db: tauri::State<'_, LedgerManager>, currency: String) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::set_base_currency(&pool, currency)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Records one day's exchange rate for a currency pair.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `rate_date` - Day the rate applies from (YYYY-MM-DD)
+/// * `from_currency` - Currency being converted
+/// * `to_currency` - Currency converted into
+/// * `rate` - Units of `to_currency` per unit of `from_currency`
+///
+/// # Returns
+/// * `Ok(())` - Rate stored, replacing any rate of the same pair and day
+/// * `Err(String)` - Invalid code or rate, or database error
+#[tauri::command]
+async fn set_exchange_rate(
+    db: tauri::State<'_, LedgerManager>,
+    rate_date: String,
+    from_currency: String,
+    to_currency: String,
+    rate: f64,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::set_exchange_rate(&pool, rate_date, from_currency, to_currency, rate)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Lists stored exchange rates, newest first.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `currency` - Optional currency the rates must quote on either side
+/// * `limit` - Maximum number of rates, defaults to 100
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Rates with id, rate_date, from_currency, to_currency and rate
+/// * `Err(String)` - Invalid code or database error
+#[tauri::command]
+async fn get_exchange_rates(
+    db: tauri::State<'_, LedgerManager>,
+    currency: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_exchange_rates(&pool, currency, limit.unwrap_or(100))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes a stored exchange rate.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `rate_id` - Database ID of the rate
+///
+/// # Returns
+/// * `Ok(())` - Rate deleted
+/// * `Err(String)` - Unknown rate or database error
+#[tauri::command]
+async fn delete_exchange_rate(db: tauri::State<'_, LedgerManager>, rate_id: i64) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_exchange_rate(&pool, rate_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the rate converting one currency into another on a date.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `from_currency` - Currency being converted
+/// * `to_currency` - Currency converted into
+/// * `date` - Day of the conversion (YYYY-MM-DD)
+///
+/// # Returns
+/// * `Ok(f64)` - Units of `to_currency` per unit of `from_currency`
+/// * `Err(String)` - No known rate, invalid code, or database error
+#[tauri::command]
+async fn get_exchange_rate(
+    db: tauri::State<'_, LedgerManager>,
+    from_currency: String,
+    to_currency: String,
+    date: String,
+) -> Result<f64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_exchange_rate(&pool, from_currency, to_currency, date)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Imports daily exchange rates from a CSV file with one column per currency,
+/// such as the ECB's `eurofxref-hist.csv`.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `path` - Path of the CSV file
+/// * `quoted_against` - Currency the rates are expressed against, defaults to "EUR"
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Number of `imported` rates, the `currencies`
+///   found and the covered `date_from` and `date_to`
+/// * `Err(String)` - Unreadable or malformed file, or database error; nothing is imported
+///
+/// # Examples
+/// ```javascript
+/// const summary = await invoke('import_exchange_rates', { path: 'C:/Downloads/eurofxref-hist.csv' });
+/// console.log(`${summary.imported} rates for ${summary.currencies.join(', ')}`);
+/// ```
+#[tauri::command]
+async fn import_exchange_rates(
+    db: tauri::State<'_, LedgerManager>,
+    path: String,
+    quoted_against: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let csv = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::import_exchange_rates(&pool, &csv, quoted_against.unwrap_or_else(|| "EUR".to_string()))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Reports each account's balance and the net worth in the base currency.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `as_of` - Optional date (YYYY-MM-DD) to value balances on; defaults to today
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - `base_currency`, `as_of`, `accounts` with
+///   `balance_cents`, `holdings_value_cents` and their base currency values,
+///   and `net_worth_cents`
+/// * `Err(String)` - Missing exchange rate or database error
+///
+/// # Examples
+/// ```javascript
+/// const report = await invoke('get_net_worth', { asOf: '2025-12-31' });
+/// ```
+#[tauri::command]
+async fn get_net_worth(
+    db: tauri::State<'_, LedgerManager>,
+    as_of: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_net_worth(&pool, as_of)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Reports income and expenses per category, plus realized FX differences of
+/// transfers, in the base currency.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `date_from` - Optional first date (YYYY-MM-DD) included
+/// * `date_to` - Optional last date (YYYY-MM-DD) included
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - `categories` with `income_cents` and
+///   `expense_cents`, totals, `fx_differences` and `net_cents`
+/// * `Err(String)` - Missing exchange rate or database error
+#[tauri::command]
+async fn get_income_statement(
+    db: tauri::State<'_, LedgerManager>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_income_statement(&pool, date_from, date_to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Lists all securities with their latest price.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Securities by ticker with `latest_price`
+///   and `latest_price_date`
+/// * `Err(String)` - Database error
+#[tauri::command]
+async fn get_securities(db: tauri::State<'_, LedgerManager>) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_securities(&pool).await.map_err(|e| e.to_string())
+}
+
+/// Defines a new security.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `ticker` - Trading symbol, stored in uppercase
+/// * `name` - Display name
+/// * `security_type` - "stock", "etf", "mutual_fund", "bond", "crypto" or "other"
+/// * `currency` - Currency the security trades in; defaults to the base currency
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the new security
+/// * `Err(String)` - Ticker already in use, invalid input or database error
+///
+/// # Examples
+/// ```javascript
+/// const id = await invoke('add_security', { ticker: 'VTI', name: 'Vanguard Total Stock Market ETF', securityType: 'etf' });
+/// ```
+#[tauri::command]
+async fn add_security(
+    db: tauri::State<'_, LedgerManager>,
+    ticker: String,
+    name: String,
+    security_type: SecurityType,
+    currency: Option<String>,
+) -> Result<i64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::add_security(&pool, ticker, name, security_type, currency)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Renames a security or changes its type.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `security_id` - Database ID of the security
+/// * `name` - New display name
+/// * `security_type` - New kind of security
+///
+/// # Returns
+/// * `Ok(())` - Security updated
+/// * `Err(String)` - Unknown security, empty name or database error
+#[tauri::command]
+async fn update_security(
+    db: tauri::State<'_, LedgerManager>,
+    security_id: i64,
+    name: String,
+    security_type: SecurityType,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::update_security(&pool, security_id, name, security_type)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes a security that has never been traded, with its prices.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `security_id` - Database ID of the security
+///
+/// # Returns
+/// * `Ok(())` - Security deleted
+/// * `Err(String)` - Unknown or traded security, or database error
+#[tauri::command]
+async fn delete_security(db: tauri::State<'_, LedgerManager>, security_id: i64) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_security(&pool, security_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Records a buy, sell, dividend or split in an investment account, with the
+/// cash side as a linked transaction. A split applies to the security in every
+/// account and is recorded once.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_id` - Database ID of the account
+/// * `security_id` - Database ID of the security
+/// * `trade_date` - Trade date (YYYY-MM-DD)
+/// * `trade` - The trade, tagged by `action`; a sale in an account using
+///   specific identification may list the `lots` it sells
+/// * `description` - Optional description of the cash transaction
+///
+/// # Returns
+/// * `Ok(i64)` - ID of the investment transaction
+/// * `Err(String)` - Invalid trade, a split already recorded, a sale of more
+///   shares than held, or database error; nothing is recorded
+///
+/// # Examples
+/// ```javascript
+/// await invoke('add_investment_transaction', {
+///     accountId: 3,
+///     securityId: 1,
+///     tradeDate: '2025-02-03',
+///     trade: {
+///         action: 'buy',
+///         quantity: 10,
+///         amount: { amount_cents: 250000, currency: 'USD' },
+///         fee: { amount_cents: 495, currency: 'USD' }
+///     }
+/// });
+/// ```
+#[tauri::command]
+async fn add_investment_transaction(
+    db: tauri::State<'_, LedgerManager>,
+    account_id: i64,
+    security_id: i64,
+    trade_date: String,
+    trade: InvestmentTrade,
+    description: Option<String>,
+) -> Result<i64, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::add_investment_transaction(&pool, account_id, security_id, trade_date, &trade, description)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes an investment transaction and trashes its cash transaction. A
+/// deleted split no longer applies to any account.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `investment_transaction_id` - Database ID of the trade
+///
+/// # Returns
+/// * `Ok(())` - Trade deleted
+/// * `Err(String)` - Unknown trade, a later sale depending on it, or database error
+#[tauri::command]
+async fn delete_investment_transaction(
+    db: tauri::State<'_, LedgerManager>,
+    investment_transaction_id: i64,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::delete_investment_transaction(&pool, investment_transaction_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Lists the trades of an investment account, most recent first.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_id` - Database ID of the account
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Trades with ticker, action, quantity and amounts
+/// * `Err(String)` - Database error
+#[tauri::command]
+async fn get_investment_transactions(
+    db: tauri::State<'_, LedgerManager>,
+    account_id: i64,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_investment_transactions(&pool, account_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Stores a security's closing price for a day.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `security_id` - Database ID of the security
+/// * `price_date` - Day of the price (YYYY-MM-DD)
+/// * `price` - Price of one share in the security's currency
+///
+/// # Returns
+/// * `Ok(())` - Price stored, replacing any price for that day
+/// * `Err(String)` - Unknown security, invalid price or database error
+#[tauri::command]
+async fn set_security_price(
+    db: tauri::State<'_, LedgerManager>,
+    security_id: i64,
+    price_date: String,
+    price: f64,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::set_security_price(&pool, security_id, price_date, price)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Lists a security's stored prices, newest first.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `security_id` - Database ID of the security
+/// * `limit` - Maximum number of prices, defaults to 100
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Prices with price_date and price
+/// * `Err(String)` - Database error
+#[tauri::command]
+async fn get_security_prices(
+    db: tauri::State<'_, LedgerManager>,
+    security_id: i64,
+    limit: Option<i64>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_security_prices(&pool, security_id, limit.unwrap_or(100))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Imports daily security prices from a CSV file.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `path` - Path of the CSV file with Date, Ticker and Close columns
+/// * `ticker` - Security of every row, for single-security files without a ticker column
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Number of `imported` prices, the `securities`
+///   found and the covered `date_from` and `date_to`
+/// * `Err(String)` - Unreadable or malformed file, unknown ticker or database
+///   error; nothing is imported
+///
+/// # Examples
+/// ```javascript
+/// const summary = await invoke('import_security_prices', { path: 'C:/Downloads/VTI.csv', ticker: 'VTI' });
+/// ```
+#[tauri::command]
+async fn import_security_prices(
+    db: tauri::State<'_, LedgerManager>,
+    path: String,
+    ticker: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let csv = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::import_security_prices(&pool, &csv, ticker)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Lists the open lots of an investment account.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_id` - Database ID of the account
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` - Lots with ticker, acquired date, quantity
+///   and cost_basis_cents
+/// * `Err(String)` - Database error
+#[tauri::command]
+async fn get_lots(db: tauri::State<'_, LedgerManager>, account_id: i64) -> Result<Vec<serde_json::Value>, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_lots(&pool, account_id).await.map_err(|e| e.to_string())
+}
+
+/// Reports the securities held per account with cost basis, market value and
+/// unrealized gain.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_id` - One account, or omitted for all
+/// * `as_of` - Optional valuation date (YYYY-MM-DD); defaults to today
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - `as_of` and `accounts` with their `positions`
+///   and totals
+/// * `Err(String)` - Database error
+///
+/// # Examples
+/// ```javascript
+/// const holdings = await invoke('get_holdings', { accountId: 3 });
+/// ```
+#[tauri::command]
+async fn get_holdings(
+    db: tauri::State<'_, LedgerManager>,
+    account_id: Option<i64>,
+    as_of: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_holdings(&pool, account_id, as_of)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Chooses how sales in an account pick lots and their cost basis.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `account_id` - Database ID of the account
+/// * `method` - "fifo", "lifo", "specific" or "average"
+///
+/// # Returns
+/// * `Ok(())` - Method saved; it applies to sales recorded from now on, earlier
+///   sales keep the method they were recorded with
+/// * `Err(String)` - Unknown account or database error
+#[tauri::command]
+async fn set_cost_basis_method(
+    db: tauri::State<'_, LedgerManager>,
+    account_id: i64,
+    method: CostBasisMethod,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::set_cost_basis_method(&pool, account_id, method)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Reports the gains and losses realized in a tax year, split into short and
+/// long term, with wash sales flagged.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `tax_year` - Calendar year of the sales
+/// * `account_id` - One account, or omitted for all
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - `sales` per lot sold, `short_term` and
+///   `long_term` totals and `total_gain_cents` in the base currency
+/// * `Err(String)` - Missing exchange rate or database error
+///
+/// # Examples
+/// ```javascript
+/// const gains = await invoke('get_realized_gains', { taxYear: 2025 });
+/// console.log(`Short term: ${gains.short_term.gain_cents}, long term: ${gains.long_term.gain_cents}`);
+/// ```
+#[tauri::command]
+async fn get_realized_gains(
+    db: tauri::State<'_, LedgerManager>,
+    tax_year: i32,
+    account_id: Option<i64>,
+) -> Result<serde_json::Value, String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::get_realized_gains(&pool, tax_year, account_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Exports the realized gains of a tax year to a CSV file, one row per lot sold.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `tax_year` - Calendar year of the sales
+/// * `account_id` - One account, or omitted for all
+/// * `path` - Destination file path; an existing file is overwritten
+///
+/// # Returns
+/// * `Ok(())` - CSV written
+/// * `Err(String)` - Missing exchange rate, database or file error
+///
+/// # Examples
+/// ```javascript
+/// await invoke('export_realized_gains', { taxYear: 2025, path: 'D:/Taxes/gains-2025.csv' });
+/// ```
+#[tauri::command]
+async fn export_realized_gains(
+    db: tauri::State<'_, LedgerManager>,
+    tax_year: i32,
+    account_id: Option<i64>,
+    path: String,
+) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    let csv = database::export_realized_gains_csv(&pool, tax_year, account_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    std::fs::write(&path, csv).map_err(|e| e.to_string())
+}
+
+/// Returns the filesystem path of the active ledger's database file.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(String)` - Absolute or configured path of the open database file
+#[tauri::command]
+async fn get_database_location(db: tauri::State<'_, LedgerManager>) -> Result<String, String> {
+    db.path()
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Moves the active ledger's database file to a new location and switches to it.
+///
+/// Copies a consistent snapshot to the destination, verifies it opens, records
+/// the new path in the settings file and removes the old file. The application
+/// keeps running against the new location without a restart.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `destination` - Path of the new database file; must not already exist
+///
+/// # Returns
+/// * `Ok(String)` - The new database path
+/// * `Err(String)` - Error message for frontend display; the old database stays in use
+///
+/// # Examples
+/// ```javascript
+/// const path = await invoke('move_database', {
+///     destination: 'D:/Finance/finsight.db'
+/// });
+/// ```
+#[tauri::command]
+async fn move_database(
+    db: tauri::State<'_, LedgerManager>,
+    destination: String,
+) -> Result<String, String> {
+    db.relocate(&PathBuf::from(destination))
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Lists open ledgers and recently used ledger files.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `active` path, `locked` path of an
+///   encrypted ledger awaiting its passphrase, `open` ledgers (name, path, active
+///   and encrypted flags) and `recent` paths, most recent first
+/// * `Err(String)` - Settings error message for frontend display
+#[tauri::command]
+async fn list_ledgers(db: tauri::State<'_, LedgerManager>) -> Result<serde_json::Value, String> {
+    db.list().await.map_err(|e| e.to_string())
+}
+
+/// Creates a new, empty ledger file and makes it the active ledger.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `path` - Path of the new database file; must not already exist
+/// * `passphrase` - Optional passphrase to encrypt the new ledger with
+///
+/// # Returns
+/// * `Ok(String)` - Path of the new active ledger
+/// * `Err(String)` - Error message for frontend display
+///
+/// # Examples
+/// ```javascript
+/// await invoke('create_ledger', { path: 'D:/Finance/mom.db', passphrase: null });
+/// ```
+#[tauri::command]
+async fn create_ledger(
+    db: tauri::State<'_, LedgerManager>,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<String, String> {
+    db.create(&PathBuf::from(path), passphrase.as_deref())
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Opens an existing ledger file and makes it the active ledger.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `path` - Path of an existing database file
+/// * `passphrase` - Passphrase of an encrypted ledger; `null` for plain files
+///
+/// # Returns
+/// * `Ok(String)` - Path of the new active ledger
+/// * `Err(String)` - Error message for frontend display
+#[tauri::command]
+async fn open_ledger(
+    db: tauri::State<'_, LedgerManager>,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<String, String> {
+    db.open_existing(&PathBuf::from(path), passphrase.as_deref())
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Makes an already open ledger the active ledger.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `path` - Path of an open ledger
+///
+/// # Returns
+/// * `Ok(String)` - Path of the new active ledger
+/// * `Err(String)` - Error message for frontend display
+#[tauri::command]
+async fn switch_ledger(db: tauri::State<'_, LedgerManager>, path: String) -> Result<String, String> {
+    db.switch(&PathBuf::from(path))
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Closes an open ledger. Closing the active ledger activates another open one.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `path` - Path of an open ledger; the last open ledger cannot be closed
+///
+/// # Returns
+/// * `Ok(String)` - Path of the active ledger after closing
+/// * `Err(String)` - Error message for frontend display
+#[tauri::command]
+async fn close_ledger(db: tauri::State<'_, LedgerManager>, path: String) -> Result<String, String> {
+    db.close(&PathBuf::from(path))
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Unlocks the encrypted startup ledger.
+///
+/// When the startup ledger is encrypted, every other command fails until the
+/// user enters the passphrase; `list_ledgers` reports it under `locked`.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `passphrase` - The ledger's passphrase
+///
+/// # Returns
+/// * `Ok(String)` - Path of the now active ledger
+/// * `Err(String)` - Error message for frontend display (e.g. wrong passphrase)
+///
+/// # Examples
+/// ```javascript
+/// const { locked } = await invoke('list_ledgers');
+/// if (locked) {
+///     await invoke('unlock_ledger', { passphrase: prompt(`Passphrase for ${locked}`) });
+/// }
+/// ```
+#[tauri::command]
+async fn unlock_ledger(
+    db: tauri::State<'_, LedgerManager>,
+    passphrase: String,
+) -> Result<String, String> {
+    db.unlock(&passphrase)
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Encrypts, decrypts or changes the passphrase of the active ledger.
+///
+/// Pass no `current` passphrase to encrypt a plain ledger, no `new` passphrase
+/// to convert an encrypted ledger back to a plain file, or both to change the
+/// passphrase. Existing backups keep the key they were written with.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `current` - Current passphrase; `null` for a plain ledger
+/// * `new` - New passphrase; `null` to remove encryption
+///
+/// # Returns
+/// * `Ok(())` - Ledger converted and reopened
+/// * `Err(String)` - Error message for frontend display; the ledger is unchanged
+///
+/// # Examples
+/// ```javascript
+/// // Encrypt a plain ledger
+/// await invoke('change_passphrase', { current: null, new: 'correct horse battery staple' });
+/// ```
+#[tauri::command]
+async fn change_passphrase(
+    db: tauri::State<'_, LedgerManager>,
+    current: Option<String>,
+    new: Option<String>,
+) -> Result<(), String> {
+    db.change_encryption(current.as_deref(), new.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Takes a backup of the active ledger immediately.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(String)` - Path of the new backup file
+/// * `Err(String)` - Error message for frontend display
+#[tauri::command]
+async fn create_backup(db: tauri::State<'_, LedgerManager>) -> Result<String, String> {
+    db.backup()
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Lists the active ledger's backups, newest first.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Object with `backups` (file_name, path,
+///   size_bytes, created_at and transaction_count, null when the backup cannot
+///   be read) and `last_failure`, the error of the most recent backup attempt
+///   or null if it succeeded
+/// * `Err(String)` - Error message for frontend display
+///
+/// # Examples
+/// ```javascript
+/// const { backups, last_failure } = await invoke('list_backups');
+/// if (last_failure) console.warn(`Last backup failed: ${last_failure}`);
+/// backups.forEach(b => console.log(`${b.created_at}: ${b.transaction_count} transactions`));
+/// ```
+#[tauri::command]
+async fn list_backups(db: tauri::State<'_, LedgerManager>) -> Result<serde_json::Value, String> {
+    let backups = db.list_backups().await.map_err(|e| e.to_string())?;
+
+    Ok(serde_json::json!({
+        "backups": backups,
+        "last_failure": db.last_backup_failure()
+    }))
+}
+
+/// Restores the active ledger from one of its backups.
+///
+/// Takes a safety backup of the current state before overwriting it, so the
+/// restore itself can be reverted.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `file_name` - File name of the backup, as returned by `list_backups`
+///
+/// # Returns
+/// * `Ok(String)` - Path of the safety backup taken before restoring
+/// * `Err(String)` - Error message for frontend display
+#[tauri::command]
+async fn restore_backup(
+    db: tauri::State<'_, LedgerManager>,
+    file_name: String,
+) -> Result<String, String> {
+    db.restore_backup(&file_name)
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Exports the active ledger to a portable JSON file.
+///
+/// The file is independent of SQLite and of the app's schema version, so it
+/// can be kept under version control, diffed, or imported into another ledger.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `path` - Destination file path; an existing file is overwritten
+///
+/// # Returns
+/// * `Ok(())` - Export written
+/// * `Err(String)` - Error message for frontend display
+///
+/// # Examples
+/// ```javascript
+/// await invoke('export_ledger', { path: 'D:/Finance/household-2025.json' });
+/// ```
+#[tauri::command]
+async fn export_ledger(db: tauri::State<'_, LedgerManager>, path: String) -> Result<(), String> {
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    let document = database::portable::export_ledger(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    database::portable::write_document(&PathBuf::from(path), &document).map_err(|e| e.to_string())
+}
+
+/// Imports a portable JSON export into the active ledger.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `path` - Path of a file written by `export_ledger`
+/// * `mode` - `"restore"` to recreate the export in an empty ledger with its
+///   original ids, or `"merge"` to add it to the current data with new ids
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - Counts of created `accounts`, `categories` and
+///   `transactions`, plus `categories_matched` by name when merging
+/// * `Err(String)` - Error message for frontend display; nothing is imported
+///
+/// # Examples
+/// ```javascript
+/// const summary = await invoke('import_ledger', {
+///     path: 'D:/Finance/mom.json',
+///     mode: 'merge'
+/// });
+/// console.log(`Imported ${summary.transactions} transactions`);
+/// ```
+#[tauri::command]
+async fn import_ledger(
+    db: tauri::State<'_, LedgerManager>,
+    path: String,
+    mode: database::portable::ImportMode,
+) -> Result<serde_json::Value, String> {
+    let document =
+        database::portable::read_document(&PathBuf::from(path)).map_err(|e| e.to_string())?;
+    let pool = db.pool().await.map_err(|e| e.to_string())?;
+    database::portable::import_ledger(&pool, &document, mode)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Sets how many daily, weekly and monthly backups are retained.
+///
+/// # Arguments
+/// * `db` - Ledger manager routing to the active ledger's database
+/// * `daily` - Number of most recent days to keep one backup for
+/// * `weekly` - Number of most recent weeks to keep one backup for
+/// * `monthly` - Number of most recent months to keep one backup for
+///
+/// # Returns
+/// * `Ok(())` - Policy saved; applied from the next backup on
+/// * `Err(String)` - Error message for frontend display
+#[tauri::command]
+async fn set_backup_policy(
+    db: tauri::State<'_, LedgerManager>,
+    daily: usize,
+    weekly: usize,
+    monthly: usize,
+) -> Result<(), String> {
+    db.set_backup_policy(database::backups::BackupPolicy {
+        daily,
+        weekly,
+        monthly,
+    })
+    .map_err(|e| e.to_string())
+}